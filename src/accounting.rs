@@ -1,6 +1,9 @@
-use anyhow::Result;
-use rusqlite::{params, Connection};
+use chrono::{Datelike, Days, Local, NaiveDate};
+use crate::busy;
+use crate::error::{HermesError, Result};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -12,6 +15,145 @@ pub struct CumulativeStats {
     pub total_traditional_estimate: u64,
     pub cumulative_savings_tokens: u64,
     pub cumulative_savings_pct: f64,
+    pub searches: OperationStats,
+    pub fetches: OperationStats,
+}
+
+/// Totals for one day or week of the time series returned by
+/// [`Accountant::get_daily_stats`] and [`Accountant::get_weekly_stats`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyStats {
+    /// First day of the period, `YYYY-MM-DD` in local time.
+    pub date: String,
+    pub queries: u64,
+    pub pointer_tokens: u64,
+    pub fetched_tokens: u64,
+    pub traditional_estimate: u64,
+    pub tokens_saved: u64,
+    pub savings_pct: f64,
+}
+
+impl DailyStats {
+    fn new(
+        date: String,
+        queries: u64,
+        pointer_tokens: u64,
+        fetched_tokens: u64,
+        traditional_estimate: u64,
+    ) -> Self {
+        let tokens_saved = traditional_estimate.saturating_sub(pointer_tokens + fetched_tokens);
+        let savings_pct = if traditional_estimate > 0 {
+            (tokens_saved as f64 / traditional_estimate as f64) * 100.0
+        } else {
+            0.0
+        };
+        Self {
+            date,
+            queries,
+            pointer_tokens,
+            fetched_tokens,
+            traditional_estimate,
+            tokens_saved,
+            savings_pct,
+        }
+    }
+}
+
+/// Totals for one normalized query text, as returned by
+/// [`Accountant::top_queries`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryStats {
+    /// `query_text` lowercased and trimmed. For fetches this is the pointer
+    /// id or file path that was fetched.
+    pub query: String,
+    pub count: u64,
+    pub pointer_tokens: u64,
+    pub fetched_tokens: u64,
+    pub traditional_estimate: u64,
+    pub tokens_saved: u64,
+}
+
+/// Totals for one session, as returned by [`Accountant::list_sessions`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// The session id, or `legacy` for rows recorded before sessions were.
+    pub session_id: String,
+    /// UTC `YYYY-MM-DD HH:MM:SS` of the first and last recorded operation.
+    pub first_query_at: String,
+    pub last_query_at: String,
+    pub queries: u64,
+    pub pointer_tokens: u64,
+    pub fetched_tokens: u64,
+    pub traditional_estimate: u64,
+    pub tokens_saved: u64,
+    pub savings_pct: f64,
+}
+
+/// Bucket for accounting rows without a session id.
+pub const LEGACY_SESSION: &str = "legacy";
+
+/// Most days or weeks the MCP `hermes_stats` tool returns in one series.
+pub const MAX_STATS_PERIODS: u32 = 730;
+
+/// Ranking used by [`Accountant::top_queries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopQueryOrder {
+    Frequency,
+    FetchedTokens,
+    Savings,
+}
+
+impl TopQueryOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Frequency => "frequency",
+            Self::FetchedTokens => "fetched_tokens",
+            Self::Savings => "savings",
+        }
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "frequency" => Some(Self::Frequency),
+            "fetched_tokens" => Some(Self::FetchedTokens),
+            "savings" => Some(Self::Savings),
+            _ => None,
+        }
+    }
+
+    fn order_clause(&self) -> &'static str {
+        match self {
+            Self::Frequency => "n DESC, fetched DESC",
+            Self::FetchedTokens => "fetched DESC, n DESC",
+            Self::Savings => "trad - ptr - fetched DESC, n DESC",
+        }
+    }
+}
+
+/// Count and latency percentiles of one kind of operation. Percentiles are
+/// `None` when no row of that kind recorded a latency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperationStats {
+    pub count: u64,
+    pub p50_latency_ms: Option<u64>,
+    pub p95_latency_ms: Option<u64>,
+}
+
+/// What an accounting row measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Search,
+    /// A node fetch or a direct file-range fetch.
+    Fetch,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Search => "search",
+            Self::Fetch => "fetch",
+        }
+    }
 }
 
 pub struct Accountant {
@@ -29,6 +171,7 @@ impl Accountant {
         }
     }
 
+    /// Records a search without a latency; see [`Self::record_operation`].
     pub fn record_query(
         &self,
         query_text: &str,
@@ -36,19 +179,43 @@ impl Accountant {
         fetched_tokens: u64,
         traditional_estimate: u64,
     ) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "INSERT INTO accounting (project_id, session_id, query_text, pointer_tokens, fetched_tokens, traditional_est)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                self.project_id,
-                self.session_id,
-                query_text,
-                pointer_tokens as i64,
-                fetched_tokens as i64,
-                traditional_estimate as i64,
-            ],
-        )?;
+        self.record_operation(
+            Operation::Search,
+            query_text,
+            pointer_tokens,
+            fetched_tokens,
+            traditional_estimate,
+            None,
+        )
+    }
+
+    pub fn record_operation(
+        &self,
+        operation: Operation,
+        query_text: &str,
+        pointer_tokens: u64,
+        fetched_tokens: u64,
+        traditional_estimate: u64,
+        latency: Option<Duration>,
+    ) -> Result<()> {
+        let conn = self.db.lock()?;
+        busy::retry_busy(|| {
+            conn.execute(
+                "INSERT INTO accounting
+                 (project_id, session_id, query_text, pointer_tokens, fetched_tokens, traditional_est, operation, latency_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    self.project_id,
+                    self.session_id,
+                    query_text,
+                    pointer_tokens as i64,
+                    fetched_tokens as i64,
+                    traditional_estimate as i64,
+                    operation.as_str(),
+                    latency.map(|d| d.as_millis() as i64),
+                ],
+            )
+        })?;
         Ok(())
     }
 
@@ -57,7 +224,7 @@ impl Accountant {
     }
 
     pub fn get_stats_since(&self, since: Option<Duration>) -> Result<CumulativeStats> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
 
         let (query, params_values): (String, Vec<String>) = if let Some(dur) = since {
             let secs = dur.as_secs() as i64;
@@ -87,7 +254,7 @@ impl Accountant {
         };
 
         let mut stmt = conn.prepare(&query)?;
-        let stats = stmt.query_row(rusqlite::params_from_iter(params_values.iter()), |row| {
+        let mut stats = stmt.query_row(rusqlite::params_from_iter(params_values.iter()), |row| {
             let total_queries: u64 = row.get(0)?;
             let ptr_tokens: u64 = row.get(1)?;
             let fetch_tokens: u64 = row.get(2)?;
@@ -106,13 +273,26 @@ impl Accountant {
                 total_traditional_estimate: trad_est,
                 cumulative_savings_tokens: saved,
                 cumulative_savings_pct: pct,
+                searches: OperationStats::default(),
+                fetches: OperationStats::default(),
             })
         })?;
+        let since_clause = since
+            .map(|dur| format!("AND created_at >= datetime('now', '-{} seconds')", dur.as_secs()))
+            .unwrap_or_default();
+        (stats.searches, stats.fetches) = operation_breakdown(
+            &conn,
+            &format!("project_id = ?1 {since_clause}"),
+            &[&self.project_id],
+        )?;
+        if since.is_none() {
+            add_rollup(&conn, &self.project_id, &mut stats)?;
+        }
         Ok(stats)
     }
 
     pub fn get_session_stats(&self) -> Result<CumulativeStats> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let mut stmt = conn.prepare(
             "SELECT COUNT(*),
                     COALESCE(SUM(pointer_tokens), 0),
@@ -120,7 +300,7 @@ impl Accountant {
                     COALESCE(SUM(traditional_est), 0)
              FROM accounting WHERE project_id = ?1 AND session_id = ?2",
         )?;
-        let stats = stmt.query_row(params![self.project_id, self.session_id], |row| {
+        let mut stats = stmt.query_row(params![self.project_id, self.session_id], |row| {
             let total_queries: u64 = row.get(0)?;
             let ptr_tokens: u64 = row.get(1)?;
             let fetch_tokens: u64 = row.get(2)?;
@@ -139,8 +319,15 @@ impl Accountant {
                 total_traditional_estimate: trad_est,
                 cumulative_savings_tokens: saved,
                 cumulative_savings_pct: pct,
+                searches: OperationStats::default(),
+                fetches: OperationStats::default(),
             })
         })?;
+        (stats.searches, stats.fetches) = operation_breakdown(
+            &conn,
+            "project_id = ?1 AND session_id = ?2",
+            &[&self.project_id, &self.session_id],
+        )?;
         Ok(stats)
     }
 
@@ -149,7 +336,7 @@ impl Accountant {
     /// midnight, because it uses the SQLite `date('now','localtime')` function
     /// rather than the session_id string that was set at startup.
     pub fn get_today_stats(&self) -> Result<CumulativeStats> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let mut stmt = conn.prepare(
             "SELECT COUNT(*),
                     COALESCE(SUM(pointer_tokens), 0),
@@ -159,7 +346,7 @@ impl Accountant {
              WHERE project_id = ?1
                AND date(created_at, 'localtime') = date('now', 'localtime')",
         )?;
-        let stats = stmt.query_row(params![self.project_id], |row| {
+        let mut stats = stmt.query_row(params![self.project_id], |row| {
             let total_queries: u64 = row.get(0)?;
             let ptr_tokens: u64 = row.get(1)?;
             let fetch_tokens: u64 = row.get(2)?;
@@ -178,12 +365,294 @@ impl Accountant {
                 total_traditional_estimate: trad_est,
                 cumulative_savings_tokens: saved,
                 cumulative_savings_pct: pct,
+                searches: OperationStats::default(),
+                fetches: OperationStats::default(),
             })
         })?;
+        (stats.searches, stats.fetches) = operation_breakdown(
+            &conn,
+            "project_id = ?1 AND date(created_at, 'localtime') = date('now', 'localtime')",
+            &[&self.project_id],
+        )?;
         Ok(stats)
     }
 }
 
+impl Accountant {
+    /// Deletes accounting rows older than `age` and returns how many were
+    /// deleted. Their totals are first folded into `accounting_rollup`, so
+    /// [`Self::get_cumulative_stats`] keeps counting them; windowed, daily
+    /// and top-query reports only see the rows that are left.
+    pub fn prune_older_than(&self, age: Duration) -> Result<u64> {
+        let conn = self.db.lock()?;
+        let cutoff = format!("-{} seconds", age.as_secs());
+        let tx = busy::write_transaction(&conn)?;
+        tx.execute(
+            "INSERT INTO accounting_rollup
+             (project_id, queries, pointer_tokens, fetched_tokens, traditional_est, searches, fetches, pruned_through)
+             SELECT ?1, COUNT(*),
+                    COALESCE(SUM(pointer_tokens), 0),
+                    COALESCE(SUM(fetched_tokens), 0),
+                    COALESCE(SUM(traditional_est), 0),
+                    COALESCE(SUM(operation = 'search'), 0),
+                    COALESCE(SUM(operation = 'fetch'), 0),
+                    MAX(created_at)
+             FROM accounting
+             WHERE project_id = ?1 AND created_at < datetime('now', ?2)
+             HAVING COUNT(*) > 0
+             ON CONFLICT (project_id) DO UPDATE SET
+                queries         = queries + excluded.queries,
+                pointer_tokens  = pointer_tokens + excluded.pointer_tokens,
+                fetched_tokens  = fetched_tokens + excluded.fetched_tokens,
+                traditional_est = traditional_est + excluded.traditional_est,
+                searches        = searches + excluded.searches,
+                fetches         = fetches + excluded.fetches,
+                pruned_through  = MAX(COALESCE(pruned_through, ''), excluded.pruned_through)",
+            params![self.project_id, cutoff],
+        )?;
+        let deleted = tx.execute(
+            "DELETE FROM accounting WHERE project_id = ?1 AND created_at < datetime('now', ?2)",
+            params![self.project_id, cutoff],
+        )?;
+        tx.commit()?;
+        Ok(deleted as u64)
+    }
+
+    /// One entry per local calendar day for the last `days` days, oldest
+    /// first and ending today. Days without activity are included as zeros.
+    /// Fails with [`HermesError::InvalidInput`] when `days` reaches back
+    /// past the earliest representable date.
+    pub fn get_daily_stats(&self, days: u32) -> Result<Vec<DailyStats>> {
+        let today = Local::now().date_naive();
+        let first = days_before(today, u64::from(days.max(1) - 1))?;
+        self.daily_series(first, today)
+    }
+
+    /// Weekly rollup of [`Self::get_daily_stats`]: the last `weeks` weeks,
+    /// each starting on a Monday, ending with the current (partial) week.
+    pub fn get_weekly_stats(&self, weeks: u32) -> Result<Vec<DailyStats>> {
+        let today = Local::now().date_naive();
+        let this_monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+        let first = days_before(this_monday, 7 * u64::from(weeks.max(1) - 1))?;
+        let days = self.daily_series(first, today)?;
+        Ok(days
+            .chunks(7)
+            .map(|week| {
+                DailyStats::new(
+                    week[0].date.clone(),
+                    week.iter().map(|d| d.queries).sum(),
+                    week.iter().map(|d| d.pointer_tokens).sum(),
+                    week.iter().map(|d| d.fetched_tokens).sum(),
+                    week.iter().map(|d| d.traditional_estimate).sum(),
+                )
+            })
+            .collect())
+    }
+
+    /// The `limit` most frequent, most fetched-from or most saving query
+    /// texts. Texts are grouped after lowercasing and trimming, so `Cache`
+    /// and ` cache ` count as one query.
+    pub fn top_queries(&self, limit: usize, order_by: TopQueryOrder) -> Result<Vec<QueryStats>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT lower(trim(query_text)) AS q,
+                    COUNT(*) AS n,
+                    COALESCE(SUM(pointer_tokens), 0) AS ptr,
+                    COALESCE(SUM(fetched_tokens), 0) AS fetched,
+                    COALESCE(SUM(traditional_est), 0) AS trad
+             FROM accounting
+             WHERE project_id = ?1
+             GROUP BY q
+             ORDER BY {}, q
+             LIMIT ?2",
+            order_by.order_clause()
+        ))?;
+        let rows = stmt
+            .query_map(params![self.project_id, limit as i64], |row| {
+                let pointer_tokens: u64 = row.get(2)?;
+                let fetched_tokens: u64 = row.get(3)?;
+                let traditional_estimate: u64 = row.get(4)?;
+                Ok(QueryStats {
+                    query: row.get(0)?,
+                    count: row.get(1)?,
+                    pointer_tokens,
+                    fetched_tokens,
+                    traditional_estimate,
+                    tokens_saved: traditional_estimate
+                        .saturating_sub(pointer_tokens + fetched_tokens),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Per-session totals, most recently active first.
+    pub fn list_sessions(&self, limit: usize) -> Result<Vec<SessionStats>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT CASE session_id WHEN '' THEN ?2 ELSE session_id END AS session,
+                    MIN(created_at), MAX(created_at) AS last_at,
+                    COUNT(*),
+                    COALESCE(SUM(pointer_tokens), 0),
+                    COALESCE(SUM(fetched_tokens), 0),
+                    COALESCE(SUM(traditional_est), 0)
+             FROM accounting
+             WHERE project_id = ?1
+             GROUP BY session
+             ORDER BY last_at DESC, session DESC
+             LIMIT ?3",
+        )?;
+        let sessions = stmt
+            .query_map(params![self.project_id, LEGACY_SESSION, limit as i64], |row| {
+                let pointer_tokens: u64 = row.get(4)?;
+                let fetched_tokens: u64 = row.get(5)?;
+                let traditional_estimate: u64 = row.get(6)?;
+                let tokens_saved =
+                    traditional_estimate.saturating_sub(pointer_tokens + fetched_tokens);
+                let savings_pct = if traditional_estimate > 0 {
+                    (tokens_saved as f64 / traditional_estimate as f64) * 100.0
+                } else {
+                    0.0
+                };
+                Ok(SessionStats {
+                    session_id: row.get(0)?,
+                    first_query_at: row.get(1)?,
+                    last_query_at: row.get(2)?,
+                    queries: row.get(3)?,
+                    pointer_tokens,
+                    fetched_tokens,
+                    traditional_estimate,
+                    tokens_saved,
+                    savings_pct,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(sessions)
+    }
+
+    fn daily_series(&self, first: NaiveDate, last: NaiveDate) -> Result<Vec<DailyStats>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT date(created_at, 'localtime') AS day,
+                    COUNT(*),
+                    COALESCE(SUM(pointer_tokens), 0),
+                    COALESCE(SUM(fetched_tokens), 0),
+                    COALESCE(SUM(traditional_est), 0)
+             FROM accounting
+             WHERE project_id = ?1 AND date(created_at, 'localtime') BETWEEN ?2 AND ?3
+             GROUP BY day",
+        )?;
+        let by_day = stmt
+            .query_map(
+                params![self.project_id, first.to_string(), last.to_string()],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        (
+                            row.get::<_, u64>(1)?,
+                            row.get::<_, u64>(2)?,
+                            row.get::<_, u64>(3)?,
+                            row.get::<_, u64>(4)?,
+                        ),
+                    ))
+                },
+            )?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .map(|day| {
+                let date = day.to_string();
+                let (queries, pointer, fetched, traditional) =
+                    by_day.get(&date).copied().unwrap_or_default();
+                DailyStats::new(date, queries, pointer, fetched, traditional)
+            })
+            .collect())
+    }
+}
+
+/// Adds the totals of pruned rows to `stats`. Latency percentiles only cover
+/// the rows still in `accounting`.
+fn add_rollup(conn: &Connection, project_id: &str, stats: &mut CumulativeStats) -> Result<()> {
+    let rollup = conn
+        .query_row(
+            "SELECT queries, pointer_tokens, fetched_tokens, traditional_est, searches, fetches
+             FROM accounting_rollup WHERE project_id = ?1",
+            params![project_id],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, u64>(3)?,
+                    row.get::<_, u64>(4)?,
+                    row.get::<_, u64>(5)?,
+                ))
+            },
+        )
+        .optional()?;
+    let Some((queries, pointer, fetched, traditional, searches, fetches)) = rollup else {
+        return Ok(());
+    };
+    stats.total_queries += queries;
+    stats.total_pointer_tokens += pointer;
+    stats.total_fetched_tokens += fetched;
+    stats.total_traditional_estimate += traditional;
+    stats.searches.count += searches;
+    stats.fetches.count += fetches;
+    let actual = stats.total_pointer_tokens + stats.total_fetched_tokens;
+    stats.cumulative_savings_tokens = stats.total_traditional_estimate.saturating_sub(actual);
+    stats.cumulative_savings_pct = if stats.total_traditional_estimate > 0 {
+        (stats.cumulative_savings_tokens as f64 / stats.total_traditional_estimate as f64) * 100.0
+    } else {
+        0.0
+    };
+    Ok(())
+}
+
+/// Search and fetch counts and latency percentiles over the accounting rows
+/// matching `filter`.
+fn operation_breakdown(
+    conn: &Connection,
+    filter: &str,
+    filter_params: &[&dyn rusqlite::ToSql],
+) -> Result<(OperationStats, OperationStats)> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT operation, latency_ms FROM accounting WHERE {filter}"
+    ))?;
+    let rows = stmt
+        .query_map(filter_params, |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let summarize = |operation: Operation| {
+        let mut latencies: Vec<u64> = Vec::new();
+        let mut count = 0;
+        for (op, latency) in &rows {
+            if op == operation.as_str() {
+                count += 1;
+                latencies.extend(latency.map(|ms| ms.max(0) as u64));
+            }
+        }
+        latencies.sort_unstable();
+        OperationStats {
+            count,
+            p50_latency_ms: percentile(&latencies, 50),
+            p95_latency_ms: percentile(&latencies, 95),
+        }
+    };
+    Ok((summarize(Operation::Search), summarize(Operation::Fetch)))
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[u64], pct: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
 pub fn parse_since_duration(s: &str) -> Option<Duration> {
     match s.trim().to_lowercase().as_str() {
         "all" => None,
@@ -199,6 +668,14 @@ pub fn parse_since_duration(s: &str) -> Option<Duration> {
     }
 }
 
+/// `date` moved back `days` days, or [`HermesError::InvalidInput`] when
+/// that falls before the earliest date chrono represents.
+fn days_before(date: NaiveDate, days: u64) -> Result<NaiveDate> {
+    date.checked_sub_days(Days::new(days)).ok_or_else(|| {
+        HermesError::InvalidInput(format!("cannot report stats {days} days back from {date}"))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -303,6 +780,191 @@ mod tests {
         assert_eq!(all.total_queries, 2);
     }
 
+    #[test]
+    fn operations_are_counted_with_latency_percentiles() {
+        let engine = HermesEngine::in_memory("test-ops").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-ops", engine.session_id());
+        for ms in 1..=20 {
+            acct.record_operation(Operation::Search, "q", 10, 0, 100, Some(Duration::from_millis(ms)))
+                .unwrap();
+        }
+        acct.record_operation(Operation::Fetch, "p1", 0, 40, 0, Some(Duration::from_millis(7)))
+            .unwrap();
+        acct.record_query("legacy", 10, 0, 100).unwrap();
+
+        for stats in [
+            acct.get_cumulative_stats().unwrap(),
+            acct.get_session_stats().unwrap(),
+            acct.get_today_stats().unwrap(),
+            acct.get_stats_since(Some(Duration::from_secs(3600))).unwrap(),
+        ] {
+            assert_eq!(stats.total_queries, 22);
+            assert_eq!(stats.searches.count, 21);
+            assert_eq!(stats.searches.p50_latency_ms, Some(10));
+            assert_eq!(stats.searches.p95_latency_ms, Some(19));
+            assert_eq!(stats.fetches.count, 1);
+            assert_eq!(stats.fetches.p50_latency_ms, Some(7));
+        }
+    }
+
+    #[test]
+    fn top_queries_order_by_each_mode() {
+        let engine = HermesEngine::in_memory("test-top").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-top", engine.session_id());
+        // "retry" is the most frequent, "Cache" (in two spellings) fetches
+        // the most, "parser" saves the most.
+        for _ in 0..3 {
+            acct.record_query("retry", 50, 0, 300).unwrap();
+        }
+        acct.record_query("Cache", 50, 400, 500).unwrap();
+        acct.record_query("  cache ", 50, 400, 500).unwrap();
+        acct.record_query("parser", 50, 0, 5000).unwrap();
+
+        let queries = |order| -> Vec<String> {
+            acct.top_queries(10, order).unwrap().into_iter().map(|q| q.query).collect()
+        };
+        assert_eq!(queries(TopQueryOrder::Frequency), ["retry", "cache", "parser"]);
+        assert_eq!(queries(TopQueryOrder::FetchedTokens), ["cache", "retry", "parser"]);
+        assert_eq!(queries(TopQueryOrder::Savings), ["parser", "retry", "cache"]);
+
+        let top = acct.top_queries(1, TopQueryOrder::FetchedTokens).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].count, 2);
+        assert_eq!(top[0].fetched_tokens, 800);
+        assert_eq!(top[0].tokens_saved, 100);
+    }
+
+    #[test]
+    fn list_sessions_groups_legacy_rows() {
+        let engine = HermesEngine::in_memory("test-sessions").unwrap();
+        record_days_ago(&engine, "test-sessions", 3, 100);
+        record_days_ago(&engine, "test-sessions", 2, 100);
+        let old = Accountant::new(engine.db().clone(), "test-sessions", "2026-01-01");
+        old.record_query("a", 100, 0, 1000).unwrap();
+        engine
+            .db()
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE accounting SET created_at = datetime('now', '-1 days') WHERE session_id = '2026-01-01'",
+                [],
+            )
+            .unwrap();
+        let current = Accountant::new(engine.db().clone(), "test-sessions", "2026-01-02");
+        current.record_query("b", 100, 0, 1000).unwrap();
+        current.record_query("c", 300, 0, 1000).unwrap();
+
+        let sessions = current.list_sessions(10).unwrap();
+        let ids: Vec<&str> = sessions.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, ["2026-01-02", "2026-01-01", LEGACY_SESSION]);
+        assert_eq!(sessions[0].queries, 2);
+        assert_eq!(sessions[0].tokens_saved, 1600);
+        assert_eq!(sessions[2].queries, 2);
+        assert!(sessions[2].first_query_at < sessions[2].last_query_at);
+
+        assert_eq!(current.list_sessions(1).unwrap().len(), 1);
+    }
+
+    fn record_days_ago(engine: &HermesEngine, project: &str, days_ago: u32, pointer_tokens: i64) {
+        let conn = engine.db().lock().unwrap();
+        conn.execute(
+            "INSERT INTO accounting (project_id, query_text, pointer_tokens, traditional_est, created_at)
+             VALUES (?1, 'q', ?2, 1000, datetime('now', ?3))",
+            params![project, pointer_tokens, format!("-{days_ago} days")],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_cumulative_totals() {
+        let engine = HermesEngine::in_memory("test-prune").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-prune", engine.session_id());
+        record_days_ago(&engine, "test-prune", 120, 100);
+        record_days_ago(&engine, "test-prune", 100, 200);
+        record_days_ago(&engine, "test-prune", 10, 300);
+        acct.record_operation(Operation::Fetch, "p1", 0, 50, 0, None).unwrap();
+        let before = acct.get_cumulative_stats().unwrap();
+
+        let ninety_days = parse_since_duration("90d").unwrap();
+        assert_eq!(acct.prune_older_than(ninety_days).unwrap(), 2);
+        assert_eq!(acct.prune_older_than(ninety_days).unwrap(), 0);
+        let remaining: u64 = engine
+            .db()
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM accounting", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 2);
+
+        let after = acct.get_cumulative_stats().unwrap();
+        assert_eq!(after.total_queries, before.total_queries);
+        assert_eq!(after.total_pointer_tokens, 600);
+        assert_eq!(after.total_fetched_tokens, before.total_fetched_tokens);
+        assert_eq!(after.cumulative_savings_tokens, before.cumulative_savings_tokens);
+        assert_eq!(after.fetches.count, 1);
+
+        // A second prune adds to the rollup instead of replacing it.
+        assert_eq!(acct.prune_older_than(Duration::from_secs(86_400)).unwrap(), 1);
+        let again = acct.get_cumulative_stats().unwrap();
+        assert_eq!(again.total_queries, 4);
+        assert_eq!(again.total_pointer_tokens, 600);
+        assert_eq!(acct.get_stats_since(Some(ninety_days)).unwrap().total_queries, 1);
+    }
+
+    #[test]
+    fn daily_stats_fill_gaps_with_zeros() {
+        let engine = HermesEngine::in_memory("test-daily").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-daily", engine.session_id());
+        record_days_ago(&engine, "test-daily", 0, 100);
+        record_days_ago(&engine, "test-daily", 2, 50);
+        record_days_ago(&engine, "test-daily", 2, 150);
+        record_days_ago(&engine, "test-daily", 30, 10);
+
+        let daily = acct.get_daily_stats(4).unwrap();
+        assert_eq!(daily.len(), 4);
+        assert_eq!(daily[3].date, Local::now().date_naive().to_string());
+        let queries: Vec<u64> = daily.iter().map(|d| d.queries).collect();
+        assert_eq!(queries, [0, 2, 0, 1]);
+        assert_eq!(daily[1].pointer_tokens, 200);
+        assert_eq!(daily[1].tokens_saved, 1800);
+        assert_eq!(daily[0].savings_pct, 0.0);
+    }
+
+    #[test]
+    fn weekly_stats_roll_up_days_from_monday() {
+        let engine = HermesEngine::in_memory("test-weekly").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-weekly", engine.session_id());
+        record_days_ago(&engine, "test-weekly", 0, 100);
+        record_days_ago(&engine, "test-weekly", 7, 100);
+        record_days_ago(&engine, "test-weekly", 8, 100);
+
+        let weekly = acct.get_weekly_stats(3).unwrap();
+        assert_eq!(weekly.len(), 3);
+        for week in &weekly {
+            let start = NaiveDate::parse_from_str(&week.date, "%Y-%m-%d").unwrap();
+            assert_eq!(start.weekday(), chrono::Weekday::Mon);
+        }
+        assert_eq!(weekly.iter().map(|w| w.queries).sum::<u64>(), 3);
+        assert_eq!(weekly[2].queries, 1);
+    }
+
+    #[test]
+    fn stats_reaching_past_the_calendar_are_rejected() {
+        let engine = HermesEngine::in_memory("test-stats-range").unwrap();
+        let acct = Accountant::new(engine.db().clone(), "test-stats-range", engine.session_id());
+        for result in [acct.get_daily_stats(200_000_000), acct.get_weekly_stats(u32::MAX)] {
+            assert!(matches!(result, Err(HermesError::InvalidInput(_))));
+        }
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&[5], 95), Some(5));
+        assert_eq!(percentile(&[1, 2, 3, 4], 50), Some(2));
+        assert_eq!(percentile(&[1, 2, 3, 4], 95), Some(4));
+    }
+
     #[test]
     fn savings_pct_zero_when_no_traditional_estimate() {
         let engine = HermesEngine::in_memory("test-zero-est").unwrap();
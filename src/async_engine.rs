@@ -0,0 +1,190 @@
+//! An async front for [`HermesEngine`], for embedding Hermes in tokio services.
+//!
+//! Every call runs the synchronous engine method on tokio's blocking pool, so
+//! the connection mutex and file reads never stall a runtime worker. The lock
+//! is taken and released inside the blocking closure and is never held across
+//! an `.await`.
+
+use crate::error::{HermesError, Result};
+use crate::facade::{EngineStats, IndexOptions};
+use crate::ingestion::IngestionReport;
+use crate::pointer::{FetchResponse, PointerResponse};
+use crate::search::SearchOptions;
+use crate::temporal::{FactType, TemporalFact};
+use crate::HermesEngine;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Wraps a [`HermesEngine`]; clones share the same engine, connection and
+/// caches, so one handle can be cloned into every request handler.
+#[derive(Clone)]
+pub struct AsyncHermesEngine {
+    engine: HermesEngine,
+}
+
+impl AsyncHermesEngine {
+    pub fn new(engine: HermesEngine) -> Self {
+        Self { engine }
+    }
+
+    /// The wrapped engine, for synchronous calls outside the runtime.
+    pub fn engine(&self) -> &HermesEngine {
+        &self.engine
+    }
+
+    /// See [`HermesEngine::search`].
+    pub async fn search(&self, query: String, opts: SearchOptions) -> Result<PointerResponse> {
+        self.run(move |engine| engine.search(&query, &opts)).await
+    }
+
+    /// See [`HermesEngine::search_compact`].
+    pub async fn search_compact(&self, query: String, opts: SearchOptions) -> Result<String> {
+        self.run(move |engine| engine.search_compact(&query, &opts)).await
+    }
+
+    /// See [`HermesEngine::fetch`].
+    pub async fn fetch(
+        &self,
+        node_id: String,
+        max_tokens: Option<u64>,
+    ) -> Result<Option<FetchResponse>> {
+        self.run(move |engine| engine.fetch(&node_id, max_tokens)).await
+    }
+
+    /// See [`HermesEngine::fetch_range`].
+    pub async fn fetch_range(
+        &self,
+        project_root: PathBuf,
+        file_path: String,
+        start: i64,
+        end: i64,
+        max_tokens: Option<u64>,
+    ) -> Result<FetchResponse> {
+        self.run(move |engine| {
+            engine.fetch_range(&project_root, &file_path, start, end, max_tokens)
+        })
+        .await
+    }
+
+    /// See [`HermesEngine::index`].
+    pub async fn index(&self, project_root: PathBuf, opts: IndexOptions) -> Result<IngestionReport> {
+        self.run(move |engine| engine.index(&project_root, &opts)).await
+    }
+
+    /// See [`HermesEngine::stats`].
+    pub async fn stats(&self, since: Option<Duration>) -> Result<EngineStats> {
+        self.run(move |engine| engine.stats(since)).await
+    }
+
+    /// See [`HermesEngine::add_fact`].
+    pub async fn add_fact(
+        &self,
+        node_id: Option<String>,
+        fact_type: FactType,
+        content: String,
+        source_reference: Option<String>,
+    ) -> Result<String> {
+        self.run(move |engine| {
+            engine.add_fact(node_id.as_deref(), fact_type, &content, source_reference.as_deref())
+        })
+        .await
+    }
+
+    /// See [`HermesEngine::invalidate_fact`].
+    pub async fn invalidate_fact(
+        &self,
+        fact_id: String,
+        superseded_by: Option<String>,
+    ) -> Result<()> {
+        self.run(move |engine| engine.invalidate_fact(&fact_id, superseded_by.as_deref())).await
+    }
+
+    /// See [`crate::temporal::TemporalStore::get_active_facts`].
+    pub async fn active_facts(&self, fact_type: Option<FactType>) -> Result<Vec<TemporalFact>> {
+        self.run(move |engine| engine.temporal_store().get_active_facts(fact_type.as_ref()))
+            .await
+    }
+
+    /// See [`crate::temporal::TemporalStore::get_fact_history`].
+    pub async fn fact_history(&self, node_id: String) -> Result<Vec<TemporalFact>> {
+        self.run(move |engine| engine.temporal_store().get_fact_history(&node_id)).await
+    }
+
+    /// Runs `op` on the blocking pool against a clone of the engine. A panic
+    /// in `op` resumes on the caller, as it would have in a synchronous call.
+    async fn run<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&HermesEngine) -> Result<T> + Send + 'static,
+    {
+        let engine = self.engine.clone();
+        match tokio::task::spawn_blocking(move || op(&engine)).await {
+            Ok(result) => result,
+            Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+            Err(error) => Err(HermesError::Io(std::io::Error::other(error))),
+        }
+    }
+}
+
+impl From<HermesEngine> for AsyncHermesEngine {
+    fn from(engine: HermesEngine) -> Self {
+        Self::new(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchMode;
+
+    fn indexed_engine(dir: &tempfile::TempDir) -> AsyncHermesEngine {
+        for (name, body) in [("config.rs", "parse_config"), ("server.rs", "start_server")] {
+            let source = format!("pub fn {body}() -> u32 {{\n    7\n}}\n");
+            std::fs::write(dir.path().join(name), source).unwrap();
+        }
+        AsyncHermesEngine::new(HermesEngine::in_memory("async").unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_searches_share_one_engine() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = indexed_engine(&dir);
+        let report = engine.index(dir.path().to_path_buf(), IndexOptions::default()).await;
+        assert_eq!(report.unwrap().indexed, 2);
+
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..SearchOptions::default() };
+        let mut searches = tokio::task::JoinSet::new();
+        for i in 0..16 {
+            let engine = engine.clone();
+            let query = if i % 2 == 0 { "parse_config" } else { "start_server" };
+            let opts = opts.clone();
+            searches.spawn(async move { (query, engine.search(query.to_string(), opts).await) });
+        }
+        while let Some(joined) = searches.join_next().await {
+            let (query, response) = joined.unwrap();
+            let response = response.unwrap();
+            let pointer = response.pointers.first().expect("indexed function is found");
+            let fetched = engine.fetch(pointer.id.clone(), None).await.unwrap().unwrap();
+            assert!(fetched.content.contains(query));
+        }
+
+        let stats = engine.stats(None).await.unwrap();
+        assert_eq!(stats.cumulative.searches.count, 16);
+        assert_eq!(stats.cumulative.fetches.count, 16);
+    }
+
+    #[tokio::test]
+    async fn facts_round_trip_through_the_blocking_pool() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = indexed_engine(&dir);
+        let content = "keep config parsing pure".to_string();
+        let first = engine.add_fact(None, FactType::Decision, content, None).await.unwrap();
+        let content = "config parsing may read env".to_string();
+        let second = engine.add_fact(None, FactType::Decision, content, None).await.unwrap();
+        engine.invalidate_fact(first, Some(second.clone())).await.unwrap();
+
+        let active = engine.active_facts(Some(FactType::Decision)).await.unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, second);
+    }
+}
@@ -1,15 +1,28 @@
 use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
 use hermes_engine::{
-    accounting::{parse_since_duration, Accountant},
-    graph::KnowledgeGraph,
-    ingestion::IngestionPipeline,
+    accounting::{parse_since_duration, TopQueryOrder},
+    facade::{EngineStats, IndexOptions},
+    graph::{EdgeDirection, EdgeType, MetadataFilter, NodeType},
+    graph_export::{ExportOptions, ImportMode},
+    maintenance::MaintenanceOp,
+    ingestion::{IngestionPhase, IngestionProgress, ProgressSink},
     mcp_server,
-    search::{SearchEngine, SearchMode},
-    temporal::{FactType, TemporalStore},
-    HermesEngine,
+    search::{aliases, clamp_top_k, SearchMode, SearchOptions, DEFAULT_NEIGHBOR_LIMIT},
+    temporal::FactType,
+    HermesConfig, HermesEngine,
+};
+use std::{
+    env,
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
-use std::{env, path::PathBuf};
 
 #[derive(Parser)]
 #[command(name = "hermes", about = "Token-efficient code navigation", arg_required_else_help = true, after_help = "\
@@ -17,7 +30,40 @@ Environment variables:
   HERMES_PROJECT_ROOT             Root directory to index (default: cwd)
   HERMES_DB_PATH                  SQLite DB path (default: <project_root>/.hermes.db)
   HERMES_AUTO_INDEX_INTERVAL_SECS Re-index interval when running as MCP server
-                                  (default: 300 = 5 min; 0 = disabled)")]
+                                  (default: 300 = 5 min; 0 = disabled)
+  HERMES_WATCH                    1 = also re-index changed files as they change when
+                                  running as MCP server (default: unset)
+  HERMES_ACCOUNTING_RETENTION_DAYS
+                                  Prune accounting rows older than this many days
+                                  after each auto-reindex (default: unset = keep all)
+  HERMES_AUTO_OPTIMIZE            1 = run PRAGMA optimize after each auto-reindex (default: unset)
+  HERMES_INDEX_NOTIFICATIONS      1 = notify MCP clients when a background re-index finishes (default: unset)
+  HERMES_CACHE_TTL_SECS           Search cache lifetime in seconds (default: 60)
+  HERMES_CACHE_MAX_ENTRIES        Cached search responses kept (default: 256)
+  HERMES_FETCH_CACHE_MAX_ENTRIES  Cached fetch contents kept (default: 50)
+  HERMES_SHORT_CIRCUIT_SKIP_ALL   Literal score that skips FTS + vector tiers
+                                  (default: 0.9; >1.0 = never)
+  HERMES_SHORT_CIRCUIT_SKIP_L2    Literal score that skips the vector tier (default: 0.8)
+  HERMES_DEFAULT_TOP_K            Results returned when top_k is omitted (default: 10)
+  HERMES_RRF_K                    Rank fusion constant k in 1/(k + rank) (default: 60)
+  HERMES_OVERLAP_EPSILON          Score gap under which nested results from one file are both kept (default: 0.01)
+  HERMES_FTS_WEIGHT_NAME          bm25 weight of the name column (default: 5)
+  HERMES_FTS_WEIGHT_CONTENT       bm25 weight of the content column (default: 1)
+  HERMES_FTS_WEIGHT_FILE_PATH     bm25 weight of the file_path column (default: 2)
+  HERMES_EMBEDDING_PROVIDER       auto, hashed, gemini or ollama (default: auto = gemini if GEMINI_API_KEY is set)
+  OLLAMA_BASE_URL                 Ollama server for the ollama provider (default: http://localhost:11434)
+  OLLAMA_EMBED_MODEL              Ollama embedding model (default: nomic-embed-text)
+  HERMES_ANN_THRESHOLD            Node count from which vector search uses an ANN index (default: 5000)
+  HERMES_VECTOR_STOPWORDS         Drop English stopwords from hashed vectors (default: true)
+  HERMES_VECTOR_STEMMING          Strip plural/verb suffixes in hashed vectors (default: true)
+  HERMES_CRAWL_HIDDEN             Also index hidden directories such as .github (default: false)
+  HERMES_EXTRA_EXTENSIONS         More file extensions to index, e.g. proto,sql (default: none)
+  HERMES_MAX_FILE_BYTES           Files larger than this are not indexed (default: 1048576)
+  HERMES_BUSY_TIMEOUT_MS          How long to wait for another process's database lock (default: 5000)
+  HERMES_READ_ONLY                1 = open the database read-only: queries work, writes fail,
+                                  searches are not recorded (default: unset)
+
+Indexing skips paths matched by .gitignore files and by <project_root>/.hermesignore.")]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
@@ -25,32 +71,202 @@ struct Cli {
     /// Run as MCP JSON-RPC 2.0 stdio server
     #[arg(long)]
     stdio: bool,
+
+    /// Serve MCP to many clients on a TCP address (127.0.0.1:7420) or a Unix
+    /// socket (unix:/tmp/hermes.sock); each connection is its own session
+    #[arg(long, value_name = "ADDR", conflicts_with = "stdio")]
+    listen: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Re-index the project (run when files change)
-    Index,
+    /// [path...] - Re-index the project (run when files change)
+    Index {
+        /// Only re-index these files or directories, absolute or relative to the project root
+        paths: Vec<PathBuf>,
+
+        /// Afterwards also delete edges, FTS rows and hashes left dangling (see `hermes doctor`)
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Re-index files as they change, until interrupted
+    Watch,
 
     /// <query> - Search codebase; returns pointers (no full content)
     Search {
         query: String,
+
+        /// Maximum number of pointers to return (clamped to 1-100; default: HERMES_DEFAULT_TOP_K or 10)
+        #[arg(long, conflicts_with = "token_budget")]
+        top_k: Option<usize>,
+
+        /// Return as many pointers as fit in this many tokens instead of --top-k
+        #[arg(long)]
+        token_budget: Option<u64>,
+
+        /// Drop results whose fused relevance is below this score
+        #[arg(long)]
+        min_score: Option<f64>,
+
+        /// Output detail: pointer, smart (adds a preview) or full (inlines content)
+        #[arg(long, default_value = "smart")]
+        mode: String,
+
+        /// Restrict results to a path prefix (src/search/) or glob (**/*.md)
+        #[arg(long)]
+        path_filter: Option<String>,
+
+        /// Only return nodes with this metadata value, e.g. language=rust; repeatable
+        #[arg(long = "where", value_name = "KEY=VALUE")]
+        metadata: Vec<String>,
+
+        /// Omit the per-result match excerpt to save tokens
+        #[arg(long)]
+        no_snippets: bool,
+
+        /// Append contained/called neighbors of the top hits
+        #[arg(long)]
+        expand: bool,
+
+        /// Do not attach recorded facts relevant to the results
+        #[arg(long)]
+        no_facts: bool,
+
+        /// Add a per-tier scoring breakdown to the response (bypasses the cache)
+        #[arg(long)]
+        explain: bool,
+
+        /// Print one compact line per pointer instead of JSON
+        #[arg(long)]
+        plain: bool,
     },
 
-    /// <node_id> - Fetch full content for a specific pointer
+    /// <node_id> | --file <path> --lines <start-end> - Fetch content for a pointer or a file range
     Fetch {
+        /// Pointer ID from a search (p1, p2, …) or a node ID
+        #[arg(required_unless_present = "file", conflicts_with = "file")]
+        node_id: Option<String>,
+
+        /// File to read directly (absolute or relative to the project root)
+        #[arg(long, requires = "lines")]
+        file: Option<String>,
+
+        /// Line range for --file, e.g. 10-40
+        #[arg(long, requires = "file")]
+        lines: Option<String>,
+
+        /// Truncate the content to about this many tokens (whole lines)
+        #[arg(long)]
+        max_tokens: Option<u64>,
+    },
+
+    /// <node_id> - List the nodes connected to a pointer, as pointers
+    Neighbors {
+        /// Pointer ID from a search (p1, p2, …) or a node ID
         node_id: String,
+
+        /// Only follow edges of this type (calls, imports, implements, depends_on, contains,
+        /// documents); repeatable
+        #[arg(long = "edge", value_name = "TYPE")]
+        edges: Vec<String>,
+
+        /// Only edges from the node (what it contains or calls)
+        #[arg(long, conflicts_with = "incoming")]
+        out: bool,
+
+        /// Only edges to the node (what contains or calls it)
+        #[arg(long = "in")]
+        incoming: bool,
+
+        /// Maximum number of neighbors to list
+        #[arg(long, default_value_t = DEFAULT_NEIGHBOR_LIMIT)]
+        limit: usize,
     },
 
-    /// <type> <text> - Record a decision/learning (types: architecture, decision, learning, constraint, error_pattern, api_contract)
+    /// <type> <text> - Record a decision/learning (types: architecture, decision, learning, constraint, error_pattern, api_contract, or a custom name such as todo)
     Fact {
         fact_type: String,
         content: String,
+
+        /// Attach the fact to this indexed file, e.g. src/search/fts.rs
+        #[arg(long, value_name = "PATH")]
+        file: Option<String>,
     },
 
-    /// [type] - List active facts, optionally filtered by type
+    /// [type] - List active facts, optionally filtered by type; show, edit or purge one fact, or list the types in use
+    #[command(args_conflicts_with_subcommands = true)]
     Facts {
+        #[command(subcommand)]
+        action: Option<FactCommand>,
+
         filter: Option<String>,
+
+        /// List the facts valid at this RFC 3339 time or date (midnight UTC), e.g. 2024-03-01
+        #[arg(long, value_name = "TIME")]
+        as_of: Option<String>,
+    },
+
+    /// Write the graph to stdout as Graphviz DOT or JSON, e.g. `hermes export --root p3 > graph.dot`
+    Export {
+        /// Output format: dot or json (every node and edge, for `hermes import`)
+        #[arg(long, default_value = "dot")]
+        format: String,
+
+        /// Only draw the subgraph reachable from this pointer or node ID (dot only)
+        #[arg(long)]
+        root: Option<String>,
+
+        /// Hops from --root
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+
+        /// Only draw nodes of this type (file, function, struct, …); repeatable (dot only)
+        #[arg(long = "node-type", value_name = "TYPE")]
+        node_types: Vec<String>,
+
+        /// Draw at most this many nodes (dot only)
+        #[arg(long)]
+        max_nodes: Option<usize>,
+    },
+
+    /// <file> - Load a graph written by `hermes export --format json` (`-` reads stdin)
+    Import {
+        file: PathBuf,
+
+        /// Delete the project's graph first instead of merging into it
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// Show index size and health: counts per node and edge type, orphaned edges, DB size
+    GraphStats,
+
+    /// Delete orphaned edges, FTS rows and embeddings, and hashes of files without nodes
+    Doctor {
+        /// Only count what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List the projects indexed in this database, with node counts and last index time
+    Projects,
+
+    /// Manage the projects sharing this database
+    Project {
+        #[command(subcommand)]
+        action: ProjectCommand,
+    },
+
+    /// Compact, analyze or check the database file
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+
+    /// <name> - List functions that call <name> (or Type::<name>), most mentions first
+    Callers {
+        name: String,
     },
 
     /// [duration] or [--since <duration>] - Show token savings (duration: 24h, 7d, 30d, all)
@@ -61,6 +277,84 @@ enum Commands {
         /// Explicit duration flag (e.g., `hermes stats --since 24h`)
         #[arg(long = "since")]
         since_flag: Option<String>,
+
+        /// Also report per-day totals for the last N days (e.g., `--daily 14`)
+        #[arg(long, value_name = "DAYS", conflicts_with = "weekly")]
+        daily: Option<u32>,
+
+        /// Also report per-week totals for the last N weeks, starting Mondays
+        #[arg(long, value_name = "WEEKS")]
+        weekly: Option<u32>,
+
+        /// Also report the N top query texts (e.g., `--top-queries 20`)
+        #[arg(long, value_name = "N")]
+        top_queries: Option<usize>,
+
+        /// Ranking for --top-queries: frequency, fetched_tokens or savings
+        #[arg(long, default_value = "frequency", requires = "top_queries")]
+        order_by: String,
+
+        /// Delete accounting rows older than this (e.g., `--prune 90d`);
+        /// cumulative totals keep counting them
+        #[arg(long, value_name = "AGE")]
+        prune: Option<String>,
+
+        /// Also list per-session totals, most recent first (default: 20 sessions)
+        #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "20")]
+        sessions: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// <id> - Delete every node, edge, fact, hash and accounting row of a project
+    Delete {
+        project_id: String,
+
+        /// Confirm the deletion; without it only the project's size is reported
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum FactCommand {
+    /// List the types of the active facts, built-in and custom, with their counts
+    Types,
+    /// <id> - Print one fact, valid or not
+    Show {
+        fact_id: String,
+    },
+    /// <id> <text> - Correct a fact; the old text stays in its history
+    Edit {
+        fact_id: String,
+        content: String,
+    },
+    /// <id> - Permanently delete a fact with every fact it superseded or was superseded by
+    Purge {
+        fact_id: String,
+
+        /// Confirm the deletion; without it only the chain's size is reported
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Reclaim the space of deleted rows and truncate the write-ahead log
+    Vacuum,
+    /// Refresh the query planner's statistics
+    Analyze,
+    /// Run SQLite's integrity check; fails if it reports any problem
+    Check,
+    /// <file> - Copy the database to <file>, also while the MCP server is writing
+    Backup {
+        file: PathBuf,
+    },
+    /// <file> - Replace the database with a backup that passes the integrity check
+    Restore {
+        file: PathBuf,
     },
 }
 
@@ -72,16 +366,157 @@ fn main() -> Result<()> {
     if cli.stdio {
         return mcp_server::run(&engine, &project_root);
     }
+    if let Some(addr) = cli.listen {
+        return mcp_server::run_listener(&engine, &project_root, &addr);
+    }
 
     match cli.command.unwrap() {
-        Commands::Index => cmd_index(&engine, &project_root),
-        Commands::Search { query } => cmd_search(&engine, &query),
-        Commands::Fetch { node_id } => cmd_fetch(&engine, &node_id),
-        Commands::Fact { fact_type, content } => cmd_add_fact(&engine, &fact_type, &content),
-        Commands::Facts { filter } => cmd_list_facts(&engine, filter.as_deref()),
-        Commands::Stats { since, since_flag } => {
+        Commands::Index { paths, repair } => cmd_index(&engine, &project_root, &paths, repair),
+        Commands::Watch => {
+            eprintln!("Watching {} for changes (Ctrl-C to stop)", project_root.display());
+            mcp_server::watch(&engine, &project_root, &AtomicBool::new(false), |report| {
+                println!("{report}")
+            })
+        }
+        Commands::Search {
+            query,
+            top_k,
+            token_budget,
+            min_score,
+            mode,
+            path_filter,
+            metadata,
+            no_snippets,
+            expand,
+            no_facts,
+            explain,
+            plain,
+        } => {
+            let Some(mode) = SearchMode::parse_str(&mode) else {
+                bail!("invalid --mode '{mode}' (expected pointer, smart or full)");
+            };
+            let top_k = top_k.map_or_else(|| engine.config().effective_top_k(), clamp_top_k);
+            let metadata_filters = metadata
+                .iter()
+                .map(|f| {
+                    MetadataFilter::parse_str(f).ok_or_else(|| {
+                        anyhow::anyhow!("invalid --where '{f}' (expected KEY=VALUE)")
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let opts = SearchOptions {
+                top_k,
+                mode,
+                path_filter,
+                metadata_filters,
+                snippets: !no_snippets,
+                expand_graph: expand,
+                token_budget,
+                min_score,
+                explain,
+                facts: !no_facts,
+            };
+            cmd_search(&engine, &query, &opts, plain)
+        }
+        Commands::Fetch { node_id, file, lines, max_tokens } => match (node_id, file, lines) {
+            (Some(node_id), _, _) => cmd_fetch(&engine, &node_id, max_tokens),
+            (None, Some(file), Some(lines)) => {
+                let Some((start, end)) = parse_line_range(&lines) else {
+                    bail!("invalid --lines '{lines}' (expected <start>-<end>, e.g. 10-40)");
+                };
+                cmd_fetch_range(&engine, &project_root, &file, start, end, max_tokens)
+            }
+            _ => bail!("fetch requires a node id or --file with --lines"),
+        },
+        Commands::Fact { fact_type, content, file } => {
+            cmd_add_fact(&engine, &project_root, &fact_type, &content, file.as_deref())
+        }
+        Commands::Facts { action: Some(action), .. } => cmd_fact(&engine, action),
+        Commands::Facts { action: None, filter, as_of } => {
+            cmd_list_facts(&engine, filter.as_deref(), as_of.as_deref())
+        }
+        Commands::Neighbors { node_id, edges, out, incoming, limit } => {
+            let direction = match (out, incoming) {
+                (true, _) => EdgeDirection::Outgoing,
+                (_, true) => EdgeDirection::Incoming,
+                _ => EdgeDirection::Both,
+            };
+            let edge_types = edges
+                .iter()
+                .map(|name| {
+                    EdgeType::ALL
+                        .into_iter()
+                        .find(|t| t.as_str() == name)
+                        .ok_or_else(|| anyhow::anyhow!("unknown edge type '{name}'"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let edge_types = (!edge_types.is_empty()).then_some(edge_types.as_slice());
+            cmd_neighbors(&engine, &node_id, direction, edge_types, limit)
+        }
+        Commands::Projects => {
+            let output = serde_json::json!({
+                "current": engine.project_id(),
+                "projects": engine.list_projects()?,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            Ok(())
+        }
+        Commands::Project { action: ProjectCommand::Delete { project_id, yes } } => {
+            cmd_delete_project(&engine, &project_id, yes)
+        }
+        Commands::Db { action } => cmd_db(&engine, action),
+        Commands::Callers { name } => cmd_callers(&engine, &name),
+        Commands::Export { format, root, depth, node_types, max_nodes } => {
+            cmd_export(&engine, &format, root.as_deref(), depth, &node_types, max_nodes)
+        }
+        Commands::Import { file, replace } => {
+            let mode = if replace { ImportMode::Replace } else { ImportMode::Merge };
+            cmd_import(&engine, &file, mode)
+        }
+        Commands::GraphStats => {
+            let graph = engine.graph();
+            println!("{}", serde_json::to_string_pretty(&graph.stats()?)?);
+            Ok(())
+        }
+        Commands::Doctor { dry_run } => {
+            let graph = engine.graph();
+            let report = if dry_run { graph.diagnose()? } else { graph.repair()? };
+            if !dry_run && report.total() > 0 {
+                engine.invalidate_search_cache();
+            }
+            let output = serde_json::json!({
+                "dry_run": dry_run,
+                "orphaned_edges": report.orphaned_edges,
+                "orphaned_fts_rows": report.orphaned_fts_rows,
+                "orphaned_embeddings": report.orphaned_embeddings,
+                "stale_file_hashes": report.stale_file_hashes,
+                "stale_chunk_hashes": report.stale_chunk_hashes,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            Ok(())
+        }
+        Commands::Stats {
+            since,
+            since_flag,
+            daily,
+            weekly,
+            top_queries,
+            order_by,
+            prune,
+            sessions,
+        } => {
             let effective_since = since_flag.as_deref().or(since.as_deref());
-            cmd_stats(&engine, effective_since)
+            let Some(order_by) = TopQueryOrder::parse_str(&order_by) else {
+                bail!("invalid --order-by '{order_by}' (expected frequency, fetched_tokens or savings)");
+            };
+            let top_queries = top_queries.map(|limit| (limit, order_by));
+            let prune = match prune.as_deref() {
+                None => None,
+                Some(age) => Some(parse_since_duration(age).ok_or_else(|| {
+                    anyhow::anyhow!("invalid --prune '{age}' (expected a duration like 90d or 12h)")
+                })?),
+            };
+            cmd_stats(&engine, effective_since, daily, weekly, top_queries, prune, sessions)
         }
     }
 }
@@ -101,84 +536,400 @@ fn open_engine() -> Result<(HermesEngine, PathBuf)> {
         .unwrap_or("unknown")
         .to_string();
 
-    let engine = HermesEngine::new(&db_path, &project_id)?;
+    let engine = if matches!(env::var("HERMES_READ_ONLY").as_deref(), Ok("1" | "true")) {
+        HermesEngine::open_read_only(&db_path, &project_id)?
+    } else {
+        HermesEngine::new(&db_path, &project_id)?
+    };
+    let engine = engine.with_config(config_from_env()?);
     Ok((engine, project_root))
 }
 
-fn cmd_index(engine: &HermesEngine, project_root: &std::path::Path) -> Result<()> {
-    let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let pipeline = IngestionPipeline::new(&graph);
-    let report = pipeline.ingest_directory(project_root)?;
-    engine.invalidate_search_cache();
-    let output = serde_json::json!({
+fn config_from_env() -> Result<HermesConfig> {
+    let mut config = HermesConfig::default();
+    env_override("HERMES_CACHE_TTL_SECS", &mut config.cache_ttl_secs)?;
+    env_override("HERMES_CACHE_MAX_ENTRIES", &mut config.cache_max_entries)?;
+    env_override("HERMES_FETCH_CACHE_MAX_ENTRIES", &mut config.fetch_cache_max_entries)?;
+    env_override("HERMES_SHORT_CIRCUIT_SKIP_ALL", &mut config.short_circuit_skip_all)?;
+    env_override("HERMES_SHORT_CIRCUIT_SKIP_L2", &mut config.short_circuit_skip_l2)?;
+    env_override("HERMES_DEFAULT_TOP_K", &mut config.default_top_k)?;
+    env_override("HERMES_RRF_K", &mut config.rrf_k)?;
+    env_override("HERMES_OVERLAP_EPSILON", &mut config.overlap_epsilon)?;
+    env_override("HERMES_FTS_WEIGHT_NAME", &mut config.fts_weights.name)?;
+    env_override("HERMES_FTS_WEIGHT_CONTENT", &mut config.fts_weights.content)?;
+    env_override("HERMES_FTS_WEIGHT_FILE_PATH", &mut config.fts_weights.file_path)?;
+    env_override("HERMES_EMBEDDING_PROVIDER", &mut config.embedding_provider)?;
+    env_override("HERMES_ANN_THRESHOLD", &mut config.ann_threshold)?;
+    env_override("HERMES_VECTOR_STOPWORDS", &mut config.vector_tokenizer.stopwords)?;
+    env_override("HERMES_VECTOR_STEMMING", &mut config.vector_tokenizer.stemming)?;
+    env_override("HERMES_CRAWL_HIDDEN", &mut config.crawl.include_hidden)?;
+    env_override("HERMES_MAX_FILE_BYTES", &mut config.crawl.max_file_bytes)?;
+    env_override("HERMES_BUSY_TIMEOUT_MS", &mut config.busy_timeout_ms)?;
+    if let Ok(raw) = env::var("HERMES_EXTRA_EXTENSIONS") {
+        config.crawl.extra_extensions = raw
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+    }
+    Ok(config)
+}
+
+/// Overwrites `slot` when `var` is set; an unparseable value is an error
+/// rather than being silently ignored.
+fn env_override<T: FromStr>(var: &str, slot: &mut T) -> Result<()> {
+    let Ok(raw) = env::var(var) else {
+        return Ok(());
+    };
+    match raw.trim().parse() {
+        Ok(value) => {
+            *slot = value;
+            Ok(())
+        }
+        Err(_) => bail!("invalid {var}='{raw}'"),
+    }
+}
+
+fn cmd_delete_project(engine: &HermesEngine, project_id: &str, yes: bool) -> Result<()> {
+    let projects = engine.list_projects()?;
+    let Some(project) = projects.iter().find(|p| p.project_id == project_id) else {
+        let known: Vec<&str> = projects.iter().map(|p| p.project_id.as_str()).collect();
+        bail!("no project '{project_id}' in this database (found: {})", known.join(", "));
+    };
+    if !yes {
+        bail!(
+            "this deletes project '{project_id}' ({} nodes) with its facts and accounting; \
+             re-run with --yes to confirm",
+            project.node_count
+        );
+    }
+    let rows = engine.delete_project(project_id)?;
+    let output = serde_json::json!({ "deleted_project": project_id, "rows_deleted": rows });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn cmd_db(engine: &HermesEngine, action: DbCommand) -> Result<()> {
+    let ops: &[MaintenanceOp] = match action {
+        DbCommand::Backup { file } => {
+            let bytes = engine.backup_to(&file)?;
+            let output = serde_json::json!({ "backup": file, "bytes": bytes });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            return Ok(());
+        }
+        DbCommand::Restore { file } => {
+            let bytes = engine.restore_from(&file)?;
+            let output = serde_json::json!({ "restored_from": file, "bytes": bytes });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            return Ok(());
+        }
+        // VACUUM in WAL mode writes the compacted pages to the log, so the
+        // checkpoint is what shrinks the files on disk.
+        DbCommand::Vacuum => &[MaintenanceOp::Vacuum, MaintenanceOp::Checkpoint],
+        DbCommand::Analyze => &[MaintenanceOp::Analyze],
+        DbCommand::Check => &[MaintenanceOp::IntegrityCheck],
+    };
+    let report = engine.maintenance(ops)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if let Some(errors) = report.integrity_errors.as_ref().filter(|e| !e.is_empty()) {
+        bail!("integrity check reported {} problem(s)", errors.len());
+    }
+    Ok(())
+}
+
+fn cmd_index(
+    engine: &HermesEngine,
+    project_root: &std::path::Path,
+    paths: &[PathBuf],
+    repair: bool,
+) -> Result<()> {
+    let opts = IndexOptions {
+        paths: paths.to_vec(),
+        repair,
+        progress: std::io::stderr().is_terminal().then(stderr_progress),
+    };
+    let report = engine.index(project_root, &opts)?;
+    let mut output = serde_json::json!({
         "total_files":  report.total_files,
         "indexed":      report.indexed,
         "skipped":      report.skipped,
         "errors":       report.errors,
+        "error_details": report.error_details,
+        "error_details_truncated": report.error_details_truncated,
+        "skipped_too_large": report.skipped_too_large,
+        "skipped_binary": report.skipped_binary,
+        "deleted":      report.deleted_paths.len(),
+        "renamed":      report.renamed_paths.len(),
         "nodes_created": report.nodes_created,
+        "chunks_removed": report.chunks_removed,
+        "timings":      report.timings,
     });
+    if let Some(repair) = &report.repair {
+        output["repair"] = serde_json::to_value(repair)?;
+    }
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
 
-fn cmd_search(engine: &HermesEngine, query: &str) -> Result<()> {
-    let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let search = SearchEngine::new(&graph, engine.search_cache());
-    let response = search.search(query, 10, &SearchMode::Smart)?;
-
-    let acct = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    acct.record_query(
-        query,
-        response.accounting.pointer_tokens,
-        0,
-        response.accounting.traditional_rag_estimate,
-    )?;
+/// Renders ingestion progress as a single, rewritten stderr line, cleared
+/// once linking starts so only the report remains.
+fn stderr_progress() -> ProgressSink {
+    let total = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    Arc::new(move |event| {
+        let line = match event {
+            IngestionProgress::Phase(IngestionPhase::Crawl) => "crawling...".to_string(),
+            IngestionProgress::Phase(IngestionPhase::HashCheck) => {
+                "checking for changes...".to_string()
+            }
+            IngestionProgress::Phase(IngestionPhase::Ingest { files }) => {
+                total.store(files, Ordering::Relaxed);
+                format!("indexing 0/{files}")
+            }
+            IngestionProgress::Phase(IngestionPhase::Cleanup) => String::new(),
+            IngestionProgress::FileIndexed { .. } | IngestionProgress::FileErrored { .. } => {
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                let total = total.load(Ordering::Relaxed).max(1);
+                format!("indexing {done}/{total} ({}%)", done * 100 / total)
+            }
+            IngestionProgress::FileStarted { .. } | IngestionProgress::FileSkipped { .. } => return,
+        };
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[2K{line}");
+        let _ = stderr.flush();
+    })
+}
 
-    println!("{}", serde_json::to_string_pretty(&response)?);
+fn cmd_search(engine: &HermesEngine, query: &str, opts: &SearchOptions, plain: bool) -> Result<()> {
+    if plain {
+        println!("{}", engine.search_compact(query, opts)?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(&engine.search(query, opts)?)?);
+    }
     Ok(())
 }
 
-fn cmd_fetch(engine: &HermesEngine, node_id: &str) -> Result<()> {
-    let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let search = SearchEngine::new(&graph, engine.search_cache());
-
-    let Some(response) = search.fetch(node_id)? else {
+fn cmd_fetch(engine: &HermesEngine, node_id: &str, max_tokens: Option<u64>) -> Result<()> {
+    let Some(response) = engine.fetch(node_id, max_tokens)? else {
         bail!("node not found: {node_id}");
     };
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    Ok(())
+}
 
-    let traditional_estimate = response.token_count * 15;
-    let acct = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    acct.record_query(node_id, 0, response.token_count, traditional_estimate)?;
-
+fn cmd_fetch_range(
+    engine: &HermesEngine,
+    project_root: &std::path::Path,
+    file: &str,
+    start: i64,
+    end: i64,
+    max_tokens: Option<u64>,
+) -> Result<()> {
+    let response = engine.fetch_range(project_root, file, start, end, max_tokens)?;
     println!("{}", serde_json::to_string_pretty(&response)?);
     Ok(())
 }
 
-fn cmd_add_fact(engine: &HermesEngine, fact_type_str: &str, content: &str) -> Result<()> {
-    let store = TemporalStore::new(engine.db().clone(), engine.project_id());
+/// Parses `10-40` (or a single line `10`) into an inclusive line range.
+fn parse_line_range(s: &str) -> Option<(i64, i64)> {
+    let (start, end) = match s.split_once('-') {
+        Some((a, b)) => (a.trim().parse().ok()?, b.trim().parse().ok()?),
+        None => {
+            let line = s.trim().parse().ok()?;
+            (line, line)
+        }
+    };
+    Some((start, end))
+}
+
+fn cmd_add_fact(
+    engine: &HermesEngine,
+    project_root: &std::path::Path,
+    fact_type_str: &str,
+    content: &str,
+    file: Option<&str>,
+) -> Result<()> {
     let fact_type = FactType::parse_str(fact_type_str);
-    let id = store.add_fact(None, fact_type, content, None)?;
+    let id = match file {
+        Some(file) => engine.add_file_fact(project_root, file, fact_type, content)?,
+        None => engine.add_fact(None, fact_type, content, None)?,
+    };
     println!("{}", serde_json::json!({ "id": id, "status": "recorded" }));
     Ok(())
 }
 
-fn cmd_list_facts(engine: &HermesEngine, filter: Option<&str>) -> Result<()> {
-    let store = TemporalStore::new(engine.db().clone(), engine.project_id());
+fn cmd_list_facts(engine: &HermesEngine, filter: Option<&str>, as_of: Option<&str>) -> Result<()> {
     let fact_type = filter.map(FactType::parse_str);
-    let facts = store.get_active_facts(fact_type.as_ref())?;
+    let store = engine.temporal_store();
+    let facts = match as_of {
+        Some(as_of) => store.get_facts_as_of(as_of, fact_type.as_ref())?,
+        None => store.get_active_facts(fact_type.as_ref())?,
+    };
     println!("{}", serde_json::to_string_pretty(&facts)?);
     Ok(())
 }
 
-fn cmd_stats(engine: &HermesEngine, since_arg: Option<&str>) -> Result<()> {
-    let acct = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    let session = acct.get_session_stats()?;
+fn cmd_fact(engine: &HermesEngine, action: FactCommand) -> Result<()> {
+    let output = match action {
+        FactCommand::Types => {
+            let counts = engine.temporal_store().count_active_by_type()?;
+            let types: Vec<_> = counts
+                .into_iter()
+                .map(|(fact_type, active)| {
+                    let builtin = FactType::BUILT_IN.contains(&fact_type);
+                    let name = fact_type.as_str();
+                    serde_json::json!({ "fact_type": name, "active": active, "builtin": builtin })
+                })
+                .collect();
+            serde_json::Value::from(types)
+        }
+        FactCommand::Show { fact_id } => {
+            let Some(fact) = engine.temporal_store().get_fact(&fact_id)? else {
+                bail!("no such fact: {fact_id}");
+            };
+            serde_json::to_value(fact)?
+        }
+        FactCommand::Edit { fact_id, content } => {
+            let id = engine.update_fact_content(&fact_id, &content)?;
+            serde_json::json!({ "id": id, "supersedes": fact_id, "status": "superseded" })
+        }
+        FactCommand::Purge { fact_id, yes } => {
+            let chain = engine.temporal_store().get_fact_chain(&fact_id)?;
+            if chain.is_empty() {
+                bail!("no such fact: {fact_id}");
+            }
+            if !yes {
+                bail!(
+                    "this permanently deletes {} fact(s) in the supersession chain of {fact_id}; \
+                     re-run with --yes to confirm",
+                    chain.len()
+                );
+            }
+            let deleted = engine.purge_fact(&fact_id)?;
+            serde_json::json!({ "purged": fact_id, "facts_deleted": deleted })
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn cmd_neighbors(
+    engine: &HermesEngine,
+    node_id: &str,
+    direction: EdgeDirection,
+    edge_types: Option<&[EdgeType]>,
+    limit: usize,
+) -> Result<()> {
+    let graph = engine.graph();
+    let search = engine.search_engine(&graph);
+    let Some(response) = search.neighbors(node_id, direction, edge_types, limit.max(1))? else {
+        bail!("node not found: {node_id}");
+    };
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    Ok(())
+}
+
+fn cmd_export(
+    engine: &HermesEngine,
+    format: &str,
+    root: Option<&str>,
+    depth: usize,
+    node_types: &[String],
+    max_nodes: Option<usize>,
+) -> Result<()> {
+    let graph = engine.graph();
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    match format {
+        "json" => {
+            if root.is_some() || !node_types.is_empty() || max_nodes.is_some() {
+                bail!("--root, --node-type and --max-nodes only apply to --format dot");
+            }
+            graph.export_json(&mut out)?;
+        }
+        "dot" => {
+            let root = match root {
+                Some(id) if aliases::is_alias(id) => match aliases::resolve(&graph, id)? {
+                    Some(node_id) => Some(node_id),
+                    None => bail!("node not found: {id}"),
+                },
+                other => other.map(str::to_string),
+            };
+            let node_types = node_types
+                .iter()
+                .map(|name| {
+                    NodeType::ALL
+                        .into_iter()
+                        .find(|t| t.as_str() == name)
+                        .ok_or_else(|| anyhow::anyhow!("unknown node type '{name}'"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let options = ExportOptions {
+                node_types: (!node_types.is_empty()).then_some(node_types),
+                root,
+                depth,
+                max_nodes: max_nodes.unwrap_or(usize::MAX),
+            };
+            graph.export_dot(&mut out, &options)?;
+        }
+        other => bail!("unknown --format '{other}' (expected dot or json)"),
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn cmd_import(engine: &HermesEngine, file: &std::path::Path, mode: ImportMode) -> Result<()> {
+    let graph = engine.graph();
+    let report = if file == std::path::Path::new("-") {
+        graph.import_json(std::io::stdin().lock(), mode)?
+    } else {
+        let reader = std::fs::File::open(file)
+            .map_err(|e| anyhow::anyhow!("cannot open {}: {e}", file.display()))?;
+        graph.import_json(std::io::BufReader::new(reader), mode)?
+    };
+    engine.invalidate_search_cache();
+    let mut output = serde_json::to_value(&report)?;
+    output["mode"] = mode.as_str().into();
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
 
-    let since_dur = since_arg.and_then(parse_since_duration);
-    let cumulative = acct.get_stats_since(since_dur)?;
+fn cmd_callers(engine: &HermesEngine, name: &str) -> Result<()> {
+    let graph = engine.graph();
+    let callers: Vec<serde_json::Value> = graph
+        .callers_of(name)?
+        .into_iter()
+        .map(|(caller, callee, weight)| {
+            serde_json::json!({
+                "caller":     caller.name,
+                "pointer_id": caller.id,
+                "file_path":  caller.file_path,
+                "start_line": caller.start_line,
+                "end_line":   caller.end_line,
+                "callee":     callee.name,
+                "mentions":   weight as u64,
+            })
+        })
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&callers)?);
+    Ok(())
+}
+
+fn cmd_stats(
+    engine: &HermesEngine,
+    since_arg: Option<&str>,
+    daily: Option<u32>,
+    weekly: Option<u32>,
+    top_queries: Option<(usize, TopQueryOrder)>,
+    prune: Option<Duration>,
+    sessions: Option<usize>,
+) -> Result<()> {
+    let acct = engine.accountant();
+    let pruned = prune.map(|age| acct.prune_older_than(age)).transpose()?;
+    let EngineStats { session, cumulative, search_cache: cache, .. } =
+        engine.stats(since_arg.and_then(parse_since_duration))?;
 
     let since_label = since_arg.unwrap_or("all");
-    let output = serde_json::json!({
+    let mut output = serde_json::json!({
         "project_id": engine.project_id(),
         "since_filter": since_label,
         "session": {
@@ -189,6 +940,8 @@ fn cmd_stats(engine: &HermesEngine, since_arg: Option<&str>) -> Result<()> {
             "traditional_rag_estimate": session.total_traditional_estimate,
             "tokens_saved":             session.cumulative_savings_tokens,
             "savings_pct":              format!("{:.1}%", session.cumulative_savings_pct),
+            "searches":                 session.searches,
+            "fetches":                  session.fetches,
         },
         "cumulative": {
             "total_queries":            cumulative.total_queries,
@@ -198,8 +951,34 @@ fn cmd_stats(engine: &HermesEngine, since_arg: Option<&str>) -> Result<()> {
             "traditional_rag_estimate": cumulative.total_traditional_estimate,
             "tokens_saved":             cumulative.cumulative_savings_tokens,
             "savings_pct":              format!("{:.1}%", cumulative.cumulative_savings_pct),
+            "searches":                 cumulative.searches,
+            "fetches":                  cumulative.fetches,
+        },
+        "search_cache": {
+            "hits":        cache.hits,
+            "misses":      cache.misses,
+            "hit_rate":    format!("{:.1}%", cache.hit_rate() * 100.0),
+            "evictions":   cache.evictions,
+            "expirations": cache.expirations,
+            "entries":     cache.entries,
+            "capacity":    cache.capacity,
         },
     });
+    if let Some(days) = daily {
+        output["daily"] = serde_json::to_value(acct.get_daily_stats(days)?)?;
+    }
+    if let Some(weeks) = weekly {
+        output["weekly"] = serde_json::to_value(acct.get_weekly_stats(weeks)?)?;
+    }
+    if let Some((limit, order_by)) = top_queries {
+        output["top_queries"] = serde_json::to_value(acct.top_queries(limit, order_by)?)?;
+    }
+    if let Some(limit) = sessions {
+        output["sessions"] = serde_json::to_value(acct.list_sessions(limit)?)?;
+    }
+    if let Some(rows) = pruned {
+        output["pruned_rows"] = rows.into();
+    }
     println!("{}", serde_json::to_string_pretty(&output)?);
     Ok(())
 }
@@ -0,0 +1,100 @@
+use crate::error::{HermesError, Result};
+use rusqlite::{Connection, DatabaseName, ErrorCode, Transaction, TransactionBehavior};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_ATTEMPTS: u32 = 5;
+const BASE_DELAY: Duration = Duration::from_millis(25);
+
+/// Whether `error` means another connection (usually another process on
+/// the same database file) holds a conflicting lock.
+pub fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `f` again after a jittered backoff while it fails with
+/// `SQLITE_BUSY` or `SQLITE_LOCKED`, up to [`MAX_ATTEMPTS`] times.
+///
+/// The busy timeout already makes SQLite wait for most locks; this covers
+/// the busy errors it returns without waiting, such as a write that would
+/// otherwise deadlock against another writer.
+pub fn retry_busy<T>(mut f: impl FnMut() -> rusqlite::Result<T>) -> Result<T> {
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if is_busy(&e) && attempt < MAX_ATTEMPTS => {
+                std::thread::sleep(backoff_delay(attempt));
+                attempt += 1;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Begins a transaction that takes the write lock up front, so its writes
+/// cannot fail halfway because another process wrote in between.
+///
+/// The caller must hold the connection's mutex, which is what makes the
+/// unchecked transaction safe.
+pub fn write_transaction(conn: &Connection) -> Result<Transaction<'_>> {
+    ensure_writable(conn)?;
+    retry_busy(|| Transaction::new_unchecked(conn, TransactionBehavior::Immediate))
+}
+
+/// Fails with [`HermesError::ReadOnly`] before any work is done when `conn`
+/// was opened read-only.
+pub fn ensure_writable(conn: &Connection) -> Result<()> {
+    if conn.is_readonly(DatabaseName::Main)? {
+        return Err(HermesError::ReadOnly);
+    }
+    Ok(())
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+    let exp = BASE_DELAY.saturating_mul(1 << (attempt - 1));
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    exp + exp.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_busy_retries_only_busy_errors() {
+        let busy = || {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+                None,
+            )
+        };
+        let mut calls = 0;
+        let value = retry_busy(|| {
+            calls += 1;
+            if calls < 3 { Err(busy()) } else { Ok(calls) }
+        })
+        .unwrap();
+        assert_eq!(value, 3);
+
+        let mut calls = 0;
+        let result: Result<()> = retry_busy(|| {
+            calls += 1;
+            Err(busy())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, MAX_ATTEMPTS);
+
+        let mut calls = 0;
+        let result: Result<()> = retry_busy(|| {
+            calls += 1;
+            Err(rusqlite::Error::QueryReturnedNoRows)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1, "other errors are not retried");
+    }
+}
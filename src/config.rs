@@ -0,0 +1,194 @@
+use crate::search::{clamp_top_k, DEFAULT_TOP_K};
+use std::str::FromStr;
+
+/// Which [`crate::embedding::EmbeddingProvider`] produces L2 vectors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmbeddingBackend {
+    /// Gemini when `GEMINI_API_KEY` is set, otherwise hashed.
+    #[default]
+    Auto,
+    /// Local token hashing; no external service.
+    Hashed,
+    /// Google Gemini `EmbeddingGenerator`.
+    Gemini,
+    /// A local Ollama server.
+    Ollama,
+}
+
+impl FromStr for EmbeddingBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "hashed" => Ok(Self::Hashed),
+            "gemini" => Ok(Self::Gemini),
+            "ollama" => Ok(Self::Ollama),
+            other => Err(format!(
+                "unknown embedding provider '{other}' (expected auto, hashed, gemini or ollama)"
+            )),
+        }
+    }
+}
+
+/// Per-column bm25 weights for FTS ranking. `node_id` and `project_id` are
+/// never weighted. Higher values make a match in that column count for more.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FtsWeights {
+    pub name: f64,
+    pub content: f64,
+    pub file_path: f64,
+}
+
+impl Default for FtsWeights {
+    fn default() -> Self {
+        Self {
+            name: 5.0,
+            content: 1.0,
+            file_path: 2.0,
+        }
+    }
+}
+
+/// Token filters applied before hashing text into L2 vectors. Both help
+/// prose but can blur code identifiers, so each can be switched off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenizerOptions {
+    /// Drop common English words ("the", "with", "from", ...).
+    pub stopwords: bool,
+    /// Strip plural and verb suffixes so "caches", "caching" and "cache"
+    /// share a bucket. Identifiers containing `_` or digits are left as is.
+    pub stemming: bool,
+}
+
+impl Default for TokenizerOptions {
+    fn default() -> Self {
+        Self {
+            stopwords: true,
+            stemming: true,
+        }
+    }
+}
+
+/// Which files the crawler visits besides the built-in ignore rules and any
+/// `.gitignore` / `.hermesignore` patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrawlOptions {
+    /// Also descend into hidden directories such as `.github`.
+    pub include_hidden: bool,
+    /// Extensions indexed on top of the built-in list, without the dot.
+    /// Files of unknown types are indexed as a single chunk.
+    pub extra_extensions: Vec<String>,
+    /// Larger files are left out of the index.
+    pub max_file_bytes: u64,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            include_hidden: false,
+            extra_extensions: Vec::new(),
+            max_file_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Tunables for caching and search that used to be compile-time constants.
+///
+/// `HermesConfig::default()` reproduces the historical behaviour; the CLI
+/// layers `HERMES_*` environment overrides on top of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HermesConfig {
+    /// How long a cached search response stays valid.
+    pub cache_ttl_secs: u64,
+    /// Maximum number of cached search responses before the least recently
+    /// used one is evicted. Applied to the shared cache by
+    /// [`crate::HermesEngine::with_config`].
+    pub cache_max_entries: usize,
+    /// Maximum number of cached fetch contents per `SearchEngine`.
+    pub fetch_cache_max_entries: usize,
+    /// If the weakest of the top_k literal hits scores at least this, FTS and
+    /// vector tiers are skipped. Values above 1.0 disable the short-circuit.
+    pub short_circuit_skip_all: f64,
+    /// If the weakest of the top_k literal hits scores at least this, only the
+    /// vector tier is skipped.
+    pub short_circuit_skip_l2: f64,
+    /// Result count used when a caller does not pass `top_k`.
+    pub default_top_k: usize,
+    /// Reciprocal rank fusion constant `k` in `1 / (k + rank)`. Larger values
+    /// flatten the advantage of top-ranked hits within a tier.
+    pub rrf_k: f64,
+    /// Nested results from one file (an impl and its methods) are collapsed
+    /// to the better one unless their fused scores are within this of each
+    /// other. File nodes always give way to the chunks they contain.
+    pub overlap_epsilon: f64,
+    /// Column weights passed to fts5 `bm25()`.
+    pub fts_weights: FtsWeights,
+    /// Embedding backend resolved by [`crate::HermesEngine::with_config`].
+    pub embedding_provider: EmbeddingBackend,
+    /// Tokenizer used for hashed vectors at ingestion and query time.
+    pub vector_tokenizer: TokenizerOptions,
+    /// Node count from which the vector tier builds and probes an ANN index
+    /// instead of scanning every vector.
+    pub ann_threshold: usize,
+    /// What `hermes index` and auto-reindexing crawl.
+    pub crawl: CrawlOptions,
+    /// How long a statement waits for another process's lock on the
+    /// database file before failing with "database is locked".
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for HermesConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: 60,
+            cache_max_entries: 256,
+            fetch_cache_max_entries: 50,
+            short_circuit_skip_all: 0.9,
+            short_circuit_skip_l2: 0.8,
+            default_top_k: DEFAULT_TOP_K,
+            rrf_k: 60.0,
+            overlap_epsilon: 0.01,
+            fts_weights: FtsWeights::default(),
+            embedding_provider: EmbeddingBackend::default(),
+            vector_tokenizer: TokenizerOptions::default(),
+            ann_threshold: 5_000,
+            crawl: CrawlOptions::default(),
+            busy_timeout_ms: 5_000,
+        }
+    }
+}
+
+impl HermesConfig {
+    /// `default_top_k` clamped into the range every other top_k obeys.
+    pub fn effective_top_k(&self) -> usize {
+        clamp_top_k(self.default_top_k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_thresholds_are_ordered() {
+        let config = HermesConfig::default();
+        assert!(config.short_circuit_skip_all > config.short_circuit_skip_l2);
+        assert!(config.short_circuit_skip_all <= 1.0);
+        assert!(config.short_circuit_skip_l2 > 0.0);
+    }
+
+    #[test]
+    fn effective_top_k_is_clamped() {
+        let config = HermesConfig { default_top_k: 0, ..Default::default() };
+        assert_eq!(config.effective_top_k(), 1);
+    }
+
+    #[test]
+    fn embedding_backend_parses_case_insensitively() {
+        assert_eq!("Gemini".parse(), Ok(EmbeddingBackend::Gemini));
+        assert_eq!(" hashed ".parse(), Ok(EmbeddingBackend::Hashed));
+        assert_eq!("ollama".parse(), Ok(EmbeddingBackend::Ollama));
+        assert!("openai".parse::<EmbeddingBackend>().is_err());
+    }
+}
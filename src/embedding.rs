@@ -5,50 +5,260 @@
 //! search pipeline — the built-in vector search in `search::vector` uses a
 //! local token-hashing approach that requires no external service.
 //!
-//! You can wire this module into the ingestion or search pipeline if you want
-//! higher-quality semantic embeddings.  To use it, set:
+//! Every backend (hashed, Gemini, Ollama) implements [`EmbeddingProvider`];
+//! [`provider_for`] picks one from [`EmbeddingBackend`] (by default Gemini
+//! when `GEMINI_API_KEY` is set).
+//! To use Gemini, set:
 //!
 //! - `GEMINI_API_KEY`           — your Google AI API key (required)
 //! - `GEMINI_EMBEDDING_MODEL`   — model name (default: `text-embedding-004`)
 //! - `EMBEDDING_RPM`            — rate limit in requests/min (default: 60)
+//! - `EMBEDDING_BATCH_SIZE`     — texts per `batchEmbedContents` call (default: 100)
+//! - `EMBEDDING_MAX_ATTEMPTS`   — attempts per call on 429/5xx (default: 5)
 
-use anyhow::{Context, Result};
+use crate::config::{EmbeddingBackend, HermesConfig};
+use crate::error::{HermesError, Result};
+use crate::search::vector::HashedEmbedder;
 use serde::{Deserialize, Serialize};
 use std::env;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
 
+/// Turns texts into vectors for the L2 tier. Stored vectors are tagged with
+/// [`EmbeddingProvider::model`] so vectors from different providers are never
+/// compared with each other.
+pub trait EmbeddingProvider: Send + Sync {
+    fn model(&self) -> &str;
+    /// One vector per input text, in input order.
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+
+    /// Like [`Self::embed`] but with one result per text, so a single bad
+    /// input does not discard the others.
+    fn embed_each(&self, texts: &[&str]) -> Vec<Result<Vec<f32>>> {
+        match self.embed(texts) {
+            Ok(vectors) => vectors.into_iter().map(Ok).collect(),
+            Err(e) => texts.iter().map(|_| Err(HermesError::EmbeddingApi(e.to_string()))).collect(),
+        }
+    }
+}
+
+/// Returns the provider selected by `config.embedding_provider`. Falls back
+/// to the hashed provider (with a warning) when an external one cannot be set
+/// up.
+pub fn provider_for(config: &HermesConfig) -> Arc<dyn EmbeddingProvider> {
+    let wants_gemini = match config.embedding_provider {
+        EmbeddingBackend::Hashed => false,
+        EmbeddingBackend::Gemini => true,
+        EmbeddingBackend::Ollama => {
+            match OllamaEmbeddingProvider::from_env() {
+                Ok(provider) => return Arc::new(provider),
+                Err(e) => warn!(error = %e, "Ollama embeddings unavailable, using hashed vectors"),
+            }
+            false
+        }
+        EmbeddingBackend::Auto => env::var("GEMINI_API_KEY").is_ok_and(|k| !k.is_empty()),
+    };
+    if wants_gemini {
+        match EmbeddingGenerator::new().and_then(GeminiEmbedder::new) {
+            Ok(embedder) => return Arc::new(embedder),
+            Err(e) => warn!(error = %e, "Gemini embeddings unavailable, using hashed vectors"),
+        }
+    }
+    Arc::new(HashedEmbedder::new(config.vector_tokenizer))
+}
+
+/// Blocking wrapper around [`EmbeddingGenerator`] for the synchronous engine.
+/// Must not be called from inside another tokio runtime.
+pub struct GeminiEmbedder {
+    generator: EmbeddingGenerator,
+    runtime: tokio::runtime::Runtime,
+    model_tag: String,
+}
+
+impl GeminiEmbedder {
+    pub fn new(generator: EmbeddingGenerator) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| api_error("Failed to start embedding runtime", e))?;
+        let model_tag = format!("gemini:{}", generator.model);
+        Ok(Self {
+            generator,
+            runtime,
+            model_tag,
+        })
+    }
+}
+
+impl EmbeddingProvider for GeminiEmbedder {
+    fn model(&self) -> &str {
+        &self.model_tag
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.runtime.block_on(self.generator.generate_embeddings(texts))
+    }
+
+    fn embed_each(&self, texts: &[&str]) -> Vec<Result<Vec<f32>>> {
+        self.runtime.block_on(self.generator.embed_each(texts))
+    }
+}
+
+/// Embeddings from a local Ollama server (`POST /api/embeddings`), for code
+/// that must not leave the machine. Configured with `OLLAMA_BASE_URL` and
+/// `OLLAMA_EMBED_MODEL`.
+pub struct OllamaEmbeddingProvider {
+    base_url: String,
+    model_name: String,
+    model_tag: String,
+    client: reqwest::Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    embedding: Vec<f32>,
+}
+
+impl OllamaEmbeddingProvider {
+    pub fn from_env() -> Result<Self> {
+        let base_url =
+            env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| DEFAULT_OLLAMA_BASE_URL.to_string());
+        let model =
+            env::var("OLLAMA_EMBED_MODEL").unwrap_or_else(|_| DEFAULT_OLLAMA_MODEL.to_string());
+        Self::new(&base_url, &model)
+    }
+
+    pub fn new(base_url: &str, model: &str) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| api_error("Failed to start embedding runtime", e))?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model_name: model.to_string(),
+            model_tag: format!("ollama:{model}"),
+            client: reqwest::Client::new(),
+            runtime,
+        })
+    }
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>> {
+        let url = format!("{}/api/embeddings", self.base_url);
+        let response = self
+            .client
+            .post(&url)
+            .json(&OllamaRequest { model: &self.model_name, prompt: text })
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_connect() {
+                    HermesError::EmbeddingApi(format!(
+                        "Could not connect to Ollama at {}; is `ollama serve` running?",
+                        self.base_url
+                    ))
+                } else {
+                    api_error("Failed to call Ollama embeddings API", e)
+                }
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(HermesError::EmbeddingApi(format!("Ollama returned {status}: {body}")));
+        }
+        let parsed: OllamaResponse = response
+            .json()
+            .await
+            .map_err(|e| api_error("Failed to parse Ollama embedding response", e))?;
+        if parsed.embedding.is_empty() {
+            return Err(HermesError::EmbeddingApi(format!(
+                "Ollama returned an empty embedding; does model '{}' support embeddings?",
+                self.model_name
+            )));
+        }
+        Ok(parsed.embedding)
+    }
+}
+
+impl EmbeddingProvider for OllamaEmbeddingProvider {
+    fn model(&self) -> &str {
+        &self.model_tag
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_each(texts).into_iter().collect()
+    }
+
+    fn embed_each(&self, texts: &[&str]) -> Vec<Result<Vec<f32>>> {
+        self.runtime.block_on(async {
+            let mut results = Vec::with_capacity(texts.len());
+            for text in texts {
+                results.push(self.embed_one(text).await);
+            }
+            results
+        })
+    }
+}
+
+const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+const DEFAULT_OLLAMA_MODEL: &str = "nomic-embed-text";
 const DEFAULT_MODEL: &str = "text-embedding-004";
 const DEFAULT_DIMENSION: usize = 768;
 const DEFAULT_RPM: usize = 60;
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+/// Inputs longer than this (roughly the model's 2,048-token limit) are split
+/// into pieces whose vectors are averaged.
+const MAX_INPUT_CHARS: usize = 8_000;
 
 #[derive(Clone)]
 pub struct EmbeddingGenerator {
     api_key: String,
     model: String,
+    base_url: String,
+    batch_size: usize,
+    max_attempts: u32,
+    retry_base_delay: Duration,
     client: reqwest::Client,
-    rate_limiter: Arc<Semaphore>,
+    rate_limiter: Arc<RateLimiter>,
 }
 
 #[derive(Debug, Serialize)]
-struct EmbeddingRequest {
-    model: String,
-    content: EmbeddingContent,
+struct BatchEmbeddingRequest<'a> {
+    requests: Vec<EmbeddingRequest<'a>>,
 }
 
 #[derive(Debug, Serialize)]
-struct EmbeddingContent {
-    parts: Vec<EmbeddingPart>,
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    content: EmbeddingContent<'a>,
 }
 
 #[derive(Debug, Serialize)]
-struct EmbeddingPart {
-    text: String,
+struct EmbeddingContent<'a> {
+    parts: [EmbeddingPart<'a>; 1],
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingPart<'a> {
+    text: &'a str,
 }
 
 #[derive(Debug, Deserialize)]
-struct EmbeddingResponse {
-    embedding: EmbeddingValues,
+struct BatchEmbeddingResponse {
+    embeddings: Vec<EmbeddingValues>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,20 +269,78 @@ struct EmbeddingValues {
 impl EmbeddingGenerator {
     pub fn new() -> Result<Self> {
         let api_key = env::var("GEMINI_API_KEY")
-            .context("GEMINI_API_KEY environment variable not set")?;
+            .map_err(|e| api_error("GEMINI_API_KEY environment variable not set", e))?;
         let model = env::var("GEMINI_EMBEDDING_MODEL")
             .unwrap_or_else(|_| DEFAULT_MODEL.to_string());
         let rpm: usize = env::var("EMBEDDING_RPM")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(DEFAULT_RPM);
+        let batch_size: usize = env::var("EMBEDDING_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_BATCH_SIZE);
+        let max_attempts: u32 = env::var("EMBEDDING_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
 
-        Ok(Self {
-            api_key,
-            model,
+        Ok(Self::with_key(&api_key, &model)
+            .with_rate_limit(rpm)
+            .with_batch_size(batch_size)
+            .with_retry(max_attempts, DEFAULT_RETRY_BASE_DELAY))
+    }
+
+    /// A generator with explicit credentials and default limits, without
+    /// reading the environment.
+    pub fn with_key(api_key: &str, model: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_base_delay: DEFAULT_RETRY_BASE_DELAY,
             client: reqwest::Client::new(),
-            rate_limiter: Arc::new(Semaphore::new(rpm)),
-        })
+            rate_limiter: Arc::new(RateLimiter::per_minute(DEFAULT_RPM)),
+        }
+    }
+
+    /// Throttles to `rpm` requests per minute, shared by all clones of the
+    /// returned generator.
+    pub fn with_rate_limit(mut self, rpm: usize) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::per_minute(rpm));
+        self
+    }
+
+    /// Shares an existing limiter, e.g. between generators for different models.
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
+    /// Maximum texts per `batchEmbedContents` call.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Attempts per request (including the first) for 429 and 5xx responses,
+    /// and the delay before the first retry, doubled on each further retry.
+    pub fn with_retry(mut self, max_attempts: u32, base_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.retry_base_delay = base_delay;
+        self
+    }
+
+    /// Overrides the API root, e.g. to point at a local test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
     }
 
     pub fn dimension() -> usize {
@@ -80,61 +348,578 @@ impl EmbeddingGenerator {
     }
 
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let _permit = self.rate_limiter.acquire().await
-            .map_err(|e| anyhow::anyhow!("Rate limiter closed: {e}"))?;
-
-        let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:embedContent?key={}",
-            self.model, self.api_key
-        );
-
-        let request = EmbeddingRequest {
-            model: format!("models/{}", self.model),
-            content: EmbeddingContent {
-                parts: vec![EmbeddingPart {
-                    text: text.to_string(),
-                }],
-            },
+        self.embed_each(&[text])
+            .await
+            .pop()
+            .ok_or_else(no_vector)?
+    }
+
+    /// Fails on the first text that could not be embedded. Use
+    /// [`Self::embed_each`] to keep the vectors of the other texts.
+    pub async fn generate_embeddings(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_each(texts).await.into_iter().collect()
+    }
+
+    /// Embeds `texts` in `batchEmbedContents` calls of at most `batch_size`
+    /// and returns one result per text, in input order. When a batch is
+    /// rejected with a 4xx other than 429, its texts are retried one by one
+    /// so a single bad input only fails itself. Any other failure, such as
+    /// rate limiting that outlasted the retries, fails the whole batch:
+    /// splitting it would only spend more of the quota.
+    pub async fn embed_each(&self, texts: &[&str]) -> Vec<Result<Vec<f32>>> {
+        let pieces: Vec<(usize, &str)> = texts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, text)| split_oversized(text).into_iter().map(move |p| (i, p)))
+            .collect();
+
+        let mut piece_results: Vec<Result<Vec<f32>>> = Vec::with_capacity(pieces.len());
+        for batch in pieces.chunks(self.batch_size) {
+            let batch_texts: Vec<&str> = batch.iter().map(|(_, text)| *text).collect();
+            match self.request_batch(&batch_texts).await {
+                Ok(vectors) => piece_results.extend(vectors.into_iter().map(Ok)),
+                Err(BatchError::Rejected(_)) if batch_texts.len() > 1 => {
+                    for text in batch_texts {
+                        let single = match self.request_batch(&[text]).await {
+                            Ok(mut v) => v.pop().ok_or_else(no_vector),
+                            Err(e) => Err(e.into_inner()),
+                        };
+                        piece_results.push(single);
+                    }
+                }
+                Err(e) => {
+                    let message = e.into_inner().to_string();
+                    piece_results.extend(
+                        batch.iter().map(|_| Err(HermesError::EmbeddingApi(message.clone()))),
+                    );
+                }
+            }
+        }
+
+        let mut grouped: Vec<Vec<Result<Vec<f32>>>> = texts.iter().map(|_| Vec::new()).collect();
+        for ((index, _), result) in pieces.iter().zip(piece_results) {
+            grouped[*index].push(result);
+        }
+        grouped.into_iter().map(mean_of_pieces).collect()
+    }
+
+    async fn request_batch(
+        &self,
+        texts: &[&str],
+    ) -> std::result::Result<Vec<Vec<f32>>, BatchError> {
+        // The key goes in a header: reqwest errors quote the URL, and they
+        // end up in logs and MCP replies.
+        let url = format!("{}/models/{}:batchEmbedContents", self.base_url, self.model);
+        let model = format!("models/{}", self.model);
+        let request = BatchEmbeddingRequest {
+            requests: texts
+                .iter()
+                .map(|text| EmbeddingRequest {
+                    model: &model,
+                    content: EmbeddingContent {
+                        parts: [EmbeddingPart { text }],
+                    },
+                })
+                .collect(),
         };
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await
-            .context("Failed to call embedding API")?;
+        let mut attempt = 1;
+        let response = loop {
+            // Every attempt, retries included, counts against the quota.
+            let wait = self.rate_limiter.reserve();
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            let response = self
+                .client
+                .post(&url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| api_error("Failed to call embedding API", e.without_url()))?;
 
-        let status = response.status();
-        if !status.is_success() {
+            let status = response.status();
+            if status.is_success() {
+                break response;
+            }
+            // 429 and 5xx are transient; other 4xx will fail the same way again.
+            let retryable =
+                status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            let retry_after = retry_after(response.headers());
             let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("Embedding API returned {status}: {body}");
-        }
+            if !retryable || attempt >= self.max_attempts {
+                let error = HermesError::EmbeddingApi(format!(
+                    "Embedding API returned {status} after {attempt} attempt(s): {body}"
+                ));
+                return Err(if status.is_client_error() && !retryable {
+                    BatchError::Rejected(error)
+                } else {
+                    BatchError::Failed(error)
+                });
+            }
+            let delay = retry_after.unwrap_or_else(|| backoff_delay(self.retry_base_delay, attempt));
+            warn!(
+                %status,
+                attempt,
+                max_attempts = self.max_attempts,
+                delay_ms = delay.as_millis() as u64,
+                "Embedding API request failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
 
-        let parsed: EmbeddingResponse = response
+        let parsed: BatchEmbeddingResponse = response
             .json()
             .await
-            .context("Failed to parse embedding response")?;
+            .map_err(|e| api_error("Failed to parse embedding response", e))?;
+        if parsed.embeddings.len() != texts.len() {
+            return Err(BatchError::Failed(HermesError::EmbeddingApi(format!(
+                "Embedding API returned {} vectors for {} texts",
+                parsed.embeddings.len(),
+                texts.len()
+            ))));
+        }
+        Ok(parsed.embeddings.into_iter().map(|e| e.values).collect())
+    }
+}
+
+/// Why a `batchEmbedContents` call failed.
+enum BatchError {
+    /// A 4xx other than 429: the API refused some input of the batch.
+    Rejected(HermesError),
+    /// Anything else, after the retries ran out.
+    Failed(HermesError),
+}
+
+impl BatchError {
+    fn into_inner(self) -> HermesError {
+        match self {
+            Self::Rejected(e) | Self::Failed(e) => e,
+        }
+    }
+}
 
-        Ok(parsed.embedding.values)
+impl From<HermesError> for BatchError {
+    fn from(error: HermesError) -> Self {
+        Self::Failed(error)
     }
+}
 
-    pub async fn generate_embeddings(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
-        let mut results = Vec::with_capacity(texts.len());
-        for text in texts {
-            let embedding = self.generate_embedding(text).await?;
-            results.push(embedding);
+/// Source of the current time for [`RateLimiter`], so tests can drive it
+/// with a simulated clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Sliding-window limiter allowing at most `limit` requests in any `window`.
+///
+/// [`RateLimiter::reserve`] books the earliest free slot and returns how long
+/// the caller must wait for it, so concurrent callers queue up behind each
+/// other instead of all firing once the window frees.
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    /// Start times of booked requests, oldest first; may lie in the future.
+    slots: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(rpm: usize) -> Self {
+        Self::new(rpm, Duration::from_secs(60), Arc::new(SystemClock))
+    }
+
+    pub fn new(limit: usize, window: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            limit: limit.max(1),
+            window,
+            clock,
+            slots: Mutex::new(VecDeque::new()),
         }
-        Ok(results)
     }
+
+    /// Books a request slot and returns the delay before it may be sent.
+    pub fn reserve(&self) -> Duration {
+        let now = self.clock.now();
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        while slots.front().is_some_and(|t| *t + self.window <= now) {
+            slots.pop_front();
+        }
+        let start = if slots.len() < self.limit {
+            now
+        } else {
+            // The slot `limit` bookings back must leave the window first.
+            (slots[slots.len() - self.limit] + self.window).max(now)
+        };
+        slots.push_back(start);
+        start - now
+    }
+}
+
+/// Seconds from a `Retry-After` header, capped at [`MAX_BACKOFF`]. HTTP-date
+/// values are ignored in favour of the computed backoff.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(MAX_BACKOFF))
+}
+
+/// `base * 2^(attempt - 1)` capped at [`MAX_BACKOFF`], plus up to 50% jitter
+/// so parallel ingestion threads do not retry in lockstep.
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let exp = base
+        .saturating_mul(1u32 << (attempt - 1).min(16))
+        .min(MAX_BACKOFF);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = exp.mul_f64(f64::from(nanos % 1000) / 2000.0);
+    exp + jitter
+}
+
+/// Splits `text` into pieces of at most [`MAX_INPUT_CHARS`] bytes on char
+/// boundaries. Empty text stays a single empty piece.
+fn split_oversized(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > MAX_INPUT_CHARS {
+        let mut cut = MAX_INPUT_CHARS;
+        while !rest.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let (head, tail) = rest.split_at(cut);
+        pieces.push(head);
+        rest = tail;
+    }
+    pieces.push(rest);
+    pieces
+}
+
+fn api_error(context: &str, error: impl std::fmt::Display) -> HermesError {
+    HermesError::EmbeddingApi(format!("{context}: {error}"))
+}
+
+fn no_vector() -> HermesError {
+    HermesError::EmbeddingApi("Embedding API returned no vector".to_string())
+}
+
+/// Averages the vectors of a split input; any failed piece fails the input.
+fn mean_of_pieces(pieces: Vec<Result<Vec<f32>>>) -> Result<Vec<f32>> {
+    let count = pieces.len();
+    let mut iter = pieces.into_iter();
+    let mut sum = iter.next().ok_or_else(no_vector)??;
+    if count == 1 {
+        return Ok(sum);
+    }
+    for piece in iter {
+        for (acc, v) in sum.iter_mut().zip(piece?) {
+            *acc += v;
+        }
+    }
+    for v in &mut sum {
+        *v /= count as f32;
+    }
+    Ok(sum)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    type RequestLog = Arc<Mutex<Vec<(String, serde_json::Value)>>>;
 
     #[test]
     fn dimension_is_768() {
         assert_eq!(EmbeddingGenerator::dimension(), 768);
     }
+
+    #[test]
+    fn hashed_backend_needs_no_api_key() {
+        let config = HermesConfig {
+            embedding_provider: EmbeddingBackend::Hashed,
+            ..Default::default()
+        };
+        let provider = provider_for(&config);
+        assert_eq!(provider.model(), HashedEmbedder::default().model());
+        let vectors = provider.embed(&["exchange rate", "redis worker"]).unwrap();
+        assert_eq!(vectors.len(), 2);
+    }
+
+    #[test]
+    fn oversized_text_is_split_on_char_boundaries() {
+        let text = "é".repeat(MAX_INPUT_CHARS);
+        let pieces = split_oversized(&text);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces.concat(), text);
+        assert_eq!(split_oversized(""), vec![""]);
+    }
+
+    /// Minimal HTTP server that answers every request with `respond(body)`
+    /// and records the path and JSON body of each request.
+    fn http_stub(
+        mut respond: impl FnMut(&serde_json::Value) -> (&'static str, String) + Send + 'static,
+    ) -> (String, RequestLog) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or("").to_string();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let (status, payload) = respond(&body);
+                log.lock().unwrap().push((path, body));
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nRetry-After: 0\r\nConnection: close\r\n\r\n{payload}",
+                    payload.len()
+                );
+            }
+        });
+        (base, seen)
+    }
+
+    /// Stub of `batchEmbedContents`. The first requests get the statuses in
+    /// `failures`; after that each text embeds to `[len, 1.0]` and a batch
+    /// containing "BAD" is rejected with 400.
+    fn mock_server(failures: Vec<&'static str>) -> (String, RequestLog) {
+        let mut failures = failures.into_iter();
+        http_stub(move |body| {
+            let texts: Vec<String> = body["requests"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["content"]["parts"][0]["text"].as_str().unwrap().to_string())
+                .collect();
+            if let Some(status) = failures.next() {
+                (status, r#"{"error":"try again"}"#.to_string())
+            } else if texts.iter().any(|t| t.contains("BAD")) {
+                ("400 Bad Request", r#"{"error":"bad input"}"#.to_string())
+            } else {
+                let embeddings: Vec<_> = texts
+                    .iter()
+                    .map(|t| serde_json::json!({ "values": [t.len() as f32, 1.0] }))
+                    .collect();
+                ("200 OK", serde_json::json!({ "embeddings": embeddings }).to_string())
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn batches_requests_and_preserves_order() {
+        let (base, seen) = mock_server(vec![]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model")
+            .with_base_url(&base)
+            .with_batch_size(2);
+
+        let texts = ["a", "bb", "ccc", "dddd", "eeeee"];
+        let vectors = generator.generate_embeddings(&texts).await.unwrap();
+        let lengths: Vec<f32> = vectors.iter().map(|v| v[0]).collect();
+        assert_eq!(lengths, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 3);
+        let (path, body) = &seen[0];
+        assert_eq!(path, "/models/test-model:batchEmbedContents");
+        assert_eq!(body["requests"].as_array().unwrap().len(), 2);
+        assert_eq!(body["requests"][0]["model"], "models/test-model");
+        assert_eq!(body["requests"][1]["content"]["parts"][0]["text"], "bb");
+    }
+
+    #[tokio::test]
+    async fn a_bad_item_only_fails_itself() {
+        let (base, _seen) = mock_server(vec![]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model").with_base_url(&base);
+
+        let results = generator.embed_each(&["ok", "BAD chunk", "fine"]).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap()[0], 2.0);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap()[0], 4.0);
+        assert!(generator.generate_embeddings(&["ok", "BAD"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limits_until_success() {
+        let (base, seen) = mock_server(vec!["429 Too Many Requests", "429 Too Many Requests"]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model").with_base_url(&base);
+
+        let vector = generator.generate_embedding("abc").await.unwrap();
+        assert_eq!(vector[0], 3.0);
+        assert_eq!(seen.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (base, seen) = mock_server(vec!["503 Service Unavailable"; 3]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model")
+            .with_base_url(&base)
+            .with_retry(2, Duration::ZERO);
+
+        assert!(generator.generate_embedding("abc").await.is_err());
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn exhausted_rate_limits_fail_the_batch_without_splitting_it() {
+        let (base, seen) = mock_server(vec!["429 Too Many Requests"; 2]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model")
+            .with_base_url(&base)
+            .with_retry(2, Duration::ZERO);
+
+        let results = generator.embed_each(&["a", "bb", "ccc"]).await;
+        assert_eq!(results.len(), 3);
+        for result in &results {
+            let err = result.as_ref().unwrap_err().to_string();
+            assert!(err.contains("429"), "{err}");
+        }
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (base, seen) = mock_server(vec![]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model").with_base_url(&base);
+
+        assert!(generator.generate_embedding("BAD").await.is_err());
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    struct FakeClock {
+        start: Instant,
+        elapsed: Mutex<Duration>,
+    }
+
+    impl FakeClock {
+        fn advance(&self, by: Duration) {
+            *self.elapsed.lock().unwrap() += by;
+        }
+        fn elapsed(&self) -> Duration {
+            *self.elapsed.lock().unwrap()
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed()
+        }
+    }
+
+    #[test]
+    fn rate_limiter_throttles_to_rpm() {
+        let clock = Arc::new(FakeClock { start: Instant::now(), elapsed: Mutex::new(Duration::ZERO) });
+        let rpm = 10;
+        let limiter = RateLimiter::new(rpm, Duration::from_secs(60), clock.clone());
+
+        for _ in 0..=rpm {
+            let wait = limiter.reserve();
+            clock.advance(wait + Duration::from_millis(10));
+        }
+        assert!(clock.elapsed() >= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn rate_limiter_queues_concurrent_reservations() {
+        let clock = Arc::new(FakeClock { start: Instant::now(), elapsed: Mutex::new(Duration::ZERO) });
+        let limiter = RateLimiter::new(2, Duration::from_secs(60), clock.clone());
+        let waits: Vec<Duration> = (0..5).map(|_| limiter.reserve()).collect();
+        let secs: Vec<u64> = waits.iter().map(Duration::as_secs).collect();
+        assert_eq!(secs, vec![0, 0, 60, 60, 120]);
+    }
+
+    #[test]
+    fn backoff_doubles_and_is_capped() {
+        let base = Duration::from_millis(100);
+        let first = backoff_delay(base, 1);
+        let third = backoff_delay(base, 3);
+        assert!(first >= base && first <= base.mul_f64(1.5));
+        assert!(third >= base * 4 && third <= (base * 4).mul_f64(1.5));
+        assert!(backoff_delay(base, 30) <= MAX_BACKOFF.mul_f64(1.5));
+    }
+
+    #[test]
+    fn ollama_posts_prompt_and_reads_embedding() {
+        let (base, seen) = http_stub(|body| {
+            let len = body["prompt"].as_str().unwrap().len();
+            ("200 OK", serde_json::json!({ "embedding": [len as f32, 0.5, 0.25] }).to_string())
+        });
+        let provider = OllamaEmbeddingProvider::new(&base, "nomic-embed-text").unwrap();
+
+        let vectors = provider.embed(&["ab", "abcd"]).unwrap();
+        assert_eq!(vectors, vec![vec![2.0, 0.5, 0.25], vec![4.0, 0.5, 0.25]]);
+        assert_eq!(provider.model(), "ollama:nomic-embed-text");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0].0, "/api/embeddings");
+        assert_eq!(seen[0].1["model"], "nomic-embed-text");
+        assert_eq!(seen[1].1["prompt"], "abcd");
+    }
+
+    #[test]
+    fn ollama_not_running_is_reported_clearly() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let provider = OllamaEmbeddingProvider::new(&format!("http://127.0.0.1:{port}"), "m").unwrap();
+        let err = provider.embed(&["text"]).unwrap_err().to_string();
+        assert!(err.contains("ollama serve"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn failed_requests_do_not_reveal_the_api_key() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let generator = EmbeddingGenerator::with_key("SECRET-API-KEY", "test-model")
+            .with_base_url(&format!("http://127.0.0.1:{port}"));
+        let err = generator.generate_embedding("text").await.unwrap_err().to_string();
+        assert!(err.starts_with("Failed to call embedding API"), "{err}");
+        assert!(!err.contains("SECRET-API-KEY"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn single_text_goes_through_the_batch_endpoint() {
+        let (base, seen) = mock_server(vec![]);
+        let generator = EmbeddingGenerator::with_key("k", "test-model").with_base_url(&base);
+
+        let long = "x".repeat(MAX_INPUT_CHARS + 10);
+        let vector = generator.generate_embedding(&long).await.unwrap();
+        // Two pieces of 8000 and 10 bytes, averaged.
+        assert_eq!(vector, vec![4005.0, 1.0]);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].0.contains(":batchEmbedContents"));
+    }
 }
@@ -0,0 +1,90 @@
+use std::sync::PoisonError;
+
+/// The error returned by the library's public API, split by what a caller
+/// can do about it. The binary and MCP server wrap it in `anyhow` at the
+/// edges; the MCP server maps the variants to JSON-RPC error codes.
+#[derive(Debug, thiserror::Error)]
+pub enum HermesError {
+    /// A node, project, file or other named thing does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    Database(rusqlite::Error),
+    /// A search query, filter or option that cannot be run as given.
+    #[error("{0}")]
+    InvalidQuery(String),
+    /// Data that fails validation: a malformed node, edge or graph snapshot.
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A thread panicked while holding the database lock.
+    #[error("database lock poisoned: {0}")]
+    LockPoisoned(String),
+    /// An external embedding provider failed or could not be set up.
+    #[error("{0}")]
+    EmbeddingApi(String),
+    /// A file handed straight to `ingest_file` is over
+    /// `CrawlOptions::max_file_bytes`; crawled files are filtered out earlier.
+    #[error("file is {bytes} bytes, over the {max_bytes} byte limit")]
+    FileTooLarge { bytes: usize, max_bytes: u64 },
+    /// The database was migrated by a newer build than this one.
+    #[error(
+        "database schema version {found} is newer than version {supported} supported by this \
+         build; upgrade hermes or point HERMES_DB_PATH at another database"
+    )]
+    SchemaTooNew { found: u32, supported: u32 },
+    /// The database predates this build, and a read-only engine cannot
+    /// migrate it.
+    #[error(
+        "database schema version {found} is older than version {supported} of this build; \
+         open it read-write once to migrate it"
+    )]
+    SchemaTooOld { found: u32, supported: u32 },
+    /// A write through an engine opened with `HermesEngine::open_read_only`.
+    #[error("the index is open read-only")]
+    ReadOnly,
+    /// `HermesEngine::index` was called while another clone of the engine
+    /// was still indexing.
+    #[error("already indexing; try again when the running index finishes")]
+    AlreadyIndexing,
+}
+
+/// A write refused because the connection is read-only becomes
+/// [`HermesError::ReadOnly`], wherever in the library it happens.
+impl From<rusqlite::Error> for HermesError {
+    fn from(error: rusqlite::Error) -> Self {
+        if error.sqlite_error_code() == Some(rusqlite::ErrorCode::ReadOnly) {
+            HermesError::ReadOnly
+        } else {
+            HermesError::Database(error)
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for HermesError {
+    fn from(error: PoisonError<T>) -> Self {
+        HermesError::LockPoisoned(error.to_string())
+    }
+}
+
+/// Writing JSON only fails on I/O; reading it fails on malformed input.
+impl From<serde_json::Error> for HermesError {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            HermesError::Io(error.into())
+        } else {
+            HermesError::InvalidInput(error.to_string())
+        }
+    }
+}
+
+/// Patterns are built from filters and queries, so one that does not
+/// compile is an invalid query.
+impl From<regex::Error> for HermesError {
+    fn from(error: regex::Error) -> Self {
+        HermesError::InvalidQuery(error.to_string())
+    }
+}
+
+pub type Result<T, E = HermesError> = std::result::Result<T, E>;
@@ -0,0 +1,479 @@
+use crate::accounting::{Accountant, CumulativeStats, Operation};
+use crate::error::{HermesError, Result};
+use crate::graph::KnowledgeGraph;
+use crate::ingestion::crawler::resolve_project_path;
+use crate::ingestion::{IngestionPipeline, IngestionReport, ProgressSink};
+use crate::pointer::{FetchResponse, PointerResponse};
+use crate::search::{estimate_tokens, CacheStats, SearchEngine, SearchOptions};
+use crate::temporal::{FactType, TemporalStore};
+use crate::HermesEngine;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Indexed paths suggested when a fact names a file that is not indexed.
+const FILE_SUGGESTIONS: usize = 3;
+
+/// What [`HermesEngine::index`] re-indexes and how.
+#[derive(Clone, Default)]
+pub struct IndexOptions {
+    /// Files or directories to re-index, absolute or relative to the project
+    /// root; empty re-indexes the whole root.
+    pub paths: Vec<PathBuf>,
+    /// Run [`KnowledgeGraph::repair`] after removing deleted files.
+    pub repair: bool,
+    pub progress: Option<ProgressSink>,
+}
+
+/// Token accounting and search cache counters, from [`HermesEngine::stats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineStats {
+    pub session: CumulativeStats,
+    pub today: CumulativeStats,
+    /// Everything recorded for the project, or only within the `since` window.
+    pub cumulative: CumulativeStats,
+    pub search_cache: CacheStats,
+}
+
+impl HermesEngine {
+    /// The knowledge graph of this engine's project.
+    pub fn graph(&self) -> KnowledgeGraph {
+        KnowledgeGraph::new(self.db().clone(), self.project_id())
+    }
+
+    /// Records operations of this engine's project and session.
+    pub fn accountant(&self) -> Accountant {
+        Accountant::new(self.db().clone(), self.project_id(), self.session_id())
+    }
+
+    pub fn temporal_store(&self) -> TemporalStore {
+        TemporalStore::new(self.db().clone(), self.project_id())
+    }
+
+    /// A search engine over `graph` sharing this engine's caches, config and
+    /// embedding provider.
+    pub fn search_engine<'a>(&self, graph: &'a KnowledgeGraph) -> SearchEngine<'a> {
+        SearchEngine::new(graph, self.search_cache(), self.config().clone())
+            .with_embedder(self.embedder())
+            .with_ann_cache(self.ann_cache())
+    }
+
+    /// An ingestion pipeline into `graph` configured from this engine.
+    pub fn ingestion_pipeline<'a>(&self, graph: &'a KnowledgeGraph) -> IngestionPipeline<'a> {
+        IngestionPipeline::new(graph)
+            .with_embedder(self.embedder())
+            .with_tokenizer(self.config().vector_tokenizer)
+            .with_crawl_options(self.config().crawl.clone())
+            .with_read_connections(self.db_path())
+    }
+
+    /// Searches the project and records the pointers, and any attached
+    /// facts, as a search in the accounting.
+    pub fn search(&self, query: &str, opts: &SearchOptions) -> Result<PointerResponse> {
+        let (response, latency) = self.timed_search(query, opts)?;
+        let a = &response.accounting;
+        self.record_operation(
+            Operation::Search,
+            query,
+            a.pointer_tokens + a.fact_tokens,
+            a.fetched_tokens,
+            a.traditional_rag_estimate,
+            Some(latency),
+        )?;
+        Ok(response)
+    }
+
+    /// Like [`Self::search`], but returns
+    /// [`PointerResponse::to_compact_string`] and records the tokens of that
+    /// text instead of the JSON estimate. Content inlined by Smart/Full mode
+    /// is recorded as fetched tokens either way.
+    pub fn search_compact(&self, query: &str, opts: &SearchOptions) -> Result<String> {
+        let (response, latency) = self.timed_search(query, opts)?;
+        let text = response.to_compact_string();
+        let a = &response.accounting;
+        let pointer_tokens = estimate_tokens(&text).saturating_sub(a.fetched_tokens);
+        self.record_operation(
+            Operation::Search,
+            query,
+            pointer_tokens,
+            a.fetched_tokens,
+            a.traditional_rag_estimate,
+            Some(latency),
+        )?;
+        Ok(text)
+    }
+
+    fn timed_search(
+        &self,
+        query: &str,
+        opts: &SearchOptions,
+    ) -> Result<(PointerResponse, Duration)> {
+        let graph = self.graph();
+        let started = Instant::now();
+        let response = self.search_engine(&graph).search(query, opts)?;
+        Ok((response, started.elapsed()))
+    }
+
+    /// The content of a node by pointer alias or id, recorded as a fetch;
+    /// `None` if there is no such node.
+    pub fn fetch(&self, node_id: &str, max_tokens: Option<u64>) -> Result<Option<FetchResponse>> {
+        let graph = self.graph();
+        let started = Instant::now();
+        let Some(response) = self.search_engine(&graph).fetch(node_id, max_tokens)? else {
+            return Ok(None);
+        };
+        self.record_fetch(node_id, &response, started.elapsed())?;
+        Ok(Some(response))
+    }
+
+    /// Lines `start..=end` of a project file, recorded as a fetch under the
+    /// response's synthetic pointer id.
+    pub fn fetch_range(
+        &self,
+        project_root: &Path,
+        file_path: &str,
+        start: i64,
+        end: i64,
+        max_tokens: Option<u64>,
+    ) -> Result<FetchResponse> {
+        let graph = self.graph();
+        let started = Instant::now();
+        let search = self.search_engine(&graph);
+        let response = search.fetch_range(project_root, file_path, start, end, max_tokens)?;
+        self.record_fetch(&response.pointer_id, &response, started.elapsed())?;
+        Ok(response)
+    }
+
+    /// The naive-RAG baseline for fetched content was already counted by the
+    /// search that returned the pointer; a fetch only adds to Hermes' cost,
+    /// which includes the facts attached to it.
+    fn record_fetch(&self, query: &str, response: &FetchResponse, latency: Duration) -> Result<()> {
+        let fact_tokens: u64 =
+            response.related_facts.iter().map(|fact| fact.estimate_token_count()).sum();
+        self.record_operation(
+            Operation::Fetch,
+            query,
+            0,
+            response.token_count + fact_tokens,
+            0,
+            Some(latency),
+        )
+    }
+
+    /// Records an operation in the accounting; read-only engines skip it, so
+    /// querying them never fails on the write.
+    pub(crate) fn record_operation(
+        &self,
+        operation: Operation,
+        query: &str,
+        pointer_tokens: u64,
+        fetched_tokens: u64,
+        traditional_estimate: u64,
+        latency: Option<Duration>,
+    ) -> Result<()> {
+        if self.is_read_only() {
+            return Ok(());
+        }
+        self.accountant().record_operation(
+            operation,
+            query,
+            pointer_tokens,
+            fetched_tokens,
+            traditional_estimate,
+            latency,
+        )
+    }
+
+    /// Re-indexes `project_root`, or just `opts.paths` below it, and drops
+    /// the cached searches that pointed into changed files. Only one index
+    /// runs at a time across the engine's clones; another call meanwhile
+    /// fails with [`HermesError::AlreadyIndexing`].
+    pub fn index(&self, project_root: &Path, opts: &IndexOptions) -> Result<IngestionReport> {
+        if self.is_read_only() {
+            return Err(HermesError::ReadOnly);
+        }
+        if self.indexing.swap(true, Ordering::Acquire) {
+            return Err(HermesError::AlreadyIndexing);
+        }
+        let _indexing = IndexingGuard(&self.indexing);
+        let graph = self.graph();
+        let mut pipeline = self.ingestion_pipeline(&graph).with_repair(opts.repair);
+        if let Some(progress) = &opts.progress {
+            pipeline = pipeline.with_progress(progress.clone());
+        }
+        let report = if opts.paths.is_empty() {
+            pipeline.ingest_directory(project_root)?
+        } else {
+            let resolved = opts
+                .paths
+                .iter()
+                .map(|path| resolve_project_path(project_root, path))
+                .collect::<Result<Vec<_>>>()?;
+            pipeline.ingest_paths(&resolved)?
+        };
+        if report.vectors_backfilled > 0 {
+            self.invalidate_search_cache();
+        } else {
+            self.invalidate_search_cache_for_files(&report.touched_paths());
+        }
+        *self.last_indexed.lock()? = Some(Instant::now());
+        Ok(report)
+    }
+
+    /// Whether [`Self::index`] is running on this engine or a clone of it.
+    pub fn is_indexing(&self) -> bool {
+        self.indexing.load(Ordering::Acquire)
+    }
+
+    /// When [`Self::index`] last succeeded on this engine or a clone of it.
+    pub fn last_indexed(&self) -> Option<Instant> {
+        self.last_indexed.lock().ok().and_then(|at| *at)
+    }
+
+    /// Records a fact about the project, or about `node_id` in it, and
+    /// returns its id. Fact writes drop the cached search responses, which
+    /// carry the facts relevant to them.
+    pub fn add_fact(
+        &self,
+        node_id: Option<&str>,
+        fact_type: FactType,
+        content: &str,
+        source_reference: Option<&str>,
+    ) -> Result<String> {
+        let id = self.temporal_store().add_fact(node_id, fact_type, content, source_reference)?;
+        self.invalidate_search_responses();
+        Ok(id)
+    }
+
+    /// Records a fact about the indexed file at `source_path`, absolute or
+    /// relative to `project_root`: it is attached to the file's node and
+    /// keeps the path relative to the root as its source reference. Fails
+    /// with [`HermesError::NotFound`], naming the closest indexed paths, when
+    /// the file is not indexed.
+    pub fn add_file_fact(
+        &self,
+        project_root: &Path,
+        source_path: &str,
+        fact_type: FactType,
+        content: &str,
+    ) -> Result<String> {
+        let path = match resolve_project_path(project_root, Path::new(source_path)) {
+            Ok(path) => path,
+            Err(HermesError::NotFound(_)) => project_root.join(source_path),
+            Err(e) => return Err(e),
+        };
+        let relative = relative_path(project_root, &path.to_string_lossy());
+        let graph = self.graph();
+        let Some(file) = graph.file_node(&path.to_string_lossy())? else {
+            return Err(not_indexed(&graph, project_root, &relative)?);
+        };
+        self.add_fact(Some(&file.id), fact_type, content, Some(&relative))
+    }
+
+    /// See [`TemporalStore::supersede_fact`].
+    pub fn supersede_fact(
+        &self,
+        fact_id: &str,
+        content: &str,
+        source_reference: Option<&str>,
+    ) -> Result<String> {
+        let id = self.temporal_store().supersede_fact(fact_id, content, source_reference)?;
+        self.invalidate_search_responses();
+        Ok(id)
+    }
+
+    /// See [`TemporalStore::update_fact_content`].
+    pub fn update_fact_content(&self, fact_id: &str, new_content: &str) -> Result<String> {
+        let id = self.temporal_store().update_fact_content(fact_id, new_content)?;
+        self.invalidate_search_responses();
+        Ok(id)
+    }
+
+    /// See [`TemporalStore::purge_fact`].
+    pub fn purge_fact(&self, fact_id: &str) -> Result<usize> {
+        let deleted = self.temporal_store().purge_fact(fact_id)?;
+        self.invalidate_search_responses();
+        Ok(deleted)
+    }
+
+    /// See [`TemporalStore::invalidate_fact`].
+    pub fn invalidate_fact(&self, fact_id: &str, superseded_by: Option<&str>) -> Result<()> {
+        self.temporal_store().invalidate_fact(fact_id, superseded_by)?;
+        self.invalidate_search_responses();
+        Ok(())
+    }
+
+    /// Token savings of the current session, today and overall (or within
+    /// `since`), with the search cache counters.
+    pub fn stats(&self, since: Option<Duration>) -> Result<EngineStats> {
+        let acct = self.accountant();
+        Ok(EngineStats {
+            session: acct.get_session_stats()?,
+            today: acct.get_today_stats()?,
+            cumulative: acct.get_stats_since(since)?,
+            search_cache: self.cache_stats(),
+        })
+    }
+}
+
+/// `path` below `project_root`, with `/` separators; other paths as given.
+fn relative_path(project_root: &Path, path: &str) -> String {
+    Path::new(path)
+        .strip_prefix(project_root)
+        .map_or_else(|_| path.to_string(), |relative| relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// The error for a file that is not indexed, suggesting the indexed paths
+/// closest to `relative` by edit distance.
+fn not_indexed(graph: &KnowledgeGraph, project_root: &Path, relative: &str) -> Result<HermesError> {
+    // A limit of usize::MAX reaches SQLite as -1, which lists every file.
+    let mut candidates: Vec<(usize, String)> = graph
+        .file_paths_after(None, usize::MAX)?
+        .into_iter()
+        .map(|path| relative_path(project_root, &path))
+        .map(|path| (strsim::levenshtein(relative, &path), path))
+        .collect();
+    candidates.sort();
+    let suggestions: Vec<String> =
+        candidates.into_iter().take(FILE_SUGGESTIONS).map(|(_, path)| path).collect();
+    let hint = if suggestions.is_empty() {
+        "no files are indexed; run hermes index first".to_string()
+    } else {
+        format!("did you mean: {}?", suggestions.join(", "))
+    };
+    Ok(HermesError::NotFound(format!("{relative} is not indexed ({hint})")))
+}
+
+/// Clears the indexing flag when an index returns or panics.
+struct IndexingGuard<'a>(&'a AtomicBool);
+
+impl Drop for IndexingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchMode;
+
+    #[test]
+    fn facade_indexes_searches_fetches_and_records_accounting() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() -> u32 {\n    7\n}\n")
+            .unwrap();
+        let engine = HermesEngine::in_memory("facade").unwrap();
+
+        let report = engine.index(dir.path(), &IndexOptions::default()).unwrap();
+        assert_eq!(report.indexed, 1);
+
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..SearchOptions::default() };
+        let response = engine.search("parse_config", &opts).unwrap();
+        let pointer = response.pointers.first().expect("indexed function is found");
+        let fetched = engine.fetch(&pointer.id, None).unwrap().unwrap();
+        assert!(fetched.content.contains("parse_config"));
+        assert!(engine.fetch("no-such-node", None).unwrap().is_none());
+        let compact = engine.search_compact("parse_config", &opts).unwrap();
+        assert!(compact.contains("lib.rs"));
+
+        engine.add_fact(None, FactType::Decision, "keep config parsing pure", None).unwrap();
+        assert_eq!(engine.temporal_store().get_active_facts(None).unwrap().len(), 1);
+
+        let stats = engine.stats(None).unwrap();
+        assert_eq!(stats.session.total_queries, 3);
+        assert_eq!((stats.cumulative.searches.count, stats.cumulative.fetches.count), (2, 1));
+        assert_eq!(stats.search_cache.hits, 1);
+    }
+
+    #[test]
+    fn searches_attach_facts_and_fact_writes_refresh_cached_responses() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() -> u32 {\n    7\n}\n")
+            .unwrap();
+        let engine = HermesEngine::in_memory("facade").unwrap();
+        engine.index(dir.path(), &IndexOptions::default()).unwrap();
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..SearchOptions::default() };
+        let first = engine.add_fact(None, FactType::Decision, "config is read once", None);
+        let first = first.unwrap();
+
+        let fresh = engine.search("parse_config", &opts).unwrap();
+        assert_eq!(fresh.facts.len(), 1);
+        assert_eq!(fresh.facts[0].id, first);
+        let cached = engine.search("parse_config", &opts).unwrap();
+        assert_eq!((engine.cache_stats().hits, cached.facts.len()), (1, 1));
+        let a = &cached.accounting;
+        assert!(a.fact_tokens > 0);
+        assert_eq!(a.total_tokens, a.pointer_tokens + a.fact_tokens);
+
+        let second = engine.supersede_fact(&first, "config is reloaded on change", None).unwrap();
+        let refreshed = engine.search("parse_config", &opts).unwrap();
+        assert_eq!(engine.cache_stats().hits, 1, "the fact write dropped the cached response");
+        assert_eq!(refreshed.facts[0].id, second);
+
+        let without = SearchOptions { facts: false, ..opts };
+        let bare = engine.search("parse_config", &without).unwrap();
+        assert!(bare.facts.is_empty());
+        assert_eq!(bare.accounting.fact_tokens, 0);
+
+        let recorded = engine.stats(None).unwrap().cumulative.total_pointer_tokens;
+        let reported: u64 = [&fresh, &cached, &refreshed, &bare]
+            .iter()
+            .map(|r| r.accounting.pointer_tokens + r.accounting.fact_tokens)
+            .sum();
+        assert_eq!(recorded, reported);
+    }
+
+    #[test]
+    fn file_facts_attach_to_the_file_node_and_come_back_with_fetches() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let source = "pub fn parse_config() -> u32 {\n    7\n}\n";
+        std::fs::write(dir.path().join("src/lib.rs"), source).unwrap();
+        let engine = HermesEngine::in_memory("facade").unwrap();
+        engine.index(dir.path(), &IndexOptions::default()).unwrap();
+
+        let id = engine
+            .add_file_fact(dir.path(), "src/lib.rs", FactType::Decision, "config is read once")
+            .unwrap();
+        let fact = engine.temporal_store().get_fact(&id).unwrap().unwrap();
+        let path = dir.path().join("src/lib.rs").to_string_lossy().into_owned();
+        let file = engine.graph().file_node(&path).unwrap().unwrap();
+        assert_eq!(fact.node_id.as_deref(), Some(file.id.as_str()));
+        assert_eq!(fact.source_reference.as_deref(), Some("src/lib.rs"));
+        assert_eq!(engine.temporal_store().get_facts_for_file(&path).unwrap().len(), 1);
+
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..SearchOptions::default() };
+        let response = engine.search("parse_config", &opts).unwrap();
+        let pointer = response.pointers.first().expect("indexed function is found");
+        let fetched = engine.fetch(&pointer.id, None).unwrap().unwrap();
+        assert_eq!(fetched.related_facts.len(), 1);
+        assert_eq!(fetched.related_facts[0].id, id);
+        let range = engine.fetch_range(dir.path(), "src/lib.rs", 1, 2, None).unwrap();
+        assert_eq!(range.related_facts[0].content, "config is read once");
+
+        let error = engine
+            .add_file_fact(dir.path(), "src/lbi.rs", FactType::Decision, "typo")
+            .unwrap_err();
+        assert!(matches!(&error, HermesError::NotFound(message)
+            if message.contains("src/lbi.rs") && message.contains("did you mean: src/lib.rs")));
+    }
+
+    #[test]
+    fn index_refuses_to_run_twice_at_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn a() {}\n").unwrap();
+        let engine = HermesEngine::in_memory("facade").unwrap();
+        let other = engine.clone();
+
+        engine.indexing.store(true, Ordering::Release);
+        assert!(other.is_indexing());
+        let refused = other.index(dir.path(), &IndexOptions::default());
+        assert!(matches!(refused, Err(HermesError::AlreadyIndexing)));
+
+        engine.indexing.store(false, Ordering::Release);
+        assert_eq!(other.index(dir.path(), &IndexOptions::default()).unwrap().indexed, 1);
+        assert!(!engine.is_indexing(), "the flag is cleared when the index returns");
+    }
+}
@@ -1,10 +1,12 @@
-use anyhow::{Context, Result};
-use chrono::Utc;
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
+use crate::error::Result;
 pub use crate::graph_builders::{EdgeBuilder, NodeBuilder};
+use crate::graph_queries::metadata_from_sql;
+pub use crate::graph_writes::GraphWriter;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
@@ -17,6 +19,14 @@ pub struct Node {
     pub end_line: Option<i64>,
     pub summary: Option<String>,
     pub content_hash: Option<String>,
+    /// When the node was last written, as stored in `nodes.updated_at`.
+    /// `None` for nodes that have not been read back from the database.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    /// Free-form attributes (language, visibility, owner, …) stored as JSON
+    /// in `nodes.metadata`. Search can filter on them, see [`MetadataFilter`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -33,6 +43,18 @@ pub enum NodeType {
 }
 
 impl NodeType {
+    pub const ALL: [NodeType; 9] = [
+        Self::File,
+        Self::Module,
+        Self::Function,
+        Self::Struct,
+        Self::Impl,
+        Self::Trait,
+        Self::Enum,
+        Self::Concept,
+        Self::Document,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::File => "file",
@@ -84,6 +106,15 @@ pub enum EdgeType {
 }
 
 impl EdgeType {
+    pub const ALL: [EdgeType; 6] = [
+        Self::Calls,
+        Self::Imports,
+        Self::Implements,
+        Self::DependsOn,
+        Self::Contains,
+        Self::Documents,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Calls => "calls",
@@ -108,6 +139,157 @@ impl EdgeType {
     }
 }
 
+/// Which edges of a node [`KnowledgeGraph::get_neighbors_filtered`] follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeDirection {
+    /// Edges starting at the node: what it contains, calls, imports.
+    Outgoing,
+    /// Edges ending at the node: what contains, calls or imports it.
+    Incoming,
+    Both,
+}
+
+impl EdgeDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Outgoing => "out",
+            Self::Incoming => "in",
+            Self::Both => "both",
+        }
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "out" => Some(Self::Outgoing),
+            "in" => Some(Self::Incoming),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+}
+
+/// A `metadata.<key> = <value>` condition on [`Node::metadata`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataFilter {
+    /// Dotted path into the metadata object, e.g. `language` or `owner.team`.
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+impl MetadataFilter {
+    /// Parses `metadata.language = "rust"`, `language=rust` or
+    /// `complexity=3`. The value is read as JSON when it is valid JSON and as
+    /// a string otherwise; key segments are letters, digits and `_`.
+    pub fn parse_str(s: &str) -> Option<Self> {
+        let (key, value) = s.split_once('=')?;
+        let key = key.trim();
+        let key = key.strip_prefix("metadata.").unwrap_or(key);
+        let valid_key = key
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        if !valid_key {
+            return None;
+        }
+        let value = value.trim();
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        Some(Self {
+            key: key.to_string(),
+            value,
+        })
+    }
+
+    pub fn matches(&self, node: &Node) -> bool {
+        let found = node.metadata.as_ref().and_then(|m| {
+            self.key
+                .split('.')
+                .try_fold(m, |value, part| value.get(part))
+        });
+        found == Some(&self.value)
+    }
+
+    /// The `json_extract` path of the key.
+    pub(crate) fn json_path(&self) -> String {
+        format!("$.{}", self.key)
+    }
+
+    /// The value `json_extract` returns for a matching node: booleans come
+    /// back as integers, and objects and arrays as minified JSON text.
+    pub(crate) fn sql_value(&self) -> rusqlite::types::Value {
+        use rusqlite::types::Value;
+        match &self.value {
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            serde_json::Value::Bool(b) => Value::Integer(i64::from(*b)),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::Null => Value::Null,
+            other => Value::Text(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "metadata.{}={}", self.key, self.value)
+    }
+}
+
+/// A node reached by [`KnowledgeGraph::traverse`], with the number of hops
+/// from the start and the edge it was first reached over.
+#[derive(Debug, Clone)]
+pub struct TraversalStep {
+    pub node: Node,
+    pub depth: usize,
+    pub edge: Edge,
+}
+
+/// Size and health of one project's graph, from [`KnowledgeGraph::stats`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphStats {
+    pub nodes: u64,
+    /// Keyed by [`NodeType::as_str`].
+    pub nodes_by_type: BTreeMap<String, u64>,
+    pub edges: u64,
+    /// Keyed by [`EdgeType::as_str`].
+    pub edges_by_type: BTreeMap<String, u64>,
+    /// Distinct file paths with at least one node.
+    pub files: u64,
+    pub fts_rows: u64,
+    pub embeddings: u64,
+    /// Edges whose source or target node no longer exists; should be zero.
+    pub orphaned_edges: u64,
+    pub nodes_without_file_path: u64,
+    /// Size of the whole database (all projects), from its page count.
+    pub db_bytes: u64,
+}
+
+/// Rows left behind by deleted nodes, found (and by default deleted) by
+/// [`KnowledgeGraph::repair`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepairReport {
+    /// Edges whose source or target node no longer exists.
+    pub orphaned_edges: u64,
+    /// FTS rows of nodes that no longer exist.
+    pub orphaned_fts_rows: u64,
+    pub orphaned_embeddings: u64,
+    /// File hashes of paths without nodes, which would make ingestion skip
+    /// the file as unchanged.
+    pub stale_file_hashes: u64,
+    pub stale_chunk_hashes: u64,
+}
+
+impl RepairReport {
+    pub fn total(&self) -> u64 {
+        self.orphaned_edges
+            + self.orphaned_fts_rows
+            + self.orphaned_embeddings
+            + self.stale_file_hashes
+            + self.stale_chunk_hashes
+    }
+}
+
 pub struct KnowledgeGraph {
     db: Arc<Mutex<Connection>>,
     project_id: String,
@@ -122,32 +304,13 @@ impl KnowledgeGraph {
     }
 
     pub fn add_node(&self, node: &Node) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        let now = Utc::now().to_rfc3339();
-        conn.execute(
-            "INSERT OR REPLACE INTO nodes
-             (id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                node.id,
-                node.project_id,
-                node.name,
-                node.node_type.as_str(),
-                node.file_path,
-                node.start_line,
-                node.end_line,
-                node.summary,
-                node.content_hash,
-                now,
-            ],
-        )?;
-        Ok(())
+        self.in_transaction(|w| w.add_node(node))
     }
 
     pub fn get_node(&self, node_id: &str) -> Result<Option<Node>> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
              FROM nodes WHERE id = ?1 AND project_id = ?2",
         )?;
         let result = stmt
@@ -162,41 +325,68 @@ impl KnowledgeGraph {
                     end_line: row.get(6)?,
                     summary: row.get(7)?,
                     content_hash: row.get(8)?,
+                    updated_at: row.get(9)?,
+                    metadata: metadata_from_sql(row.get(10)?),
                 })
             })
-            .optional()
-            .context("Failed to query node")?;
+            .optional()?;
         Ok(result)
     }
 
     pub fn add_edge(&self, edge: &Edge) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "INSERT OR IGNORE INTO edges (id, project_id, source_id, target_id, edge_type, weight)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                edge.id,
-                edge.project_id,
-                edge.source_id,
-                edge.target_id,
-                edge.edge_type.as_str(),
-                edge.weight,
-            ],
-        )?;
-        Ok(())
+        self.in_transaction(|w| w.add_edge(edge))
     }
 
     pub fn get_neighbors(&self, node_id: &str) -> Result<Vec<(Edge, Node)>> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        let mut stmt = conn.prepare(
+        self.get_neighbors_filtered(node_id, EdgeDirection::Both, None, usize::MAX)
+    }
+
+    /// Up to `limit` neighbors of `node_id` over edges in `direction`,
+    /// optionally only edges of `edge_types`, ordered by edge type and then
+    /// the neighbor's location.
+    pub fn get_neighbors_filtered(
+        &self,
+        node_id: &str,
+        direction: EdgeDirection,
+        edge_types: Option<&[EdgeType]>,
+        limit: usize,
+    ) -> Result<Vec<(Edge, Node)>> {
+        let (touches, neighbor) = match direction {
+            EdgeDirection::Outgoing => ("e.source_id = ?1", "e.target_id"),
+            EdgeDirection::Incoming => ("e.target_id = ?1", "e.source_id"),
+            EdgeDirection::Both => (
+                "(e.source_id = ?1 OR e.target_id = ?1)",
+                "CASE WHEN e.source_id = ?1 THEN e.target_id ELSE e.source_id END",
+            ),
+        };
+        let mut values = vec![node_id.to_string(), self.project_id.clone()];
+        let type_filter = match edge_types {
+            Some([]) => return Ok(Vec::new()),
+            Some(types) => {
+                let first = values.len() + 1;
+                values.extend(types.iter().map(|t| t.as_str().to_string()));
+                let placeholders: Vec<String> =
+                    (first..values.len() + 1).map(|i| format!("?{i}")).collect();
+                format!("AND e.edge_type IN ({})", placeholders.join(", "))
+            }
+            None => String::new(),
+        };
+        // SQLite reads a negative LIMIT as no limit.
+        let limit = i64::try_from(limit).unwrap_or(-1);
+
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(&format!(
             "SELECT e.id, e.project_id, e.source_id, e.target_id, e.edge_type, e.weight,
-                    n.id, n.project_id, n.name, n.node_type, n.file_path, n.start_line, n.end_line, n.summary, n.content_hash
+                    n.id, n.project_id, n.name, n.node_type, n.file_path, n.start_line, n.end_line, n.summary, n.content_hash,
+                    n.updated_at, n.metadata
              FROM edges e
-             JOIN nodes n ON n.id = CASE WHEN e.source_id = ?1 THEN e.target_id ELSE e.source_id END
-             WHERE (e.source_id = ?1 OR e.target_id = ?1) AND e.project_id = ?2",
-        )?;
+             JOIN nodes n ON n.id = {neighbor}
+             WHERE {touches} AND e.project_id = ?2 {type_filter}
+             ORDER BY e.edge_type, n.file_path, n.start_line, n.name
+             LIMIT {limit}"
+        ))?;
         let rows = stmt
-            .query_map(params![node_id, self.project_id], |row| {
+            .query_map(rusqlite::params_from_iter(&values), |row| {
                 Ok((
                     Edge {
                         id: row.get(0)?,
@@ -216,6 +406,8 @@ impl KnowledgeGraph {
                         end_line: row.get(12)?,
                         summary: row.get(13)?,
                         content_hash: row.get(14)?,
+                        updated_at: row.get(15)?,
+                        metadata: metadata_from_sql(row.get(16)?),
                     },
                 ))
             })?
@@ -224,17 +416,7 @@ impl KnowledgeGraph {
     }
 
     pub fn index_fts(&self, node: &Node, content: &str) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "DELETE FROM fts_content WHERE node_id = ?1",
-            params![node.id],
-        )?;
-        conn.execute(
-            "INSERT INTO fts_content (node_id, project_id, name, content, file_path)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![node.id, node.project_id, node.name, content, node.file_path,],
-        )?;
-        Ok(())
+        self.in_transaction(|w| w.index_fts(node, content))
     }
 
     pub fn db(&self) -> &Arc<Mutex<Connection>> {
@@ -254,6 +436,20 @@ impl KnowledgeGraph {
     }
 }
 
+trait OptionalRow {
+    fn optional(self) -> std::result::Result<Option<Node>, rusqlite::Error>;
+}
+
+impl OptionalRow for std::result::Result<Node, rusqlite::Error> {
+    fn optional(self) -> std::result::Result<Option<Node>, rusqlite::Error> {
+        match self {
+            Ok(node) => Ok(Some(node)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -263,6 +459,40 @@ mod tests {
         KnowledgeGraph::new(engine.db().clone(), engine.project_id())
     }
 
+    #[test]
+    fn metadata_filters_parse_json_values_and_match_nested_keys() {
+        let filter = MetadataFilter::parse_str("metadata.language = \"rust\"").unwrap();
+        assert_eq!(
+            (filter.key.as_str(), &filter.value),
+            ("language", &serde_json::json!("rust"))
+        );
+        assert_eq!(
+            MetadataFilter::parse_str("language=rust"),
+            Some(filter.clone())
+        );
+        assert_eq!(filter.to_string(), "metadata.language=\"rust\"");
+        let complexity = MetadataFilter::parse_str("complexity=3").unwrap();
+        assert_eq!(complexity.value, serde_json::json!(3));
+        for invalid in [
+            "language",
+            "=rust",
+            "lang uage=rust",
+            "owner..team=core",
+            "$.x=1",
+        ] {
+            assert_eq!(MetadataFilter::parse_str(invalid), None, "{invalid}");
+        }
+
+        let mut node = sample_node("p");
+        assert!(!filter.matches(&node));
+        node.metadata = Some(serde_json::json!({"language": "rust", "owner": {"team": "core"}}));
+        assert!(filter.matches(&node));
+        assert!(MetadataFilter::parse_str("owner.team=core")
+            .unwrap()
+            .matches(&node));
+        assert!(!complexity.matches(&node));
+    }
+
     fn sample_node(project_id: &str) -> Node {
         Node {
             id: "node-1".to_string(),
@@ -274,6 +504,8 @@ mod tests {
             end_line: Some(20),
             summary: Some("Does something".to_string()),
             content_hash: Some("abc123".to_string()),
+            updated_at: None,
+            metadata: None,
         }
     }
 
@@ -378,6 +610,8 @@ mod tests {
             end_line: None,
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
         let n2 = Node {
             id: "n2".to_string(),
@@ -389,6 +623,8 @@ mod tests {
             end_line: None,
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
         graph.add_node(&n1).unwrap();
         graph.add_node(&n2).unwrap();
@@ -413,6 +649,71 @@ mod tests {
         assert_eq!(neighbors_of_n2[0].1.name, "caller");
     }
 
+    #[test]
+    fn get_neighbors_filtered_by_direction_type_and_limit() {
+        let engine = HermesEngine::in_memory("graph-neighbors-filtered").unwrap();
+        let graph = make_graph(&engine);
+        let file = graph
+            .create_node_builder()
+            .name("lib.rs")
+            .node_type(NodeType::File)
+            .build();
+        let importer = graph
+            .create_node_builder()
+            .name("main.rs")
+            .node_type(NodeType::File)
+            .build();
+        let children: Vec<Node> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                graph
+                    .create_node_builder()
+                    .name(name)
+                    .node_type(NodeType::Function)
+                    .build()
+            })
+            .collect();
+        for node in children.iter().chain([&file, &importer]) {
+            graph.add_node(node).unwrap();
+        }
+        let link = |source: &Node, target: &Node, edge_type: EdgeType| {
+            let edge = graph
+                .create_edge_builder()
+                .source(&source.id)
+                .target(&target.id)
+                .edge_type(edge_type)
+                .build();
+            graph.add_edge(&edge).unwrap();
+        };
+        for child in &children {
+            link(&file, child, EdgeType::Contains);
+        }
+        link(&importer, &file, EdgeType::Imports);
+
+        let names = |direction, types: Option<&[EdgeType]>, limit| -> Vec<String> {
+            graph
+                .get_neighbors_filtered(&file.id, direction, types, limit)
+                .unwrap()
+                .into_iter()
+                .map(|(_, node)| node.name)
+                .collect()
+        };
+        assert_eq!(names(EdgeDirection::Outgoing, None, 10), ["a", "b", "c"]);
+        assert_eq!(names(EdgeDirection::Incoming, None, 10), ["main.rs"]);
+        assert_eq!(names(EdgeDirection::Both, None, 10).len(), 4);
+        assert_eq!(
+            names(EdgeDirection::Both, Some(&[EdgeType::Imports]), 10),
+            ["main.rs"]
+        );
+        assert!(names(EdgeDirection::Outgoing, Some(&[EdgeType::Imports]), 10).is_empty());
+        assert!(names(EdgeDirection::Both, Some(&[]), 10).is_empty());
+        assert_eq!(
+            names(EdgeDirection::Outgoing, Some(&[EdgeType::Contains]), 2),
+            ["a", "b"]
+        );
+        assert_eq!(graph.get_neighbors(&file.id).unwrap().len(), 4);
+    }
+
     #[test]
     fn add_edge_ignore_duplicates() {
         let engine = HermesEngine::in_memory("graph-edge-dup").unwrap();
@@ -430,6 +731,8 @@ mod tests {
                     end_line: None,
                     summary: None,
                     content_hash: None,
+                    updated_at: None,
+                    metadata: None,
                 })
                 .unwrap();
         }
@@ -460,7 +763,9 @@ mod tests {
         graph.index_fts(&node, "updated content").unwrap();
 
         // Verify via raw FTS query returns one row
-        let results = graph.fts_search("\"updated\"", 10).unwrap();
+        let results = graph
+            .fts_search("\"updated\"", 10, None, &crate::FtsWeights::default())
+            .unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].0.id, "node-1");
     }
@@ -474,17 +779,3 @@ mod tests {
         assert!(graph.get_neighbors("node-1").unwrap().is_empty());
     }
 }
-
-trait OptionalRow {
-    fn optional(self) -> std::result::Result<Option<Node>, rusqlite::Error>;
-}
-
-impl OptionalRow for std::result::Result<Node, rusqlite::Error> {
-    fn optional(self) -> std::result::Result<Option<Node>, rusqlite::Error> {
-        match self {
-            Ok(node) => Ok(Some(node)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e),
-        }
-    }
-}
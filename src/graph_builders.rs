@@ -1,8 +1,30 @@
+use crate::error::{HermesError, Result};
 use crate::graph::{Edge, EdgeType, Node, NodeType};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+/// The checks of [`NodeBuilder::try_build`], for nodes that come from
+/// elsewhere, such as an imported snapshot.
+pub(crate) fn validate_node(node: &Node) -> Result<()> {
+    if node.name.trim().is_empty() {
+        return Err(HermesError::InvalidInput(
+            "node has an empty name".to_string(),
+        ));
+    }
+    if let (Some(start), Some(end)) = (node.start_line, node.end_line) {
+        if start > end {
+            return Err(HermesError::InvalidInput(format!(
+                "node '{}' ends at line {end}, before it starts at {start}",
+                node.name
+            )));
+        }
+    }
+    Ok(())
+}
+
 pub struct NodeBuilder {
     node: Node,
+    occurrence: Option<usize>,
 }
 
 impl NodeBuilder {
@@ -18,10 +40,22 @@ impl NodeBuilder {
                 end_line: None,
                 summary: None,
                 content_hash: None,
+                updated_at: None,
+                metadata: None,
             },
+            occurrence: None,
         }
     }
 
+    /// Derive the id from the project, file path, name and type instead of
+    /// generating a random one, so re-ingesting a file replaces its nodes.
+    /// `occurrence` tells apart same-named nodes of one file, such as an
+    /// inherent impl and a trait impl of the same type.
+    pub fn deterministic_id(mut self, occurrence: usize) -> Self {
+        self.occurrence = Some(occurrence);
+        self
+    }
+
     pub fn name(mut self, name: &str) -> Self {
         self.node.name = name.to_string();
         self
@@ -53,7 +87,51 @@ impl NodeBuilder {
         self
     }
 
+    /// Free-form JSON attributes, e.g. `json!({"language": "rust"})`.
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.node.metadata = Some(metadata);
+        self
+    }
+
+    /// Builds the node, failing if it has no name or ends before it starts.
+    pub fn try_build(self) -> Result<Node> {
+        validate_node(&self.node)?;
+        Ok(self.build_unchecked())
+    }
+
+    /// Builds the node without the [`Self::try_build`] checks, which only
+    /// debug builds run (and panic on). Prefer `try_build` for input that
+    /// is not known to be valid.
     pub fn build(self) -> Node {
+        if cfg!(debug_assertions) {
+            let name = self.node.name.clone();
+            return self
+                .try_build()
+                .unwrap_or_else(|e| panic!("invalid node '{name}': {e}"));
+        }
+        self.build_unchecked()
+    }
+
+    fn build_unchecked(mut self) -> Node {
+        if let Some(occurrence) = self.occurrence {
+            let node = &self.node;
+            let mut hasher = Sha256::new();
+            for part in [
+                node.project_id.as_str(),
+                node.file_path.as_deref().unwrap_or_default(),
+                node.name.as_str(),
+                node.node_type.as_str(),
+                &occurrence.to_string(),
+            ] {
+                hasher.update(part.as_bytes());
+                hasher.update([0]);
+            }
+            let mut bytes = [0u8; 16];
+            bytes.copy_from_slice(&hasher.finalize()[..16]);
+            self.node.id = uuid::Builder::from_custom_bytes(bytes)
+                .into_uuid()
+                .to_string();
+        }
         self.node
     }
 }
@@ -96,7 +174,37 @@ impl EdgeBuilder {
         self
     }
 
+    /// Builds the edge, failing unless it joins two different, named nodes
+    /// with a finite, non-negative weight.
+    pub fn try_build(self) -> Result<Edge> {
+        let edge = &self.edge;
+        let edge_type = edge.edge_type.as_str();
+        if edge.source_id.is_empty() || edge.target_id.is_empty() {
+            let message = format!("{edge_type} edge is missing its source or target");
+            return Err(HermesError::InvalidInput(message));
+        }
+        if edge.source_id == edge.target_id {
+            let message = format!("{edge_type} edge from node {} to itself", edge.source_id);
+            return Err(HermesError::InvalidInput(message));
+        }
+        if !edge.weight.is_finite() || edge.weight < 0.0 {
+            let message = format!(
+                "{edge_type} edge has weight {}, not a finite non-negative number",
+                edge.weight
+            );
+            return Err(HermesError::InvalidInput(message));
+        }
+        Ok(self.edge)
+    }
+
+    /// Builds the edge without the [`Self::try_build`] checks, which only
+    /// debug builds run (and panic on).
     pub fn build(self) -> Edge {
+        if cfg!(debug_assertions) {
+            return self
+                .try_build()
+                .unwrap_or_else(|e| panic!("invalid edge: {e}"));
+        }
         self.edge
     }
 }
@@ -109,7 +217,8 @@ mod tests {
 
     #[test]
     fn node_builder_defaults() {
-        let node = NodeBuilder::new("proj-1").build();
+        // Read unbuilt: a node without a name does not build.
+        let node = NodeBuilder::new("proj-1").node;
         assert_eq!(node.project_id, "proj-1");
         assert!(node.name.is_empty());
         assert_eq!(node.node_type, NodeType::Concept);
@@ -144,16 +253,36 @@ mod tests {
 
     #[test]
     fn node_builder_produces_unique_ids() {
-        let a = NodeBuilder::new("p").build();
-        let b = NodeBuilder::new("p").build();
+        let a = NodeBuilder::new("p").name("a").build();
+        let b = NodeBuilder::new("p").name("a").build();
         assert_ne!(a.id, b.id);
     }
 
+    #[test]
+    fn deterministic_ids_depend_on_identity_only() {
+        let build = |project: &str, name: &str, occurrence: usize, start: i64| {
+            NodeBuilder::new(project)
+                .name(name)
+                .node_type(NodeType::Function)
+                .file_path("src/lib.rs")
+                .lines(start, start + 3)
+                .deterministic_id(occurrence)
+                .build()
+                .id
+        };
+        assert_eq!(build("p", "run", 0, 1), build("p", "run", 0, 40));
+        assert_ne!(build("p", "run", 0, 1), build("p", "run", 1, 1));
+        assert_ne!(build("p", "run", 0, 1), build("q", "run", 0, 1));
+        assert_ne!(build("p", "run", 0, 1), build("p", "walk", 0, 1));
+        assert!(Uuid::parse_str(&build("p", "run", 0, 1)).is_ok());
+    }
+
     // ── EdgeBuilder ───────────────────────────────────────────────────────
 
     #[test]
     fn edge_builder_defaults() {
-        let edge = EdgeBuilder::new("proj").build();
+        // Read unbuilt: an edge without endpoints does not build.
+        let edge = EdgeBuilder::new("proj").edge;
         assert_eq!(edge.project_id, "proj");
         assert!(edge.source_id.is_empty());
         assert!(edge.target_id.is_empty());
@@ -178,8 +307,49 @@ mod tests {
 
     #[test]
     fn edge_builder_produces_unique_ids() {
-        let a = EdgeBuilder::new("p").build();
-        let b = EdgeBuilder::new("p").build();
+        let a = EdgeBuilder::new("p").source("n1").target("n2").build();
+        let b = EdgeBuilder::new("p").source("n1").target("n2").build();
         assert_ne!(a.id, b.id);
     }
+
+    #[test]
+    fn try_build_rejects_nameless_and_inverted_nodes() {
+        let node = |name: &str| NodeBuilder::new("p").name(name);
+        assert!(matches!(
+            node("").try_build(),
+            Err(HermesError::InvalidInput(_))
+        ));
+        assert!(node("  ").try_build().is_err());
+        let inverted = node("run").lines(10, 9).try_build().unwrap_err();
+        assert!(inverted.to_string().contains("line 9"), "{inverted}");
+        assert!(node("run").lines(10, 10).try_build().is_ok());
+        assert!(node("run").try_build().is_ok(), "lines are optional");
+    }
+
+    #[test]
+    fn try_build_rejects_dangling_looping_and_badly_weighted_edges() {
+        let edge = |source: &str, target: &str| {
+            EdgeBuilder::new("p")
+                .source(source)
+                .target(target)
+                .edge_type(EdgeType::Calls)
+        };
+        assert!(edge("", "n2").try_build().is_err());
+        assert!(edge("n1", "").try_build().is_err());
+        assert!(edge("n1", "n1").try_build().is_err());
+        for weight in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(
+                edge("n1", "n2").weight(weight).try_build().is_err(),
+                "{weight}"
+            );
+        }
+        assert!(edge("n1", "n2").weight(0.0).try_build().is_ok());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid edge")]
+    fn build_panics_on_invalid_edges_in_debug_builds() {
+        EdgeBuilder::new("p").source("n1").build();
+    }
 }
@@ -0,0 +1,617 @@
+use crate::error::{HermesError, Result};
+use crate::graph::{Edge, EdgeDirection, EdgeType, KnowledgeGraph, Node, NodeType};
+use crate::graph_builders::validate_node;
+use crate::graph_queries::{metadata_from_sql, node_from_row};
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+
+/// Version of the [`KnowledgeGraph::export_json`] schema.
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// Which part of the graph [`KnowledgeGraph::export_dot`] draws.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Only nodes of these types; edges are drawn between exported nodes.
+    pub node_types: Option<Vec<NodeType>>,
+    /// Draw the subgraph reachable from this node over outgoing edges
+    /// instead of the whole project.
+    pub root: Option<String>,
+    /// Hops from `root`; ignored without one.
+    pub depth: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            node_types: None,
+            root: None,
+            depth: 2,
+            max_nodes: usize::MAX,
+        }
+    }
+}
+
+/// A node as written by `export_json`. `content` is the indexed FTS text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedNode {
+    pub id: String,
+    pub name: String,
+    pub node_type: String,
+    pub file_path: Option<String>,
+    pub start_line: Option<i64>,
+    pub end_line: Option<i64>,
+    pub summary: Option<String>,
+    pub content_hash: Option<String>,
+    pub updated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedEdge {
+    pub id: String,
+    pub source_id: String,
+    pub target_id: String,
+    pub edge_type: String,
+    pub weight: f64,
+}
+
+/// The document written by [`KnowledgeGraph::export_json`].
+#[derive(Debug, Deserialize)]
+struct GraphSnapshot {
+    version: u32,
+    project_id: String,
+    nodes: Vec<ExportedNode>,
+    edges: Vec<ExportedEdge>,
+}
+
+/// How [`KnowledgeGraph::import_json`] treats the project's existing graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keep existing nodes and edges; imported ones replace those with the
+    /// same id.
+    Merge,
+    /// Delete the project's nodes, edges, FTS rows, embeddings and file
+    /// hashes first.
+    Replace,
+}
+
+impl ImportMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Merge => "merge",
+            Self::Replace => "replace",
+        }
+    }
+}
+
+/// What [`KnowledgeGraph::import_json`] did with each record.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub nodes_imported: usize,
+    pub edges_imported: usize,
+    /// Nodes whose id belongs to another project in this database.
+    pub nodes_skipped: usize,
+    /// Edges whose source or target is neither imported nor already present.
+    pub edges_skipped: usize,
+    /// Records with an empty id or name, or an unknown node or edge type.
+    pub invalid_nodes: usize,
+    pub invalid_edges: usize,
+    /// The snapshot's project, when it was not this one; its nodes and
+    /// edges were moved into this project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remapped_from: Option<String>,
+}
+
+impl KnowledgeGraph {
+    /// Loads a snapshot written by [`Self::export_json`] into this project in
+    /// one transaction, indexing each node's `content` for full-text search.
+    /// Embeddings are not part of a snapshot.
+    pub fn import_json(&self, reader: impl Read, mode: ImportMode) -> Result<ImportReport> {
+        let snapshot: GraphSnapshot = serde_json::from_reader(reader).map_err(|e| {
+            HermesError::InvalidInput(format!("Failed to parse graph snapshot: {e}"))
+        })?;
+        if snapshot.version > EXPORT_FORMAT_VERSION {
+            return Err(HermesError::InvalidInput(format!(
+                "graph snapshot version {} is newer than the supported {EXPORT_FORMAT_VERSION}",
+                snapshot.version
+            )));
+        }
+        let project_id = self.project_id();
+        let mut report = ImportReport {
+            remapped_from: (snapshot.project_id != project_id).then_some(snapshot.project_id),
+            ..ImportReport::default()
+        };
+
+        self.in_transaction(|w| {
+            let conn = w.conn();
+            if mode == ImportMode::Replace {
+                for sql in [
+                    "DELETE FROM fts_content WHERE project_id = ?1",
+                    "DELETE FROM embeddings WHERE project_id = ?1",
+                    "DELETE FROM edges WHERE project_id = ?1",
+                    "DELETE FROM nodes WHERE project_id = ?1",
+                    "DELETE FROM file_hashes WHERE project_id = ?1",
+                    "DELETE FROM chunk_hashes WHERE project_id = ?1",
+                ] {
+                    conn.execute(sql, params![project_id])?;
+                }
+            }
+            let owner = |id: &str| -> Result<Option<String>> {
+                let sql = "SELECT project_id FROM nodes WHERE id = ?1";
+                let project = conn.query_row(sql, params![id], |row| row.get(0)).optional()?;
+                Ok(project)
+            };
+
+            let mut imported: HashSet<String> = HashSet::new();
+            for record in snapshot.nodes {
+                let node_type = NodeType::ALL.into_iter().find(|t| t.as_str() == record.node_type);
+                let named = !record.id.is_empty() && !record.name.is_empty();
+                let Some(node_type) = node_type.filter(|_| named) else {
+                    report.invalid_nodes += 1;
+                    continue;
+                };
+                let node = Node {
+                    id: record.id,
+                    project_id: project_id.to_string(),
+                    name: record.name,
+                    node_type,
+                    file_path: record.file_path,
+                    start_line: record.start_line,
+                    end_line: record.end_line,
+                    summary: record.summary,
+                    content_hash: record.content_hash,
+                    updated_at: record.updated_at,
+                    metadata: record.metadata,
+                };
+                if validate_node(&node).is_err() {
+                    report.invalid_nodes += 1;
+                    continue;
+                }
+                if owner(&node.id)?.is_some_and(|owner| owner != project_id) {
+                    report.nodes_skipped += 1;
+                    continue;
+                }
+                w.restore_node(&node)?;
+                if let Some(content) = &record.content {
+                    w.index_fts(&node, content)?;
+                }
+                report.nodes_imported += 1;
+                imported.insert(node.id);
+            }
+
+            for record in snapshot.edges {
+                let edge_type = EdgeType::ALL.into_iter().find(|t| t.as_str() == record.edge_type);
+                let Some(edge_type) = edge_type.filter(|_| !record.id.is_empty()) else {
+                    report.invalid_edges += 1;
+                    continue;
+                };
+                let present = |id: &str| -> Result<bool> {
+                    Ok(imported.contains(id) || owner(id)?.is_some_and(|owner| owner == project_id))
+                };
+                if !present(&record.source_id)? || !present(&record.target_id)? {
+                    report.edges_skipped += 1;
+                    continue;
+                }
+                w.add_edge(&Edge {
+                    id: record.id,
+                    project_id: project_id.to_string(),
+                    source_id: record.source_id,
+                    target_id: record.target_id,
+                    edge_type,
+                    weight: record.weight,
+                })?;
+                report.edges_imported += 1;
+            }
+            Ok(())
+        })?;
+        Ok(report)
+    }
+
+    /// Writes the project's graph (or the part selected by `options`) as a
+    /// Graphviz digraph: one shape per node type, edges labelled with their
+    /// type. Nodes and edges are streamed to `writer` as they are read.
+    pub fn export_dot(&self, writer: &mut impl Write, options: &ExportOptions) -> Result<()> {
+        let selected = |node: &Node| {
+            options.node_types.as_ref().is_none_or(|types| types.contains(&node.node_type))
+        };
+        writeln!(writer, "digraph hermes {{")?;
+        writeln!(writer, "  node [fontname=\"Helvetica\", fontsize=10];")?;
+        writeln!(writer, "  edge [fontname=\"Helvetica\", fontsize=8];")?;
+
+        let mut exported: HashSet<String> = HashSet::new();
+        if let Some(root) = &options.root {
+            let Some(start) = self.get_node(root)? else {
+                return Err(HermesError::NotFound(format!("node not found: {root}")));
+            };
+            let steps =
+                self.traverse(&start.id, options.depth, None, EdgeDirection::Outgoing, usize::MAX)?;
+            let nodes = std::iter::once(start).chain(steps.into_iter().map(|step| step.node));
+            for node in nodes.filter(|n| selected(n)).take(options.max_nodes) {
+                write_dot_node(writer, &node)?;
+                exported.insert(node.id);
+            }
+        } else {
+            let conn = self.db().lock()?;
+            let mut stmt = conn.prepare(
+                "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+                 FROM nodes WHERE project_id = ?1 ORDER BY file_path, start_line, name",
+            )?;
+            let mut rows = stmt.query(params![self.project_id()])?;
+            while let Some(row) = rows.next()? {
+                if exported.len() == options.max_nodes {
+                    break;
+                }
+                let node = node_from_row(row)?;
+                if selected(&node) {
+                    write_dot_node(writer, &node)?;
+                    exported.insert(node.id);
+                }
+            }
+        }
+
+        self.for_each_edge(|edge| {
+            if exported.contains(&edge.source_id) && exported.contains(&edge.target_id) {
+                writeln!(
+                    writer,
+                    "  \"{}\" -> \"{}\" [label=\"{}\"{}];",
+                    edge.source_id,
+                    edge.target_id,
+                    edge.edge_type.as_str(),
+                    match edge.edge_type {
+                        EdgeType::Contains => "",
+                        EdgeType::Imports | EdgeType::DependsOn => ", style=dashed",
+                        _ => ", color=\"#555555\"",
+                    }
+                )?;
+            }
+            Ok(())
+        })?;
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+
+    /// Writes every node (with its indexed content) and edge of the project
+    /// as one JSON object, `{"version", "project_id", "nodes", "edges"}`,
+    /// streaming rows to `writer` as they are read. Nodes with indexed
+    /// content come first, then the rest by id.
+    pub fn export_json(&self, writer: &mut impl Write) -> Result<()> {
+        write!(
+            writer,
+            "{{\"version\":{EXPORT_FORMAT_VERSION},\"project_id\":{},\"nodes\":[",
+            serde_json::to_string(self.project_id())?
+        )?;
+        {
+            let conn = self.db().lock()?;
+            // `fts_content.node_id` has no index, and SQLite cannot build one
+            // on a virtual table, so it is scanned once on the outer side
+            // (CROSS JOIN pins the order) with nodes looked up by id.
+            let mut with_content = conn.prepare(
+                "SELECT n.id, n.name, n.node_type, n.file_path, n.start_line, n.end_line, n.summary,
+                        n.content_hash, n.updated_at, f.content, n.metadata
+                 FROM fts_content f
+                 CROSS JOIN nodes n ON n.id = f.node_id
+                 WHERE n.project_id = ?1",
+            )?;
+            let mut rows = with_content.query(params![self.project_id()])?;
+            let mut written: HashSet<String> = HashSet::new();
+            while let Some(row) = rows.next()? {
+                let node = exported_node(row)?;
+                if written.contains(&node.id) {
+                    continue;
+                }
+                if !written.is_empty() {
+                    writer.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *writer, &node)?;
+                written.insert(node.id);
+            }
+            // Then the nodes without indexed content.
+            let mut all = conn.prepare(
+                "SELECT id, name, node_type, file_path, start_line, end_line, summary,
+                        content_hash, updated_at, NULL, metadata
+                 FROM nodes WHERE project_id = ?1 ORDER BY id",
+            )?;
+            let mut rows = all.query(params![self.project_id()])?;
+            let mut first = written.is_empty();
+            while let Some(row) = rows.next()? {
+                let node = exported_node(row)?;
+                if written.contains(&node.id) {
+                    continue;
+                }
+                if !std::mem::take(&mut first) {
+                    writer.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut *writer, &node)?;
+            }
+        }
+        writer.write_all(b"],\"edges\":[")?;
+        let mut first = true;
+        self.for_each_edge(|edge| {
+            if !std::mem::take(&mut first) {
+                writer.write_all(b",")?;
+            }
+            let edge = ExportedEdge {
+                id: edge.id,
+                source_id: edge.source_id,
+                target_id: edge.target_id,
+                edge_type: edge.edge_type.as_str().to_string(),
+                weight: edge.weight,
+            };
+            serde_json::to_writer(&mut *writer, &edge)?;
+            Ok(())
+        })?;
+        writer.write_all(b"]}\n")?;
+        Ok(())
+    }
+
+    /// Calls `f` with each edge of the project, ordered by id, while holding
+    /// the connection lock.
+    fn for_each_edge(&self, mut f: impl FnMut(Edge) -> Result<()>) -> Result<()> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, source_id, target_id, edge_type, weight
+             FROM edges WHERE project_id = ?1 ORDER BY id",
+        )?;
+        let mut rows = stmt.query(params![self.project_id()])?;
+        while let Some(row) = rows.next()? {
+            f(Edge {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                source_id: row.get(2)?,
+                target_id: row.get(3)?,
+                edge_type: EdgeType::parse_str(&row.get::<_, String>(4)?),
+                weight: row.get(5)?,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// An [`ExportedNode`] from the columns selected by `export_json`.
+fn exported_node(row: &rusqlite::Row<'_>) -> rusqlite::Result<ExportedNode> {
+    Ok(ExportedNode {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        node_type: row.get(2)?,
+        file_path: row.get(3)?,
+        start_line: row.get(4)?,
+        end_line: row.get(5)?,
+        summary: row.get(6)?,
+        content_hash: row.get(7)?,
+        updated_at: row.get(8)?,
+        metadata: metadata_from_sql(row.get(10)?),
+        content: row.get(9)?,
+    })
+}
+
+fn write_dot_node(writer: &mut impl Write, node: &Node) -> Result<()> {
+    let shape = match node.node_type {
+        NodeType::File => "folder",
+        NodeType::Module => "tab",
+        NodeType::Function => "ellipse",
+        NodeType::Struct => "box",
+        NodeType::Impl => "component",
+        NodeType::Trait => "hexagon",
+        NodeType::Enum => "octagon",
+        NodeType::Concept => "plaintext",
+        NodeType::Document => "note",
+    };
+    writeln!(
+        writer,
+        "  \"{}\" [label=\"{}\\n({})\", shape={shape}];",
+        node.id,
+        dot_escape(&node.name),
+        node.node_type.as_str()
+    )?;
+    Ok(())
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HermesEngine;
+
+    fn sample_graph(engine: &HermesEngine) -> (KnowledgeGraph, Vec<Node>) {
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let file = graph.create_node_builder().name("lib.rs").node_type(NodeType::File).build();
+        let parse = graph.create_node_builder().name("parse").node_type(NodeType::Function).build();
+        let quoted = graph.create_node_builder().name("say \"hi\"").node_type(NodeType::Struct).build();
+        let other = graph.create_node_builder().name("other.rs").node_type(NodeType::File).build();
+        for node in [&file, &parse, &quoted, &other] {
+            graph.add_node(node).unwrap();
+        }
+        graph.index_fts(&parse, "fn parse() {}").unwrap();
+        for (source, target, edge_type) in [
+            (&file, &parse, EdgeType::Contains),
+            (&file, &quoted, EdgeType::Contains),
+            (&other, &file, EdgeType::Imports),
+        ] {
+            let edge = graph
+                .create_edge_builder()
+                .source(&source.id)
+                .target(&target.id)
+                .edge_type(edge_type)
+                .build();
+            graph.add_edge(&edge).unwrap();
+        }
+        (graph, vec![file, parse, quoted, other])
+    }
+
+    #[test]
+    fn dot_export_draws_shapes_and_labels_and_honors_filters() {
+        let engine = HermesEngine::in_memory("export-dot").unwrap();
+        let (graph, nodes) = sample_graph(&engine);
+
+        let mut out = Vec::new();
+        graph.export_dot(&mut out, &ExportOptions::default()).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.starts_with("digraph hermes {") && dot.trim_end().ends_with('}'));
+        assert!(dot.contains("label=\"say \\\"hi\\\"\\n(struct)\", shape=box"), "{dot}");
+        assert!(dot.contains("shape=folder"));
+        assert_eq!(dot.matches(" -> ").count(), 3);
+        assert!(dot.contains("[label=\"imports\", style=dashed]"));
+
+        let rooted = ExportOptions { root: Some(nodes[0].id.clone()), depth: 1, ..Default::default() };
+        let mut out = Vec::new();
+        graph.export_dot(&mut out, &rooted).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(!dot.contains("other.rs"));
+        assert_eq!(dot.matches(" -> ").count(), 2);
+
+        let functions = ExportOptions { node_types: Some(vec![NodeType::Function]), ..Default::default() };
+        let mut out = Vec::new();
+        graph.export_dot(&mut out, &functions).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.contains("parse") && !dot.contains("lib.rs"));
+        assert_eq!(dot.matches(" -> ").count(), 0);
+
+        let missing = ExportOptions { root: Some("missing".into()), ..Default::default() };
+        assert!(graph.export_dot(&mut Vec::new(), &missing).is_err());
+    }
+
+    fn exported(graph: &KnowledgeGraph) -> Vec<u8> {
+        let mut out = Vec::new();
+        graph.export_json(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn import_round_trips_an_export_into_another_database() {
+        let source = HermesEngine::in_memory("import-source").unwrap();
+        let (graph, nodes) = sample_graph(&source);
+        let snapshot = exported(&graph);
+
+        let target = HermesEngine::in_memory("import-target").unwrap();
+        let imported = KnowledgeGraph::new(target.db().clone(), target.project_id());
+        let stale = imported.create_node_builder().name("stale").node_type(NodeType::Function).build();
+        imported.add_node(&stale).unwrap();
+
+        let report = imported.import_json(snapshot.as_slice(), ImportMode::Replace).unwrap();
+        assert_eq!((report.nodes_imported, report.edges_imported), (4, 3));
+        assert_eq!(report.remapped_from.as_deref(), Some("import-source"));
+        assert!(imported.get_node(&stale.id).unwrap().is_none());
+        let parse = imported.get_node(&nodes[1].id).unwrap().unwrap();
+        assert_eq!(parse.project_id, "import-target");
+        let original = graph.get_node(&nodes[1].id).unwrap().unwrap();
+        assert!(parse.updated_at.is_some());
+        assert_eq!(parse.updated_at, original.updated_at);
+        assert_eq!(imported.get_neighbors(&nodes[0].id).unwrap().len(), 3);
+        let weights = crate::FtsWeights::default();
+        assert_eq!(imported.fts_search("parse", 5, None, &weights).unwrap().len(), 1);
+
+        imported.add_node(&stale).unwrap();
+        let merged = imported.import_json(snapshot.as_slice(), ImportMode::Merge).unwrap();
+        assert_eq!(merged.nodes_imported, 4);
+        assert!(imported.get_node(&stale.id).unwrap().is_some());
+        assert_eq!(imported.stats().unwrap().edges, 3);
+    }
+
+    #[test]
+    fn import_counts_invalid_skipped_and_foreign_records() {
+        let engine = HermesEngine::in_memory("import-checks").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let foreign = KnowledgeGraph::new(engine.db().clone(), "someone-else");
+        let theirs = foreign.create_node_builder().name("theirs").node_type(NodeType::Function).build();
+        foreign.add_node(&theirs).unwrap();
+
+        let node = |id: &str, node_type: &str| {
+            serde_json::json!({ "id": id, "name": id, "node_type": node_type, "file_path": null,
+                                "start_line": null, "end_line": null, "summary": null,
+                                "content_hash": null, "updated_at": null })
+        };
+        // A hand-edited range that ends before it starts would break fetches.
+        let mut inverted = node("c", "function");
+        inverted["start_line"] = 6.into();
+        inverted["end_line"] = 2.into();
+        let edge = |id: &str, source: &str, target: &str, edge_type: &str| {
+            serde_json::json!({ "id": id, "source_id": source, "target_id": target,
+                                "edge_type": edge_type, "weight": 1.0 })
+        };
+        let snapshot = serde_json::json!({
+            "version": EXPORT_FORMAT_VERSION,
+            "project_id": "import-checks",
+            "nodes": [node("a", "function"), node("b", "widget"), node(&theirs.id, "function"),
+                      inverted],
+            "edges": [edge("e1", "a", "a", "calls"), edge("e2", "a", "missing", "calls"),
+                      edge("e3", "a", &theirs.id, "calls"), edge("e4", "a", "a", "owns")],
+        });
+
+        let report = graph.import_json(snapshot.to_string().as_bytes(), ImportMode::Merge).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                nodes_imported: 1,
+                edges_imported: 1,
+                nodes_skipped: 1,
+                edges_skipped: 2,
+                invalid_nodes: 2,
+                invalid_edges: 1,
+                remapped_from: None,
+            }
+        );
+        assert_eq!(foreign.get_node(&theirs.id).unwrap().unwrap().project_id, "someone-else");
+        assert!(graph.get_node("c").unwrap().is_none());
+
+        let newer = serde_json::json!({ "version": 99, "project_id": "x", "nodes": [], "edges": [] });
+        assert!(graph.import_json(newer.to_string().as_bytes(), ImportMode::Merge).is_err());
+    }
+
+    #[test]
+    fn json_export_of_thousands_of_nodes_stays_fast() {
+        let engine = HermesEngine::in_memory("export-many").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let nodes: Vec<Node> = (0..5000)
+            .map(|i| {
+                let name = format!("function_{i}");
+                graph.create_node_builder().name(&name).node_type(NodeType::Function).build()
+            })
+            .collect();
+        graph
+            .in_transaction(|w| {
+                for (i, node) in nodes.iter().enumerate() {
+                    w.add_node(node)?;
+                    if i % 2 == 0 {
+                        w.index_fts(node, &format!("fn {}() {{}}", node.name))?;
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let json: serde_json::Value = serde_json::from_slice(&exported(&graph)).unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5), "{:?}", started.elapsed());
+        let exported: Vec<ExportedNode> = serde_json::from_value(json["nodes"].clone()).unwrap();
+        assert_eq!(exported.len(), 5000);
+        assert_eq!(exported.iter().filter(|n| n.content.is_some()).count(), 2500);
+    }
+
+    #[test]
+    fn json_export_lists_every_node_and_edge_with_content() {
+        let engine = HermesEngine::in_memory("export-json").unwrap();
+        let (graph, nodes) = sample_graph(&engine);
+
+        let mut out = Vec::new();
+        graph.export_json(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["version"], EXPORT_FORMAT_VERSION);
+        assert_eq!(json["project_id"], "export-json");
+        let exported: Vec<ExportedNode> = serde_json::from_value(json["nodes"].clone()).unwrap();
+        assert_eq!(exported.len(), 4);
+        let parse = exported.iter().find(|n| n.id == nodes[1].id).unwrap();
+        assert_eq!((parse.node_type.as_str(), parse.content.as_deref()), ("function", Some("fn parse() {}")));
+        let edges: Vec<ExportedEdge> = serde_json::from_value(json["edges"].clone()).unwrap();
+        assert_eq!(edges.len(), 3);
+        assert!(edges.iter().any(|e| e.edge_type == "imports" && e.target_id == nodes[0].id));
+    }
+}
@@ -1,23 +1,54 @@
-use crate::graph::{KnowledgeGraph, Node, NodeType};
-use anyhow::Result;
-use rusqlite::params;
-use std::collections::HashSet;
+use crate::busy;
+use crate::config::FtsWeights;
+use crate::error::Result;
+use crate::graph::{
+    EdgeDirection, EdgeType, GraphStats, KnowledgeGraph, MetadataFilter, Node, NodeType,
+    RepairReport, TraversalStep,
+};
+use rusqlite::types::Value;
+use rusqlite::{params, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 impl KnowledgeGraph {
-    pub fn literal_search_by_name(&self, query: &str) -> Result<Vec<Node>> {
-        let conn = self.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+    /// `path_like` is an optional `LIKE` pattern (escaped with `\\`) applied to
+    /// `file_path` in SQL before any name matching happens.
+    pub fn literal_search_by_name(&self, query: &str, path_like: Option<&str>) -> Result<Vec<Node>> {
+        self.literal_search_by_name_where(query, path_like, &[])
+    }
+
+    /// [`Self::literal_search_by_name`] over the nodes whose metadata
+    /// matches every one of `metadata` (compared with `json_extract`).
+    pub fn literal_search_by_name_where(
+        &self,
+        query: &str,
+        path_like: Option<&str>,
+        metadata: &[MetadataFilter],
+    ) -> Result<Vec<Node>> {
+        let conn = self.db().lock()?;
         // Use Rust's Unicode-aware to_lowercase() rather than SQLite's LOWER()
         // which only folds ASCII letters (é, ü, Cyrillic, etc. are left as-is).
         // We fetch all nodes for the project and filter in Rust so that
         // non-ASCII case folding works correctly for every script.
         let query_lower = query.to_lowercase();
 
-        let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash
-             FROM nodes WHERE project_id = ?1",
-        )?;
+        let mut values: Vec<Value> = vec![
+            Value::Text(self.project_id().to_string()),
+            path_like.map_or(Value::Null, |p| Value::Text(p.to_string())),
+        ];
+        let mut conditions = String::new();
+        for filter in metadata {
+            values.push(Value::Text(filter.json_path()));
+            values.push(filter.sql_value());
+            let (path, value) = (values.len() - 1, values.len());
+            conditions.push_str(&format!(" AND json_extract(metadata, ?{path}) = ?{value}"));
+        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+             FROM nodes
+             WHERE project_id = ?1 AND (?2 IS NULL OR file_path LIKE ?2 ESCAPE '\\'){conditions}"
+        ))?;
         let all_nodes: Vec<Node> = stmt
-            .query_map(params![self.project_id()], node_from_row)?
+            .query_map(rusqlite::params_from_iter(&values), node_from_row)?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
         // Prefer prefix matches; fall back to contains matches.
@@ -38,8 +69,53 @@ impl KnowledgeGraph {
         Ok(results)
     }
 
+    /// Nodes whose lowercased name contains at least one of `tokens` (which
+    /// must already be lowercase). Candidates for identifier-token matching.
+    pub fn literal_search_by_tokens(&self, tokens: &[String], path_like: Option<&str>) -> Result<Vec<Node>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+             FROM nodes
+             WHERE project_id = ?1 AND (?2 IS NULL OR file_path LIKE ?2 ESCAPE '\\')",
+        )?;
+        let all_nodes: Vec<Node> = stmt
+            .query_map(params![self.project_id(), path_like], node_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(all_nodes
+            .into_iter()
+            .filter(|n| {
+                let name = n.name.to_lowercase();
+                tokens.iter().any(|t| name.contains(t.as_str()))
+            })
+            .collect())
+    }
+
+    /// Nodes whose name length (in characters) lies within `min_len..=max_len`,
+    /// used as a cheap pre-filter before edit-distance ranking.
+    pub fn fuzzy_name_candidates(
+        &self,
+        min_len: usize,
+        max_len: usize,
+        path_like: Option<&str>,
+    ) -> Result<Vec<Node>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+             FROM nodes
+             WHERE project_id = ?1 AND length(name) BETWEEN ?2 AND ?3
+               AND (?4 IS NULL OR file_path LIKE ?4 ESCAPE '\\')",
+        )?;
+        let nodes = stmt
+            .query_map(
+                params![self.project_id(), min_len as i64, max_len as i64, path_like],
+                node_from_row,
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(nodes)
+    }
+
     pub fn get_all_file_paths(&self) -> Result<HashSet<String>> {
-        let conn = self.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db().lock()?;
         let mut stmt = conn.prepare(
             "SELECT DISTINCT file_path FROM nodes
              WHERE project_id = ?1 AND node_type = 'file' AND file_path IS NOT NULL",
@@ -50,30 +126,303 @@ impl KnowledgeGraph {
         Ok(paths)
     }
 
-    pub fn delete_nodes_for_file(&self, file_path: &str) -> Result<()> {
-        let conn = self.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "DELETE FROM fts_content WHERE node_id IN
-             (SELECT id FROM nodes WHERE file_path = ?1 AND project_id = ?2)",
-            params![file_path, self.project_id()],
+    /// Maps each indexed file path to its File node id, the most recently
+    /// written one if a path has several.
+    pub fn file_node_ids(&self) -> Result<HashMap<String, String>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT file_path, id FROM nodes
+             WHERE project_id = ?1 AND node_type = 'file' AND file_path IS NOT NULL
+             ORDER BY updated_at, rowid",
         )?;
-        conn.execute(
-            "DELETE FROM edges WHERE
-             source_id IN (SELECT id FROM nodes WHERE file_path = ?1 AND project_id = ?2)
-             OR target_id IN (SELECT id FROM nodes WHERE file_path = ?1 AND project_id = ?2)",
-            params![file_path, self.project_id()],
+        let ids = stmt
+            .query_map(params![self.project_id()], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<std::result::Result<HashMap<_, _>, _>>()?;
+        Ok(ids)
+    }
+
+    /// The indexed files with an Imports edge into any of `paths`. Looked up
+    /// from the target file, not through the project index (`+`).
+    pub fn file_importers(&self, paths: &[String]) -> Result<HashSet<String>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.file_path FROM edges e
+             JOIN nodes s ON s.id = e.source_id
+             JOIN nodes t ON t.id = e.target_id
+             WHERE +e.project_id = ?1 AND e.edge_type = 'imports'
+               AND t.node_type = 'file' AND t.file_path = ?2 AND s.file_path IS NOT NULL",
         )?;
-        conn.execute(
-            "DELETE FROM nodes WHERE file_path = ?1 AND project_id = ?2",
-            params![file_path, self.project_id()],
+        let mut importers = HashSet::new();
+        for path in paths {
+            let rows = stmt.query_map(params![self.project_id(), path], |row| row.get(0))?;
+            for row in rows {
+                importers.insert(row?);
+            }
+        }
+        Ok(importers)
+    }
+
+    /// Up to `limit` indexed file paths that sort after `after`, in order, so
+    /// a caller can page through the files by passing the last path back.
+    pub fn file_paths_after(&self, after: Option<&str>, limit: usize) -> Result<Vec<String>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT file_path FROM nodes
+             WHERE project_id = ?1 AND node_type = 'file' AND file_path > ?2
+             ORDER BY file_path LIMIT ?3",
         )?;
-        Ok(())
+        let paths = stmt
+            .query_map(params![self.project_id(), after.unwrap_or(""), limit as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(paths)
+    }
+
+    /// Records the token estimate of a node's full content.
+    pub fn set_token_count(&self, node_id: &str, tokens: u64) -> Result<()> {
+        self.in_transaction(|w| w.set_token_count(node_id, tokens))
+    }
+
+    /// Recorded token counts for those of `node_ids` that have one.
+    pub fn token_counts(&self, node_ids: &[&str]) -> Result<HashMap<String, u64>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT token_count FROM nodes WHERE id = ?1 AND project_id = ?2 AND token_count IS NOT NULL",
+        )?;
+        let mut counts = HashMap::new();
+        for id in node_ids {
+            let tokens: Option<i64> = stmt
+                .query_row(params![id, self.project_id()], |row| row.get(0))
+                .optional()?;
+            if let Some(tokens) = tokens {
+                counts.insert(id.to_string(), tokens.max(0) as u64);
+            }
+        }
+        Ok(counts)
+    }
+
+    pub fn delete_nodes_for_file(&self, file_path: &str) -> Result<()> {
+        self.in_transaction(|w| {
+            let conn = w.conn();
+            conn.execute(
+                "DELETE FROM fts_content WHERE node_id IN
+                 (SELECT id FROM nodes WHERE file_path = ?1 AND project_id = ?2)",
+                params![file_path, self.project_id()],
+            )?;
+            conn.execute(
+                "DELETE FROM embeddings WHERE node_id IN
+                 (SELECT id FROM nodes WHERE file_path = ?1 AND project_id = ?2)",
+                params![file_path, self.project_id()],
+            )?;
+            // Edges cascade; facts about the nodes keep a NULL node_id.
+            conn.execute(
+                "DELETE FROM nodes WHERE file_path = ?1 AND project_id = ?2",
+                params![file_path, self.project_id()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Deletes a node with its FTS row, embedding and incident edges. Returns
+    /// `false` if the project has no node `node_id`.
+    pub fn delete_node(&self, node_id: &str) -> Result<bool> {
+        self.in_transaction(|w| w.delete_node(node_id))
+    }
+
+    /// Replaces a node's summary. Returns `false` if the node is not found.
+    pub fn update_node_summary(&self, node_id: &str, summary: Option<&str>) -> Result<bool> {
+        self.in_transaction(|w| w.update_node_summary(node_id, summary))
+    }
+
+    /// Moves a node to new lines. Returns `false` if the node is not found.
+    pub fn update_node_lines(&self, node_id: &str, start_line: i64, end_line: i64) -> Result<bool> {
+        self.in_transaction(|w| w.update_node_lines(node_id, start_line, end_line))
+    }
+
+    /// Counts nodes, edges, files, FTS rows and embeddings of the project,
+    /// and the inconsistencies worth knowing about.
+    pub fn stats(&self) -> Result<GraphStats> {
+        let conn = self.db().lock()?;
+        let project = self.project_id();
+        let count = |sql: &str| -> Result<u64> {
+            let n: i64 = conn.query_row(sql, params![project], |row| row.get(0))?;
+            Ok(n.max(0) as u64)
+        };
+        let by_type = |sql: &str| -> Result<BTreeMap<String, u64>> {
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt
+                .query_map(params![project], |row| Ok((row.get(0)?, row.get::<_, i64>(1)?.max(0) as u64)))?
+                .collect::<std::result::Result<_, _>>()?;
+            Ok(rows)
+        };
+        let nodes_by_type =
+            by_type("SELECT node_type, COUNT(*) FROM nodes WHERE project_id = ?1 GROUP BY node_type")?;
+        let edges_by_type =
+            by_type("SELECT edge_type, COUNT(*) FROM edges WHERE project_id = ?1 GROUP BY edge_type")?;
+        let page_bytes: i64 = conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(GraphStats {
+            nodes: nodes_by_type.values().sum(),
+            nodes_by_type,
+            edges: edges_by_type.values().sum(),
+            edges_by_type,
+            files: count(
+                "SELECT COUNT(DISTINCT file_path) FROM nodes WHERE project_id = ?1 AND file_path IS NOT NULL",
+            )?,
+            fts_rows: count("SELECT COUNT(*) FROM fts_content WHERE project_id = ?1")?,
+            embeddings: count("SELECT COUNT(*) FROM embeddings WHERE project_id = ?1")?,
+            orphaned_edges: count(
+                "SELECT COUNT(*) FROM edges e WHERE e.project_id = ?1
+                 AND (NOT EXISTS (SELECT 1 FROM nodes WHERE id = e.source_id)
+                      OR NOT EXISTS (SELECT 1 FROM nodes WHERE id = e.target_id))",
+            )?,
+            nodes_without_file_path: count(
+                "SELECT COUNT(*) FROM nodes WHERE project_id = ?1 AND file_path IS NULL",
+            )?,
+            db_bytes: page_bytes.max(0) as u64,
+        })
+    }
+
+    /// Deletes the project's rows that point at nodes or files no longer in
+    /// the graph, in one transaction, and counts them per kind.
+    pub fn repair(&self) -> Result<RepairReport> {
+        self.repair_with(true)
+    }
+
+    /// Counts what [`Self::repair`] would delete without deleting it.
+    pub fn diagnose(&self) -> Result<RepairReport> {
+        self.repair_with(false)
+    }
+
+    fn repair_with(&self, apply: bool) -> Result<RepairReport> {
+        // Each entry is the table and the condition selecting its dangling rows.
+        const CHECKS: [(&str, &str); 5] = [
+            (
+                "edges",
+                "project_id = ?1 AND (source_id NOT IN (SELECT id FROM nodes)
+                                      OR target_id NOT IN (SELECT id FROM nodes))",
+            ),
+            ("fts_content", "project_id = ?1 AND node_id NOT IN (SELECT id FROM nodes)"),
+            ("embeddings", "project_id = ?1 AND node_id NOT IN (SELECT id FROM nodes)"),
+            (
+                "file_hashes",
+                "project_id = ?1 AND file_path NOT IN
+                 (SELECT file_path FROM nodes WHERE project_id = ?1 AND file_path IS NOT NULL)",
+            ),
+            (
+                "chunk_hashes",
+                "project_id = ?1 AND substr(chunk_key, 1, instr(chunk_key, '::') - 1) NOT IN
+                 (SELECT file_path FROM nodes WHERE project_id = ?1 AND file_path IS NOT NULL)",
+            ),
+        ];
+        let conn = self.db().lock()?;
+        let tx = busy::write_transaction(&conn)?;
+        let mut counts = [0u64; 5];
+        for ((table, condition), count) in CHECKS.iter().zip(&mut counts) {
+            let n: i64 = tx.query_row(
+                &format!("SELECT COUNT(*) FROM {table} WHERE {condition}"),
+                params![self.project_id()],
+                |row| row.get(0),
+            )?;
+            *count = n.max(0) as u64;
+            if apply && n > 0 {
+                let sql = format!("DELETE FROM {table} WHERE {condition}");
+                tx.execute(&sql, params![self.project_id()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(RepairReport {
+            orphaned_edges: counts[0],
+            orphaned_fts_rows: counts[1],
+            orphaned_embeddings: counts[2],
+            stale_file_hashes: counts[3],
+            stale_chunk_hashes: counts[4],
+        })
+    }
+
+    /// Breadth-first walk from `start_id` over edges in `direction`, optionally
+    /// only of `edge_types`, up to `max_depth` hops. Each node is visited
+    /// once, at its shallowest depth, so cycles end the walk; it stops after
+    /// `max_nodes` nodes. The start node itself is not returned.
+    pub fn traverse(
+        &self,
+        start_id: &str,
+        max_depth: usize,
+        edge_types: Option<&[EdgeType]>,
+        direction: EdgeDirection,
+        max_nodes: usize,
+    ) -> Result<Vec<TraversalStep>> {
+        let mut visited: HashSet<String> = HashSet::from([start_id.to_string()]);
+        let mut frontier = vec![start_id.to_string()];
+        let mut steps = Vec::new();
+        for depth in 1..=max_depth {
+            let mut next = Vec::new();
+            for node_id in &frontier {
+                let neighbors = self.get_neighbors_filtered(node_id, direction, edge_types, usize::MAX)?;
+                for (edge, node) in neighbors {
+                    if steps.len() == max_nodes {
+                        return Ok(steps);
+                    }
+                    if visited.insert(node.id.clone()) {
+                        next.push(node.id.clone());
+                        steps.push(TraversalStep { node, depth, edge });
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        Ok(steps)
+    }
+
+    /// The File node of `file_path`, if the file is indexed.
+    pub fn file_node(&self, file_path: &str) -> Result<Option<Node>> {
+        let conn = self.db().lock()?;
+        let node = conn
+            .query_row(
+                "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+                 FROM nodes WHERE file_path = ?1 AND project_id = ?2 AND node_type = 'file'",
+                params![file_path, self.project_id()],
+                node_from_row,
+            )
+            .optional()?;
+        Ok(node)
+    }
+
+    /// Every node of `file_path` (the File node and its chunks), in line order.
+    pub fn get_nodes_for_file(&self, file_path: &str) -> Result<Vec<Node>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+             FROM nodes WHERE file_path = ?1 AND project_id = ?2
+             ORDER BY start_line, end_line DESC, name",
+        )?;
+        let rows = stmt
+            .query_map(params![file_path, self.project_id()], node_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Deletes the nodes of `file_path` whose ids are not in `keep`, with
+    /// their FTS rows, embeddings and edges. Returns the deleted nodes.
+    pub fn delete_file_nodes_except(
+        &self,
+        file_path: &str,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<Node>> {
+        self.in_transaction(|w| w.delete_file_nodes_except(file_path, keep))
     }
 
     pub fn get_all_nodes(&self) -> Result<Vec<Node>> {
-        let conn = self.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db().lock()?;
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash
+            "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
              FROM nodes WHERE project_id = ?1",
         )?;
         let rows = stmt
@@ -82,31 +431,205 @@ impl KnowledgeGraph {
         Ok(rows)
     }
 
-    pub fn fts_search(&self, query: &str, limit: usize) -> Result<Vec<(Node, f64)>> {
-        let conn = self.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+    /// Every Function node with the content indexed for it.
+    pub fn function_nodes_with_content(&self) -> Result<Vec<(Node, String)>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT n.id, n.project_id, n.name, n.node_type, n.file_path,
+                    n.start_line, n.end_line, n.summary, n.content_hash, n.updated_at, f.content,
+                    n.metadata
+             FROM nodes n
+             JOIN fts_content f ON f.node_id = n.id
+             WHERE n.project_id = ?1 AND n.node_type = 'function'",
+        )?;
+        let rows = stmt
+            .query_map(params![self.project_id()], |row| Ok((node_from_row(row)?, row.get(10)?)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Functions with a Calls edge into a function named `name` (or
+    /// `Type::name`), as `(caller, callee, weight)`, heaviest first.
+    pub fn callers_of(&self, name: &str) -> Result<Vec<(Node, Node, f64)>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.project_id, s.name, s.node_type, s.file_path,
+                    s.start_line, s.end_line, s.summary, s.content_hash, s.updated_at,
+                    t.id, t.project_id, t.name, t.node_type, t.file_path,
+                    t.start_line, t.end_line, t.summary, t.content_hash, t.updated_at, e.weight,
+                    s.metadata, t.metadata AS callee_metadata
+             FROM edges e
+             JOIN nodes s ON s.id = e.source_id
+             JOIN nodes t ON t.id = e.target_id
+             WHERE e.project_id = ?1 AND e.edge_type = 'calls'
+               AND (t.name = ?2
+                    OR substr(t.name, -length(?2) - 2) = '::' || ?2
+                    OR substr(t.name, -length(?2) - 1) = '.' || ?2)
+             ORDER BY e.weight DESC, s.name",
+        )?;
+        let rows = stmt
+            .query_map(params![self.project_id(), name], |row| {
+                let callee = Node {
+                    id: row.get(10)?,
+                    project_id: row.get(11)?,
+                    name: row.get(12)?,
+                    node_type: NodeType::parse_str(&row.get::<_, String>(13)?),
+                    file_path: row.get(14)?,
+                    start_line: row.get(15)?,
+                    end_line: row.get(16)?,
+                    summary: row.get(17)?,
+                    content_hash: row.get(18)?,
+                    updated_at: row.get(19)?,
+                    metadata: metadata_from_sql(row.get("callee_metadata")?),
+                };
+                Ok((node_from_row(row)?, callee, row.get(20)?))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Stores (or replaces) the embedding of `node_id`, tagged with the model
+    /// that produced it.
+    pub fn upsert_embedding(&self, node_id: &str, vector: &[f32], model: &str) -> Result<()> {
+        self.in_transaction(|w| w.upsert_embedding(node_id, vector, model))
+    }
+
+    /// Every node of the project with its stored embedding for `model`, or
+    /// `None` when the node has not been embedded with that model yet (or the
+    /// stored blob does not match its recorded dimension).
+    pub fn nodes_with_embeddings(
+        &self,
+        model: &str,
+        path_like: Option<&str>,
+    ) -> Result<Vec<(Node, Option<Vec<f32>>)>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT n.id, n.project_id, n.name, n.node_type, n.file_path,
+                    n.start_line, n.end_line, n.summary, n.content_hash, n.updated_at, e.vector, e.dimension,
+                    n.metadata
+             FROM nodes n
+             LEFT JOIN embeddings e ON e.node_id = n.id AND e.model = ?2
+             WHERE n.project_id = ?1
+               AND (?3 IS NULL OR n.file_path LIKE ?3 ESCAPE '\\')",
+        )?;
+        let rows = stmt
+            .query_map(params![self.project_id(), model, path_like], |row| {
+                let dimension = row.get::<_, Option<i64>>(11)?.unwrap_or(0) as usize;
+                let vector = row
+                    .get::<_, Option<Vec<u8>>>(10)?
+                    .map(|blob| decode_vector(&blob))
+                    .filter(|v| dimension == 0 || v.len() == dimension);
+                Ok((node_from_row(row)?, vector))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// SQLite's `PRAGMA data_version` of the graph's connection, which
+    /// changes whenever another connection, such as another process,
+    /// commits to the database.
+    pub fn data_version(&self) -> Result<i64> {
+        let conn = self.db().lock()?;
+        Ok(conn.query_row("PRAGMA data_version", [], |row| row.get(0))?)
+    }
+
+    /// The project's nodes with no stored embedding for `model`.
+    pub fn nodes_without_embedding(&self, model: &str) -> Result<Vec<Node>> {
+        let conn = self.db().lock()?;
         let mut stmt = conn.prepare(
-            "SELECT n.id, n.project_id, n.name, n.node_type, n.file_path, n.start_line, n.end_line, n.summary, n.content_hash,
-                    bm25(fts_content) as rank
+            "SELECT n.id, n.project_id, n.name, n.node_type, n.file_path,
+                    n.start_line, n.end_line, n.summary, n.content_hash, n.updated_at, n.metadata
+             FROM nodes n
+             WHERE n.project_id = ?1
+               AND NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.node_id = n.id AND e.model = ?2)",
+        )?;
+        let nodes = stmt
+            .query_map(params![self.project_id(), model], node_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(nodes)
+    }
+
+    /// Returns each hit with its bm25 rank (columns weighted by `weights`) and
+    /// a short `snippet()` excerpt of the indexed content around the match.
+    pub fn fts_search(
+        &self,
+        query: &str,
+        limit: usize,
+        path_like: Option<&str>,
+        weights: &FtsWeights,
+    ) -> Result<Vec<(Node, f64, String)>> {
+        let conn = self.db().lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT n.id, n.project_id, n.name, n.node_type, n.file_path, n.start_line, n.end_line, n.summary, n.content_hash, n.updated_at,
+                    bm25(fts_content, 0.0, 0.0, ?5, ?6, ?7) as rank,
+                    snippet(fts_content, 3, '', '', '…', 16), n.metadata
              FROM fts_content f
              JOIN nodes n ON n.id = f.node_id
              WHERE fts_content MATCH ?1 AND f.project_id = ?2
+               AND (?4 IS NULL OR n.file_path LIKE ?4 ESCAPE '\\')
              ORDER BY rank
              LIMIT ?3",
         )?;
         let rows = stmt
-            .query_map(params![query, self.project_id(), limit as i64], |row| {
-                Ok((node_from_row(row)?, row.get::<_, f64>(9)?))
-            })?
+            .query_map(
+                params![
+                    query,
+                    self.project_id(),
+                    limit as i64,
+                    path_like,
+                    weights.name,
+                    weights.content,
+                    weights.file_path
+                ],
+                |row| {
+                    Ok((
+                        node_from_row(row)?,
+                        row.get::<_, f64>(10)?,
+                        row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+                    ))
+                },
+            )?
             .collect::<std::result::Result<Vec<_>, _>>()?;
         Ok(rows)
     }
 }
 
+fn decode_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+pub(crate) fn node_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Node> {
+    Ok(Node {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        name: row.get(2)?,
+        node_type: NodeType::parse_str(&row.get::<_, String>(3)?),
+        file_path: row.get(4)?,
+        start_line: row.get(5)?,
+        end_line: row.get(6)?,
+        summary: row.get(7)?,
+        content_hash: row.get(8)?,
+        updated_at: row.get(9)?,
+        metadata: metadata_from_sql(row.get("metadata")?),
+    })
+}
+
+/// Parses a `nodes.metadata` value. NULL, and text that is not JSON, read
+/// as no metadata.
+pub(crate) fn metadata_from_sql(text: Option<String>) -> Option<serde_json::Value> {
+    text.and_then(|text| serde_json::from_str(&text).ok())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        graph::{Edge, EdgeType, KnowledgeGraph, Node, NodeType},
-        HermesEngine,
+        graph::{
+            Edge, EdgeDirection, EdgeType, KnowledgeGraph, MetadataFilter, Node, NodeType,
+            RepairReport,
+        },
+        FtsWeights, HermesEngine, HermesError,
     };
 
     fn make_graph(engine: &HermesEngine) -> KnowledgeGraph {
@@ -124,6 +647,8 @@ mod tests {
             end_line: Some(10),
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
         graph.add_node(&node).unwrap();
         node
@@ -138,7 +663,7 @@ mod tests {
         insert_node(&graph, "n1", "fetch_alerts", "src/api.rs");
         insert_node(&graph, "n2", "process_alerts", "src/api.rs");
 
-        let results = graph.literal_search_by_name("fetch").unwrap();
+        let results = graph.literal_search_by_name("fetch", None).unwrap();
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].name, "fetch_alerts");
     }
@@ -150,7 +675,7 @@ mod tests {
         insert_node(&graph, "n1", "fetch_alerts_handler", "src/api.rs");
 
         // "alerts" is not a prefix but is contained in the name
-        let results = graph.literal_search_by_name("alerts").unwrap();
+        let results = graph.literal_search_by_name("alerts", None).unwrap();
         assert!(!results.is_empty());
         assert_eq!(results[0].name, "fetch_alerts_handler");
     }
@@ -161,7 +686,7 @@ mod tests {
         let graph = make_graph(&engine);
         insert_node(&graph, "n1", "HandleRequest", "src/server.rs");
 
-        let results = graph.literal_search_by_name("handlerequest").unwrap();
+        let results = graph.literal_search_by_name("handlerequest", None).unwrap();
         assert_eq!(results.len(), 1);
     }
 
@@ -171,10 +696,96 @@ mod tests {
         let graph = make_graph(&engine);
         insert_node(&graph, "n1", "my_func", "src/lib.rs");
 
-        let results = graph.literal_search_by_name("nonexistent_xyz").unwrap();
+        let results = graph.literal_search_by_name("nonexistent_xyz", None).unwrap();
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn literal_search_pushes_down_path_like() {
+        let engine = HermesEngine::in_memory("gq-path-like").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fetch_alerts", "/p/src/api/alerts.rs");
+        insert_node(&graph, "n2", "fetch_rates", "/p/src/fx/rates.rs");
+
+        let results = graph.literal_search_by_name("fetch", Some("%src/api/%")).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "fetch_alerts");
+    }
+
+    #[test]
+    fn node_metadata_round_trips_and_filters_literal_search() {
+        let engine = HermesEngine::in_memory("gq-metadata").unwrap();
+        let graph = make_graph(&engine);
+        let add = |name: &str, metadata: serde_json::Value| {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(NodeType::Function)
+                .file_path("src/lib.rs")
+                .metadata(metadata)
+                .build();
+            graph.add_node(&node).unwrap();
+            node.id
+        };
+        let pub_rust = add(
+            "parse_config",
+            serde_json::json!({"language": "rust", "visibility": "pub", "complexity": 3,
+                               "owner": {"team": "core"}, "generated": false}),
+        );
+        add("parse_args", serde_json::json!({"language": "python", "complexity": 3}));
+        // A row from before the column existed.
+        insert_node(&graph, "legacy", "parse_legacy", "src/lib.rs");
+
+        let stored = graph.get_node(&pub_rust).unwrap().unwrap();
+        assert_eq!(stored.metadata.as_ref().unwrap()["owner"]["team"], "core");
+        assert_eq!(graph.get_node("legacy").unwrap().unwrap().metadata, None);
+
+        let names = |filters: &[&str]| -> Vec<String> {
+            let filters: Vec<MetadataFilter> =
+                filters.iter().map(|f| MetadataFilter::parse_str(f).unwrap()).collect();
+            let mut names: Vec<String> = graph
+                .literal_search_by_name_where("parse", None, &filters)
+                .unwrap()
+                .into_iter()
+                .map(|n| n.name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&[]).len(), 3);
+        assert_eq!(names(&["metadata.language = \"rust\""]), ["parse_config"]);
+        assert_eq!(names(&["complexity=3"]), ["parse_args", "parse_config"]);
+        assert_eq!(names(&["complexity=3", "language=python"]), ["parse_args"]);
+        assert_eq!(names(&["owner.team=core", "generated=false"]), ["parse_config"]);
+        assert!(names(&["language=go"]).is_empty());
+    }
+
+    #[test]
+    fn literal_search_by_tokens_matches_any_token() {
+        let engine = HermesEngine::in_memory("gq-tokens").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fetchExchangeRate", "src/fx.rs");
+        insert_node(&graph, "n2", "update_rate", "src/fx.rs");
+        insert_node(&graph, "n3", "unrelated", "src/other.rs");
+
+        let tokens = vec!["exchange".to_string(), "rate".to_string()];
+        let results = graph.literal_search_by_tokens(&tokens, None).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn fuzzy_name_candidates_bounds_by_length() {
+        let engine = HermesEngine::in_memory("gq-fuzzy").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "abc", "src/a.rs");
+        insert_node(&graph, "n2", "abcdef", "src/a.rs");
+        insert_node(&graph, "n3", "abcdefghijkl", "src/a.rs");
+
+        let results = graph.fuzzy_name_candidates(4, 8, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "abcdef");
+    }
+
     // ── get_all_nodes ────────────────────────────────────────────────────────────
 
     #[test]
@@ -213,6 +824,8 @@ mod tests {
             end_line: None,
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
         graph.add_node(&file_node).unwrap();
 
@@ -264,6 +877,283 @@ mod tests {
         assert!(neighbors.is_empty());
     }
 
+    #[test]
+    fn embeddings_round_trip_and_are_deleted_with_file() {
+        let engine = HermesEngine::in_memory("gq-embed").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fn_a", "src/a.rs");
+        insert_node(&graph, "n2", "fn_b", "src/b.rs");
+        graph.upsert_embedding("n1", &[0.5, -0.25, 1.0], "m").unwrap();
+
+        let rows = graph.nodes_with_embeddings("m", None).unwrap();
+        let stored = rows.iter().find(|(n, _)| n.id == "n1").unwrap();
+        assert_eq!(stored.1.as_deref(), Some(&[0.5, -0.25, 1.0][..]));
+        assert!(rows.iter().find(|(n, _)| n.id == "n2").unwrap().1.is_none());
+        // A different model's vectors are not returned.
+        assert!(graph.nodes_with_embeddings("other", None).unwrap().iter().all(|(_, v)| v.is_none()));
+
+        graph.delete_nodes_for_file("src/a.rs").unwrap();
+        let conn = engine.db().lock().unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    // ── single-node CRUD ─────────────────────────────────────────────────────
+
+    #[test]
+    fn delete_node_removes_its_fts_row_embedding_and_edges() {
+        let engine = HermesEngine::in_memory("gq-delete-node").unwrap();
+        let graph = make_graph(&engine);
+        let n1 = insert_node(&graph, "n1", "fn_a", "src/a.rs");
+        let n2 = insert_node(&graph, "n2", "fn_b", "src/a.rs");
+        graph.index_fts(&n1, "fn fn_a() {}").unwrap();
+        graph.upsert_embedding("n1", &[1.0], "m").unwrap();
+        for (id, source, target) in [("e1", "n1", "n2"), ("e2", "n2", "n1")] {
+            graph
+                .add_edge(&Edge {
+                    id: id.to_string(),
+                    project_id: graph.project_id().to_string(),
+                    source_id: source.to_string(),
+                    target_id: target.to_string(),
+                    edge_type: EdgeType::Calls,
+                    weight: 1.0,
+                })
+                .unwrap();
+        }
+
+        assert!(graph.delete_node("n1").unwrap());
+        assert!(graph.get_node("n1").unwrap().is_none());
+        assert!(graph.get_neighbors(&n2.id).unwrap().is_empty());
+        let conn = engine.db().lock().unwrap();
+        for table in ["fts_content", "embeddings"] {
+            let sql = format!("SELECT COUNT(*) FROM {table} WHERE node_id = 'n1'");
+            let count: i64 = conn.query_row(&sql, [], |row| row.get(0)).unwrap();
+            assert_eq!(count, 0, "{table}");
+        }
+    }
+
+    #[test]
+    fn delete_node_reports_missing_and_foreign_nodes() {
+        let engine = HermesEngine::in_memory("gq-delete-missing").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fn_a", "src/a.rs");
+        let other = KnowledgeGraph::new(engine.db().clone(), "another-project");
+
+        assert!(!graph.delete_node("missing").unwrap());
+        assert!(!other.delete_node("n1").unwrap());
+        assert!(graph.get_node("n1").unwrap().is_some());
+    }
+
+    #[test]
+    fn update_node_summary_and_lines_change_only_those_fields() {
+        let engine = HermesEngine::in_memory("gq-update-node").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fn_a", "src/a.rs");
+
+        assert!(graph.update_node_summary("n1", Some("Parses the header")).unwrap());
+        assert!(graph.update_node_lines("n1", 20, 35).unwrap());
+        let node = graph.get_node("n1").unwrap().unwrap();
+        assert_eq!(node.summary.as_deref(), Some("Parses the header"));
+        assert_eq!((node.start_line, node.end_line), (Some(20), Some(35)));
+        assert_eq!(node.name, "fn_a");
+
+        assert!(graph.update_node_summary("n1", None).unwrap());
+        assert!(graph.get_node("n1").unwrap().unwrap().summary.is_none());
+        assert!(!graph.update_node_summary("missing", Some("x")).unwrap());
+        assert!(!graph.update_node_lines("missing", 1, 2).unwrap());
+        let other = KnowledgeGraph::new(engine.db().clone(), "another-project");
+        assert!(!other.update_node_lines("n1", 1, 2).unwrap());
+        for (start, end) in [(6, 2), (0, 3), (-1, -1)] {
+            let error = graph.update_node_lines("n1", start, end).unwrap_err();
+            assert!(matches!(error, HermesError::InvalidInput(_)), "{error}");
+        }
+        assert_eq!(graph.get_node("n1").unwrap().unwrap().start_line, Some(20));
+    }
+
+    #[test]
+    fn get_nodes_for_file_lists_its_nodes_in_line_order() {
+        let engine = HermesEngine::in_memory("gq-file-nodes").unwrap();
+        let graph = make_graph(&engine);
+        insert_node(&graph, "n1", "fn_late", "src/a.rs");
+        insert_node(&graph, "n2", "fn_early", "src/a.rs");
+        insert_node(&graph, "n3", "fn_b", "src/b.rs");
+        graph.update_node_lines("n1", 40, 50).unwrap();
+
+        let names: Vec<String> =
+            graph.get_nodes_for_file("src/a.rs").unwrap().into_iter().map(|n| n.name).collect();
+        assert_eq!(names, ["fn_early", "fn_late"]);
+        assert!(graph.get_nodes_for_file("src/missing.rs").unwrap().is_empty());
+    }
+
+    // ── stats ────────────────────────────────────────────────────────────────
+
+    #[test]
+    fn stats_count_the_project_and_find_orphaned_edges() {
+        let engine = HermesEngine::in_memory("gq-stats").unwrap();
+        let graph = make_graph(&engine);
+        let a = insert_node(&graph, "a", "fn_a", "src/a.rs");
+        insert_node(&graph, "b", "fn_b", "src/a.rs");
+        insert_node(&graph, "c", "fn_c", "src/c.rs");
+        graph.index_fts(&a, "fn fn_a() {}").unwrap();
+        graph.upsert_embedding("a", &[1.0], "m").unwrap();
+        for (id, target) in [("e1", "b"), ("e2", "c")] {
+            graph
+                .add_edge(&Edge {
+                    id: id.to_string(),
+                    project_id: graph.project_id().to_string(),
+                    source_id: "a".to_string(),
+                    target_id: target.to_string(),
+                    edge_type: EdgeType::Calls,
+                    weight: 1.0,
+                })
+                .unwrap();
+        }
+        // Foreign keys normally prevent this, but a connection without them
+        // (such as the sqlite3 shell) can still leave dangling edges.
+        engine
+            .db()
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "PRAGMA foreign_keys = OFF; DELETE FROM nodes WHERE id = 'c'; PRAGMA foreign_keys = ON;",
+            )
+            .unwrap();
+        KnowledgeGraph::new(engine.db().clone(), "another-project")
+            .add_node(&Node { project_id: "another-project".into(), id: "x".into(), ..a.clone() })
+            .unwrap();
+
+        let stats = graph.stats().unwrap();
+        assert_eq!((stats.nodes, stats.nodes_by_type["function"]), (2, 2));
+        assert_eq!((stats.edges, stats.edges_by_type["calls"]), (2, 2));
+        assert_eq!(stats.files, 1);
+        assert_eq!((stats.fts_rows, stats.embeddings), (1, 1));
+        assert_eq!(stats.orphaned_edges, 1);
+        assert_eq!(stats.nodes_without_file_path, 0);
+        assert!(stats.db_bytes > 0);
+    }
+
+    #[test]
+    fn repair_deletes_dangling_rows_and_diagnose_only_counts_them() {
+        let engine = HermesEngine::in_memory("gq-repair").unwrap();
+        let graph = make_graph(&engine);
+        let a = insert_node(&graph, "a", "fn_a", "src/a.rs");
+        let c = insert_node(&graph, "c", "fn_c", "src/c.rs");
+        graph.index_fts(&a, "fn fn_a() {}").unwrap();
+        graph.index_fts(&c, "fn fn_c() {}").unwrap();
+        graph.upsert_embedding("c", &[1.0], "m").unwrap();
+        graph
+            .add_edge(&Edge {
+                id: "e1".to_string(),
+                project_id: graph.project_id().to_string(),
+                source_id: "a".to_string(),
+                target_id: "c".to_string(),
+                edge_type: EdgeType::Calls,
+                weight: 1.0,
+            })
+            .unwrap();
+        {
+            let conn = engine.db().lock().unwrap();
+            for path in ["src/a.rs", "src/c.rs"] {
+                conn.execute(
+                    "INSERT INTO file_hashes (file_path, project_id, content_hash) VALUES (?1, ?2, 'h')",
+                    rusqlite::params![path, graph.project_id()],
+                )
+                .unwrap();
+                conn.execute(
+                    "INSERT INTO chunk_hashes (project_id, chunk_key, content_hash) VALUES (?1, ?2, 'h')",
+                    rusqlite::params![graph.project_id(), format!("{path}::Type::method")],
+                )
+                .unwrap();
+            }
+            // Removing `c` without the usual cleanup, as a crashed run or the
+            // sqlite3 shell could, leaves its edge, FTS row, embedding and hashes.
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF; DELETE FROM nodes WHERE id = 'c'; PRAGMA foreign_keys = ON;",
+            )
+            .unwrap();
+        }
+        let expected = RepairReport {
+            orphaned_edges: 1,
+            orphaned_fts_rows: 1,
+            orphaned_embeddings: 1,
+            stale_file_hashes: 1,
+            stale_chunk_hashes: 1,
+        };
+
+        assert_eq!(graph.diagnose().unwrap(), expected);
+        assert_eq!(graph.diagnose().unwrap(), expected, "diagnose must not delete");
+        assert_eq!(graph.repair().unwrap(), expected);
+        assert_eq!(graph.diagnose().unwrap().total(), 0);
+        let stats = graph.stats().unwrap();
+        assert_eq!((stats.edges, stats.fts_rows, stats.embeddings), (0, 1, 0));
+        let hashes: i64 = engine
+            .db()
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM file_hashes) + (SELECT COUNT(*) FROM chunk_hashes)",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(hashes, 2, "the hashes of src/a.rs are kept");
+    }
+
+    // ── traverse ─────────────────────────────────────────────────────────────
+
+    #[test]
+    fn traverse_walks_breadth_first_and_stops_at_cycles_depth_and_cap() {
+        let engine = HermesEngine::in_memory("gq-traverse").unwrap();
+        let graph = make_graph(&engine);
+        for id in ["m", "a", "b", "c", "d"] {
+            insert_node(&graph, id, &format!("fn_{id}"), "src/m.rs");
+        }
+        let edges = [
+            ("m", "a", EdgeType::Contains),
+            ("m", "b", EdgeType::Contains),
+            ("a", "c", EdgeType::Calls),
+            ("c", "m", EdgeType::Calls),
+            ("c", "d", EdgeType::Imports),
+        ];
+        for (i, (source, target, edge_type)) in edges.into_iter().enumerate() {
+            graph
+                .add_edge(&Edge {
+                    id: format!("e{i}"),
+                    project_id: graph.project_id().to_string(),
+                    source_id: source.to_string(),
+                    target_id: target.to_string(),
+                    edge_type,
+                    weight: 1.0,
+                })
+                .unwrap();
+        }
+        let walk = |depth, types: Option<&[EdgeType]>, cap| -> Vec<(String, usize)> {
+            graph
+                .traverse("m", depth, types, EdgeDirection::Outgoing, cap)
+                .unwrap()
+                .into_iter()
+                .map(|step| (step.node.id, step.depth))
+                .collect()
+        };
+        let ids = |steps: Vec<(String, usize)>| -> Vec<String> {
+            steps.into_iter().map(|(id, _)| id).collect()
+        };
+
+        let all = walk(10, None, 100);
+        assert_eq!(all, [("a".into(), 1), ("b".into(), 1), ("c".into(), 2), ("d".into(), 3)]);
+        assert_eq!(ids(walk(2, None, 100)), ["a", "b", "c"]);
+        let contains_calls = [EdgeType::Contains, EdgeType::Calls];
+        assert_eq!(ids(walk(10, Some(&contains_calls), 100)), ["a", "b", "c"]);
+        assert_eq!(ids(walk(10, None, 2)), ["a", "b"]);
+        assert!(walk(0, None, 100).is_empty());
+
+        let steps = graph.traverse("c", 1, None, EdgeDirection::Incoming, 10).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!((steps[0].node.id.as_str(), &steps[0].edge.edge_type), ("a", &EdgeType::Calls));
+    }
+
     // ── fts_search ───────────────────────────────────────────────────────────────
 
     #[test]
@@ -275,9 +1165,27 @@ mod tests {
             .index_fts(&node, "handles incoming alert notifications")
             .unwrap();
 
-        let results = graph.fts_search("\"alert\"", 10).unwrap();
+        let results = graph.fts_search("\"alert\"", 10, None, &FtsWeights::default()).unwrap();
         assert!(!results.is_empty());
         assert_eq!(results[0].0.id, "n1");
+        assert!(results[0].2.contains("alert"));
+    }
+
+    #[test]
+    fn fts_search_ranks_name_matches_above_content_matches() {
+        let engine = HermesEngine::in_memory("gq-fts-weights").unwrap();
+        let graph = make_graph(&engine);
+        let content = "helper that calls parse_since_duration for the stats window";
+        let named = insert_node(&graph, "named", "parse_since_duration", "src/a.rs");
+        let other = insert_node(&graph, "other", "stats_window", "src/b.rs");
+        graph.index_fts(&other, content).unwrap();
+        graph.index_fts(&named, content).unwrap();
+
+        let results = graph
+            .fts_search("\"parse_since_duration\"", 10, None, &FtsWeights::default())
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0.id, "named");
     }
 
     #[test]
@@ -287,7 +1195,7 @@ mod tests {
         let node = insert_node(&graph, "n1", "handler", "src/api.rs");
         graph.index_fts(&node, "something completely different").unwrap();
 
-        let results = graph.fts_search("\"xyznonexistent\"", 10).unwrap();
+        let results = graph.fts_search("\"xyznonexistent\"", 10, None, &FtsWeights::default()).unwrap();
         assert!(results.is_empty());
     }
 
@@ -308,21 +1216,7 @@ mod tests {
                 .unwrap();
         }
 
-        let results = graph.fts_search("\"shared\"", 3).unwrap();
+        let results = graph.fts_search("\"shared\"", 3, None, &FtsWeights::default()).unwrap();
         assert!(results.len() <= 3);
     }
 }
-
-pub(crate) fn node_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Node> {
-    Ok(Node {
-        id: row.get(0)?,
-        project_id: row.get(1)?,
-        name: row.get(2)?,
-        node_type: NodeType::parse_str(&row.get::<_, String>(3)?),
-        file_path: row.get(4)?,
-        start_line: row.get(5)?,
-        end_line: row.get(6)?,
-        summary: row.get(7)?,
-        content_hash: row.get(8)?,
-    })
-}
@@ -0,0 +1,350 @@
+use crate::busy;
+use crate::error::{HermesError, Result};
+use crate::graph::{Edge, EdgeType, KnowledgeGraph, Node};
+use crate::graph_queries::node_from_row;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashSet;
+
+/// Graph writes against one borrowed connection. Inside
+/// [`KnowledgeGraph::in_transaction`] they all commit together; the
+/// single-call methods of `KnowledgeGraph` use it for one write each.
+pub struct GraphWriter<'a> {
+    conn: &'a Connection,
+    project_id: &'a str,
+}
+
+impl KnowledgeGraph {
+    /// Runs `f` in one transaction, holding the connection lock until it
+    /// commits. Nothing `f` calls may lock the connection again. The
+    /// transaction takes the database's write lock up front, waiting out
+    /// other processes' writes.
+    pub fn in_transaction<T>(&self, f: impl FnOnce(&GraphWriter) -> Result<T>) -> Result<T> {
+        let conn = self.db().lock()?;
+        let tx = busy::write_transaction(&conn)?;
+        let result = f(&GraphWriter::new(&tx, self.project_id()))?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Fails with [`crate::HermesError::ReadOnly`] when the graph's connection was
+    /// opened read-only, for callers about to do work that ends in a write.
+    pub fn ensure_writable(&self) -> Result<()> {
+        busy::ensure_writable(&*self.db().lock()?)
+    }
+}
+
+impl<'a> GraphWriter<'a> {
+    pub fn new(conn: &'a Connection, project_id: &'a str) -> Self {
+        Self { conn, project_id }
+    }
+
+    /// The underlying connection, for writes to tables outside the graph.
+    pub fn conn(&self) -> &Connection {
+        self.conn
+    }
+
+    pub fn add_node(&self, node: &Node) -> Result<()> {
+        self.upsert_node(node, &Utc::now().to_rfc3339())
+    }
+
+    /// Like [`Self::add_node`], but keeps `node.updated_at` when it is set,
+    /// for nodes restored from a snapshot rather than indexed now.
+    pub fn restore_node(&self, node: &Node) -> Result<()> {
+        match &node.updated_at {
+            Some(updated_at) => self.upsert_node(node, updated_at),
+            None => self.add_node(node),
+        }
+    }
+
+    fn upsert_node(&self, node: &Node, updated_at: &str) -> Result<()> {
+        self.conn.execute(
+            // An upsert rather than INSERT OR REPLACE, whose delete of the
+            // old row would cascade to the node's edges.
+            "INSERT INTO nodes
+             (id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT (id) DO UPDATE SET
+                project_id = excluded.project_id, name = excluded.name,
+                node_type = excluded.node_type, file_path = excluded.file_path,
+                start_line = excluded.start_line, end_line = excluded.end_line,
+                summary = excluded.summary, content_hash = excluded.content_hash,
+                updated_at = excluded.updated_at, metadata = excluded.metadata",
+            params![
+                node.id,
+                node.project_id,
+                node.name,
+                node.node_type.as_str(),
+                node.file_path,
+                node.start_line,
+                node.end_line,
+                node.summary,
+                node.content_hash,
+                updated_at,
+                node.metadata.as_ref().map(|m| m.to_string()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_edge(&self, edge: &Edge) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO edges (id, project_id, source_id, target_id, edge_type, weight)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                edge.id,
+                edge.project_id,
+                edge.source_id,
+                edge.target_id,
+                edge.edge_type.as_str(),
+                edge.weight,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the `edge_type` edges leaving `source_id`, returning how many
+    /// there were.
+    pub fn delete_edges_from(&self, source_id: &str, edge_type: &EdgeType) -> Result<usize> {
+        // `+` keeps SQLite off the project index, which matches every edge.
+        Ok(self.conn.execute(
+            "DELETE FROM edges WHERE source_id = ?2 AND edge_type = ?3 AND +project_id = ?1",
+            params![self.project_id, source_id, edge_type.as_str()],
+        )?)
+    }
+
+    pub fn index_fts(&self, node: &Node, content: &str) -> Result<()> {
+        self.conn.execute("DELETE FROM fts_content WHERE node_id = ?1", params![node.id])?;
+        self.conn.execute(
+            "INSERT INTO fts_content (node_id, project_id, name, content, file_path)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![node.id, node.project_id, node.name, content, node.file_path],
+        )?;
+        Ok(())
+    }
+
+    /// Records the token estimate of a node's full content.
+    pub fn set_token_count(&self, node_id: &str, tokens: u64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE nodes SET token_count = ?1 WHERE id = ?2 AND project_id = ?3",
+            params![tokens as i64, node_id, self.project_id],
+        )?;
+        Ok(())
+    }
+
+    /// Stores (or replaces) the embedding of `node_id`, tagged with the model
+    /// that produced it.
+    pub fn upsert_embedding(&self, node_id: &str, vector: &[f32], model: &str) -> Result<()> {
+        let blob: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.conn.execute(
+            "INSERT OR REPLACE INTO embeddings
+             (node_id, project_id, vector, model, dimension, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+            params![node_id, self.project_id, blob, model, vector.len() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the project's embeddings whose model tag starts with `prefix`
+    /// but is not `keep`, and returns how many there were.
+    pub fn delete_embeddings_by_prefix(&self, prefix: &str, keep: &str) -> Result<usize> {
+        Ok(self.conn.execute(
+            "DELETE FROM embeddings
+             WHERE project_id = ?1 AND substr(model, 1, length(?2)) = ?2 AND model != ?3",
+            params![self.project_id, prefix, keep],
+        )?)
+    }
+
+    /// Deletes a node with its FTS row, embedding and incident edges. Returns
+    /// `false` if the project has no node `node_id`.
+    pub fn delete_node(&self, node_id: &str) -> Result<bool> {
+        let exists = self
+            .conn
+            .query_row(
+                "SELECT 1 FROM nodes WHERE id = ?1 AND project_id = ?2",
+                params![node_id, self.project_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if exists {
+            self.conn.execute("DELETE FROM fts_content WHERE node_id = ?1", params![node_id])?;
+            self.conn.execute("DELETE FROM embeddings WHERE node_id = ?1", params![node_id])?;
+            // Edges cascade; facts about the node keep a NULL node_id.
+            self.conn.execute("DELETE FROM nodes WHERE id = ?1", params![node_id])?;
+        }
+        Ok(exists)
+    }
+
+    /// Moves the nodes of the file at `from` to `to`, keeping their ids: the
+    /// File node is renamed, and FTS rows and file and chunk hashes follow
+    /// the path. Returns the number of nodes moved.
+    pub fn rename_file(&self, from: &str, to: &str) -> Result<usize> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.execute(
+            "UPDATE nodes SET name = ?3 WHERE project_id = ?1 AND file_path = ?2
+             AND node_type = 'file' AND name = ?2",
+            params![self.project_id, from, to],
+        )?;
+        let moved = self.conn.execute(
+            "UPDATE nodes SET file_path = ?3, updated_at = ?4
+             WHERE project_id = ?1 AND file_path = ?2",
+            params![self.project_id, from, to, now],
+        )?;
+        self.conn.execute(
+            "UPDATE fts_content SET file_path = ?3, name = CASE WHEN name = ?2 THEN ?3 ELSE name END
+             WHERE project_id = ?1 AND file_path = ?2",
+            params![self.project_id, from, to],
+        )?;
+        // Hashes left under `to` by an earlier index of that path would
+        // collide with the moved ones.
+        self.conn.execute(
+            "DELETE FROM file_hashes WHERE project_id = ?1 AND file_path = ?2",
+            params![self.project_id, to],
+        )?;
+        self.conn.execute(
+            "UPDATE file_hashes SET file_path = ?3 WHERE project_id = ?1 AND file_path = ?2",
+            params![self.project_id, from, to],
+        )?;
+        self.conn.execute(
+            "DELETE FROM chunk_hashes
+             WHERE project_id = ?1 AND substr(chunk_key, 1, length(?2) + 2) = ?2 || '::'",
+            params![self.project_id, to],
+        )?;
+        self.conn.execute(
+            "UPDATE chunk_hashes SET chunk_key = ?3 || substr(chunk_key, length(?2) + 1)
+             WHERE project_id = ?1 AND substr(chunk_key, 1, length(?2) + 2) = ?2 || '::'",
+            params![self.project_id, from, to],
+        )?;
+        Ok(moved)
+    }
+
+    /// Replaces a node's summary. Returns `false` if the node is not found.
+    pub fn update_node_summary(&self, node_id: &str, summary: Option<&str>) -> Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE nodes SET summary = ?1, updated_at = ?2 WHERE id = ?3 AND project_id = ?4",
+            params![summary, Utc::now().to_rfc3339(), node_id, self.project_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Moves a node to new lines. Returns `false` if the node is not found,
+    /// and fails on a range that starts before line 1 or ends before it
+    /// starts.
+    pub fn update_node_lines(&self, node_id: &str, start_line: i64, end_line: i64) -> Result<bool> {
+        if start_line < 1 || end_line < start_line {
+            return Err(HermesError::InvalidInput(format!(
+                "invalid line range {start_line}-{end_line} for node '{node_id}'"
+            )));
+        }
+        let updated = self.conn.execute(
+            "UPDATE nodes SET start_line = ?1, end_line = ?2, updated_at = ?3
+             WHERE id = ?4 AND project_id = ?5",
+            params![start_line, end_line, Utc::now().to_rfc3339(), node_id, self.project_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Deletes the nodes of `file_path` whose ids are not in `keep`, with
+    /// their FTS rows, embeddings and edges. Returns the deleted nodes.
+    pub fn delete_file_nodes_except(
+        &self,
+        file_path: &str,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<Node>> {
+        let stale: Vec<Node> = self
+            .conn
+            .prepare(
+                "SELECT id, project_id, name, node_type, file_path, start_line, end_line, summary, content_hash, updated_at, metadata
+                 FROM nodes WHERE file_path = ?1 AND project_id = ?2",
+            )?
+            .query_map(params![file_path, self.project_id], node_from_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|node| !keep.contains(&node.id))
+            .collect();
+        for node in &stale {
+            self.delete_node(&node.id)?;
+        }
+        Ok(stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{HermesError, Result};
+    use crate::graph::{EdgeType, KnowledgeGraph, NodeType};
+    use crate::HermesEngine;
+
+    #[test]
+    fn failed_transaction_writes_nothing() {
+        let engine = HermesEngine::in_memory("test-transaction").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let node = graph.create_node_builder().name("orphan").node_type(NodeType::Function).build();
+        let callee = graph.create_node_builder().name("callee").node_type(NodeType::Function).build();
+
+        let result: Result<()> = graph.in_transaction(|w| {
+            w.add_node(&node)?;
+            w.index_fts(&node, "fn orphan() {}")?;
+            Err(HermesError::InvalidInput("chunking failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert!(graph.get_node(&node.id).unwrap().is_none());
+
+        graph
+            .in_transaction(|w| {
+                w.add_node(&node)?;
+                w.add_node(&callee)?;
+                let edge = graph
+                    .create_edge_builder()
+                    .source(&node.id)
+                    .target(&callee.id)
+                    .edge_type(EdgeType::Calls)
+                    .build();
+                w.add_edge(&edge)
+            })
+            .unwrap();
+        assert!(graph.get_node(&node.id).unwrap().is_some());
+        assert_eq!(graph.get_neighbors(&node.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn deleting_a_node_cascades_to_edges_and_detaches_facts() {
+        use crate::temporal::{FactType, TemporalStore};
+        let engine = HermesEngine::in_memory("test-cascade").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let function = |name: &str| {
+            graph.create_node_builder().name(name).node_type(NodeType::Function).build()
+        };
+        let (caller, callee) = (function("caller"), function("callee"));
+        graph.add_node(&caller).unwrap();
+        graph.add_node(&callee).unwrap();
+        let edge = graph
+            .create_edge_builder()
+            .source(&caller.id)
+            .target(&callee.id)
+            .edge_type(EdgeType::Calls)
+            .build();
+        graph.add_edge(&edge).unwrap();
+        let facts = TemporalStore::new(engine.db().clone(), engine.project_id());
+        facts.add_fact(Some(&callee.id), FactType::Learning, "hot path", None).unwrap();
+
+        // Rewriting a node in place must not cascade.
+        graph.add_node(&callee).unwrap();
+        assert_eq!(graph.get_neighbors(&caller.id).unwrap().len(), 1);
+
+        assert!(graph.delete_node(&callee.id).unwrap());
+        assert!(graph.get_neighbors(&caller.id).unwrap().is_empty());
+        let active = facts.get_active_facts(None).unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!((active[0].content.as_str(), active[0].node_id.as_deref()), ("hot path", None));
+
+        let orphan = graph
+            .create_edge_builder()
+            .source(&caller.id)
+            .target("missing")
+            .edge_type(EdgeType::Calls)
+            .build();
+        assert!(graph.add_edge(&orphan).is_err(), "foreign keys are enforced");
+    }
+}
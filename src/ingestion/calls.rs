@@ -0,0 +1,118 @@
+use crate::graph::Node;
+use std::collections::{HashMap, HashSet};
+
+/// Names shorter than this (`new`, `get`, `run`) match too much to link.
+const MIN_CALLEE_NAME_LEN: usize = 4;
+/// Names defined in more files than this are treated as too common to link.
+const MAX_CALLEE_FILES: usize = 10;
+/// Most Calls edges kept per function, heaviest first.
+pub const MAX_CALLS_PER_FUNCTION: usize = 50;
+
+/// `(caller, callee, mentions)` index triples into `functions` for the
+/// functions at `callers`, found by matching whole identifiers of each body
+/// against the short names of the other functions (`rank` for
+/// `SearchEngine::rank` or `Engine.rank`).
+pub fn call_edges(functions: &[(Node, String)], callers: &[usize]) -> Vec<(usize, usize, usize)> {
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (node, _)) in functions.iter().enumerate() {
+        by_name.entry(short_name(&node.name)).or_default().push(i);
+    }
+    by_name.retain(|name, defs| {
+        let files: HashSet<_> = defs.iter().map(|&i| &functions[i].0.file_path).collect();
+        name.chars().count() >= MIN_CALLEE_NAME_LEN && files.len() <= MAX_CALLEE_FILES
+    });
+
+    let mut edges = Vec::new();
+    for &caller in callers {
+        let (node, content) = &functions[caller];
+        let own_name = short_name(&node.name);
+        let mut mentions: HashMap<&str, usize> = HashMap::new();
+        for word in words(content) {
+            if by_name.contains_key(word) {
+                *mentions.entry(word).or_default() += 1;
+            }
+        }
+        // The function's own declaration names it once.
+        if let Some(count) = mentions.get_mut(own_name) {
+            *count -= 1;
+        }
+
+        let mut calls: Vec<(usize, usize)> = mentions
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .flat_map(|(word, count)| by_name[word].iter().map(move |&callee| (callee, count)))
+            .filter(|&(callee, _)| callee != caller)
+            .collect();
+        calls.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        calls.truncate(MAX_CALLS_PER_FUNCTION);
+        edges.extend(calls.into_iter().map(|(callee, count)| (caller, callee, count)));
+    }
+    edges
+}
+
+/// Whether `content` mentions any of `names` as a whole identifier.
+pub fn mentions_any(content: &str, names: &HashSet<&str>) -> bool {
+    words(content).any(|word| names.contains(word))
+}
+
+fn words(content: &str) -> impl Iterator<Item = &str> {
+    content.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+}
+
+/// The name a call site uses: `rank` for `SearchEngine::rank` or `Engine.rank`.
+pub fn short_name(name: &str) -> &str {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{KnowledgeGraph, NodeType};
+    use crate::HermesEngine;
+
+    fn functions(defs: &[(&str, &str, &str)]) -> Vec<(Node, String)> {
+        let engine = HermesEngine::in_memory("test-calls").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        defs.iter()
+            .map(|(name, file, body)| {
+                let node = graph
+                    .create_node_builder()
+                    .name(name)
+                    .node_type(NodeType::Function)
+                    .file_path(file)
+                    .build();
+                (node, body.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn links_mentions_by_short_name_excluding_self() {
+        let functions = functions(&[
+            ("Engine::search", "a.rs", "fn search() { rank(); rank(); parse_query(); search(); }"),
+            ("rank", "b.rs", "fn rank() { let ranked = 1; }"),
+            ("parse_query", "c.rs", "fn parse_query() { new(); }"),
+            ("new", "c.rs", "fn new() {}"),
+        ]);
+        // `search` mentions itself in a recursive call, which is not an edge.
+        assert_eq!(call_edges(&functions, &[0, 1, 2, 3]), [(0, 1, 2), (0, 2, 1)]);
+    }
+
+    #[test]
+    fn skips_names_defined_in_too_many_files() {
+        let mut defs = vec![("caller", "main.rs", "fn caller() { render(); }")];
+        let files: Vec<String> = (0..=MAX_CALLEE_FILES).map(|n| format!("view{n}.rs")).collect();
+        defs.extend(files.iter().map(|file| ("render", file.as_str(), "fn render() {}")));
+        assert!(call_edges(&functions(&defs), &[0]).is_empty());
+    }
+
+    #[test]
+    fn caps_fan_out() {
+        let names: Vec<String> = (0..60).map(|n| format!("helper{n}")).collect();
+        let body = format!("fn caller() {{ {} }}", names.join("(); "));
+        let mut defs = vec![("caller", "main.rs", body.as_str())];
+        defs.extend(names.iter().map(|name| (name.as_str(), "helpers.rs", "fn h() {}")));
+        assert_eq!(call_edges(&functions(&defs), &[0]).len(), MAX_CALLS_PER_FUNCTION);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::graph::NodeType;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
 use std::path::Path;
 
 #[derive(Debug, Clone)]
@@ -18,11 +20,372 @@ pub fn chunk_file(path: &Path, content: &str) -> Vec<Chunk> {
         "rs" => chunk_rust(content),
         "md" => chunk_markdown(content),
         "tsx" | "ts" | "jsx" | "js" => chunk_typescript(content),
+        "py" => chunk_python(path, content),
+        "go" => chunk_go(content),
+        "java" => chunk_jvm(content, false),
+        "kt" => chunk_jvm(content, true),
+        "c" | "h" | "cpp" | "hpp" => chunk_c_cpp(content),
+        "toml" | "json" | "yml" | "yaml" => chunk_config(path, ext, content),
         _ => chunk_whole_file(path, content),
     }
 }
 
+/// Config files shorter than this stay whole; their sections would be noise.
+const CONFIG_SECTION_MIN_LINES: usize = 30;
+
+/// One Document chunk per top-level TOML table or JSON/YAML key, falling
+/// back to the whole file when it is short or has no such sections.
+fn chunk_config(path: &Path, ext: &str, content: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() < CONFIG_SECTION_MIN_LINES {
+        return chunk_whole_file(path, content);
+    }
+    let sections = match ext {
+        "toml" => toml_sections(&lines),
+        "json" => json_sections(content),
+        _ => yaml_sections(&lines),
+    };
+    if sections.is_empty() {
+        return chunk_whole_file(path, content);
+    }
+    sections
+        .into_iter()
+        .map(|(name, start, end)| Chunk {
+            summary: build_summary(&name, &NodeType::Document, lines[start]),
+            name,
+            node_type: NodeType::Document,
+            content: lines[start..=end].join("\n"),
+            start_line: start + 1,
+            end_line: end + 1,
+        })
+        .collect()
+}
+
+/// `(name, first line, last line)` of each `[table]` or `[[array]]`, plus a
+/// "root" section for keys above the first table. Sections end at their
+/// last non-blank line.
+fn toml_sections(lines: &[&str]) -> Vec<(String, usize, usize)> {
+    let mut starts = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let header = trimmed
+            .strip_prefix("[[")
+            .and_then(|rest| rest.split("]]").next())
+            .or_else(|| trimmed.strip_prefix('[').and_then(|rest| rest.split(']').next()));
+        if let Some(header) = header.filter(|_| !line.starts_with([' ', '\t'])) {
+            starts.push((header.trim().to_string(), i));
+        }
+    }
+    let has_root = starts.first().is_none_or(|(_, first)| {
+        lines[..*first]
+            .iter()
+            .any(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+    });
+    if has_root {
+        starts.insert(0, ("root".to_string(), 0));
+    }
+    config_section_ranges(lines, starts)
+}
+
+/// Sections of top-level YAML keys, each running until the next one.
+fn yaml_sections(lines: &[&str]) -> Vec<(String, usize, usize)> {
+    let starts = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with([' ', '\t', '#', '-']))
+        .filter_map(|(i, line)| {
+            let colon = line
+                .find(": ")
+                .or_else(|| line.trim_end().strip_suffix(':').map(str::len))?;
+            let key = line[..colon].trim().trim_matches(['"', '\'']);
+            (!key.is_empty()).then(|| (key.to_string(), i))
+        })
+        .collect();
+    config_section_ranges(lines, starts)
+}
+
+/// Turns section start lines into inclusive ranges, trimming trailing blank
+/// and comment lines.
+fn config_section_ranges(
+    lines: &[&str],
+    starts: Vec<(String, usize)>,
+) -> Vec<(String, usize, usize)> {
+    let bounds: Vec<usize> = starts.iter().skip(1).map(|(_, i)| *i).collect();
+    starts
+        .into_iter()
+        .enumerate()
+        .map(|(n, (name, start))| {
+            let mut end = bounds.get(n).map_or(lines.len(), |next| *next) - 1;
+            while end > start {
+                let trimmed = lines[end].trim();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    break;
+                }
+                end -= 1;
+            }
+            (name, start, end)
+        })
+        .collect()
+}
+
+/// Sections of the keys of a top-level JSON object, from the line of the key
+/// to the last line of its value.
+fn json_sections(content: &str) -> Vec<(String, usize, usize)> {
+    if !matches!(serde_json::from_str(content), Ok(serde_json::Value::Object(_))) {
+        return Vec::new();
+    }
+    let mut sections = Vec::new();
+    let mut depth = 0usize;
+    let mut line = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut string_start = (0, 0);
+    // The last string seen at depth 1, which becomes a key when `:` follows.
+    let mut last_string: Option<(String, usize)> = None;
+    let mut current: Option<(String, usize)> = None;
+    // Line of the last non-blank character before the current one.
+    let mut last_line = 0;
+
+    for (i, c) in content.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+                if depth == 1 {
+                    let key = serde_json::from_str(&content[string_start.0..=i]);
+                    last_string = Some((key.unwrap_or_default(), string_start.1));
+                }
+            }
+        } else {
+            match c {
+                '"' => {
+                    in_string = true;
+                    string_start = (i, line);
+                }
+                ':' if depth == 1 => current = last_string.take(),
+                ',' if depth == 1 => {
+                    if let Some((name, start)) = current.take() {
+                        sections.push((name, start, last_line));
+                    }
+                }
+                '{' | '[' => depth += 1,
+                '}' | ']' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        if let Some((name, start)) = current.take() {
+                            sections.push((name, start, last_line));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if c == '\n' {
+            line += 1;
+        } else if !c.is_whitespace() {
+            last_line = line;
+        }
+    }
+    sections
+}
+
+/// Parses the file with `syn` and emits one chunk per item, including items
+/// in inline modules, impl methods as `Type::method` and trait methods as
+/// `Trait::method`. Files `syn` cannot parse fall back to
+/// [`chunk_rust_lines`].
 fn chunk_rust(content: &str) -> Vec<Chunk> {
+    let chunks = match syn::parse_file(content) {
+        Ok(file) => {
+            let lines: Vec<&str> = content.lines().collect();
+            let mut chunks = Vec::new();
+            rust_item_chunks(&file.items, &lines, &mut chunks);
+            chunks
+        }
+        Err(_) => chunk_rust_lines(content),
+    };
+    // Spans of the parse above are no longer referenced; free their source.
+    proc_macro2::extra::invalidate_current_thread_spans();
+    chunks
+}
+
+fn rust_item_chunks(items: &[syn::Item], lines: &[&str], chunks: &mut Vec<Chunk>) {
+    for item in items {
+        match item {
+            syn::Item::Fn(f) => chunks.push(rust_chunk(&f.sig.ident, NodeType::Function, f, lines)),
+            syn::Item::Struct(s) => chunks.push(rust_chunk(&s.ident, NodeType::Struct, s, lines)),
+            syn::Item::Union(u) => chunks.push(rust_chunk(&u.ident, NodeType::Struct, u, lines)),
+            syn::Item::Enum(e) => chunks.push(rust_chunk(&e.ident, NodeType::Enum, e, lines)),
+            syn::Item::Const(c) => chunks.push(rust_chunk(&c.ident, NodeType::Concept, c, lines)),
+            syn::Item::Static(s) => chunks.push(rust_chunk(&s.ident, NodeType::Concept, s, lines)),
+            syn::Item::Type(t) => chunks.push(rust_chunk(&t.ident, NodeType::Concept, t, lines)),
+            syn::Item::Macro(m) => {
+                if let Some(ident) = &m.ident {
+                    chunks.push(rust_chunk(ident, NodeType::Function, m, lines));
+                }
+            }
+            syn::Item::Mod(m) => {
+                chunks.push(rust_chunk(&m.ident, NodeType::Module, m, lines));
+                if let Some((_, items)) = &m.content {
+                    rust_item_chunks(items, lines, chunks);
+                }
+            }
+            syn::Item::Trait(t) => {
+                chunks.push(rust_chunk(&t.ident, NodeType::Trait, t, lines));
+                for member in &t.items {
+                    if let syn::TraitItem::Fn(f) = member {
+                        let name = format!("{}::{}", t.ident, f.sig.ident);
+                        chunks.push(rust_chunk(&name, NodeType::Function, f, lines));
+                    }
+                }
+            }
+            syn::Item::Impl(i) => {
+                let Some(type_name) = rust_type_name(&i.self_ty) else {
+                    continue;
+                };
+                chunks.push(rust_chunk(&type_name, NodeType::Impl, i, lines));
+                for member in &i.items {
+                    if let syn::ImplItem::Fn(f) = member {
+                        let name = format!("{type_name}::{}", f.sig.ident);
+                        chunks.push(rust_chunk(&name, NodeType::Function, f, lines));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `Foo` for `Foo<T>`, `&mut Foo` or `crate::Foo`.
+fn rust_type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        syn::Type::Reference(r) => rust_type_name(&r.elem),
+        syn::Type::Paren(p) => rust_type_name(&p.elem),
+        _ => None,
+    }
+}
+
+/// A chunk for `item`, starting at its first attribute or doc comment and
+/// summarized from the first sentence of its docs, or from the line holding
+/// its declaration when it has none.
+fn rust_chunk(
+    name: &impl ToString,
+    node_type: NodeType,
+    item: &impl ToTokens,
+    lines: &[&str],
+) -> Chunk {
+    let name = name.to_string();
+    let tokens: Vec<TokenTree> = item.to_token_stream().into_iter().collect();
+    let mut attribute_line: Option<usize> = None;
+    let mut docs = Vec::new();
+    let mut decl_line = None;
+    let mut t = 0;
+    while t < tokens.len() {
+        if let (TokenTree::Punct(p), Some(TokenTree::Group(g))) = (&tokens[t], tokens.get(t + 1)) {
+            if p.as_char() == '#' && g.delimiter() == proc_macro2::Delimiter::Bracket {
+                attribute_line.get_or_insert(p.span().start().line);
+                docs.extend(doc_attribute_text(g.stream()));
+                t += 2;
+                continue;
+            }
+        }
+        decl_line = Some(tokens[t].span().start().line);
+        break;
+    }
+    let decl_line = decl_line.unwrap_or(1).max(1);
+    let start_line = attribute_line.map_or(decl_line, |line| line.min(decl_line));
+    let end_line = tokens
+        .last()
+        .map_or(decl_line, |last| last.span().end().line)
+        .clamp(start_line, lines.len().max(start_line));
+    let decl_text = lines.get(decl_line - 1).copied().unwrap_or_default();
+    let summary = match doc_summary(&docs) {
+        Some(sentence) => format!("{}: {sentence}", node_type.as_str()),
+        None => build_summary(&name, &node_type, decl_text),
+    };
+
+    Chunk {
+        summary,
+        name,
+        node_type,
+        content: lines[start_line - 1..end_line.min(lines.len())].join("\n"),
+        start_line,
+        end_line,
+    }
+}
+
+/// The string of a `#[doc = "..."]` attribute, which is also what `///` and
+/// `/** */` comments desugar to.
+fn doc_attribute_text(attribute: TokenStream) -> Option<String> {
+    let mut tokens = attribute.into_iter();
+    match (tokens.next(), tokens.next(), tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(eq)), Some(lit), None)
+            if ident == "doc" && eq.as_char() == '=' =>
+        {
+            syn::parse2::<syn::LitStr>(lit.into_token_stream()).ok().map(|lit| lit.value())
+        }
+        _ => None,
+    }
+}
+
+/// First sentence of the first paragraph of `docs`, as plain text.
+fn doc_summary(docs: &[String]) -> Option<String> {
+    let mut paragraph = Vec::new();
+    for line in docs.iter().flat_map(|doc| doc.lines()) {
+        // `/** */` blocks usually carry a ` * ` gutter.
+        let line = line.trim();
+        let line = line.strip_prefix('*').map_or(line, str::trim_start);
+        if line.is_empty() || line.starts_with("```") {
+            if paragraph.is_empty() {
+                continue;
+            }
+            break;
+        }
+        paragraph.push(line.trim_start_matches(['#', '>']).trim());
+    }
+    let text = strip_markdown(&paragraph.join(" "));
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let end = text
+        .match_indices(". ")
+        .next()
+        .map_or(text.len(), |(i, _)| i + 1);
+    Some(text[..end].to_string())
+}
+
+/// Drops emphasis and code markers and reduces `[text](url)`,
+/// `[text][ref]` and intra-doc links to their text.
+fn strip_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' | '*' | '[' => {}
+            ']' => {
+                let close = match chars.peek() {
+                    Some('(') => ')',
+                    Some('[') => ']',
+                    _ => continue,
+                };
+                for skipped in chars.by_ref() {
+                    if skipped == close {
+                        break;
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Line-prefix fallback for files `syn` cannot parse.
+fn chunk_rust_lines(content: &str) -> Vec<Chunk> {
     let mut chunks = Vec::new();
     let lines: Vec<&str> = content.lines().collect();
     let mut i = 0;
@@ -58,57 +421,124 @@ fn try_parse_rust_item(line: &str, lines: &[&str], start: usize) -> Option<Chunk
         return None;
     };
 
-    let end = find_block_end(lines, start);
-    let block_content: String = lines[start..=end].join("\n");
-    let summary = build_summary(&name, &node_type, lines[start]);
+    let end = find_block_end(lines, start, BraceSyntax::Rust);
+    let mut first = start;
+    let mut docs = Vec::new();
+    while first > 0 {
+        let above = lines[first - 1].trim();
+        if let Some(doc) = above.strip_prefix("///") {
+            docs.push(doc.to_string());
+        } else if !above.starts_with("#[") {
+            break;
+        }
+        first -= 1;
+    }
+    docs.reverse();
+    let block_content: String = lines[first..=end].join("\n");
+    let summary = match doc_summary(&docs) {
+        Some(sentence) => format!("{}: {sentence}", node_type.as_str()),
+        None => build_summary(&name, &node_type, lines[start]),
+    };
 
     Some(Chunk {
         name,
         node_type,
         content: block_content,
-        start_line: start + 1,
+        start_line: first + 1,
         end_line: end + 1,
         summary,
     })
 }
 
+/// One Document chunk per heading of any level, spanning the heading's whole
+/// subsection and named with its ancestors, as in "Installation > Linux".
+/// Headings inside ``` and ~~~ fences are ignored; text before the first
+/// heading becomes a "Preamble" chunk.
 fn chunk_markdown(content: &str) -> Vec<Chunk> {
-    let mut chunks = Vec::new();
     let lines: Vec<&str> = content.lines().collect();
-    let mut section_start: Option<(usize, String)> = None;
-
+    let mut headings = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
     for (i, line) in lines.iter().enumerate() {
-        if line.starts_with("## ") || line.starts_with("# ") {
-            if let Some((start, heading)) = section_start.take() {
-                let section_content = lines[start..i].join("\n");
-                chunks.push(Chunk {
-                    name: heading.clone(),
-                    node_type: NodeType::Document,
-                    content: section_content,
-                    start_line: start + 1,
-                    end_line: i,
-                    summary: heading,
-                });
+        match (fence, markdown_fence(line)) {
+            (Some((open, len)), Some((c, run, rest))) if c == open && run >= len => {
+                if rest.is_empty() {
+                    fence = None;
+                }
+            }
+            (Some(_), _) => {}
+            (None, Some((c, run, _))) => fence = Some((c, run)),
+            (None, None) => {
+                if let Some(heading) = markdown_heading(line) {
+                    headings.push((i, heading));
+                }
             }
-            section_start = Some((i, line.trim_start_matches('#').trim().to_string()));
         }
     }
 
-    if let Some((start, heading)) = section_start {
-        let section_content = lines[start..].join("\n");
+    let mut chunks = Vec::new();
+    let preamble_end = headings.first().map_or(lines.len(), |(i, _)| *i);
+    if lines[..preamble_end].iter().any(|line| !line.trim().is_empty()) {
+        chunks.push(Chunk {
+            name: "Preamble".to_string(),
+            node_type: NodeType::Document,
+            content: lines[..preamble_end].join("\n"),
+            start_line: 1,
+            end_line: preamble_end,
+            summary: "Preamble".to_string(),
+        });
+    }
+
+    let mut ancestors: Vec<(usize, String)> = Vec::new();
+    for (n, (start, (level, title))) in headings.iter().enumerate() {
+        ancestors.retain(|(ancestor_level, _)| ancestor_level < level);
+        ancestors.push((*level, title.clone()));
+        let end = headings[n + 1..]
+            .iter()
+            .find(|(_, (next_level, _))| next_level <= level)
+            .map_or(lines.len(), |(next, _)| *next);
+        let name = ancestors
+            .iter()
+            .map(|(_, title)| title.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ");
         chunks.push(Chunk {
-            name: heading.clone(),
+            name: name.clone(),
             node_type: NodeType::Document,
-            content: section_content,
+            content: lines[*start..end].join("\n"),
             start_line: start + 1,
-            end_line: lines.len(),
-            summary: heading,
+            end_line: end,
+            summary: name,
         });
     }
 
     chunks
 }
 
+/// Fence character, its run length and the text after the run, for a line
+/// opening or closing a fenced code block.
+fn markdown_fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|&x| x == c).count();
+    (len >= 3).then(|| (c, len, trimmed[len..].trim()))
+}
+
+/// Level and title of an ATX heading such as `### Linux ###`.
+fn markdown_heading(line: &str) -> Option<(usize, String)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let trimmed = &line[indent..];
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim();
+    (!title.is_empty()).then(|| (level, title.to_string()))
+}
+
 fn chunk_typescript(content: &str) -> Vec<Chunk> {
     let mut chunks = Vec::new();
     let lines: Vec<&str> = content.lines().collect();
@@ -117,7 +547,7 @@ fn chunk_typescript(content: &str) -> Vec<Chunk> {
         let trimmed = line.trim();
         if is_ts_function_start(trimmed) || is_ts_component_start(trimmed) {
             let name = extract_ts_name(trimmed).unwrap_or_else(|| format!("anonymous_{i}"));
-            let end = find_block_end(&lines, i);
+            let end = find_block_end(&lines, i, BraceSyntax::Script);
             let block_content = lines[i..=end].join("\n");
             chunks.push(Chunk {
                 name: name.clone(),
@@ -133,6 +563,726 @@ fn chunk_typescript(content: &str) -> Vec<Chunk> {
     chunks
 }
 
+/// One logical Python line: physical lines `start..=end` joined by open
+/// brackets, triple-quoted strings or backslash continuations.
+struct PyLine {
+    start: usize,
+    end: usize,
+    indent: usize,
+}
+
+/// Blocks are found by indentation: a `def`/`class` extends over every
+/// following logical line indented deeper than its header. Methods are named
+/// `Class.method`; functions nested in functions stay part of their parent.
+fn chunk_python(path: &Path, content: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    let logical = python_logical_lines(&lines);
+
+    if let Some(first) = logical.first().filter(|l| l.indent == 0) {
+        if let Some(doc) = python_docstring_line(&lines[first.start..=first.end]) {
+            let name = path
+                .file_stem()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "module".to_string());
+            chunks.push(Chunk {
+                name,
+                node_type: NodeType::Document,
+                content: lines[first.start..=first.end].join("\n"),
+                start_line: first.start + 1,
+                end_line: first.end + 1,
+                summary: doc,
+            });
+        }
+    }
+
+    // Enclosing def/class headers: (indent, class name or None for a def).
+    let mut scopes: Vec<(usize, Option<String>)> = Vec::new();
+    for (k, line) in logical.iter().enumerate() {
+        while scopes.last().is_some_and(|(indent, _)| *indent >= line.indent) {
+            scopes.pop();
+        }
+        let Some((name, node_type)) = python_def(lines[line.start].trim()) else {
+            continue;
+        };
+        let inside_function = scopes.iter().any(|(_, class)| class.is_none());
+        let qualified = scopes
+            .iter()
+            .filter_map(|(_, class)| class.as_deref())
+            .chain([name.as_str()])
+            .collect::<Vec<_>>()
+            .join(".");
+        let class_name = (node_type == NodeType::Struct).then(|| name.clone());
+        scopes.push((line.indent, class_name));
+        if inside_function {
+            continue;
+        }
+
+        let body_len = logical[k + 1..]
+            .iter()
+            .take_while(|l| l.indent > line.indent)
+            .count();
+        let end = if body_len > 0 { logical[k + body_len].end } else { line.end };
+        let mut start = line.start;
+        for decorator in logical[..k].iter().rev() {
+            if decorator.indent != line.indent || !lines[decorator.start].trim_start().starts_with('@') {
+                break;
+            }
+            start = decorator.start;
+        }
+
+        let docstring = logical
+            .get(k + 1)
+            .filter(|_| body_len > 0)
+            .and_then(|body| python_docstring_line(&lines[body.start..=body.end]));
+        let header = lines[line.start].trim();
+        let summary = match docstring {
+            Some(doc) if line.start != line.end || header.len() > 80 => {
+                format!("{}: {doc}", node_type.as_str())
+            }
+            _ => build_summary(&qualified, &node_type, header),
+        };
+
+        chunks.push(Chunk {
+            name: qualified,
+            node_type,
+            content: lines[start..=end].join("\n"),
+            start_line: start + 1,
+            end_line: end + 1,
+            summary,
+        });
+    }
+
+    chunks
+}
+
+/// Splits Python source into logical lines, skipping blank and comment-only
+/// lines.
+fn python_logical_lines(lines: &[&str]) -> Vec<PyLine> {
+    let mut logical = Vec::new();
+    let mut depth = 0usize;
+    let mut triple: Option<char> = None;
+    let mut current: Option<PyLine> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if current.is_none() {
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            current = Some(PyLine {
+                start: i,
+                end: i,
+                indent: python_indent(line),
+            });
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut single: Option<char> = None;
+        let mut j = 0;
+        while j < chars.len() {
+            let c = chars[j];
+            let is_triple = |q: char| chars.get(j + 1) == Some(&q) && chars.get(j + 2) == Some(&q);
+            if let Some(q) = triple {
+                if c == '\\' {
+                    j += 1;
+                } else if c == q && is_triple(q) {
+                    triple = None;
+                    j += 2;
+                }
+            } else if let Some(q) = single {
+                if c == '\\' {
+                    j += 1;
+                } else if c == q {
+                    single = None;
+                }
+            } else {
+                match c {
+                    '#' => break,
+                    '"' | '\'' if is_triple(c) => {
+                        triple = Some(c);
+                        j += 2;
+                    }
+                    '"' | '\'' => single = Some(c),
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+            j += 1;
+        }
+        let continued = triple.is_none() && single.is_none() && trimmed.ends_with('\\');
+
+        if let Some(open) = current.as_mut() {
+            open.end = i;
+        }
+        if depth == 0 && triple.is_none() && !continued {
+            logical.extend(current.take());
+        }
+    }
+    logical.extend(current);
+    logical
+}
+
+/// Indentation width with tabs advancing to the next multiple of eight, as
+/// the Python tokenizer counts them.
+fn python_indent(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width = (width / 8 + 1) * 8,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// The name and node type of a `def`, `async def` or `class` header.
+fn python_def(line: &str) -> Option<(String, NodeType)> {
+    let (rest, node_type) = if let Some(rest) = line.strip_prefix("class ") {
+        (rest, NodeType::Struct)
+    } else {
+        let rest = line.strip_prefix("async ").map(str::trim_start).unwrap_or(line);
+        (rest.strip_prefix("def ")?, NodeType::Function)
+    };
+    let name = rest
+        .split(|c: char| c == '(' || c == ':' || c == '[' || c.is_whitespace())
+        .next()?;
+    if name.is_empty() {
+        None
+    } else {
+        Some((name.to_string(), node_type))
+    }
+}
+
+/// The first non-empty line of a docstring, when `lines` form a string
+/// literal statement.
+fn python_docstring_line(lines: &[&str]) -> Option<String> {
+    let first = lines.first()?.trim_start();
+    let literal = first.trim_start_matches(['r', 'R', 'u', 'U']);
+    let quote = ["\"\"\"", "'''", "\"", "'"]
+        .into_iter()
+        .find(|q| literal.starts_with(q))?;
+    let text = std::iter::once(&literal[quote.len()..])
+        .chain(lines[1..].iter().copied())
+        .map(|l| l.trim().trim_end_matches(quote).trim())
+        .find(|l| !l.is_empty())?;
+    Some(text.to_string())
+}
+
+/// Top-level Go declarations: functions, methods (named `Type.Method`),
+/// struct and interface types, and `const`/`var` declarations or blocks.
+fn chunk_go(content: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let Some((name, node_type)) = go_declaration(line, &lines[i..]) else {
+            i += 1;
+            continue;
+        };
+        let end = find_code_block_end(&lines, i);
+        let summary = match node_type {
+            NodeType::Concept => format!("{}: {name}", line.split_whitespace().next().unwrap_or("const")),
+            _ => build_summary(&name, &node_type, line),
+        };
+        chunks.push(Chunk {
+            name,
+            node_type,
+            content: lines[i..=end].join("\n"),
+            start_line: i + 1,
+            end_line: end + 1,
+            summary,
+        });
+        i = end + 1;
+    }
+
+    chunks
+}
+
+/// Name and node type of a top-level Go declaration starting at `rest[0]`.
+/// Grouped `const (`/`var (` blocks are named after the identifiers they
+/// declare.
+fn go_declaration(line: &str, rest: &[&str]) -> Option<(String, NodeType)> {
+    if let Some(after) = line.strip_prefix("func ") {
+        if let Some(receiver) = after.strip_prefix('(') {
+            let (receiver, method) = receiver.split_once(')')?;
+            let receiver_type = receiver
+                .split_whitespace()
+                .last()?
+                .trim_start_matches('*')
+                .split('[')
+                .next()?;
+            let name = go_identifier(method.trim_start())?;
+            return Some((format!("{receiver_type}.{name}"), NodeType::Function));
+        }
+        return Some((go_identifier(after)?, NodeType::Function));
+    }
+    if let Some(after) = line.strip_prefix("type ") {
+        let name = go_identifier(after)?;
+        let kind = after[name.len()..].trim_start();
+        // Skip type parameters: `type List[T any] struct {`.
+        let kind = if kind.starts_with('[') { kind.split_once(']')?.1.trim_start() } else { kind };
+        let node_type = if kind.starts_with("struct") {
+            NodeType::Struct
+        } else if kind.starts_with("interface") {
+            NodeType::Trait
+        } else {
+            return None;
+        };
+        return Some((name, node_type));
+    }
+    let after = line.strip_prefix("const ").or_else(|| line.strip_prefix("var "))?;
+    if after.trim_start().starts_with('(') {
+        let names: Vec<String> = rest[1..]
+            .iter()
+            .take_while(|l| !l.trim_start().starts_with(')'))
+            .filter_map(|l| go_identifier(l.trim_start()))
+            .collect();
+        (!names.is_empty()).then(|| (names.join(", "), NodeType::Concept))
+    } else {
+        Some((go_identifier(after)?, NodeType::Concept))
+    }
+}
+
+fn go_identifier(s: &str) -> Option<String> {
+    let name: String = s
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Modifiers that may precede a Java or Kotlin declaration keyword.
+const JVM_MODIFIERS: &[&str] = &[
+    "public", "protected", "private", "internal", "static", "final", "abstract", "sealed",
+    "non-sealed", "strictfp", "default", "synchronized", "native", "open", "data", "inner",
+    "annotation", "value", "companion", "override", "suspend", "inline", "operator", "infix",
+    "tailrec", "external", "expect", "actual",
+];
+
+/// Java (`kotlin == false`) and Kotlin declarations: classes, records and
+/// Kotlin objects become Struct chunks, interfaces Trait and enums Enum.
+/// Methods are named `Class.method`, nested types `Outer.Inner`; with
+/// `kotlin`, top-level `fun`s are chunked too. Annotations above a
+/// declaration are part of its chunk.
+fn chunk_jvm(content: &str, kotlin: bool) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    // Enclosing types: (last line of the body, qualified name).
+    let mut scopes: Vec<(usize, String)> = Vec::new();
+    let mut annotations: Option<usize> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        while scopes.last().is_some_and(|(end, _)| *end < i) {
+            scopes.pop();
+        }
+        let trimmed = lines[i].trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") || trimmed.starts_with('*') {
+            i += 1;
+            continue;
+        }
+        if trimmed.starts_with("/*") {
+            i = find_code_block_end(&lines, i) + 1;
+            continue;
+        }
+        let declaration = strip_jvm_annotations(trimmed);
+        if declaration.is_empty() {
+            annotations.get_or_insert(i);
+            i = find_code_block_end(&lines, i) + 1;
+            continue;
+        }
+        let start = annotations.take().unwrap_or(i);
+        let qualify = |name: &str| match scopes.last() {
+            Some((_, outer)) => format!("{outer}.{name}"),
+            None => name.to_string(),
+        };
+
+        let end = find_code_block_end(&lines, i);
+        if let Some((name, node_type)) = jvm_type_declaration(declaration) {
+            let name = qualify(&name);
+            chunks.push(Chunk {
+                summary: build_summary(&name, &node_type, declaration),
+                name: name.clone(),
+                node_type,
+                content: lines[start..=end].join("\n"),
+                start_line: start + 1,
+                end_line: end + 1,
+            });
+            scopes.push((end, name));
+            i += 1;
+            continue;
+        }
+        let enclosing = scopes.last().map(|(_, name)| name.rsplit('.').next().unwrap_or(name));
+        if let Some(name) = jvm_method_name(declaration, enclosing, kotlin) {
+            let name = qualify(&name);
+            chunks.push(Chunk {
+                summary: build_summary(&name, &NodeType::Function, declaration),
+                name,
+                node_type: NodeType::Function,
+                content: lines[start..=end].join("\n"),
+                start_line: start + 1,
+                end_line: end + 1,
+            });
+        }
+        // Fields, statements and method bodies are skipped whole.
+        i = end + 1;
+    }
+
+    chunks
+}
+
+/// `line` without leading annotations such as `@Override` or
+/// `@Table(name = "items")`.
+fn strip_jvm_annotations(mut line: &str) -> &str {
+    while let Some(rest) = line.strip_prefix('@') {
+        let name_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':'))
+            .unwrap_or(rest.len());
+        let mut after = &rest[name_len..];
+        if after.starts_with('(') {
+            let mut depth = 0;
+            let close = after.char_indices().find(|(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            });
+            // An argument list continuing on the next line: all annotation.
+            let Some((close, _)) = close else {
+                return "";
+            };
+            after = &after[close + 1..];
+        }
+        line = after.trim_start();
+    }
+    line
+}
+
+fn jvm_type_declaration(line: &str) -> Option<(String, NodeType)> {
+    let mut node_type = None;
+    for token in line.split_whitespace() {
+        match token {
+            "enum" => node_type = Some(NodeType::Enum),
+            "class" | "record" | "object" => {
+                node_type.get_or_insert(NodeType::Struct);
+            }
+            "interface" => node_type = Some(NodeType::Trait),
+            "fun" if node_type.is_none() => {}
+            _ if node_type.is_none() && JVM_MODIFIERS.contains(&token) => {}
+            _ => {
+                let node_type = node_type?;
+                let name = go_identifier(token)
+                    // `companion object {` is named Companion, as in Kotlin.
+                    .or_else(|| line.contains("companion object").then(|| "Companion".to_string()))?;
+                return Some((name, node_type));
+            }
+        }
+    }
+    None
+}
+
+/// The name of a method (or Kotlin function) declared on `line`.
+/// `enclosing` is the simple name of the surrounding type, which Java
+/// constructors are named after.
+fn jvm_method_name(line: &str, enclosing: Option<&str>, kotlin: bool) -> Option<String> {
+    let before = &line[..line.find('(')?];
+    if before.contains('=') {
+        return None;
+    }
+    let tokens: Vec<&str> = before.split_whitespace().collect();
+    let name = *tokens.last()?;
+    if kotlin {
+        let fun = tokens.iter().position(|t| *t == "fun")?;
+        if !tokens[..fun].iter().all(|t| JVM_MODIFIERS.contains(t)) || fun == tokens.len() - 1 {
+            return None;
+        }
+        // Extension functions keep their receiver type: `fun <T> List<T>.second()`
+        // is `List.second`.
+        let mut depth = 0;
+        let name: String = name
+            .chars()
+            .filter(|c| match c {
+                '<' => {
+                    depth += 1;
+                    false
+                }
+                '>' => {
+                    depth -= 1;
+                    false
+                }
+                _ => depth == 0,
+            })
+            .collect();
+        return (!name.is_empty()).then_some(name);
+    }
+    enclosing?;
+    let is_identifier = name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    const NOT_METHODS: &[&str] =
+        &["if", "for", "while", "switch", "catch", "synchronized", "return", "new", "throw"];
+    if name.is_empty() || !is_identifier || NOT_METHODS.contains(&name) {
+        return None;
+    }
+    // A return type before the name, unless it is a constructor.
+    (tokens.len() >= 2 || enclosing == Some(name)).then(|| name.to_string())
+}
+
+/// C and C++ definitions: functions (keeping qualified names such as
+/// `Foo::bar`), `struct`/`class`/`union`/`enum` bodies and `#define` macros.
+/// Prototypes and forward declarations, which end in `;` before any `{`,
+/// are skipped. `namespace` and `extern "C"` blocks are looked through.
+fn chunk_c_cpp(content: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    // First line of the declaration being read, e.g. a `template <...>` line.
+    let mut header_start: Option<usize> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if trimmed.starts_with('#') {
+            if let Some(chunk) = c_macro(&lines, i) {
+                i = chunk.end_line;
+                chunks.push(chunk);
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        if trimmed.starts_with("/*") {
+            i = find_code_block_end(&lines, i) + 1;
+            continue;
+        }
+        let closes_block = header_start.is_none() && trimmed.starts_with('}');
+        if trimmed.is_empty() || trimmed.starts_with("//") || closes_block {
+            i += 1;
+            continue;
+        }
+
+        let start = *header_start.get_or_insert(i);
+        let Some((terminator, column)) = c_terminator(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        header_start = None;
+        if terminator == ';' {
+            i += 1;
+            continue;
+        }
+        let mut header: Vec<&str> = lines[start..i].iter().map(|l| l.trim()).collect();
+        header.push(lines[i][..column].trim());
+        let header = header.join(" ");
+
+        if header.starts_with("namespace") || header.starts_with("extern \"C\"") {
+            i += 1;
+            continue;
+        }
+        let end = scan_code_block(&lines, start, true);
+        if let Some((name, node_type)) = c_definition(&header, lines[end]) {
+            chunks.push(Chunk {
+                summary: build_summary(&name, &node_type, &header),
+                name,
+                node_type,
+                content: lines[start..=end].join("\n"),
+                start_line: start + 1,
+                end_line: end + 1,
+            });
+        }
+        i = end + 1;
+    }
+
+    chunks
+}
+
+/// A `#define` at `lines[start]`, through its backslash continuations.
+fn c_macro(lines: &[&str], start: usize) -> Option<Chunk> {
+    let after = lines[start].trim().strip_prefix('#')?.trim_start().strip_prefix("define")?;
+    let name = go_identifier(after.trim_start())?;
+    let mut end = start;
+    while lines[end].trim_end().ends_with('\\') && end + 1 < lines.len() {
+        end += 1;
+    }
+    Some(Chunk {
+        summary: build_summary(&name, &NodeType::Concept, lines[start]),
+        name,
+        node_type: NodeType::Concept,
+        content: lines[start..=end].join("\n"),
+        start_line: start + 1,
+        end_line: end + 1,
+    })
+}
+
+/// The first `{` or `;` on `line` outside literals and comments, with its
+/// byte offset.
+fn c_terminator(line: &str) -> Option<(char, usize)> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut prev = ' ';
+    for (pos, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+        } else {
+            match c {
+                '/' if prev == '/' => return None,
+                '"' | '\'' => quote = Some(c),
+                '{' | ';' => return Some((c, pos)),
+                _ => {}
+            }
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Classifies the text before a definition's `{`. `last_line` is the line
+/// holding the closing brace, where a `typedef struct { ... } Name;` gets
+/// its name.
+fn c_definition(header: &str, last_line: &str) -> Option<(String, NodeType)> {
+    let paren = header.find('(');
+    let before_paren = paren.map_or(header, |p| &header[..p]);
+    if before_paren.contains('=') && !before_paren.contains("operator") {
+        // An initializer such as `static const struct ops table = {`.
+        return None;
+    }
+    if let Some(paren) = paren {
+        let before = header[..paren].trim_end();
+        // Qualified names keep their scope: `Foo::bar`, `Vec<int>::operator==`.
+        let is_name_char = |c: char| c.is_alphanumeric() || "_:~<>".contains(c);
+        let name_end = before.rfind("operator").unwrap_or(before.len());
+        let start = before[..name_end]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| !is_name_char(c))
+            .map_or(0, |(p, c)| p + c.len_utf8());
+        let name = before[start..].trim_start_matches(':');
+        const NOT_FUNCTIONS: &[&str] = &["if", "for", "while", "switch", "return", "sizeof"];
+        if name.is_empty() || NOT_FUNCTIONS.contains(&name) {
+            return None;
+        }
+        return Some((name.to_string(), NodeType::Function));
+    }
+
+    let mut tokens = header
+        .split_whitespace()
+        .skip_while(|t| !matches!(*t, "struct" | "class" | "union" | "enum"));
+    let node_type = match tokens.next()? {
+        "enum" => NodeType::Enum,
+        _ => NodeType::Struct,
+    };
+    let tag = tokens
+        .find(|t| {
+            !matches!(*t, "class" | "struct")
+                && !t.starts_with("__attribute__")
+                && !t.starts_with("alignas")
+        })
+        .and_then(go_identifier);
+    let name = match tag {
+        Some(tag) => tag,
+        None => go_identifier(last_line.trim().trim_start_matches('}').trim_start())?,
+    };
+    Some((name, node_type))
+}
+
+/// Like [`find_block_end`], for C-family syntax where braces and parentheses
+/// inside string, rune and raw (backtick) literals or comments must not count.
+/// A declaration that opens nothing, such as a one-line `const X = 1` or a
+/// body-less function, ends on the line where it started.
+fn find_code_block_end(lines: &[&str], start: usize) -> usize {
+    scan_code_block(lines, start, false)
+}
+
+/// With `until_brace`, a balanced line before the first `{` does not end the
+/// block, for C headers whose brace is on the next line. Preprocessor lines
+/// after `start` are opaque, and only the first branch of an `#if` counts,
+/// so alternative headers like `#ifdef A\n if (a) {\n#else\n if (b) {`
+/// open one block, not two.
+fn scan_code_block(lines: &[&str], start: usize, until_brace: bool) -> usize {
+    let mut depth: i32 = 0;
+    let mut raw_string = false;
+    let mut block_comment = false;
+    let mut braced = false;
+    // One entry per open `#if`: whether an `#else`/`#elif` branch is being skipped.
+    let mut conditionals: Vec<bool> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let directive = line.trim_start().strip_prefix('#').map(str::trim_start);
+        if let Some(directive) = directive.filter(|_| i > start && !raw_string && !block_comment) {
+            if directive.starts_with("if") {
+                conditionals.push(false);
+            } else if directive.starts_with("el") {
+                if let Some(skipping) = conditionals.last_mut() {
+                    *skipping = true;
+                }
+            } else if directive.starts_with("endif") {
+                conditionals.pop();
+            }
+            continue;
+        }
+        if conditionals.contains(&true) {
+            continue;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut quote: Option<char> = None;
+        let mut j = 0;
+        while j < chars.len() {
+            let c = chars[j];
+            if block_comment {
+                if c == '*' && chars.get(j + 1) == Some(&'/') {
+                    block_comment = false;
+                    j += 1;
+                }
+            } else if raw_string {
+                raw_string = c != '`';
+            } else if let Some(q) = quote {
+                if c == '\\' {
+                    j += 1;
+                } else if c == q {
+                    quote = None;
+                }
+            } else {
+                match c {
+                    '/' if chars.get(j + 1) == Some(&'/') => break,
+                    '/' if chars.get(j + 1) == Some(&'*') => {
+                        block_comment = true;
+                        j += 1;
+                    }
+                    '`' => raw_string = true,
+                    '"' | '\'' => quote = Some(c),
+                    '{' => {
+                        depth += 1;
+                        braced = true;
+                    }
+                    '(' => depth += 1,
+                    '}' | ')' => depth -= 1,
+                    _ => {}
+                }
+            }
+            j += 1;
+        }
+        if depth <= 0 && !raw_string && !block_comment && (braced || !until_brace) {
+            return i;
+        }
+    }
+
+    lines.len().saturating_sub(1)
+}
+
 fn chunk_whole_file(path: &Path, content: &str) -> Vec<Chunk> {
     let name = path
         .file_name()
@@ -144,7 +1294,8 @@ fn chunk_whole_file(path: &Path, content: &str) -> Vec<Chunk> {
         node_type: NodeType::File,
         content: content.to_string(),
         start_line: 1,
-        end_line: content.lines().count(),
+        // An empty file still spans its one, empty, line.
+        end_line: content.lines().count().max(1),
         summary: format!("File: {name}"),
     }]
 }
@@ -194,20 +1345,117 @@ fn extract_impl_name(line: &str) -> Option<String> {
     }
 }
 
-fn find_block_end(lines: &[&str], start: usize) -> usize {
+/// Literal and comment rules [`find_block_end`] applies while counting braces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BraceSyntax {
+    /// Rust: nested block comments, multi-line and raw (`r#"…"#`) strings,
+    /// and char literals told apart from lifetimes.
+    Rust,
+    /// JavaScript/TypeScript: template literals may span lines, while an
+    /// unterminated quote ends with its line, as apostrophes in JSX text do.
+    Script,
+}
+
+/// What the scanner is inside of when a line ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BraceState {
+    Code,
+    /// Depth of nested `/* */` comments.
+    BlockComment(usize),
+    Quoted(char),
+    /// A raw string closed by `"` followed by this many `#`.
+    RawString(usize),
+    Template,
+}
+
+/// Index of the line closing the first block opened at or after `start`.
+/// Braces inside strings, char literals and comments do not count.
+fn find_block_end(lines: &[&str], start: usize, syntax: BraceSyntax) -> usize {
     let mut depth: i32 = 0;
     let mut found_open = false;
+    let mut state = BraceState::Code;
 
     for (i, line) in lines.iter().enumerate().skip(start) {
-        for ch in line.chars() {
-            if ch == '{' {
-                depth += 1;
-                found_open = true;
-            } else if ch == '}' {
-                depth -= 1;
+        let chars: Vec<char> = line.chars().collect();
+        let mut j = 0;
+        while j < chars.len() {
+            let c = chars[j];
+            let next = chars.get(j + 1).copied();
+            match state {
+                BraceState::BlockComment(nesting) => {
+                    if c == '*' && next == Some('/') {
+                        state = match nesting {
+                            1 => BraceState::Code,
+                            _ => BraceState::BlockComment(nesting - 1),
+                        };
+                        j += 1;
+                    } else if c == '/' && next == Some('*') && syntax == BraceSyntax::Rust {
+                        state = BraceState::BlockComment(nesting + 1);
+                        j += 1;
+                    }
+                }
+                BraceState::Quoted(quote) => {
+                    if c == '\\' {
+                        j += 1;
+                    } else if c == quote {
+                        state = BraceState::Code;
+                    }
+                }
+                BraceState::RawString(hashes) => {
+                    let closing = chars[j + 1..].iter().take_while(|&&h| h == '#').count();
+                    if c == '"' && closing >= hashes {
+                        state = BraceState::Code;
+                        j += hashes;
+                    }
+                }
+                BraceState::Template => {
+                    if c == '\\' {
+                        j += 1;
+                    } else if c == '`' {
+                        state = BraceState::Code;
+                    }
+                }
+                BraceState::Code => match c {
+                    '/' if next == Some('/') => break,
+                    '/' if next == Some('*') => {
+                        state = BraceState::BlockComment(1);
+                        j += 1;
+                    }
+                    '"' => state = BraceState::Quoted('"'),
+                    '`' if syntax == BraceSyntax::Script => state = BraceState::Template,
+                    '\'' if syntax == BraceSyntax::Script => state = BraceState::Quoted('\''),
+                    '\'' => {
+                        // `'{'` and `'\''` are char literals; `'a` is a lifetime.
+                        if next == Some('\\') {
+                            state = BraceState::Quoted('\'');
+                            j += 1;
+                        } else if chars.get(j + 2) == Some(&'\'') {
+                            j += 2;
+                        }
+                    }
+                    'r' if syntax == BraceSyntax::Rust
+                        && !chars[..j].last().is_some_and(|p| p.is_alphanumeric() || *p == '_') =>
+                    {
+                        let hashes = chars[j + 1..].iter().take_while(|&&h| h == '#').count();
+                        if chars.get(j + 1 + hashes) == Some(&'"') {
+                            state = BraceState::RawString(hashes);
+                            j += 1 + hashes;
+                        }
+                    }
+                    '{' => {
+                        depth += 1;
+                        found_open = true;
+                    }
+                    '}' => depth -= 1,
+                    _ => {}
+                },
             }
+            j += 1;
+        }
+        if syntax == BraceSyntax::Script && matches!(state, BraceState::Quoted(_)) {
+            state = BraceState::Code;
         }
-        if found_open && depth <= 0 {
+        if found_open && depth <= 0 && state == BraceState::Code {
             return i;
         }
     }
@@ -328,6 +1576,160 @@ mod tests {
         assert_eq!(trait_chunk.unwrap().name, "Searchable");
     }
 
+    const RUST_SOURCE: &str = r#"//! Crate docs.
+
+use std::fmt;
+
+/// Maximum retries.
+pub const MAX_RETRIES: u32 = 3;
+static GREETING: &str = "fn fake() {";
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub struct Wrapper<T> {
+    inner: T,
+}
+
+impl<T: fmt::Debug> Wrapper<T> {
+    /// Wraps a value.
+    pub fn new(inner: T) -> Self {
+        // A stray } in a comment.
+        let _ = "}}}";
+        Self { inner }
+    }
+
+    #[cfg(test)] pub fn peek(&self) -> &T { &self.inner }
+}
+
+macro_rules! wrap {
+    ($e:expr) => {
+        Wrapper::new($e)
+    };
+}
+
+mod nested {
+    pub fn helper() {}
+}
+
+mod declared;
+"#;
+
+    #[test]
+    fn chunk_rust_items_with_syn() {
+        let chunks = chunk_rust(RUST_SOURCE);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("MAX_RETRIES", NodeType::Concept),
+                ("GREETING", NodeType::Concept),
+                ("Result", NodeType::Concept),
+                ("Wrapper", NodeType::Struct),
+                ("Wrapper", NodeType::Impl),
+                ("Wrapper::new", NodeType::Function),
+                ("Wrapper::peek", NodeType::Function),
+                ("wrap", NodeType::Function),
+                ("nested", NodeType::Module),
+                ("helper", NodeType::Function),
+                ("declared", NodeType::Module),
+            ]
+        );
+        let lines = |c: &Chunk| (c.start_line, c.end_line);
+        assert_eq!(lines(&chunks[1]), (7, 7));
+        assert_eq!(lines(&chunks[4]), (16, 25));
+        // Braces in strings and comments do not end `new` early.
+        assert_eq!(lines(&chunks[5]), (17, 22));
+        assert_eq!(lines(&chunks[7]), (27, 31));
+        assert_eq!(lines(&chunks[10]), (37, 37));
+        assert_eq!(chunks[4].summary, "impl: impl<T: fmt::Debug> Wrapper<T> {");
+    }
+
+    #[test]
+    fn chunk_rust_attributes_are_part_of_the_item() {
+        let chunks = chunk_rust(RUST_SOURCE);
+        let wrapper = chunks.iter().find(|c| c.node_type == NodeType::Struct).unwrap();
+        assert_eq!((wrapper.start_line, wrapper.end_line), (10, 14));
+        assert!(wrapper.content.starts_with("#[derive(Debug, Clone)]"));
+        assert_eq!(wrapper.summary, "struct: pub struct Wrapper<T> {");
+
+        let peek = chunks.iter().find(|c| c.name == "Wrapper::peek").unwrap();
+        assert_eq!((peek.start_line, peek.end_line), (24, 24));
+        assert!(peek.content.trim_start().starts_with("#[cfg(test)] pub fn peek"));
+    }
+
+    #[test]
+    fn chunk_rust_doc_comments_become_the_summary() {
+        let chunks = chunk_rust(RUST_SOURCE);
+        let max = &chunks[0];
+        assert_eq!((max.start_line, max.end_line), (5, 6));
+        assert!(max.content.starts_with("/// Maximum retries."));
+        assert_eq!(max.summary, "concept: Maximum retries.");
+        assert_eq!(chunks[5].summary, "function: Wraps a value.");
+        // Undocumented items keep the declaration line.
+        assert_eq!(chunks[1].summary, "concept: static GREETING: &str = \"fn fake() {\";");
+    }
+
+    #[test]
+    fn chunk_rust_doc_summary_is_the_first_plain_sentence() {
+        let code = r#"/// Ranks `results` by **score**, see [`SearchEngine`] and
+/// [the docs](https://example.com). Ties keep their order.
+///
+/// # Errors
+///
+/// Never fails.
+pub fn rank() {}
+
+/**
+ * Builds the *index*.
+ *
+ * Second paragraph.
+ */
+fn build() {}
+
+#[doc = "Loads the [config][cfg]."]
+#[doc = ""]
+#[doc = "More details."]
+#[inline]
+fn load() {}
+"#;
+        let chunks = chunk_rust(code);
+        let summaries: Vec<&str> = chunks.iter().map(|c| c.summary.as_str()).collect();
+        assert_eq!(
+            summaries,
+            [
+                "function: Ranks results by score, see SearchEngine and the docs.",
+                "function: Builds the index.",
+                "function: Loads the config.",
+            ]
+        );
+        let lines: Vec<(usize, usize)> =
+            chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(lines, [(1, 7), (9, 14), (16, 20)]);
+        assert!(chunks[0].content.contains("Never fails."));
+    }
+
+    #[test]
+    fn chunk_rust_lines_picks_up_doc_comments() {
+        let code = "/// Retries the `request`.\n#[inline]\npub fn broken( {\n}\n";
+        let chunks = chunk_rust(code);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 4));
+        assert_eq!(chunks[0].summary, "function: Retries the request.");
+    }
+
+    #[test]
+    fn chunk_rust_falls_back_when_syn_fails() {
+        let code = "pub fn broken( {\n    let x = ;\n}\n";
+        assert!(syn::parse_file(code).is_err());
+        let chunks = chunk_rust(code);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].name, "broken");
+    }
+
     #[test]
     fn extract_impl_name_simple() {
         assert_eq!(extract_impl_name("impl MyStruct {"), Some("MyStruct".to_string()));
@@ -372,6 +1774,84 @@ mod tests {
         assert_eq!(chunks[0].name, "config.toml");
     }
 
+    fn config_sections(file: &str, content: &str) -> Vec<(String, usize, usize)> {
+        chunk_file(Path::new(file), content)
+            .into_iter()
+            .map(|c| {
+                assert_eq!(c.node_type, NodeType::Document);
+                (c.name, c.start_line, c.end_line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunk_toml_splits_top_level_tables() {
+        let deps: String = (0..25).map(|n| format!("dep{n} = \"1\"\n")).collect();
+        let toml = format!(
+            "# Manifest\nedition = \"2021\"\n\n[package]\nname = \"hermes\"\n\n\
+             [dependencies]\n{deps}\n# trailing comment\n[[bin]]\nname = \"Hermes\"\n"
+        );
+        assert_eq!(
+            config_sections("Cargo.toml", &toml),
+            [
+                ("root".to_string(), 1, 2),
+                ("package".to_string(), 4, 5),
+                ("dependencies".to_string(), 7, 32),
+                ("bin".to_string(), 35, 36),
+            ]
+        );
+        let chunks = chunk_file(Path::new("Cargo.toml"), &toml);
+        assert!(chunks[2].content.starts_with("[dependencies]\ndep0"));
+        assert_eq!(chunks[2].summary, "document: [dependencies]");
+    }
+
+    #[test]
+    fn chunk_json_splits_top_level_keys() {
+        let deps: Vec<String> = (0..25).map(|n| format!("    \"dep{n}\": \"^1.0\"")).collect();
+        let json = format!(
+            "{{\n  \"name\": \"app\",\n  \"scripts\": {{ \"build\": \"tsc, {{x}}\" }},\n  \
+             \"dependencies\": {{\n{}\n  }},\n  \"files\": [\n    \"dist\"\n  ]\n}}\n",
+            deps.join(",\n")
+        );
+        assert_eq!(
+            config_sections("package.json", &json),
+            [
+                ("name".to_string(), 2, 2),
+                ("scripts".to_string(), 3, 3),
+                ("dependencies".to_string(), 4, 30),
+                ("files".to_string(), 31, 33),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_yaml_splits_top_level_keys() {
+        let steps: String = (0..25).map(|n| format!("  - run: step {n}\n")).collect();
+        let yaml = format!(
+            "---\n# CI\nname: build\non:\n  push: {{}}\n\njobs:\n{steps}\n\"env\": {{}}\n"
+        );
+        assert_eq!(
+            config_sections("ci.yml", &yaml),
+            [
+                ("name".to_string(), 3, 3),
+                ("on".to_string(), 4, 5),
+                ("jobs".to_string(), 7, 32),
+                ("env".to_string(), 34, 34),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_small_config_files_stay_whole() {
+        for file in ["Cargo.toml", "package.json", "ci.yaml"] {
+            let chunks = chunk_file(Path::new(file), "a: 1\n");
+            assert_eq!(chunks.len(), 1);
+            assert_eq!(chunks[0].node_type, NodeType::File);
+        }
+        let array = format!("[\n{}1\n]\n", "1,\n".repeat(40));
+        assert_eq!(chunk_file(Path::new("list.json"), &array)[0].node_type, NodeType::File);
+    }
+
     #[test]
     fn chunk_typescript_function() {
         let code = "export function handleRequest(req: Request) {\n    return req;\n}\n";
@@ -389,6 +1869,491 @@ mod tests {
         assert_eq!(chunks[0].name, "fetchData");
     }
 
+    #[test]
+    fn find_block_end_ignores_braces_in_rust_literals_and_comments() {
+        let code = r##"fn open() {
+    let s = "{";
+    let c = '{';
+}
+fn commented() {
+    // }
+    /* } /* nested } */ still a comment } */
+    let x = 1;
+}
+fn raw<'a>(s: &'a str) -> &'a str {
+    let _ = r#"}"{"#;
+    let _ = '\'';
+    s
+}
+fn after() {}"##;
+        let lines: Vec<&str> = code.lines().collect();
+        assert_eq!(find_block_end(&lines, 0, BraceSyntax::Rust), 3);
+        assert_eq!(find_block_end(&lines, 4, BraceSyntax::Rust), 8);
+        assert_eq!(find_block_end(&lines, 9, BraceSyntax::Rust), 13);
+
+        let ranges: Vec<(String, usize, usize)> = chunk_rust_lines(code)
+            .into_iter()
+            .map(|c| (c.name, c.start_line, c.end_line))
+            .collect();
+        assert_eq!(ranges[3], ("after".to_string(), 15, 15));
+    }
+
+    #[test]
+    fn find_block_end_handles_template_literals_and_jsx_text() {
+        let code = "export function Banner(props) {
+  const css = `.a { color: red;
+  }}}`;
+  return <p>Don't {props.name}</p>;
+}
+export function Next() {
+  return 1;
+}";
+        let chunks = chunk_typescript(code);
+        let ranges: Vec<(&str, usize, usize)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.start_line, c.end_line))
+            .collect();
+        assert_eq!(ranges, [("Banner", 1, 5), ("Next", 6, 8)]);
+    }
+
+    fn python_chunk<'c>(chunks: &'c [Chunk], name: &str) -> &'c Chunk {
+        chunks
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| panic!("no chunk named {name}"))
+    }
+
+    #[test]
+    fn chunk_python_module_docstring_and_methods() {
+        let code = "\"\"\"Retry helpers.\n\nBackoff policies live here.\n\"\"\"\nimport time\n\n\nclass Backoff:\n    \"\"\"Exponential backoff.\"\"\"\n\n    def delay(self, attempt):\n        def jitter():\n            return 0\n        return 2 ** attempt + jitter()\n\n    async def wait(self, attempt):\n        await sleep(self.delay(attempt))\n\n\ndef retry(fn):\n    return fn()\n";
+        let chunks = chunk_file(Path::new("pkg/retry.py"), code);
+        let names: Vec<&str> = chunks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["retry", "Backoff", "Backoff.delay", "Backoff.wait", "retry"]);
+
+        assert_eq!(chunks[0].node_type, NodeType::Document);
+        assert_eq!(chunks[0].summary, "Retry helpers.");
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 4));
+
+        let class = python_chunk(&chunks, "Backoff");
+        assert_eq!(class.node_type, NodeType::Struct);
+        assert_eq!((class.start_line, class.end_line), (8, 17));
+        let delay = python_chunk(&chunks, "Backoff.delay");
+        assert_eq!((delay.start_line, delay.end_line), (11, 14));
+        assert_eq!(delay.summary, "function: def delay(self, attempt):");
+        assert_eq!(chunks[4].node_type, NodeType::Function);
+        assert_eq!((chunks[4].start_line, chunks[4].end_line), (20, 21));
+    }
+
+    #[test]
+    fn chunk_python_keeps_decorators_with_the_block() {
+        let code = "@app.route(\n    \"/health\",\n)\n@cached\ndef health():\n    return \"ok\"\n";
+        let chunks = chunk_file(Path::new("app.py"), code);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].name, "health");
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 6));
+        assert!(chunks[0].content.starts_with("@app.route("));
+        assert_eq!(chunks[0].summary, "function: def health():");
+    }
+
+    #[test]
+    fn chunk_python_multi_line_signature_uses_docstring_summary() {
+        let code = "def connect(\n    host: str,\n    port: int = 5432,\n) -> Connection:\n    \"\"\"\n    Open a pooled database connection.\n    \"\"\"\n    return Pool(host, port).get()\n\nTIMEOUT = 5\n";
+        let chunks = chunk_file(Path::new("db.py"), code);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 8));
+        assert_eq!(chunks[0].summary, "function: Open a pooled database connection.");
+    }
+
+    #[test]
+    fn chunk_python_class_without_methods() {
+        let code = "class Empty:\n    pass\n\nclass Point(NamedTuple):\n    x: int\n    y: int\n";
+        let chunks = chunk_file(Path::new("types.py"), code);
+        let names: Vec<&str> = chunks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Empty", "Point"]);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 2));
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (4, 6));
+        assert_eq!(chunks[1].summary, "struct: class Point(NamedTuple):");
+    }
+
+    #[test]
+    fn chunk_python_tab_indented_file() {
+        let code = "class Queue:\n\tdef push(self, item):\n\t\tself.items.append(item)\n\n\tdef pop(self):\n\t\treturn self.items.pop()\n\ndef main():\n\tQueue().push(1)\n";
+        let chunks = chunk_file(Path::new("queue.py"), code);
+        let names: Vec<&str> = chunks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Queue", "Queue.push", "Queue.pop", "main"]);
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (1, 6));
+        let push = python_chunk(&chunks, "Queue.push");
+        assert_eq!((push.start_line, push.end_line), (2, 3));
+    }
+
+    const GO_SOURCE: &str = r#"package store
+
+import "fmt"
+
+// Store persists items.
+type Store interface {
+	Get(key string) (Item, error)
+	Put(item Item) error
+}
+
+type Item struct {
+	Key   string
+	Value string `json:"value"`
+}
+
+const (
+	MaxItems = 100
+	Prefix   = "item:"
+)
+
+var ErrMissing = fmt.Errorf("missing {key}")
+
+func (i *Item) String() string {
+	return fmt.Sprintf("%s={%s}", i.Key, i.Value)
+}
+
+func (i Item) Empty() bool { return i.Value == "" }
+
+func Map[T any, U any](in []T, f func(T) U) []U {
+	out := make([]U, 0, len(in))
+	for _, v := range in {
+		out = append(out, f(v))
+	}
+	return out
+}
+"#;
+
+    #[test]
+    fn chunk_go_declarations() {
+        let chunks = chunk_file(Path::new("store.go"), GO_SOURCE);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Store", NodeType::Trait),
+                ("Item", NodeType::Struct),
+                ("MaxItems, Prefix", NodeType::Concept),
+                ("ErrMissing", NodeType::Concept),
+                ("Item.String", NodeType::Function),
+                ("Item.Empty", NodeType::Function),
+                ("Map", NodeType::Function),
+            ]
+        );
+        let lines = |name: &str| {
+            let c = chunks.iter().find(|c| c.name == name).unwrap();
+            (c.start_line, c.end_line)
+        };
+        assert_eq!(lines("Store"), (6, 9));
+        assert_eq!(lines("Item"), (11, 14));
+        assert_eq!(lines("MaxItems, Prefix"), (16, 19));
+        assert_eq!(lines("ErrMissing"), (21, 21));
+        // Braces inside the format string do not end the method early.
+        assert_eq!(lines("Item.String"), (23, 25));
+        assert_eq!(lines("Item.Empty"), (27, 27));
+        assert_eq!(lines("Map"), (29, 35));
+    }
+
+    #[test]
+    fn chunk_go_method_summary_names_the_receiver() {
+        let chunks = chunk_go(GO_SOURCE);
+        let string = chunks.iter().find(|c| c.name == "Item.String").unwrap();
+        assert_eq!(string.summary, "function: func (i *Item) String() string {");
+        let long = "func (s *SubscriptionManager) RefreshAllSubscriptionsForTenant(ctx context.Context, tenantID string) error {\n\treturn nil\n}\n";
+        let chunks = chunk_go(long);
+        assert_eq!(chunks[0].summary, "function: SubscriptionManager.RefreshAllSubscriptionsForTenant");
+        assert_eq!(chunks[0].end_line, 3);
+    }
+
+    #[test]
+    fn chunk_java_classes_methods_and_annotations() {
+        let code = r#"package com.example.orders;
+
+import java.util.List;
+
+/**
+ * Order lookups.
+ */
+@Service
+@Transactional(
+    readOnly = true
+)
+public class OrderService implements Lookup {
+    private final OrderRepository repository;
+    private final Map<String, Integer> limits = Map.of(
+        "default", compute(1)
+    );
+
+    public OrderService(OrderRepository repository) {
+        this.repository = repository;
+    }
+
+    @Override
+    public <T> List<T> find(String customer) {
+        if (customer == null) { throw new IllegalArgumentException("}"); }
+        return repository.find(customer);
+    }
+
+    public static class Builder {
+        Builder withLimit(int limit) {
+            return this;
+        }
+    }
+
+    enum Status {
+        OPEN("open"),
+        CLOSED("closed");
+
+        Status(String label) {}
+    }
+}
+
+interface Lookup {
+    <T> List<T> find(String customer);
+}
+
+public record Money(long cents, String currency) {}
+"#;
+        let chunks = chunk_file(Path::new("OrderService.java"), code);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("OrderService", NodeType::Struct),
+                ("OrderService.OrderService", NodeType::Function),
+                ("OrderService.find", NodeType::Function),
+                ("OrderService.Builder", NodeType::Struct),
+                ("OrderService.Builder.withLimit", NodeType::Function),
+                ("OrderService.Status", NodeType::Enum),
+                ("OrderService.Status.Status", NodeType::Function),
+                ("Lookup", NodeType::Trait),
+                ("Lookup.find", NodeType::Function),
+                ("Money", NodeType::Struct),
+            ]
+        );
+        let class = &chunks[0];
+        assert_eq!((class.start_line, class.end_line), (8, 40));
+        assert!(class.content.starts_with("@Service\n@Transactional("));
+        assert_eq!(class.summary, "struct: public class OrderService implements Lookup {");
+        let find = &chunks[2];
+        assert_eq!((find.start_line, find.end_line), (22, 26));
+        assert!(find.content.starts_with("    @Override"));
+        assert_eq!(find.summary, "function: public <T> List<T> find(String customer) {");
+        assert_eq!((chunks[8].start_line, chunks[8].end_line), (43, 43));
+    }
+
+    #[test]
+    fn chunk_kotlin_functions_objects_and_nested_classes() {
+        let code = r#"package com.example
+
+@JvmInline
+value class Cents(val value: Long)
+
+data class Point(val x: Int, val y: Int)
+
+object Registry {
+    private val items = mutableListOf<String>()
+
+    fun register(item: String) {
+        items.add(item)
+    }
+}
+
+class Router(private val base: String) {
+    inner class Route {
+        suspend fun handle(path: String): String = "$base/$path"
+    }
+
+    companion object {
+        fun create(): Router = Router("/")
+    }
+}
+
+fun <T> List<T>.second(): T = this[1]
+
+fun main() {
+    println(Registry)
+}
+"#;
+        let chunks = chunk_file(Path::new("Router.kt"), code);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Cents", NodeType::Struct),
+                ("Point", NodeType::Struct),
+                ("Registry", NodeType::Struct),
+                ("Registry.register", NodeType::Function),
+                ("Router", NodeType::Struct),
+                ("Router.Route", NodeType::Struct),
+                ("Router.Route.handle", NodeType::Function),
+                ("Router.Companion", NodeType::Struct),
+                ("Router.Companion.create", NodeType::Function),
+                ("List.second", NodeType::Function),
+                ("main", NodeType::Function),
+            ]
+        );
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 4));
+        assert_eq!(chunks[0].summary, "struct: value class Cents(val value: Long)");
+        let router = &chunks[4];
+        assert_eq!((router.start_line, router.end_line), (16, 24));
+        assert_eq!(
+            chunks[6].summary,
+            "function: suspend fun handle(path: String): String = \"$base/$path\""
+        );
+        assert_eq!((chunks[10].start_line, chunks[10].end_line), (28, 30));
+    }
+
+    #[test]
+    fn chunk_c_source_with_knr_braces_and_macros() {
+        let code = r#"#include <stdio.h>
+
+#define MAX(a, b) \
+    ((a) > (b) ? (a) : (b))
+
+typedef struct {
+    int x;
+    int y;
+} Point;
+
+static int parse(const char *src);
+
+int
+parse(const char *src)
+{
+#ifdef STRICT
+    if (src == NULL) {
+#else
+    if (!src) {
+#endif
+        return -1;
+    }
+    printf("{%s}\n", src);
+    return 0;
+}
+
+static const int table[] = {
+    1, 2, 3,
+};
+"#;
+        let chunks = chunk_file(Path::new("parse.c"), code);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [("MAX", NodeType::Concept), ("Point", NodeType::Struct), ("parse", NodeType::Function)]
+        );
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 4));
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (6, 9));
+        // The prototype on line 11 is skipped; the K&R definition spans the
+        // `#ifdef` branches without losing track of its braces.
+        assert_eq!((chunks[2].start_line, chunks[2].end_line), (13, 25));
+        assert_eq!(chunks[2].summary, "function: int parse(const char *src)");
+    }
+
+    #[test]
+    fn chunk_cpp_templates_and_qualified_names() {
+        let code = r#"namespace geo {
+
+template <typename T>
+class Vec : public Base {
+public:
+    T norm() const { return x; }
+private:
+    T x;
+};
+
+enum class Axis : unsigned char { X, Y };
+
+double Shape::area() const {
+    return width * height;
+}
+
+template <typename T>
+T clamp(T v, T lo, T hi)
+{
+    return v < lo ? lo : (v > hi ? hi : v);
+}
+
+bool Vec<int>::operator==(const Vec &other) const {
+    return x == other.x;
+}
+
+}  // namespace geo
+"#;
+        let chunks = chunk_file(Path::new("geo.cpp"), code);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Vec", NodeType::Struct),
+                ("Axis", NodeType::Enum),
+                ("Shape::area", NodeType::Function),
+                ("clamp", NodeType::Function),
+                ("Vec<int>::operator==", NodeType::Function),
+            ]
+        );
+        assert_eq!((chunks[0].start_line, chunks[0].end_line), (3, 9));
+        assert_eq!((chunks[3].start_line, chunks[3].end_line), (17, 21));
+    }
+
+    #[test]
+    fn chunk_c_name_after_multibyte_separator() {
+        // A non-breaking space, as pasted from a web page, before the name.
+        let code = "#define A 1\nstatic int\u{a0}foo(void) {\n}\nint \u{1f600}bar(void) {\n}\n";
+        let chunks = chunk_file(Path::new("f.cpp"), code);
+        let names: Vec<&str> = chunks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["A", "foo", "bar"]);
+    }
+
+    #[test]
+    fn chunk_header_of_declarations_has_no_functions() {
+        let code = r#"#ifndef QUEUE_H
+#define QUEUE_H
+
+struct queue;
+
+struct queue_item {
+    void *data;
+    struct queue_item *next;
+};
+
+enum queue_mode { QUEUE_FIFO, QUEUE_LIFO };
+
+struct queue *queue_new(enum queue_mode mode);
+void queue_push(struct queue *q,
+                void *data);
+
+#endif
+"#;
+        let chunks = chunk_file(Path::new("queue.h"), code);
+        let names: Vec<(&str, NodeType)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.node_type.clone()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("QUEUE_H", NodeType::Concept),
+                ("queue_item", NodeType::Struct),
+                ("queue_mode", NodeType::Enum),
+            ]
+        );
+    }
+
     #[test]
     fn build_summary_short_line() {
         let summary = build_summary("my_fn", &NodeType::Function, "pub fn my_fn() {");
@@ -421,6 +2386,38 @@ mod tests {
         assert_eq!(chunks[0].name, "Only One");
     }
 
+    #[test]
+    fn markdown_nested_headings_get_hierarchical_names() {
+        let md = "Intro text.\n\n# Installation\nSteps.\n## Linux\napt\n### Arch ###\npacman\n\
+                  ## macOS\nbrew\n# Usage\nRun it.\n";
+        let chunks = chunk_markdown(md);
+        let sections: Vec<(&str, usize, usize)> = chunks
+            .iter()
+            .map(|c| (c.name.as_str(), c.start_line, c.end_line))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("Preamble", 1, 2),
+                ("Installation", 3, 10),
+                ("Installation > Linux", 5, 8),
+                ("Installation > Linux > Arch", 7, 8),
+                ("Installation > macOS", 9, 10),
+                ("Usage", 11, 12),
+            ]
+        );
+        assert!(chunks.iter().all(|c| c.node_type == NodeType::Document));
+        assert_eq!(chunks[0].content, "Intro text.\n");
+    }
+
+    #[test]
+    fn markdown_headings_inside_fences_are_ignored() {
+        let md = "# Setup\n```sh\n# not a heading\n```\n\
+                  ~~~~\n## nor this\n```\n~~~~\n#hashtag\n## Next\n";
+        let names: Vec<String> = chunk_markdown(md).into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["Setup", "Setup > Next"]);
+    }
+
     #[test]
     fn markdown_empty_returns_empty() {
         let chunks = chunk_markdown("");
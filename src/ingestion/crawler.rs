@@ -1,12 +1,21 @@
-use anyhow::Result;
+use super::gitignore::{self, IgnoreFile};
+use crate::config::CrawlOptions;
+use crate::error::{HermesError, Result};
+use std::collections::HashSet;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
+/// Project-root file with extra gitignore-style patterns for Hermes only.
+const HERMESIGNORE: &str = ".hermesignore";
+
 const SUPPORTED_EXTENSIONS: &[&str] = &[
     "rs", "tsx", "ts", "jsx", "js", "md", "toml", "json", "css",
     // scripting / config
     "py", "yml", "yaml", "sh", "ps1",
     // infrastructure
     "tf",
+    // compiled languages
+    "go", "java", "kt", "c", "h", "cpp", "hpp",
 ];
 
 const IGNORED_DIRS: &[&str] = &[
@@ -22,45 +31,168 @@ const IGNORED_DIRS: &[&str] = &[
     ".vite",
 ];
 
-pub fn crawl_directory(dir: &Path) -> Result<Vec<PathBuf>> {
+/// Supported files under `dir` (including `options.extra_extensions`),
+/// skipping [`IGNORED_DIRS`], paths matched by `.gitignore` files at any
+/// level or by a `.hermesignore` in `dir`, and hidden directories unless
+/// `options.include_hidden` is set. File sizes are not checked here.
+pub fn crawl_directory(dir: &Path, options: &CrawlOptions) -> Result<Vec<PathBuf>> {
     let mut files = Vec::new();
-    crawl_recursive(dir, &mut files)?;
+    let dir_name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if dir.is_dir() && !IGNORED_DIRS.contains(&dir_name.as_str()) {
+        let mut crawl = Crawl {
+            options,
+            hermesignore: IgnoreFile::load(&dir.join(HERMESIGNORE))?,
+            visited: HashSet::new(),
+        };
+        crawl.visit(dir, &mut Vec::new(), &mut files)?;
+    }
     files.sort();
     Ok(files)
 }
 
-fn crawl_recursive(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
-    if !dir.is_dir() {
-        return Ok(());
+/// Whether crawling `root` would report `path`, or descend into it when
+/// `is_dir`, by the same rules as [`crawl_directory`]. `path` need not
+/// exist, so deleted files can be checked too.
+pub fn would_crawl(root: &Path, path: &Path, is_dir: bool, options: &CrawlOptions) -> Result<bool> {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return Ok(false);
+    };
+    if !is_dir && !is_supported_file(path, options) {
+        return Ok(false);
+    }
+    let crawl = Crawl {
+        options,
+        hermesignore: IgnoreFile::load(&root.join(HERMESIGNORE))?,
+        visited: HashSet::new(),
+    };
+    let mut gitignores: Vec<IgnoreFile> =
+        IgnoreFile::load(&root.join(".gitignore"))?.into_iter().collect();
+    let mut current = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        current.push(component);
+        if components.peek().is_none() && !is_dir {
+            return Ok(!crawl.is_ignored(&gitignores, &current, false));
+        }
+        let name = component.as_os_str().to_string_lossy();
+        let hidden = name.starts_with('.') && !options.include_hidden;
+        if hidden
+            || IGNORED_DIRS.contains(&name.as_ref())
+            || crawl.is_ignored(&gitignores, &current, true)
+        {
+            return Ok(false);
+        }
+        gitignores.extend(IgnoreFile::load(&current.join(".gitignore"))?);
     }
+    Ok(true)
+}
 
-    let dir_name = dir
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_default();
+struct Crawl<'a> {
+    options: &'a CrawlOptions,
+    hermesignore: Option<IgnoreFile>,
+    /// Canonical paths of the directories entered so far, so a symlink back
+    /// up the tree is not followed forever.
+    visited: HashSet<PathBuf>,
+}
 
-    if IGNORED_DIRS.contains(&dir_name.as_str()) {
-        return Ok(());
-    }
+impl Crawl<'_> {
+    /// `gitignores` holds the `.gitignore` files of `dir`'s ancestors,
+    /// outermost first.
+    fn visit(
+        &mut self,
+        dir: &Path,
+        gitignores: &mut Vec<IgnoreFile>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        if !self.visited.insert(dir.canonicalize()?) {
+            return Ok(());
+        }
+        let gitignore = IgnoreFile::load(&dir.join(".gitignore"))?;
+        let pushed = gitignore.is_some();
+        gitignores.extend(gitignore);
 
-    for entry in std::fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
 
-        if path.is_dir() {
-            crawl_recursive(&path, files)?;
-        } else if is_supported_file(&path) {
-            files.push(path);
+            if path.is_dir() {
+                let hidden = name.starts_with('.') && !self.options.include_hidden;
+                if !hidden
+                    && !IGNORED_DIRS.contains(&name.as_str())
+                    && !self.is_ignored(gitignores, &path, true)
+                {
+                    self.visit(&path, gitignores, files)?;
+                }
+            } else if is_supported_file(&path, self.options)
+                && !self.is_ignored(gitignores, &path, false)
+            {
+                files.push(path);
+            }
         }
+
+        if pushed {
+            gitignores.pop();
+        }
+        Ok(())
     }
 
-    Ok(())
+    /// `.hermesignore` overrides every `.gitignore`.
+    fn is_ignored(&self, gitignores: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+        self.hermesignore
+            .as_ref()
+            .and_then(|file| file.decide(path, is_dir))
+            .unwrap_or_else(|| gitignore::is_ignored(gitignores, path, is_dir))
+    }
+}
+
+/// Whether `path` looks binary: a NUL byte within its first 8 KB.
+pub fn is_binary_file(path: &Path) -> Result<bool> {
+    let mut head = Vec::with_capacity(8192);
+    std::fs::File::open(path)?.take(8192).read_to_end(&mut head)?;
+    Ok(head.contains(&0))
 }
 
-fn is_supported_file(path: &Path) -> bool {
+/// Maps `path`, absolute or relative to `project_root`, to the form the
+/// crawler reports it in: `project_root` joined with the part below it.
+/// A path that no longer exists resolves through its parent directory.
+pub fn resolve_project_path(project_root: &Path, path: &Path) -> Result<PathBuf> {
+    let root = project_root.canonicalize().map_err(|e| {
+        let root = project_root.display();
+        HermesError::NotFound(format!("project root not found: {root}: {e}"))
+    })?;
+    let joined = root.join(path);
+    let resolved = match joined.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(_) => {
+            let not_found = || HermesError::NotFound(format!("path not found: {}", path.display()));
+            let (Some(parent), Some(name)) = (joined.parent(), joined.file_name()) else {
+                return Err(not_found());
+            };
+            parent.canonicalize().map_err(|_| not_found())?.join(name)
+        }
+    };
+    let Ok(relative) = resolved.strip_prefix(&root) else {
+        let message = format!("{} is outside the project root", path.display());
+        return Err(HermesError::InvalidInput(message));
+    };
+    Ok(if relative.as_os_str().is_empty() {
+        project_root.to_path_buf()
+    } else {
+        project_root.join(relative)
+    })
+}
+
+pub fn is_supported_file(path: &Path, options: &CrawlOptions) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map(|ext| SUPPORTED_EXTENSIONS.contains(&ext))
+        .map(|ext| {
+            SUPPORTED_EXTENSIONS.contains(&ext)
+                || options.extra_extensions.iter().any(|extra| extra.eq_ignore_ascii_case(ext))
+        })
         .unwrap_or(false)
 }
 
@@ -69,13 +201,30 @@ mod tests {
     use super::*;
     use std::fs;
 
+    #[test]
+    fn project_paths_resolve_to_crawled_form() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let root = dir.path();
+
+        let crawled = crawl_directory(root, &CrawlOptions::default()).unwrap();
+        let resolve = |p: &str| resolve_project_path(root, Path::new(p)).unwrap();
+        assert_eq!(resolve("src/lib.rs"), crawled[0]);
+        assert_eq!(resolve(&crawled[0].to_string_lossy()), crawled[0]);
+        assert_eq!(resolve("src/../src/gone.rs"), root.join("src/gone.rs"));
+        assert_eq!(resolve("."), root);
+        assert!(resolve_project_path(root, Path::new("/")).is_err());
+        assert!(resolve_project_path(root, Path::new("missing/dir/a.rs")).is_err());
+    }
+
     #[test]
     fn crawl_finds_rust_files() {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
         fs::write(dir.path().join("readme.txt"), "not indexed").unwrap();
 
-        let files = crawl_directory(dir.path()).unwrap();
+        let files = crawl_directory(dir.path(), &CrawlOptions::default()).unwrap();
         assert_eq!(files.len(), 1);
         assert!(files[0].to_string_lossy().contains("main.rs"));
     }
@@ -88,24 +237,148 @@ mod tests {
         fs::write(nm.join("lib.js"), "module.exports = {}").unwrap();
         fs::write(dir.path().join("app.ts"), "const x = 1;").unwrap();
 
-        let files = crawl_directory(dir.path()).unwrap();
+        let files = crawl_directory(dir.path(), &CrawlOptions::default()).unwrap();
         assert_eq!(files.len(), 1);
         assert!(files[0].to_string_lossy().contains("app.ts"));
     }
 
+    fn crawled(dir: &Path, options: &CrawlOptions) -> Vec<String> {
+        crawl_directory(dir, options)
+            .unwrap()
+            .iter()
+            .map(|p| p.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn crawl_honors_nested_gitignores() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::create_dir_all(root.join("web/out")).unwrap();
+        fs::write(root.join(".gitignore"), "*.rs\n!keep.rs\ngen/\n").unwrap();
+        fs::write(root.join("web/.gitignore"), "/out\n!*.rs\n").unwrap();
+        for file in ["src/a.rs", "src/keep.rs", "src/gen/x.ts", "src/main.ts", "web/b.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        fs::write(root.join("web/out/bundle.js"), "").unwrap();
+        // A file named like a directory-only pattern is kept.
+        fs::write(root.join("web/gen"), "").unwrap();
+
+        assert_eq!(
+            crawled(root, &CrawlOptions::default()),
+            ["src/keep.rs", "src/main.ts", "web/b.rs"]
+        );
+    }
+
+    #[test]
+    fn crawl_applies_hermesignore_over_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("fixtures")).unwrap();
+        fs::write(root.join(".gitignore"), "!fixtures/\n").unwrap();
+        fs::write(root.join(".hermesignore"), "fixtures/\n*.md\n").unwrap();
+        fs::write(root.join("fixtures/a.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "").unwrap();
+        fs::write(root.join("lib.rs"), "").unwrap();
+
+        assert_eq!(crawled(root, &CrawlOptions::default()), ["lib.rs"]);
+    }
+
+    #[test]
+    fn crawl_skips_hidden_directories_unless_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join(".github/workflows")).unwrap();
+        fs::write(root.join(".github/workflows/ci.yml"), "").unwrap();
+        fs::write(root.join("main.rs"), "").unwrap();
+
+        assert_eq!(crawled(root, &CrawlOptions::default()), ["main.rs"]);
+        let options = CrawlOptions { include_hidden: true, ..Default::default() };
+        assert_eq!(crawled(root, &options), [".github/workflows/ci.yml", "main.rs"]);
+    }
+
+    #[test]
+    fn would_crawl_matches_the_crawl_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/.gitignore"), "gen/\n*.ts\n").unwrap();
+        let options = CrawlOptions::default();
+        let check = |path: &str, is_dir| would_crawl(root, &root.join(path), is_dir, &options);
+
+        assert!(check("src/lib.rs", false).unwrap());
+        assert!(check("src/deleted/old.rs", false).unwrap());
+        assert!(check("src/new_dir", true).unwrap());
+        assert!(!check("notes.txt", false).unwrap());
+        assert!(!check("target/debug/build.rs", false).unwrap());
+        assert!(!check(".github/ci.yml", false).unwrap());
+        assert!(!check("src/main.ts", false).unwrap());
+        assert!(!check("src/gen/a.rs", false).unwrap());
+        assert!(!would_crawl(root, Path::new("/elsewhere/a.rs"), false, &options).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn crawl_survives_symlink_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("src/nested/back")).unwrap();
+        std::os::unix::fs::symlink(root, root.join("src/root")).unwrap();
+
+        assert_eq!(crawled(root, &CrawlOptions::default()), ["src/nested/lib.rs"]);
+    }
+
+    #[test]
+    fn binary_files_are_detected_by_nul_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("blob.json");
+        let mut bytes = b"{\"a\": 1}".to_vec();
+        bytes.extend([0, 159, 146, 150]);
+        fs::write(&binary, bytes).unwrap();
+        let text = dir.path().join("latin1.rs");
+        fs::write(&text, b"// caf\xe9\nfn main() {}\n").unwrap();
+
+        assert!(is_binary_file(&binary).unwrap());
+        assert!(!is_binary_file(&text).unwrap());
+    }
+
+    #[test]
+    fn crawl_includes_extra_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("api.proto"), "syntax = \"proto3\";").unwrap();
+        fs::write(dir.path().join("schema.SQL"), "CREATE TABLE t (id INT);").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        assert!(crawled(dir.path(), &CrawlOptions::default()).is_empty());
+        let options = CrawlOptions {
+            extra_extensions: vec!["proto".to_string(), "sql".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(crawled(dir.path(), &options), ["api.proto", "schema.SQL"]);
+    }
+
     #[test]
     fn supported_extensions_check() {
-        assert!(is_supported_file(Path::new("foo.rs")));
-        assert!(is_supported_file(Path::new("bar.tsx")));
-        assert!(is_supported_file(Path::new("doc.md")));
+        let defaults = CrawlOptions::default();
+        assert!(is_supported_file(Path::new("foo.rs"), &defaults));
+        assert!(is_supported_file(Path::new("bar.tsx"), &defaults));
+        assert!(is_supported_file(Path::new("doc.md"), &defaults));
         // new extensions
-        assert!(is_supported_file(Path::new("script.py")));
-        assert!(is_supported_file(Path::new("azure-pipelines-ci.yml")));
-        assert!(is_supported_file(Path::new("docker-compose.yaml")));
-        assert!(is_supported_file(Path::new("deploy.sh")));
-        assert!(is_supported_file(Path::new("check-lint.ps1")));
-        assert!(is_supported_file(Path::new("main.tf")));
-        assert!(!is_supported_file(Path::new("image.png")));
-        assert!(!is_supported_file(Path::new("data.csv")));
+        assert!(is_supported_file(Path::new("script.py"), &defaults));
+        assert!(is_supported_file(Path::new("azure-pipelines-ci.yml"), &defaults));
+        assert!(is_supported_file(Path::new("docker-compose.yaml"), &defaults));
+        assert!(is_supported_file(Path::new("deploy.sh"), &defaults));
+        assert!(is_supported_file(Path::new("check-lint.ps1"), &defaults));
+        assert!(is_supported_file(Path::new("main.tf"), &defaults));
+        assert!(is_supported_file(Path::new("server.go"), &defaults));
+        assert!(is_supported_file(Path::new("Service.java"), &defaults));
+        assert!(is_supported_file(Path::new("Service.kt"), &defaults));
+        assert!(is_supported_file(Path::new("parser.c"), &defaults));
+        assert!(is_supported_file(Path::new("parser.hpp"), &defaults));
+        assert!(!is_supported_file(Path::new("image.png"), &defaults));
+        assert!(!is_supported_file(Path::new("data.csv"), &defaults));
     }
 }
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use crate::error::Result;
 use regex::Regex;
 use rusqlite::Connection;
 use std::path::Path;
@@ -0,0 +1,179 @@
+use crate::error::Result;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// The patterns of one `.gitignore` (or `.hermesignore`) file, matched
+/// against paths relative to the directory holding it.
+pub struct IgnoreFile {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+struct IgnoreRule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl IgnoreFile {
+    /// Reads `path`, or returns `None` when it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(Some(Self::parse(base, &content)))
+    }
+
+    /// Blank lines, `#` comments and patterns that fail to compile are skipped.
+    pub fn parse(base: PathBuf, content: &str) -> Self {
+        let rules = content.lines().filter_map(IgnoreRule::parse).collect();
+        Self { base, rules }
+    }
+
+    /// `Some(true)` when the last pattern matching `path` ignores it,
+    /// `Some(false)` when it is a `!` re-include, `None` when none match.
+    pub fn decide(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Whether `path` is ignored by `files`, outermost first: as in git, a
+/// deeper file overrides an outer one, and within a file the last matching
+/// pattern wins.
+pub fn is_ignored(files: &[IgnoreFile], path: &Path, is_dir: bool) -> bool {
+    files
+        .iter()
+        .rev()
+        .find_map(|file| file.decide(path, is_dir))
+        .unwrap_or(false)
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        // A slash anywhere but the end anchors the pattern to the base
+        // directory; otherwise it matches at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+        let regex = Regex::new(&format!("{prefix}{}$", glob_to_regex(pattern))).ok()?;
+        Some(Self { regex, negated, dir_only })
+    }
+}
+
+/// `*` and `?` stay within one path segment, `**` spans segments and
+/// `[...]` classes are kept.
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_segment_start = i == 0 || chars[i - 1] == '/';
+                if at_segment_start && chars.get(i + 2) == Some(&'/') {
+                    out.push_str("(?:.*/)?");
+                    i += 2;
+                } else {
+                    out.push_str(".*");
+                    i += 1;
+                }
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{rest}"),
+                        None => class,
+                    };
+                    out.push('[');
+                    out.push_str(&class.replace('\\', "\\\\"));
+                    out.push(']');
+                    i += len + 1;
+                }
+                None => out.push_str("\\["),
+            },
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(patterns: &str, path: &str, is_dir: bool) -> bool {
+        let files = [IgnoreFile::parse(PathBuf::from("root"), patterns)];
+        is_ignored(&files, &Path::new("root").join(path), is_dir)
+    }
+
+    #[test]
+    fn unanchored_patterns_match_at_any_depth() {
+        assert!(ignored("*.gen.rs", "src/deep/api.gen.rs", false));
+        assert!(!ignored("*.gen.rs", "src/api.rs", false));
+        assert!(ignored("build", "crates/app/build", true));
+    }
+
+    #[test]
+    fn slashes_anchor_patterns_to_the_ignore_file() {
+        assert!(ignored("/out", "out", true));
+        assert!(!ignored("/out", "src/out", true));
+        assert!(ignored("docs/*.md", "docs/a.md", false));
+        assert!(!ignored("docs/*.md", "docs/sub/a.md", false));
+        assert!(ignored("docs/**/*.md", "docs/sub/deep/a.md", false));
+        assert!(ignored("**/fixtures", "a/b/fixtures", true));
+        assert!(ignored("vendor/**", "vendor/x/y.rs", false));
+    }
+
+    #[test]
+    fn negation_and_character_classes() {
+        let patterns = "*.rs\n!keep.rs\n# comment\nlog[0-9].txt\n";
+        assert!(ignored(patterns, "src/a.rs", false));
+        assert!(!ignored(patterns, "src/keep.rs", false));
+        assert!(ignored(patterns, "log3.txt", false));
+        assert!(!ignored(patterns, "logx.txt", false));
+    }
+
+    #[test]
+    fn directory_only_patterns_skip_files() {
+        assert!(ignored("generated/", "src/generated", true));
+        assert!(!ignored("generated/", "src/generated", false));
+    }
+
+    #[test]
+    fn deeper_files_override_outer_ones() {
+        let files = [
+            IgnoreFile::parse(PathBuf::from("root"), "*.json\n"),
+            IgnoreFile::parse(PathBuf::from("root/web"), "!package.json\n"),
+        ];
+        assert!(is_ignored(&files, Path::new("root/data.json"), false));
+        assert!(!is_ignored(&files, Path::new("root/web/package.json"), false));
+        assert!(is_ignored(&files, Path::new("root/web/tsconfig.json"), false));
+    }
+}
@@ -1,8 +1,9 @@
-use anyhow::Result;
-use rusqlite::{params, Connection};
+use crate::error::Result;
+use rusqlite::{params, Connection, OptionalExtension};
 use sha2::{Digest, Sha256};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
 
 pub struct HashTracker<'a> {
     db: &'a Arc<Mutex<Connection>>,
@@ -14,42 +15,66 @@ impl<'a> HashTracker<'a> {
         Self { db, project_id }
     }
 
+    /// Trusts a matching modification time and size without reading the
+    /// file; otherwise compares content hashes, and when only the metadata
+    /// changed (a `touch`) records the new metadata so the next check is fast.
     pub fn is_unchanged(&self, file_path: &str) -> Result<bool> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        let stored_hash: Option<String> = conn
-            .query_row(
-                "SELECT content_hash FROM file_hashes WHERE file_path = ?1 AND project_id = ?2",
+        let stored: Option<(String, Option<i64>, Option<i64>)> = {
+            let conn = self.db.lock()?;
+            conn.query_row(
+                "SELECT content_hash, mtime_ns, size FROM file_hashes
+                 WHERE file_path = ?1 AND project_id = ?2",
                 params![file_path, self.project_id],
-                |row| row.get(0),
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
             )
-            .ok();
-
-        let Some(stored) = stored_hash else {
+            .optional()?
+        };
+        let Some((stored_hash, stored_mtime, stored_size)) = stored else {
             return Ok(false);
         };
 
-        let content = std::fs::read_to_string(file_path)?;
-        let current_hash = compute_hash(&content);
-        Ok(stored == current_hash)
+        let path = Path::new(file_path);
+        let (mtime, size) = file_metadata(path)?;
+        if stored_mtime == Some(mtime) && stored_size == Some(size) {
+            return Ok(true);
+        }
+        if compute_hash(&read_lossy(path)?) != stored_hash {
+            return Ok(false);
+        }
+        let conn = self.db.lock()?;
+        conn.execute(
+            "UPDATE file_hashes SET mtime_ns = ?3, size = ?4
+             WHERE file_path = ?1 AND project_id = ?2",
+            params![file_path, self.project_id, mtime, size],
+        )?;
+        Ok(true)
+    }
+
+    /// The content hash recorded when `file_path` was last indexed.
+    pub fn stored_hash(&self, file_path: &str) -> Result<Option<String>> {
+        let conn = self.db.lock()?;
+        let hash = conn
+            .query_row(
+                "SELECT content_hash FROM file_hashes WHERE file_path = ?1 AND project_id = ?2",
+                params![file_path, self.project_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(hash)
     }
 
     pub fn update_hash(&self, file_path: &str, actual_path: &Path) -> Result<()> {
-        let content = std::fs::read_to_string(actual_path)?;
-        let hash = compute_hash(&content);
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "INSERT OR REPLACE INTO file_hashes (file_path, project_id, content_hash, indexed_at)
-             VALUES (?1, ?2, ?3, datetime('now'))",
-            params![file_path, self.project_id, hash],
-        )?;
-        Ok(())
+        let metadata = file_metadata(actual_path)?;
+        let hash = compute_hash(&read_lossy(actual_path)?);
+        let conn = self.db.lock()?;
+        write_file_hash(&conn, self.project_id, file_path, &hash, metadata)
     }
 
     pub fn is_chunk_unchanged(&self, chunk_key: &str, current_hash: &str) -> Result<bool> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let stored: Option<String> = conn
             .query_row(
-                "SELECT content_hash FROM file_hashes WHERE file_path = ?1 AND project_id = ?2",
+                "SELECT content_hash FROM chunk_hashes WHERE chunk_key = ?1 AND project_id = ?2",
                 params![chunk_key, self.project_id],
                 |row| row.get(0),
             )
@@ -58,14 +83,97 @@ impl<'a> HashTracker<'a> {
     }
 
     pub fn update_chunk_hash(&self, chunk_key: &str, hash: &str) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        conn.execute(
-            "INSERT OR REPLACE INTO file_hashes (file_path, project_id, content_hash, indexed_at)
-             VALUES (?1, ?2, ?3, datetime('now'))",
-            params![chunk_key, self.project_id, hash],
-        )?;
-        Ok(())
+        let conn = self.db.lock()?;
+        write_chunk_hash(&conn, self.project_id, chunk_key, hash)
     }
+
+    pub fn remove_chunk_hash(&self, chunk_key: &str) -> Result<()> {
+        let conn = self.db.lock()?;
+        delete_chunk_hash(&conn, self.project_id, chunk_key)
+    }
+}
+
+/// Whether `chunk_key` was last indexed with `hash` and its node `node_id`
+/// still exists. Takes the connection so ingestion workers can read through
+/// their own.
+pub fn chunk_indexed(
+    conn: &Connection,
+    project_id: &str,
+    chunk_key: &str,
+    hash: &str,
+    node_id: &str,
+) -> Result<bool> {
+    let indexed = conn.query_row(
+        "SELECT EXISTS (
+             SELECT 1 FROM chunk_hashes c JOIN nodes n ON n.id = ?4 AND n.project_id = c.project_id
+             WHERE c.project_id = ?1 AND c.chunk_key = ?2 AND c.content_hash = ?3
+         )",
+        params![project_id, chunk_key, hash, node_id],
+        |row| row.get(0),
+    )?;
+    Ok(indexed)
+}
+
+// The writes below take the connection so they can join the transaction
+// `ingest_file` writes a file's nodes in.
+
+/// `metadata` is the file's [`file_metadata`], read before its content so a
+/// write in between shows up as a changed mtime on the next run.
+pub fn write_file_hash(
+    conn: &Connection,
+    project_id: &str,
+    file_path: &str,
+    hash: &str,
+    (mtime, size): (i64, i64),
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO file_hashes
+             (file_path, project_id, content_hash, mtime_ns, size, indexed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+        params![file_path, project_id, hash, mtime, size],
+    )?;
+    Ok(())
+}
+
+pub fn write_chunk_hash(
+    conn: &Connection,
+    project_id: &str,
+    chunk_key: &str,
+    hash: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO chunk_hashes (chunk_key, project_id, content_hash, indexed_at)
+         VALUES (?1, ?2, ?3, datetime('now'))",
+        params![chunk_key, project_id, hash],
+    )?;
+    Ok(())
+}
+
+pub fn delete_chunk_hash(conn: &Connection, project_id: &str, chunk_key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM chunk_hashes WHERE chunk_key = ?1 AND project_id = ?2",
+        params![chunk_key, project_id],
+    )?;
+    Ok(())
+}
+
+/// Modification time in nanoseconds since the epoch, and size in bytes.
+pub fn file_metadata(path: &Path) -> Result<(i64, i64)> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok((mtime.as_nanos() as i64, metadata.len() as i64))
+}
+
+/// File content decoded the way `ingest_file` decodes it, so a few invalid
+/// UTF-8 sequences do not make the file unreadable.
+fn read_lossy(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The hash `ingest_file` records for the file at `path`.
+pub fn file_hash(path: &Path) -> Result<String> {
+    Ok(compute_hash(&read_lossy(path)?))
 }
 
 pub fn compute_hash(content: &str) -> String {
@@ -130,5 +238,72 @@ mod tests {
         tracker.update_chunk_hash(key, &old_hash).unwrap();
         assert!(!tracker.is_chunk_unchanged(key, &new_hash).unwrap());
     }
+
+    fn stored_mtime(engine: &crate::HermesEngine, path: &str) -> Option<i64> {
+        let conn = engine.db().lock().unwrap();
+        conn.query_row("SELECT mtime_ns FROM file_hashes WHERE file_path = ?1", [path], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn touched_but_identical_file_is_unchanged_via_the_hash() {
+        use crate::HermesEngine;
+        use std::time::{Duration, SystemTime};
+        let engine = HermesEngine::in_memory("touch-test").unwrap();
+        let tracker = HashTracker::new(engine.db(), "touch-test");
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        let path = file.to_string_lossy().to_string();
+        std::fs::write(&file, "fn main() {}").unwrap();
+        tracker.update_hash(&path, &file).unwrap();
+        assert!(tracker.is_unchanged(&path).unwrap());
+
+        let touched = SystemTime::now() + Duration::from_secs(60);
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(touched).unwrap();
+        let before = stored_mtime(&engine, &path);
+        assert!(tracker.is_unchanged(&path).unwrap());
+        assert_ne!(stored_mtime(&engine, &path), before);
+
+        // Same size and a later mtime, but different content.
+        std::fs::write(&file, "fn mian() {}").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(touched + Duration::from_secs(60))
+            .unwrap();
+        assert!(!tracker.is_unchanged(&path).unwrap());
+    }
+
+    #[test]
+    fn projects_sharing_a_db_keep_their_own_hashes() {
+        use crate::HermesEngine;
+        let engine = HermesEngine::in_memory("shared-db").unwrap();
+        let alpha = HashTracker::new(engine.db(), "alpha");
+        let beta = HashTracker::new(engine.db(), "beta");
+        let key = "src/lib.rs::main";
+        alpha.update_chunk_hash(key, "hash-a").unwrap();
+        beta.update_chunk_hash(key, "hash-b").unwrap();
+        assert!(alpha.is_chunk_unchanged(key, "hash-a").unwrap());
+        assert!(beta.is_chunk_unchanged(key, "hash-b").unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let (file_a, file_b) = (dir.path().join("a.rs"), dir.path().join("b.rs"));
+        std::fs::write(&file_a, "fn a() {}").unwrap();
+        std::fs::write(&file_b, "fn b() {}").unwrap();
+        alpha.update_hash("src/lib.rs", &file_a).unwrap();
+        beta.update_hash("src/lib.rs", &file_b).unwrap();
+        let conn = engine.db().lock().unwrap();
+        let rows: i64 = conn
+            .query_row(
+                "SELECT COUNT(DISTINCT content_hash) FROM file_hashes WHERE file_path = 'src/lib.rs'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
 }
 
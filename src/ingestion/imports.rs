@@ -0,0 +1,300 @@
+use crate::error::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+const SCRIPT_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx"];
+
+/// Finds the `use` and `import` statements of a file and resolves them to
+/// other indexed files. Imports of std, external crates or packages resolve
+/// to nothing.
+pub struct ImportResolver {
+    from_clause: Regex,
+    bare_import: Regex,
+}
+
+impl ImportResolver {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            from_clause: Regex::new(
+                r#"(?m)^\s*(?:import|export)\b[^;'"]*?\bfrom\s*['"]([^'"]+)['"]"#,
+            )?,
+            bare_import: Regex::new(r#"(?m)^\s*import\s*['"]([^'"]+)['"]"#)?,
+        })
+    }
+
+    /// Paths, as keyed in `files`, of the files `path` imports.
+    pub fn resolve(
+        &self,
+        path: &Path,
+        content: &str,
+        files: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let mut targets: Vec<String> = if ext == "rs" {
+            rust_use_paths(content)
+                .into_iter()
+                .filter_map(|segments| resolve_rust_path(path, &segments, files))
+                .collect()
+        } else if SCRIPT_EXTENSIONS.contains(&ext) {
+            self.from_clause
+                .captures_iter(content)
+                .chain(self.bare_import.captures_iter(content))
+                .filter_map(|caps| resolve_script_path(path, &caps[1], files))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let own = path.to_string_lossy();
+        targets.retain(|target| *target != own);
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+}
+
+/// Whether `content` may import the file at `target`, judged by whether it
+/// mentions a name an import of `target` would have to contain. Cheaper than
+/// resolving, and never false for a file that does import `target`.
+pub fn may_import(content: &str, target: &Path) -> bool {
+    let stem = target.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let dir = target
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|d| d.to_str())
+        .unwrap_or_default();
+    let ext = target.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    if ext == "rs" {
+        // `super::` names the parent module without naming its file.
+        let name = match stem {
+            "lib" | "main" => "crate",
+            "mod" => dir,
+            _ => stem,
+        };
+        content.contains(name) || content.contains("super")
+    } else if stem == "index" {
+        // `from '.'` or `from '..'` names a directory by position.
+        content.contains(dir) || content.contains("'.") || content.contains("\".")
+    } else {
+        content.contains(stem)
+    }
+}
+
+/// Every path named by a `use` item, with groups expanded:
+/// `use crate::a::{b, c::D}` gives `crate::a::b` and `crate::a::c::D`.
+fn rust_use_paths(content: &str) -> Vec<Vec<String>> {
+    let Ok(file) = syn::parse_file(content) else {
+        return Vec::new();
+    };
+    let mut paths = Vec::new();
+    collect_uses(&file.items, &mut paths);
+    proc_macro2::extra::invalidate_current_thread_spans();
+    paths
+}
+
+fn collect_uses(items: &[syn::Item], paths: &mut Vec<Vec<String>>) {
+    for item in items {
+        match item {
+            syn::Item::Use(u) => walk_use_tree(&u.tree, &mut Vec::new(), paths),
+            syn::Item::Mod(m) => {
+                if let Some((_, items)) = &m.content {
+                    collect_uses(items, paths);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn walk_use_tree(tree: &syn::UseTree, prefix: &mut Vec<String>, paths: &mut Vec<Vec<String>>) {
+    match tree {
+        syn::UseTree::Path(p) => {
+            prefix.push(p.ident.to_string());
+            walk_use_tree(&p.tree, prefix, paths);
+            prefix.pop();
+        }
+        syn::UseTree::Name(n) => paths.push(leaf_path(prefix, &n.ident)),
+        syn::UseTree::Rename(r) => paths.push(leaf_path(prefix, &r.ident)),
+        syn::UseTree::Glob(_) => paths.push(prefix.clone()),
+        syn::UseTree::Group(g) => {
+            for tree in &g.items {
+                walk_use_tree(tree, prefix, paths);
+            }
+        }
+    }
+}
+
+/// `use crate::a::{self}` names the module `a` itself.
+fn leaf_path(prefix: &[String], ident: &syn::Ident) -> Vec<String> {
+    let mut path = prefix.to_vec();
+    if ident != "self" {
+        path.push(ident.to_string());
+    }
+    path
+}
+
+/// Maps a `crate::`, `self::` or `super::` path to the file of the deepest
+/// module it names. The crate root is the nearest `src` directory above
+/// `importer`.
+fn resolve_rust_path(
+    importer: &Path,
+    segments: &[String],
+    files: &HashMap<String, String>,
+) -> Option<String> {
+    let root = importer
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.file_name().is_some_and(|name| name == "src"))?;
+    let (first, rest) = segments.split_first()?;
+    let mut module: Vec<String> = match first.as_str() {
+        "crate" => Vec::new(),
+        "self" | "super" => {
+            let relative = importer.strip_prefix(root).ok()?.with_extension("");
+            let mut module: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            if matches!(module.last().map(String::as_str), Some("mod" | "lib" | "main")) {
+                module.pop();
+            }
+            module
+        }
+        _ => return None,
+    };
+    let mut rest = rest;
+    if first == "super" {
+        module.pop()?;
+    }
+    while let Some(("super", tail)) = rest.split_first().map(|(s, t)| (s.as_str(), t)) {
+        module.pop()?;
+        rest = tail;
+    }
+    module.extend(rest.iter().cloned());
+
+    // `crate::a::b::Item` may be an item of `a/b.rs` or of `a.rs`.
+    (0..=module.len()).rev().find_map(|depth| {
+        let dir = module[..depth].iter().fold(root.to_path_buf(), |dir, s| dir.join(s));
+        let candidates = match depth {
+            0 => vec![root.join("lib.rs"), root.join("main.rs")],
+            _ => vec![dir.with_extension("rs"), dir.join("mod.rs")],
+        };
+        candidates.into_iter().find_map(|candidate| known_file(&candidate, files))
+    })
+}
+
+/// Resolves a relative specifier such as `./api` or `../lib/util.js`; package
+/// imports are skipped.
+fn resolve_script_path(
+    importer: &Path,
+    spec: &str,
+    files: &HashMap<String, String>,
+) -> Option<String> {
+    if !spec.starts_with('.') {
+        return None;
+    }
+    let base = normalize(&importer.parent()?.join(spec));
+    let mut candidates = vec![base.clone()];
+    for ext in SCRIPT_EXTENSIONS {
+        candidates.push(PathBuf::from(format!("{}.{ext}", base.display())));
+    }
+    for ext in SCRIPT_EXTENSIONS {
+        candidates.push(base.join(format!("index.{ext}")));
+    }
+    candidates.iter().find_map(|candidate| known_file(candidate, files))
+}
+
+fn known_file(candidate: &Path, files: &HashMap<String, String>) -> Option<String> {
+    let key = candidate.to_string_lossy();
+    files.contains_key(key.as_ref()).then(|| key.into_owned())
+}
+
+/// Resolves `.` and `..` lexically, keeping the path in the same form the
+/// crawler reported it.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> HashMap<String, String> {
+        paths.iter().map(|p| (p.to_string(), format!("id-{p}"))).collect()
+    }
+
+    #[test]
+    fn rust_use_paths_expand_groups() {
+        let code = "use std::fmt;\nuse crate::graph::{self, Node, edges::Edge};\nuse super::*;\n";
+        let paths: Vec<String> = rust_use_paths(code).iter().map(|p| p.join("::")).collect();
+        assert_eq!(
+            paths,
+            ["std::fmt", "crate::graph", "crate::graph::Node", "crate::graph::edges::Edge", "super"]
+        );
+    }
+
+    #[test]
+    fn resolves_crate_self_and_super_paths() {
+        let known = files(&[
+            "proj/src/lib.rs",
+            "proj/src/graph.rs",
+            "proj/src/search/mod.rs",
+            "proj/src/search/literal.rs",
+            "proj/src/search/vector.rs",
+        ]);
+        let resolver = ImportResolver::new().unwrap();
+        let code = "use std::sync::Arc;\nuse serde::Serialize;\n\
+                    use crate::graph::{KnowledgeGraph, Node};\n\
+                    use super::vector::{self, HashedEmbedder};\nuse self::inner::Thing;\n\
+                    use crate::search::SearchEngine;\n";
+        let targets = resolver.resolve(Path::new("proj/src/search/literal.rs"), code, &known);
+        assert_eq!(
+            targets,
+            ["proj/src/graph.rs", "proj/src/search/mod.rs", "proj/src/search/vector.rs"]
+        );
+
+        let from_mod = resolver.resolve(
+            Path::new("proj/src/search/mod.rs"),
+            "use self::literal::literal_search;\nuse super::graph::Node;\n",
+            &known,
+        );
+        assert_eq!(from_mod, ["proj/src/graph.rs", "proj/src/search/literal.rs"]);
+    }
+
+    #[test]
+    fn may_import_requires_the_name_an_import_would_use() {
+        assert!(may_import("use crate::graph::Node;", Path::new("src/graph.rs")));
+        assert!(may_import("use super::Thing;", Path::new("src/search/mod.rs")));
+        assert!(may_import("use crate::Thing;", Path::new("src/lib.rs")));
+        assert!(may_import("import x from '.';", Path::new("web/components/index.ts")));
+        assert!(!may_import("use std::fmt;", Path::new("src/graph.rs")));
+        assert!(!may_import("import { a } from './api';", Path::new("web/util.ts")));
+    }
+
+    #[test]
+    fn resolves_relative_script_imports_only() {
+        let known = files(&[
+            "web/src/app.tsx",
+            "web/src/api.ts",
+            "web/src/components/index.ts",
+            "web/lib/util.js",
+        ]);
+        let resolver = ImportResolver::new().unwrap();
+        let code = "import React from 'react';\nimport { get } from './api';\n\
+                    import {\n  Button,\n} from \"./components\";\n\
+                    import '../lib/util.js';\nexport { x } from './missing';\n";
+        let targets = resolver.resolve(Path::new("web/src/app.tsx"), code, &known);
+        assert_eq!(targets, ["web/lib/util.js", "web/src/api.ts", "web/src/components/index.ts"]);
+    }
+}
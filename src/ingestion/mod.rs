@@ -1,19 +1,64 @@
+pub mod calls;
 pub mod chunker;
 pub mod crawler;
 pub mod env_scanner;
+pub mod gitignore;
 pub mod hash_tracker;
+pub mod imports;
+pub mod watcher;
 
-use crate::graph::{EdgeType, KnowledgeGraph, NodeType};
-use anyhow::Result;
+use crate::config::{CrawlOptions, TokenizerOptions};
+use crate::embedding::EmbeddingProvider;
+use crate::error::{HermesError, Result};
+use crate::graph::{EdgeType, KnowledgeGraph, Node, NodeType, RepairReport};
+use crate::search::estimate_tokens;
+use crate::search::vector::{self, HashedEmbedder, HASHED_MODEL_PREFIX};
 use rayon::prelude::*;
-use std::collections::HashSet;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use tracing::info;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
 
 pub struct IngestionPipeline<'a> {
     graph: &'a KnowledgeGraph,
     hash_tracker: hash_tracker::HashTracker<'a>,
     env_scanner: env_scanner::EnvScanner,
+    import_resolver: imports::ImportResolver,
+    embedder: Arc<dyn EmbeddingProvider>,
+    hashed: HashedEmbedder,
+    crawl_options: CrawlOptions,
+    read_db: Option<PathBuf>,
+    progress: Option<ProgressSink>,
+    repair: bool,
+}
+
+/// Receives [`IngestionProgress`] events; called from the parallel ingestion
+/// workers, so it must be thread-safe.
+pub type ProgressSink = Arc<dyn Fn(IngestionProgress) + Send + Sync>;
+
+/// The stages of an ingestion run, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IngestionPhase {
+    Crawl,
+    HashCheck,
+    /// `files` changed files are about to be ingested.
+    Ingest { files: usize },
+    /// Removing deleted files and linking imports and calls.
+    Cleanup,
+}
+
+/// Events emitted while ingesting, for progress displays.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IngestionProgress {
+    Phase(IngestionPhase),
+    FileStarted { path: String },
+    FileIndexed { path: String, nodes: usize },
+    /// Unchanged since the last run.
+    FileSkipped { path: String },
+    FileErrored { path: String, error: String },
 }
 
 impl<'a> IngestionPipeline<'a> {
@@ -23,11 +68,122 @@ impl<'a> IngestionPipeline<'a> {
             hash_tracker: hash_tracker::HashTracker::new(graph.db(), graph.project_id()),
             env_scanner: env_scanner::EnvScanner::new()
                 .expect("env_scanner regex compilation must not fail"),
+            import_resolver: imports::ImportResolver::new()
+                .expect("import_resolver regex compilation must not fail"),
+            embedder: Arc::new(HashedEmbedder::default()),
+            hashed: HashedEmbedder::default(),
+            crawl_options: CrawlOptions::default(),
+            read_db: None,
+            progress: None,
+            repair: false,
         }
     }
 
+    /// Reports phases and per-file outcomes to `sink` as the run goes.
+    pub fn with_progress(mut self, sink: ProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// Runs [`KnowledgeGraph::repair`] after removing deleted files, so rows
+    /// left dangling by earlier runs are cleaned up too.
+    pub fn with_repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    fn emit(&self, event: IngestionProgress) {
+        if let Some(sink) = &self.progress {
+            sink(event);
+        }
+    }
+
+    /// Lets each ingestion worker read through its own read-only connection
+    /// to the database file at `path` (WAL allows them alongside the writer),
+    /// so only the per-file write transactions share the graph's connection.
+    /// Without a path (in-memory engines) every read goes through the graph.
+    pub fn with_read_connections(mut self, path: Option<&Path>) -> Self {
+        self.read_db = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// Also stores vectors from `embedder` (hashed vectors are always kept as
+    /// the fallback for the vector tier).
+    pub fn with_embedder(mut self, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        self.embedder = embedder;
+        self
+    }
+
+    /// Tokenizer for the hashed vectors; must match the one used at search time.
+    pub fn with_tokenizer(mut self, options: TokenizerOptions) -> Self {
+        self.hashed = HashedEmbedder::new(options);
+        self
+    }
+
+    /// Which directories and files `ingest_directory` and `ingest_paths` crawl.
+    pub fn with_crawl_options(mut self, options: CrawlOptions) -> Self {
+        self.crawl_options = options;
+        self
+    }
+
     pub fn ingest_directory(&self, dir_path: &Path) -> Result<IngestionReport> {
-        let files = crawler::crawl_directory(dir_path)?;
+        self.graph.ensure_writable()?;
+        let started = Instant::now();
+        self.emit(IngestionProgress::Phase(IngestionPhase::Crawl));
+        let crawled = crawler::crawl_directory(dir_path, &self.crawl_options)?;
+        self.ingest_crawled(started, crawled, StaleScope::Everything)
+    }
+
+    /// Re-indexes just `paths`, files or directories in the form the crawler
+    /// reports them (see [`crawler::resolve_project_path`]). Nodes of files
+    /// that were not crawled are only removed below the given directories,
+    /// and for given files that no longer exist.
+    pub fn ingest_paths(&self, paths: &[PathBuf]) -> Result<IngestionReport> {
+        self.graph.ensure_writable()?;
+        let started = Instant::now();
+        self.emit(IngestionProgress::Phase(IngestionPhase::Crawl));
+        let mut crawled = Vec::new();
+        let mut stale_roots = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                crawled.extend(crawler::crawl_directory(path, &self.crawl_options)?);
+                stale_roots.push(path.clone());
+            } else if !path.exists() {
+                stale_roots.push(path.clone());
+            } else if crawler::is_supported_file(path, &self.crawl_options) {
+                crawled.push(path.clone());
+            } else {
+                info!(path = %path.display(), "Skipped file with an unsupported extension");
+            }
+        }
+        crawled.sort();
+        crawled.dedup();
+        self.ingest_crawled(started, crawled, StaleScope::Under(stale_roots))
+    }
+
+    fn ingest_crawled(
+        &self,
+        started: Instant,
+        crawled: Vec<PathBuf>,
+        stale_scope: StaleScope,
+    ) -> Result<IngestionReport> {
+        let total_files = crawled.len();
+        // Oversized and binary files count as not crawled, so nodes from an
+        // earlier version are cleaned up like those of a deleted file.
+        let max_bytes = self.crawl_options.max_file_bytes;
+        let (files, too_large): (Vec<PathBuf>, Vec<PathBuf>) = crawled
+            .into_iter()
+            .partition(|p| std::fs::metadata(p).map_or(true, |m| m.len() <= max_bytes));
+        for path in &too_large {
+            info!(path = %path.display(), max_bytes, "Skipped file over the size limit");
+        }
+        let (binary, files): (Vec<PathBuf>, Vec<PathBuf>) = files
+            .into_iter()
+            .partition(|p| crawler::is_binary_file(p).unwrap_or(false));
+        for path in &binary {
+            info!(path = %path.display(), "Skipped binary file");
+        }
+        let crawl_ms = started.elapsed().as_millis() as u64;
 
         let crawled_paths: HashSet<String> = files
             .iter()
@@ -35,49 +191,121 @@ impl<'a> IngestionPipeline<'a> {
             .collect();
 
         // TRACK-040: Scan all files for env var usage/definitions → config_registry.
+        let env_scan_started = Instant::now();
         self.scan_and_populate_env_vars(&files)?;
 
         let mut report = IngestionReport {
-            total_files: files.len(),
+            total_files,
+            skipped_too_large: too_large.len(),
+            skipped_binary: binary.len(),
             ..Default::default()
         };
+        report.timings.crawl_ms = crawl_ms;
+        report.timings.env_scan_ms = env_scan_started.elapsed().as_millis() as u64;
 
+        let detection_started = Instant::now();
+        self.emit(IngestionProgress::Phase(IngestionPhase::HashCheck));
+        let indexed_paths = self.graph.get_all_file_paths()?;
         let mut to_ingest: Vec<&PathBuf> = Vec::new();
         for file_path in &files {
             let path_str = file_path.to_string_lossy().to_string();
             if self.hash_tracker.is_unchanged(&path_str)? {
                 report.skipped += 1;
+                self.emit(IngestionProgress::FileSkipped { path: path_str });
             } else {
                 to_ingest.push(file_path);
             }
         }
+        report.renamed_paths =
+            self.detect_renames(&mut to_ingest, &indexed_paths, &crawled_paths, &stale_scope)?;
+        for (from, to) in &report.renamed_paths {
+            self.rename_file(from, to)?;
+            report.skipped += 1;
+            self.emit(IngestionProgress::FileSkipped { path: to.clone() });
+        }
+        report.timings.change_detection_ms = detection_started.elapsed().as_millis() as u64;
+        // Callers of functions that re-ingesting removes must be relinked too.
+        let mut previous_functions =
+            self.function_names(to_ingest.iter().map(|p| p.to_string_lossy()))?;
 
-        let ingest_results: Vec<(String, Result<usize>)> = to_ingest
+        self.emit(IngestionProgress::Phase(IngestionPhase::Ingest { files: to_ingest.len() }));
+        let ingest_results: Vec<(String, Result<FileIngestion>)> = to_ingest
             .par_iter()
-            .map(|file_path| {
-                let path_str = file_path.to_string_lossy().to_string();
-                let result = self.ingest_file(file_path);
-                (path_str, result)
-            })
+            .map_init(
+                || self.open_reader(),
+                |reader, file_path| {
+                    let path = file_path.to_string_lossy().to_string();
+                    self.emit(IngestionProgress::FileStarted { path: path.clone() });
+                    let result = self.ingest_file_with(file_path, reader.as_ref());
+                    let event = match &result {
+                        Ok(file) => {
+                            let nodes = file.nodes_created;
+                            IngestionProgress::FileIndexed { path: path.clone(), nodes }
+                        }
+                        Err(e) => {
+                            let error = e.to_string();
+                            IngestionProgress::FileErrored { path: path.clone(), error }
+                        }
+                    };
+                    self.emit(event);
+                    (path, result)
+                },
+            )
             .collect();
 
         for (path_str, result) in ingest_results {
             match result {
-                Ok(count) => {
+                Ok(file) => {
                     report.indexed += 1;
-                    report.nodes_created += count;
-                    let p = PathBuf::from(&path_str);
-                    self.hash_tracker.update_hash(&path_str, &p)?;
+                    report.nodes_created += file.nodes_created;
+                    report.chunks_removed += file.chunks_removed;
+                    report.timings.chunk_ms += file.chunk_ms;
+                    report.timings.embed_ms += file.embed_ms;
+                    report.timings.write_ms += file.write_ms;
+                    report.changed_paths.push(path_str);
                 }
                 Err(e) => {
-                    info!(path = %path_str, error = %e, "Failed to ingest file");
+                    warn!(path = %path_str, error = %e, "Failed to ingest file");
                     report.errors += 1;
+                    if report.error_details.len() < MAX_ERROR_DETAILS {
+                        report.error_details.push(FileError::new(&path_str, &e));
+                    } else {
+                        report.error_details_truncated = true;
+                    }
                 }
             }
         }
 
-        self.cleanup_stale_nodes(&crawled_paths)?;
+        let link_started = Instant::now();
+        self.emit(IngestionProgress::Phase(IngestionPhase::Cleanup));
+        // Taken before the cleanup deletes the edges into deleted files.
+        let mut moved: Vec<String> =
+            stale_paths(&self.graph.get_all_file_paths()?, &crawled_paths, &stale_scope)
+                .into_iter()
+                .cloned()
+                .collect();
+        moved.extend(report.renamed_paths.iter().map(|(_, to)| to.clone()));
+        let importers = self.graph.file_importers(&moved)?;
+        previous_functions.extend(self.function_names(&moved)?);
+        report.deleted_paths = self.cleanup_stale_nodes(&crawled_paths, &stale_scope)?;
+        report.vectors_backfilled = self.backfill_hashed_vectors()?;
+        if self.repair {
+            report.repair = Some(self.graph.repair()?);
+        }
+        let added: Vec<&str> = report
+            .changed_paths
+            .iter()
+            .filter(|path| !indexed_paths.contains(*path))
+            .chain(report.renamed_paths.iter().map(|(_, to)| to))
+            .map(String::as_str)
+            .collect();
+        self.link_imports(&files, &report.touched_paths(), importers, &added)?;
+        if !report.touched_paths().is_empty() {
+            self.link_calls(&report.touched_paths(), previous_functions)?;
+        }
+        report.timings.link_ms = link_started.elapsed().as_millis() as u64;
 
+        report.timings.total_ms = started.elapsed().as_millis() as u64;
         Ok(report)
     }
 
@@ -97,7 +325,7 @@ impl<'a> IngestionPipeline<'a> {
             discovered.extend(self.env_scanner.scan_files(&[(path_str, content)]));
         }
 
-        let conn = self.graph.db().lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.graph.db().lock()?;
         self.env_scanner
             .populate_registry(&conn, self.graph.project_id(), &discovered)?;
         info!(
@@ -107,49 +335,369 @@ impl<'a> IngestionPipeline<'a> {
         Ok(())
     }
 
-    fn cleanup_stale_nodes(&self, crawled_paths: &HashSet<String>) -> Result<()> {
+    /// Second pass, once every file has a node: replaces the Imports edges of
+    /// each file whose imports may resolve differently than on the last run
+    /// with one to every indexed file it imports. Those are the `touched`
+    /// files, the `importers` of files deleted or moved, and the crawled
+    /// `files` that may import one of the `added` files.
+    fn link_imports(
+        &self,
+        files: &[PathBuf],
+        touched: &[String],
+        mut importers: HashSet<String>,
+        added: &[&str],
+    ) -> Result<()> {
+        importers.extend(touched.iter().cloned());
+        if !added.is_empty() {
+            for path in files {
+                let path_str = path.to_string_lossy();
+                if importers.contains(path_str.as_ref()) {
+                    continue;
+                }
+                let Ok(bytes) = std::fs::read(path) else {
+                    continue;
+                };
+                let content = String::from_utf8_lossy(&bytes);
+                if added.iter().any(|target| imports::may_import(&content, Path::new(target))) {
+                    importers.insert(path_str.into_owned());
+                }
+            }
+        }
+        if importers.is_empty() {
+            return Ok(());
+        }
+
+        let file_ids = self.graph.file_node_ids()?;
+        let mut sources = Vec::new();
+        let mut edges = Vec::new();
+        for path_str in &importers {
+            let Some(source_id) = file_ids.get(path_str) else {
+                continue;
+            };
+            let path = Path::new(path_str);
+            let Ok(bytes) = std::fs::read(path) else {
+                continue;
+            };
+            let content = String::from_utf8_lossy(&bytes);
+            for target in self.import_resolver.resolve(path, &content, &file_ids) {
+                edges.push(
+                    self.graph
+                        .create_edge_builder()
+                        .source(source_id)
+                        .target(&file_ids[&target])
+                        .edge_type(EdgeType::Imports)
+                        .try_build()?,
+                );
+            }
+            sources.push(source_id);
+        }
+        self.graph.in_transaction(|w| {
+            for source_id in &sources {
+                w.delete_edges_from(source_id, &EdgeType::Imports)?;
+            }
+            for edge in &edges {
+                w.add_edge(edge)?;
+            }
+            Ok(())
+        })?;
+        info!(files = sources.len(), count = edges.len(), "Linked file imports");
+        Ok(())
+    }
+
+    /// Relinks the Calls edges between Function nodes, weighted by how often
+    /// a body mentions the callee. Only callers whose edges may have changed
+    /// are recomputed: the functions of the `touched` files, and those that
+    /// mention a name defined in them now or, per `previous_names`, before
+    /// this run. Their old edges are replaced in one transaction, so readers
+    /// never see a partial call graph.
+    fn link_calls(&self, touched: &[String], mut previous_names: HashSet<String>) -> Result<()> {
+        let functions = self.graph.function_nodes_with_content()?;
+        let touched: HashSet<&str> = touched.iter().map(String::as_str).collect();
+        let in_touched =
+            |node: &Node| node.file_path.as_deref().is_some_and(|path| touched.contains(path));
+        previous_names.extend(
+            functions
+                .iter()
+                .filter(|(node, _)| in_touched(node))
+                .map(|(node, _)| calls::short_name(&node.name).to_string()),
+        );
+        let names: HashSet<&str> = previous_names.iter().map(String::as_str).collect();
+        let callers: Vec<usize> = functions
+            .iter()
+            .enumerate()
+            .filter(|(_, (node, content))| in_touched(node) || calls::mentions_any(content, &names))
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut edges = Vec::new();
+        for (caller, callee, mentions) in calls::call_edges(&functions, &callers) {
+            edges.push(
+                self.graph
+                    .create_edge_builder()
+                    .source(&functions[caller].0.id)
+                    .target(&functions[callee].0.id)
+                    .edge_type(EdgeType::Calls)
+                    .weight(mentions as f64)
+                    .try_build()?,
+            );
+        }
+        self.graph.in_transaction(|w| {
+            for &caller in &callers {
+                w.delete_edges_from(&functions[caller].0.id, &EdgeType::Calls)?;
+            }
+            for edge in &edges {
+                w.add_edge(edge)?;
+            }
+            Ok(())
+        })?;
+        info!(callers = callers.len(), count = edges.len(), "Linked function calls");
+        Ok(())
+    }
+
+    /// The short names of the Function nodes indexed for `paths`.
+    fn function_names(
+        &self,
+        paths: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<HashSet<String>> {
+        let mut names = HashSet::new();
+        for path in paths {
+            for node in self.graph.get_nodes_for_file(path.as_ref())? {
+                if node.node_type == NodeType::Function {
+                    names.insert(calls::short_name(&node.name).to_string());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Takes the files of `to_ingest` that were never indexed and whose
+    /// content is that of a stale file (see [`stale_paths`]) out of the list,
+    /// and returns them as `(old path, new path)`. Content shared by several
+    /// stale or new files is ambiguous and is not treated as a rename.
+    fn detect_renames(
+        &self,
+        to_ingest: &mut Vec<&PathBuf>,
+        db_paths: &HashSet<String>,
+        crawled_paths: &HashSet<String>,
+        scope: &StaleScope,
+    ) -> Result<Vec<(String, String)>> {
+        let stale = stale_paths(db_paths, crawled_paths, scope);
+        if stale.is_empty() {
+            return Ok(Vec::new());
+        }
+        // content hash -> (stale paths, new paths)
+        let mut by_hash: HashMap<String, (Vec<String>, Vec<String>)> = HashMap::new();
+        for path in stale {
+            if let Some(hash) = self.hash_tracker.stored_hash(path)? {
+                by_hash.entry(hash).or_default().0.push(path.clone());
+            }
+        }
+        for file_path in to_ingest.iter() {
+            let path = file_path.to_string_lossy().to_string();
+            if db_paths.contains(&path) {
+                continue;
+            }
+            if let Ok(hash) = hash_tracker::file_hash(file_path) {
+                if let Some((_, added)) = by_hash.get_mut(&hash) {
+                    added.push(path);
+                }
+            }
+        }
+        let mut renames: Vec<(String, String)> = by_hash
+            .into_values()
+            .filter(|(gone, added)| gone.len() == 1 && added.len() == 1)
+            .map(|(mut gone, mut added)| (gone.remove(0), added.remove(0)))
+            .collect();
+        renames.sort();
+        let targets: HashSet<&str> = renames.iter().map(|(_, to)| to.as_str()).collect();
+        to_ingest.retain(|p| !targets.contains(p.to_string_lossy().as_ref()));
+        Ok(renames)
+    }
+
+    /// Replaces hashed vectors stored under another tokenizer setting's (or
+    /// an older version's) model tag. Unchanged files are not re-ingested, so
+    /// without this their nodes would keep only stale rows, and every search
+    /// would rebuild their vectors on the fly. Returns the vectors written.
+    fn backfill_hashed_vectors(&self) -> Result<usize> {
+        let model = self.hashed.model();
+        let vectors: Vec<(String, Vec<f32>)> = self
+            .graph
+            .nodes_without_embedding(model)?
+            .into_iter()
+            .filter_map(|node| Some((node.id.clone(), self.hashed.node_vector(&node)?)))
+            .collect();
+        let deleted = self.graph.in_transaction(|w| {
+            for (node_id, vector) in &vectors {
+                w.upsert_embedding(node_id, vector, model)?;
+            }
+            w.delete_embeddings_by_prefix(HASHED_MODEL_PREFIX, model)
+        })?;
+        if deleted > 0 || !vectors.is_empty() {
+            info!(model, written = vectors.len(), deleted, "Backfilled hashed vectors");
+        }
+        Ok(vectors.len())
+    }
+
+    /// Re-points the nodes of `from` at `to` instead of deleting them and
+    /// indexing `to` afresh, so their ids, and facts linked to them, survive
+    /// the rename. Hashed vectors are rebuilt since their text includes the
+    /// path; an external provider's vectors keep it until the file changes.
+    fn rename_file(&self, from: &str, to: &str) -> Result<()> {
+        let vectors: Vec<(String, Vec<f32>)> = self
+            .graph
+            .get_nodes_for_file(from)?
+            .into_iter()
+            .filter_map(|mut node| {
+                if node.node_type == NodeType::File {
+                    node.name = to.to_string();
+                }
+                node.file_path = Some(to.to_string());
+                let vector = self.hashed.node_vector(&node)?;
+                Some((node.id, vector))
+            })
+            .collect();
+        let moved = self.graph.in_transaction(|w| {
+            let moved = w.rename_file(from, to)?;
+            for (node_id, vector) in &vectors {
+                w.upsert_embedding(node_id, vector, self.hashed.model())?;
+            }
+            Ok(moved)
+        })?;
+        info!(from, to, nodes = moved, "Re-pointed nodes of renamed file");
+        Ok(())
+    }
+
+    fn cleanup_stale_nodes(
+        &self,
+        crawled_paths: &HashSet<String>,
+        scope: &StaleScope,
+    ) -> Result<Vec<String>> {
         let db_paths = self.graph.get_all_file_paths()?;
-        for stale_path in db_paths.difference(crawled_paths) {
+        let mut removed = Vec::new();
+        for stale_path in stale_paths(&db_paths, crawled_paths, scope) {
             self.graph.delete_nodes_for_file(stale_path)?;
             info!(path = %stale_path, "Removed stale nodes for deleted file");
+            removed.push(stale_path.clone());
         }
-        Ok(())
+        Ok(removed)
+    }
+
+    /// Chunks, embeds and writes one file. The file's nodes, edges, FTS rows,
+    /// embeddings and hashes are written in one transaction, so the file
+    /// costs a single commit and a failure leaves its previous index intact.
+    pub fn ingest_file(&self, file_path: &Path) -> Result<FileIngestion> {
+        self.ingest_file_with(file_path, None)
     }
 
-    pub fn ingest_file(&self, file_path: &Path) -> Result<usize> {
+    /// A read-only connection for one ingestion worker, or `None` to read
+    /// through the graph's shared connection.
+    fn open_reader(&self) -> Option<Connection> {
+        let path = self.read_db.as_ref()?;
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        match Connection::open_with_flags(path, flags) {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Falling back to the shared connection");
+                None
+            }
+        }
+    }
+
+    /// Whether a chunk is already indexed with this content, read through
+    /// the worker's `reader` when it has one.
+    fn chunk_indexed(
+        &self,
+        reader: Option<&Connection>,
+        chunk_key: &str,
+        hash: &str,
+        node_id: &str,
+    ) -> Result<bool> {
+        let project_id = self.graph.project_id();
+        match reader {
+            Some(conn) => hash_tracker::chunk_indexed(conn, project_id, chunk_key, hash, node_id),
+            None => {
+                let conn = self.graph.db().lock()?;
+                hash_tracker::chunk_indexed(&conn, project_id, chunk_key, hash, node_id)
+            }
+        }
+    }
+
+    /// The ids of the nodes indexed for `path`, keyed by name, type and
+    /// occurrence like [`NodeBuilder::deterministic_id`] keys them.
+    ///
+    /// [`NodeBuilder::deterministic_id`]: crate::graph_builders::NodeBuilder::deterministic_id
+    fn indexed_node_ids(
+        &self,
+        reader: Option<&Connection>,
+        path: &str,
+    ) -> Result<HashMap<NodeIdentity, String>> {
+        let project_id = self.graph.project_id();
+        match reader {
+            Some(conn) => indexed_node_ids(conn, project_id, path),
+            None => {
+                let conn = self.graph.db().lock()?;
+                indexed_node_ids(&conn, project_id, path)
+            }
+        }
+    }
+
+    fn ingest_file_with(
+        &self,
+        file_path: &Path,
+        reader: Option<&Connection>,
+    ) -> Result<FileIngestion> {
+        let started = Instant::now();
+        // Taken before reading, so a write in between is caught next run.
+        let metadata = hash_tracker::file_metadata(file_path)?;
         // Read as raw bytes and convert to UTF-8 lossily so that files encoded
         // in Latin-1, Windows-1252, GBK, etc. are still indexed rather than
         // rejected with an "invalid UTF-8" error.
         let bytes = std::fs::read(file_path)?;
+        let max_bytes = self.crawl_options.max_file_bytes;
+        if bytes.len() as u64 > max_bytes {
+            return Err(HermesError::FileTooLarge { bytes: bytes.len(), max_bytes });
+        }
         let content = String::from_utf8_lossy(&bytes).into_owned();
         let path_str = file_path.to_string_lossy().to_string();
         let chunks = chunker::chunk_file(file_path, &content);
 
         let file_hash = hash_tracker::compute_hash(&content);
-        let file_node = self
+        // Nodes re-pointed by a rename keep ids derived from their old path.
+        let mut indexed_ids = self.indexed_node_ids(reader, &path_str)?;
+        let mut file_node = self
             .graph
             .create_node_builder()
             .name(&path_str)
             .node_type(NodeType::File)
             .file_path(&path_str)
-            .lines(1, content.lines().count() as i64)
+            .lines(1, content.lines().count().max(1) as i64)
             .content_hash(&file_hash)
-            .build();
-
-        self.graph.add_node(&file_node)?;
-        self.graph.index_fts(&file_node, &content)?;
+            .deterministic_id(0)
+            .try_build()?;
+        if let Some(id) = indexed_ids.remove(&(path_str.clone(), NodeType::File.as_str(), 0)) {
+            file_node.id = id;
+        }
 
-        let mut created = 1;
+        // (node, content, whether its FTS row needs rewriting)
+        let mut nodes: Vec<(Node, &str, bool)> = vec![(file_node.clone(), &content, true)];
+        let mut edges = Vec::new();
+        let mut chunk_hashes: Vec<(String, String)> = Vec::new();
+        let mut embed_queue = vec![file_node.clone()];
+        // (type, start, end, node id) of the impl blocks and markdown sections
+        // created so far; the chunkers emit a container before what it holds.
+        let mut containers: Vec<(NodeType, usize, usize, String)> = Vec::new();
+        let mut occurrences: HashMap<(&str, &str), usize> = HashMap::new();
+        let mut current_ids = HashSet::from([file_node.id.clone()]);
 
         for chunk in &chunks {
             let chunk_key = format!("{}::{}", path_str, chunk.name);
             let chunk_hash = hash_tracker::compute_hash(&chunk.content);
+            let occurrence = occurrences
+                .entry((chunk.name.as_str(), chunk.node_type.as_str()))
+                .or_default();
 
-            if self.hash_tracker.is_chunk_unchanged(&chunk_key, &chunk_hash)? {
-                continue;
-            }
-
-            let chunk_node = self
+            let identity = (chunk.name.clone(), chunk.node_type.as_str(), *occurrence);
+            let mut chunk_node = self
                 .graph
                 .create_node_builder()
                 .name(&chunk.name)
@@ -157,25 +705,271 @@ impl<'a> IngestionPipeline<'a> {
                 .file_path(&path_str)
                 .lines(chunk.start_line as i64, chunk.end_line as i64)
                 .summary(&chunk.summary)
-                .build();
+                .deterministic_id(*occurrence)
+                .try_build()?;
+            *occurrence += 1;
+            if let Some(id) = indexed_ids.remove(&identity) {
+                chunk_node.id = id;
+            }
+            current_ids.insert(chunk_node.id.clone());
 
-            self.graph.add_node(&chunk_node)?;
-            self.graph.index_fts(&chunk_node, &chunk.content)?;
+            // An unchanged chunk keeps its node, FTS row and embedding; the
+            // node is still rewritten since edits above it shift its lines.
+            let unchanged = self.chunk_indexed(reader, &chunk_key, &chunk_hash, &chunk_node.id)?;
 
-            let edge = self
-                .graph
-                .create_edge_builder()
-                .source(&file_node.id)
-                .target(&chunk_node.id)
-                .edge_type(EdgeType::Contains)
-                .build();
+            edges.push(
+                self.graph
+                    .create_edge_builder()
+                    .source(&file_node.id)
+                    .target(&chunk_node.id)
+                    .edge_type(EdgeType::Contains)
+                    .try_build()?,
+            );
+
+            let parent_type = match chunk.node_type {
+                NodeType::Function if chunk.name.contains("::") => Some(NodeType::Impl),
+                NodeType::Document => Some(NodeType::Document),
+                _ => None,
+            };
+            let parent = containers.iter().rev().find(|(node_type, start, end, _)| {
+                Some(node_type) == parent_type.as_ref()
+                    && *start <= chunk.start_line
+                    && chunk.end_line <= *end
+            });
+            if let Some((_, _, _, parent_id)) = parent {
+                edges.push(
+                    self.graph
+                        .create_edge_builder()
+                        .source(parent_id)
+                        .target(&chunk_node.id)
+                        .edge_type(EdgeType::Contains)
+                        .try_build()?,
+                );
+            }
+            if matches!(chunk.node_type, NodeType::Impl | NodeType::Document) {
+                containers.push((
+                    chunk.node_type.clone(),
+                    chunk.start_line,
+                    chunk.end_line,
+                    chunk_node.id.clone(),
+                ));
+            }
+
+            if !unchanged {
+                chunk_hashes.push((chunk_key, chunk_hash));
+                embed_queue.push(chunk_node.clone());
+            }
+            nodes.push((chunk_node, &chunk.content, !unchanged));
+        }
+        let chunk_ms = started.elapsed().as_millis() as u64;
+
+        let embed_started = Instant::now();
+        let embeddings = self.embed_nodes(&embed_queue);
+        let embed_ms = embed_started.elapsed().as_millis() as u64;
+
+        let write_started = Instant::now();
+        let current_keys: HashSet<&str> = chunks.iter().map(|c| c.name.as_str()).collect();
+        let project_id = self.graph.project_id();
+        let removed = self.graph.in_transaction(|w| {
+            for (node, content, reindex) in &nodes {
+                w.add_node(node)?;
+                w.set_token_count(&node.id, estimate_tokens(content))?;
+                if *reindex {
+                    w.index_fts(node, content)?;
+                }
+            }
+            for edge in &edges {
+                w.add_edge(edge)?;
+            }
+            for (node_id, vector, model) in &embeddings {
+                w.upsert_embedding(node_id, vector, model)?;
+            }
+            for (chunk_key, chunk_hash) in &chunk_hashes {
+                hash_tracker::write_chunk_hash(w.conn(), project_id, chunk_key, chunk_hash)?;
+            }
+            let removed = w.delete_file_nodes_except(&path_str, &current_ids)?;
+            for node in removed.iter().filter(|n| n.node_type != NodeType::File) {
+                if !current_keys.contains(node.name.as_str()) {
+                    let chunk_key = format!("{}::{}", path_str, node.name);
+                    hash_tracker::delete_chunk_hash(w.conn(), project_id, &chunk_key)?;
+                }
+            }
+            hash_tracker::write_file_hash(w.conn(), project_id, &path_str, &file_hash, metadata)?;
+            Ok(removed)
+        })?;
+        if !removed.is_empty() {
+            info!(path = %path_str, count = removed.len(), "Removed nodes of vanished chunks");
+        }
+
+        Ok(FileIngestion {
+            nodes_created: embed_queue.len(),
+            chunks_removed: removed.len(),
+            chunk_ms,
+            embed_ms,
+            write_ms: write_started.elapsed().as_millis() as u64,
+        })
+    }
 
-            self.graph.add_edge(&edge)?;
-            self.hash_tracker.update_chunk_hash(&chunk_key, &chunk_hash)?;
-            created += 1;
+    /// The L2 vectors of `nodes` as `(node id, vector, model)`, persisted so
+    /// searches only have to embed the query. External providers get all of
+    /// a file's nodes in one call; nodes they fail to embed are logged and
+    /// keep only their hashed vector.
+    fn embed_nodes<'n>(&'n self, nodes: &'n [Node]) -> Vec<(&'n str, Vec<f32>, &'n str)> {
+        let mut embeddings: Vec<(&str, Vec<f32>, &str)> = nodes
+            .iter()
+            .filter_map(|node| {
+                let vec = self.hashed.node_vector(node)?;
+                Some((node.id.as_str(), vec, self.hashed.model()))
+            })
+            .collect();
+        if self.embedder.model() == self.hashed.model() {
+            return embeddings;
         }
+        let texts: Vec<String> = nodes.iter().map(vector::embedding_text).collect();
+        let text_refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+        for (node, result) in nodes.iter().zip(self.embedder.embed_each(&text_refs)) {
+            match result {
+                Ok(vec) => embeddings.push((node.id.as_str(), vec, self.embedder.model())),
+                Err(e) => warn!(node = %node.id, error = %e, "Failed to embed node"),
+            }
+        }
+        embeddings
+    }
+}
+
+/// A node's name, type and occurrence among same-named nodes of its file.
+type NodeIdentity = (String, &'static str, usize);
 
-        Ok(created)
+fn indexed_node_ids(
+    conn: &Connection,
+    project_id: &str,
+    path: &str,
+) -> Result<HashMap<NodeIdentity, String>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, node_type FROM nodes WHERE project_id = ?1 AND file_path = ?2
+         ORDER BY start_line, end_line DESC",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![project_id, path], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+    })?;
+    let mut occurrences: HashMap<(String, &'static str), usize> = HashMap::new();
+    let mut ids = HashMap::new();
+    for row in rows {
+        let (id, name, node_type) = row?;
+        let node_type = NodeType::parse_str(&node_type).as_str();
+        let occurrence = occurrences.entry((name.clone(), node_type)).or_default();
+        ids.insert((name, node_type, *occurrence), id);
+        *occurrence += 1;
+    }
+    Ok(ids)
+}
+
+/// Which indexed files a run may remove when it did not crawl them.
+enum StaleScope {
+    Everything,
+    /// Files below (or equal to) one of these paths.
+    Under(Vec<PathBuf>),
+}
+
+/// The indexed files in `db_paths` that were not crawled and that `scope`
+/// lets the run remove.
+fn stale_paths<'p>(
+    db_paths: &'p HashSet<String>,
+    crawled_paths: &HashSet<String>,
+    scope: &StaleScope,
+) -> Vec<&'p String> {
+    let mut stale: Vec<&String> = db_paths
+        .iter()
+        .filter(|path| !crawled_paths.contains(*path))
+        .filter(|path| match scope {
+            StaleScope::Everything => true,
+            StaleScope::Under(roots) => roots.iter().any(|root| Path::new(path).starts_with(root)),
+        })
+        .collect();
+    stale.sort();
+    stale
+}
+
+/// What `ingest_file` did to one file, and how long each phase took.
+#[derive(Debug, Default)]
+pub struct FileIngestion {
+    pub nodes_created: usize,
+    /// Chunk nodes deleted because their chunk is gone from the file.
+    pub chunks_removed: usize,
+    /// Reading, chunking and comparing chunk hashes.
+    pub chunk_ms: u64,
+    pub embed_ms: u64,
+    /// The file's single write transaction.
+    pub write_ms: u64,
+}
+
+/// Milliseconds spent in each phase of `ingest_directory`. The per-file
+/// phases (chunk, embed, write) are summed over files ingested in parallel,
+/// so together they can exceed `total_ms`.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct IngestionTimings {
+    /// Crawling and filtering out oversized and binary files.
+    pub crawl_ms: u64,
+    pub env_scan_ms: u64,
+    /// Deciding which files changed since the last run.
+    pub change_detection_ms: u64,
+    pub chunk_ms: u64,
+    pub embed_ms: u64,
+    pub write_ms: u64,
+    /// Removing deleted files and rebuilding Imports and Calls edges.
+    pub link_ms: u64,
+    pub total_ms: u64,
+}
+
+/// At most this many failures are listed in `IngestionReport::error_details`.
+pub const MAX_ERROR_DETAILS: usize = 50;
+
+/// The common reasons a file fails to ingest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileErrorKind {
+    NotUtf8,
+    PermissionDenied,
+    TooLarge,
+    Other,
+}
+
+impl FileErrorKind {
+    fn of(error: &HermesError) -> Self {
+        match error {
+            HermesError::FileTooLarge { .. } => FileErrorKind::TooLarge,
+            HermesError::Io(io) => match io.kind() {
+                std::io::ErrorKind::PermissionDenied => FileErrorKind::PermissionDenied,
+                std::io::ErrorKind::InvalidData => FileErrorKind::NotUtf8,
+                _ => FileErrorKind::Other,
+            },
+            _ => FileErrorKind::Other,
+        }
+    }
+}
+
+/// A file that failed to ingest, with a message saying what to do about it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileError {
+    pub path: String,
+    pub kind: FileErrorKind,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(path: &str, error: &HermesError) -> Self {
+        let kind = FileErrorKind::of(error);
+        let message = match kind {
+            FileErrorKind::NotUtf8 => format!("not valid UTF-8: {error}"),
+            FileErrorKind::PermissionDenied => {
+                format!("permission denied, check the file's read permissions: {error}")
+            }
+            FileErrorKind::TooLarge => {
+                format!("{error}; raise HERMES_MAX_FILE_BYTES to index it")
+            }
+            FileErrorKind::Other => format!("{error}"),
+        };
+        Self { path: path.to_string(), kind, message }
     }
 }
 
@@ -185,15 +979,69 @@ pub struct IngestionReport {
     pub indexed: usize,
     pub skipped: usize,
     pub errors: usize,
+    /// The first [`MAX_ERROR_DETAILS`] failures, in path order.
+    pub error_details: Vec<FileError>,
+    /// Set when more files failed than `error_details` lists.
+    pub error_details_truncated: bool,
+    /// Files over `CrawlOptions::max_file_bytes`, left out of the index.
+    pub skipped_too_large: usize,
+    /// Files with a NUL byte near the start, left out of the index.
+    pub skipped_binary: usize,
     pub nodes_created: usize,
+    /// Chunk nodes deleted because their function, section, etc. was
+    /// removed from a file that still exists.
+    pub chunks_removed: usize,
+    pub timings: IngestionTimings,
+    /// Files that were (re-)ingested because their content changed.
+    pub changed_paths: Vec<String>,
+    /// Files whose nodes were removed because they no longer exist on disk.
+    pub deleted_paths: Vec<String>,
+    /// `(old path, new path)` of files moved without a content change, whose
+    /// nodes were re-pointed rather than re-created.
+    pub renamed_paths: Vec<(String, String)>,
+    /// What the post-cleanup repair deleted, when enabled with
+    /// [`IngestionPipeline::with_repair`].
+    pub repair: Option<RepairReport>,
+    /// Hashed vectors written for nodes that had none under the current
+    /// tokenizer's model tag, such as after a tokenizer change.
+    pub vectors_backfilled: usize,
+}
+
+impl IngestionReport {
+    /// Every path whose indexed content changed during this run.
+    pub fn touched_paths(&self) -> Vec<String> {
+        let renamed = self.renamed_paths.iter().flat_map(|(from, to)| [from, to]);
+        self.changed_paths
+            .iter()
+            .chain(self.deleted_paths.iter())
+            .chain(renamed)
+            .cloned()
+            .collect()
+    }
 }
 
 impl std::fmt::Display for IngestionReport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Ingestion: {} files ({} indexed, {} skipped, {} errors), {} nodes",
-            self.total_files, self.indexed, self.skipped, self.errors, self.nodes_created
+            "Ingestion: {} files ({} indexed, {} skipped, {} too large, {} binary, {} errors), \
+             {} nodes, {} removed chunks in {}ms (crawl {}ms, changes {}ms, chunk {}ms, \
+             embed {}ms, write {}ms, link {}ms)",
+            self.total_files,
+            self.indexed,
+            self.skipped,
+            self.skipped_too_large,
+            self.skipped_binary,
+            self.errors,
+            self.nodes_created,
+            self.chunks_removed,
+            self.timings.total_ms,
+            self.timings.crawl_ms,
+            self.timings.change_detection_ms,
+            self.timings.chunk_ms,
+            self.timings.embed_ms,
+            self.timings.write_ms,
+            self.timings.link_ms
         )
     }
 }
@@ -237,6 +1085,712 @@ mod tests {
         let report2 = pipeline.ingest_directory(dir.path()).unwrap();
         assert_eq!(report2.indexed, 0);
         assert_eq!(report2.skipped, 1);
+        assert!(report2.touched_paths().is_empty());
+    }
+
+    #[test]
+    fn test_projects_sharing_a_db_do_not_clobber_file_hashes() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "fn shared() {}").unwrap();
+
+        let engine = HermesEngine::in_memory("test-shared-a").unwrap();
+        let alpha = KnowledgeGraph::new(engine.db().clone(), "alpha");
+        let beta = KnowledgeGraph::new(engine.db().clone(), "beta");
+        for graph in [&alpha, &beta] {
+            let report = IngestionPipeline::new(graph).ingest_directory(dir.path()).unwrap();
+            assert_eq!(report.indexed, 1);
+        }
+        for graph in [&alpha, &beta] {
+            let report = IngestionPipeline::new(graph).ingest_directory(dir.path()).unwrap();
+            assert_eq!((report.indexed, report.skipped), (0, 1));
+        }
+    }
+
+    #[test]
+    fn test_ingest_file_records_its_hash_and_phase_timings() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "fn fetch_rate() {}\n\nfn store_rate() {}\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-file-transaction").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        let ingested = pipeline.ingest_file(&file).unwrap();
+        assert_eq!(ingested.nodes_created, 3);
+        assert!(pipeline.hash_tracker.is_unchanged(&file.to_string_lossy()).unwrap());
+        assert_eq!(embedded_node_count(&engine), (3, 3));
+
+        std::fs::write(&file, "fn fetch_rate() {}\n").unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(report.chunks_removed, 1);
+        assert!(report.timings.total_ms >= report.timings.link_ms);
+        assert!(report.to_string().contains("write"));
+    }
+
+    #[test]
+    fn test_file_backed_workers_read_through_their_own_connections() {
+        let dir = TempDir::new().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        for n in 0..8 {
+            let code = format!("fn stable_{n}() {{}}\n\nfn edited_{n}() {{ 1 }}\n");
+            std::fs::write(project.join(format!("m{n}.rs")), code).unwrap();
+        }
+
+        let db_path = dir.path().join("hermes.db");
+        let engine = HermesEngine::new(&db_path, "test-readers").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph).with_read_connections(engine.db_path());
+        let first = pipeline.ingest_directory(&project).unwrap();
+        assert_eq!((first.indexed, first.errors, first.nodes_created), (8, 0, 24));
+
+        for n in 0..8 {
+            let code = format!("fn stable_{n}() {{}}\n\nfn edited_{n}() {{ 2 }}\n");
+            std::fs::write(project.join(format!("m{n}.rs")), code).unwrap();
+        }
+        // Only the File node and the edited function of each file are new.
+        let second = pipeline.ingest_directory(&project).unwrap();
+        assert_eq!((second.indexed, second.errors, second.nodes_created), (8, 0, 16));
+        assert_eq!(graph.get_all_nodes().unwrap().len(), 24);
+    }
+
+    #[test]
+    fn test_ingest_paths_touches_only_the_given_paths() {
+        let dir = TempDir::new().unwrap();
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        let (a, b) = (dir.path().join("a.rs"), dir.path().join("b.rs"));
+        let (c, d) = (sub.join("c.rs"), sub.join("d.rs"));
+        for file in [&a, &b, &c, &d] {
+            std::fs::write(file, "fn original() {}").unwrap();
+        }
+
+        let engine = HermesEngine::in_memory("test-ingest-paths").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let path = |p: &PathBuf| p.to_string_lossy().to_string();
+
+        std::fs::write(&a, "fn edited() {}").unwrap();
+        std::fs::write(&b, "fn edited() {}").unwrap();
+        let report = pipeline.ingest_paths(std::slice::from_ref(&a)).unwrap();
+        assert_eq!(report.total_files, 1);
+        assert_eq!(report.changed_paths, vec![path(&a)]);
+        assert!(report.deleted_paths.is_empty());
+
+        // A deleted file outside the given directory keeps its nodes until a
+        // run covers it.
+        std::fs::remove_file(&b).unwrap();
+        std::fs::remove_file(&d).unwrap();
+        let report = pipeline.ingest_paths(std::slice::from_ref(&sub)).unwrap();
+        assert_eq!(report.deleted_paths, vec![path(&d)]);
+        let report = pipeline.ingest_paths(std::slice::from_ref(&b)).unwrap();
+        assert_eq!(report.deleted_paths, vec![path(&b)]);
+
+        let mut indexed: Vec<String> = graph.get_all_file_paths().unwrap().into_iter().collect();
+        indexed.sort();
+        assert_eq!(indexed, vec![path(&a), path(&c)]);
+    }
+
+    #[test]
+    fn test_file_errors_name_their_cause() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let error = FileError::new("src/secret.rs", &denied.into());
+        assert_eq!(error.kind, FileErrorKind::PermissionDenied);
+        assert!(error.message.contains("read permissions"));
+
+        let invalid = String::from_utf8(vec![0xff]).unwrap_err();
+        let invalid = std::io::Error::new(std::io::ErrorKind::InvalidData, invalid);
+        let error = FileError::new("a.rs", &invalid.into());
+        assert_eq!(error.kind, FileErrorKind::NotUtf8);
+
+        let dir = TempDir::new().unwrap();
+        let big = dir.path().join("big.rs");
+        std::fs::write(&big, "fn big() {}").unwrap();
+        let engine = HermesEngine::in_memory("test-file-errors").unwrap();
+        let graph = make_graph_for(&engine);
+        let options = CrawlOptions { max_file_bytes: 4, ..CrawlOptions::default() };
+        let pipeline = IngestionPipeline::new(&graph).with_crawl_options(options);
+        let failure = pipeline.ingest_file(&big).unwrap_err();
+        let error = FileError::new(&big.to_string_lossy(), &failure);
+        assert_eq!(error.kind, FileErrorKind::TooLarge);
+        assert!(error.message.contains("HERMES_MAX_FILE_BYTES"), "{}", error.message);
+        assert_eq!(serde_json::to_value(&error).unwrap()["kind"], "too_large");
+    }
+
+    #[test]
+    fn test_progress_events_cover_phases_and_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn a() {}").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn b() {}").unwrap();
+
+        let engine = HermesEngine::in_memory("test-progress").unwrap();
+        let graph = make_graph_for(&engine);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let pipeline = IngestionPipeline::new(&graph)
+            .with_progress(Arc::new(move |event| sink.lock().unwrap().push(event)));
+
+        let count = |matches: fn(&IngestionProgress) -> bool| {
+            events.lock().unwrap().iter().filter(|e| matches(e)).count()
+        };
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let phases: Vec<IngestionProgress> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| matches!(e, IngestionProgress::Phase(_)))
+            .cloned()
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                IngestionPhase::Crawl,
+                IngestionPhase::HashCheck,
+                IngestionPhase::Ingest { files: 2 },
+                IngestionPhase::Cleanup,
+            ]
+            .map(IngestionProgress::Phase)
+        );
+        assert_eq!(count(|e| matches!(e, IngestionProgress::FileStarted { .. })), 2);
+        assert_eq!(count(|e| matches!(e, IngestionProgress::FileIndexed { nodes: 2, .. })), 2);
+
+        events.lock().unwrap().clear();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(count(|e| matches!(e, IngestionProgress::FileSkipped { .. })), 2);
+        assert_eq!(count(|e| matches!(e, IngestionProgress::FileStarted { .. })), 0);
+    }
+
+    #[test]
+    fn test_repair_runs_after_cleanup_only_when_enabled() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn a() {}").unwrap();
+        let engine = HermesEngine::in_memory("test-repair").unwrap();
+        let graph = make_graph_for(&engine);
+        let add_stale_hash = || {
+            engine
+                .db()
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR REPLACE INTO file_hashes (file_path, project_id, content_hash)
+                     VALUES ('/nowhere/ghost.rs', ?1, 'h')",
+                    [graph.project_id()],
+                )
+                .unwrap();
+        };
+
+        add_stale_hash();
+        let report = IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+        assert!(report.repair.is_none());
+        assert_eq!(graph.diagnose().unwrap().stale_file_hashes, 1);
+
+        let report = IngestionPipeline::new(&graph)
+            .with_repair(true)
+            .ingest_directory(dir.path())
+            .unwrap();
+        assert_eq!(report.repair.unwrap().stale_file_hashes, 1);
+        assert_eq!(graph.diagnose().unwrap().total(), 0);
+        assert_eq!(graph.stats().unwrap().files, 1, "the indexed file is untouched");
+    }
+
+    #[test]
+    fn test_report_lists_changed_and_deleted_paths() {
+        let dir = TempDir::new().unwrap();
+        let keep = dir.path().join("keep.rs");
+        let gone = dir.path().join("gone.rs");
+        std::fs::write(&keep, "fn keep() {}").unwrap();
+        std::fs::write(&gone, "fn gone() {}").unwrap();
+
+        let engine = HermesEngine::in_memory("test-touched").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        let first = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(first.changed_paths.len(), 2);
+
+        std::fs::write(&keep, "fn keep() { changed(); }").unwrap();
+        std::fs::remove_file(&gone).unwrap();
+        let second = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(second.changed_paths, vec![keep.to_string_lossy().to_string()]);
+        assert_eq!(second.deleted_paths, vec![gone.to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn test_rename_repoints_nodes_and_keeps_their_ids() {
+        let dir = TempDir::new().unwrap();
+        let foo = dir.path().join("foo.rs");
+        let bar = dir.path().join("bar.rs");
+        std::fs::write(&foo, "fn alpha() {}\n\nfn beta() { alpha(); }\n").unwrap();
+        let (foo, bar) = (foo.to_string_lossy().to_string(), bar.to_string_lossy().to_string());
+
+        let engine = HermesEngine::in_memory("test-rename").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let ids = |path: &str| -> Vec<(String, String)> {
+            let nodes = graph.get_nodes_for_file(path).unwrap();
+            nodes.into_iter().map(|n| (n.name, n.id)).collect()
+        };
+        let before = ids(&foo);
+        assert_eq!(before.len(), 3);
+
+        std::fs::rename(&foo, &bar).unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(report.renamed_paths, vec![(foo.clone(), bar.clone())]);
+        assert_eq!((report.indexed, report.skipped), (0, 1));
+        assert!(report.deleted_paths.is_empty());
+        assert!(ids(&foo).is_empty());
+        let after = ids(&bar);
+        assert_eq!(after[0], (bar.clone(), before[0].1.clone()), "File node renamed");
+        assert_eq!(after[1..], before[1..]);
+        let fts_paths: Vec<String> = {
+            let conn = engine.db().lock().unwrap();
+            let mut stmt = conn.prepare("SELECT DISTINCT file_path FROM fts_content").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        assert_eq!(fts_paths, vec![bar.clone()]);
+        assert_eq!(graph.diagnose().unwrap().total(), 0, "hashes moved with the file");
+
+        // A later edit re-ingests the file under its new path and still
+        // keeps the ids of the nodes it re-creates.
+        std::fs::write(&bar, "fn alpha() {}\n\nfn beta() { alpha(); }\n\nfn gamma() {}\n").unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert!(report.renamed_paths.is_empty());
+        let edited = ids(&bar);
+        assert_eq!(edited.len(), 4);
+        assert_eq!(edited[..3], after[..]);
+    }
+
+    fn embedded_node_count(engine: &HermesEngine) -> (i64, i64) {
+        let conn = engine.db().lock().unwrap();
+        conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM nodes), (SELECT COUNT(*) FROM embeddings)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ingestion_persists_embeddings_and_removes_them_with_the_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "pub fn fetch_exchange_rate() {}\npub struct RateCache {}\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-embeddings").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let (nodes, embedded) = embedded_node_count(&engine);
+        assert!(nodes >= 3);
+        assert_eq!(nodes, embedded);
+
+        std::fs::remove_file(&file).unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(embedded_node_count(&engine), (0, 0));
+    }
+
+    #[test]
+    fn test_tokenizer_change_backfills_vectors_of_unchanged_files() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "pub fn fetch_exchange_rate() {}\npub struct RateCache {}\n").unwrap();
+        let engine = HermesEngine::in_memory("test-backfill").unwrap();
+        let graph = make_graph_for(&engine);
+        let first = IngestionPipeline::new(&graph);
+        assert_eq!(first.ingest_directory(dir.path()).unwrap().vectors_backfilled, 0);
+        let (nodes, _) = embedded_node_count(&engine);
+        let node_id = graph.get_nodes_for_file(&file.to_string_lossy()).unwrap()[0].id.clone();
+        graph.upsert_embedding(&node_id, &[1.0, 0.0], "external-model").unwrap();
+
+        let stopwords = !TokenizerOptions::default().stopwords;
+        let options = TokenizerOptions { stopwords, ..Default::default() };
+        let second = IngestionPipeline::new(&graph).with_tokenizer(options);
+        let report = second.ingest_directory(dir.path()).unwrap();
+        assert_eq!((report.skipped, report.vectors_backfilled), (1, nodes as usize));
+
+        let conn = engine.db().lock().unwrap();
+        let mut stmt = conn.prepare("SELECT DISTINCT model FROM embeddings ORDER BY model").unwrap();
+        let models: Vec<String> =
+            stmt.query_map([], |row| row.get(0)).unwrap().map(|m| m.unwrap()).collect();
+        assert_eq!(models, ["external-model", second.hashed.model()]);
+        drop(stmt);
+        drop(conn);
+        assert_eq!(second.ingest_directory(dir.path()).unwrap().vectors_backfilled, 0);
+    }
+
+    #[test]
+    fn test_ingestion_records_token_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "pub fn fetch_exchange_rate() {\n    let rate = 1.0;\n}\n").unwrap();
+        let engine = HermesEngine::in_memory("test-token-counts").unwrap();
+        let graph = make_graph_for(&engine);
+        IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+
+        let nodes = graph.get_all_nodes().unwrap();
+        let ids: Vec<&str> = nodes.iter().map(|n| n.id.as_str()).collect();
+        let counts = graph.token_counts(&ids).unwrap();
+        assert_eq!(counts.len(), nodes.len());
+        assert!(counts.values().all(|&tokens| tokens > 0));
+    }
+
+    #[test]
+    fn test_empty_config_files_index_without_errors() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("empty.toml"), "").unwrap();
+        std::fs::write(dir.path().join("empty.json"), "").unwrap();
+        let engine = HermesEngine::in_memory("test-empty-files").unwrap();
+        let graph = make_graph_for(&engine);
+        let report = IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+        assert_eq!((report.indexed, report.errors), (2, 0));
+
+        let nodes = graph.get_all_nodes().unwrap();
+        assert!(!nodes.is_empty());
+        assert!(nodes.iter().all(|n| n.start_line <= n.end_line));
+    }
+
+    #[test]
+    fn test_reingesting_a_changed_file_reuses_node_ids() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "fn stable_rate() {\n    1\n}\n\nfn edited_rate() {\n    2\n}\n")
+            .unwrap();
+
+        let engine = HermesEngine::in_memory("test-deterministic-ids").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let id_of = |name: &str| {
+            let nodes = graph.get_all_nodes().unwrap();
+            nodes.into_iter().find(|n| n.name == name).map(|n| n.id).unwrap()
+        };
+        let node_count = graph.get_all_nodes().unwrap().len();
+        let stable_id = id_of("stable_rate");
+
+        std::fs::write(&file, "fn stable_rate() {\n    1\n}\n\nfn edited_rate() {\n    3\n}\n")
+            .unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(report.indexed, 1);
+        assert_eq!(graph.get_all_nodes().unwrap().len(), node_count);
+        assert_eq!(id_of("stable_rate"), stable_id);
+        assert_eq!(embedded_node_count(&engine), (node_count as i64, node_count as i64));
+    }
+
+    #[test]
+    fn test_removed_function_loses_its_node_fts_row_and_hash() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("rates.rs");
+        std::fs::write(&file, "fn keep_rate() {}\n\nfn drop_exchange_rate() {}\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-vanished-chunks").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let fts_hits = |query: &str| {
+            let weights = crate::FtsWeights::default();
+            graph.fts_search(query, 10, None, &weights).unwrap().len()
+        };
+        // The File node and the Function node.
+        assert_eq!(fts_hits("drop_exchange_rate"), 2);
+
+        std::fs::write(&file, "fn keep_rate() {}\n").unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(report.chunks_removed, 1);
+        let functions: Vec<String> = graph
+            .get_all_nodes()
+            .unwrap()
+            .into_iter()
+            .filter(|n| n.node_type == NodeType::Function)
+            .map(|n| n.name)
+            .collect();
+        assert_eq!(functions, ["keep_rate"]);
+        assert_eq!(fts_hits("drop_exchange_rate"), 0);
+        let key = format!("{}::drop_exchange_rate", file.to_string_lossy());
+        let hash = hash_tracker::compute_hash("fn drop_exchange_rate() {}");
+        assert!(!pipeline.hash_tracker.is_chunk_unchanged(&key, &hash).unwrap());
+    }
+
+    #[test]
+    fn test_impl_methods_are_contained_by_the_impl_node() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("engine.rs");
+        let code = "pub struct Engine;
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine
+    }
+
+    fn rank(&self) {}
+
+    fn deduplicate_and_rank(&self) {
+        self.rank();
+    }
+}
+";
+        std::fs::write(&file, code).unwrap();
+
+        let engine = HermesEngine::in_memory("test-impl-methods").unwrap();
+        let graph = make_graph_for(&engine);
+        IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+
+        let nodes = graph.get_all_nodes().unwrap();
+        let impl_node = nodes.iter().find(|n| n.node_type == NodeType::Impl).unwrap();
+        let (children, parents): (Vec<_>, Vec<_>) = graph
+            .get_neighbors(&impl_node.id)
+            .unwrap()
+            .into_iter()
+            .filter(|(edge, _)| edge.edge_type == EdgeType::Contains)
+            .partition(|(edge, _)| edge.source_id == impl_node.id);
+        assert_eq!(parents.len(), 1);
+        assert_eq!(parents[0].1.node_type, NodeType::File);
+        let mut methods: Vec<(String, String)> = children
+            .into_iter()
+            .map(|(_, node)| {
+                assert_eq!(node.node_type, NodeType::Function);
+                (node.name, format!("{:?}-{:?}", node.start_line, node.end_line))
+            })
+            .collect();
+        methods.sort();
+        assert_eq!(
+            methods,
+            [
+                ("Engine::deduplicate_and_rank".to_string(), "Some(10)-Some(12)".to_string()),
+                ("Engine::new".to_string(), "Some(4)-Some(6)".to_string()),
+                ("Engine::rank".to_string(), "Some(8)-Some(8)".to_string()),
+            ]
+        );
+
+        let rank = nodes.iter().find(|n| n.name == "Engine::deduplicate_and_rank").unwrap();
+        let search = crate::search::SearchEngine::new(
+            &graph,
+            engine.search_cache(),
+            engine.config().clone(),
+        );
+        let fetched = search.fetch(&rank.id, None).unwrap().unwrap();
+        assert_eq!(
+            fetched.content,
+            "    fn deduplicate_and_rank(&self) {\n        self.rank();\n    }"
+        );
+    }
+
+    #[test]
+    fn test_markdown_sections_contain_their_subsections() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("README.md");
+        let doc = "# Install\nSteps.\n## Linux\napt\n## macOS\nbrew\n# Usage\nRun it.\n";
+        std::fs::write(&file, doc).unwrap();
+
+        let engine = HermesEngine::in_memory("test-md-sections").unwrap();
+        let graph = make_graph_for(&engine);
+        IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+
+        let nodes = graph.get_all_nodes().unwrap();
+        let name_of = |id: &str| nodes.iter().find(|n| n.id == id).unwrap().name.clone();
+        let conn = engine.db().lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT e.source_id, e.target_id FROM edges e
+                 JOIN nodes s ON s.id = e.source_id
+                 WHERE e.edge_type = 'contains' AND s.node_type = 'document'",
+            )
+            .unwrap();
+        let mut edges: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .unwrap()
+            .map(|row| {
+                let (source, target) = row.unwrap();
+                (name_of(&source), name_of(&target))
+            })
+            .collect();
+        edges.sort();
+        assert_eq!(
+            edges,
+            [
+                ("Install".to_string(), "Install > Linux".to_string()),
+                ("Install".to_string(), "Install > macOS".to_string()),
+            ]
+        );
+    }
+
+    fn import_edges(engine: &HermesEngine) -> Vec<(String, String)> {
+        let conn = engine.db().lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.name, t.name FROM edges e
+                 JOIN nodes s ON s.id = e.source_id JOIN nodes t ON t.id = e.target_id
+                 WHERE e.edge_type = 'imports' ORDER BY s.name, t.name",
+            )
+            .unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_imports_link_files_once() {
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("web")).unwrap();
+        std::fs::write(src.join("lib.rs"), "pub mod graph;\nuse std::fmt;\n").unwrap();
+        std::fs::write(src.join("graph.rs"), "use crate::Thing;\npub fn g() {}\n").unwrap();
+        std::fs::write(src.join("web/app.ts"), "import { a } from './api';\nimport x from 'x';\n")
+            .unwrap();
+        std::fs::write(src.join("web/api.ts"), "export const a = 1;\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-imports").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+
+        let name = |p: &str| src.join(p).to_string_lossy().to_string();
+        let expected = vec![
+            (name("graph.rs"), name("lib.rs")),
+            (name("web/app.ts"), name("web/api.ts")),
+        ];
+        assert_eq!(import_edges(&engine), expected);
+
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(import_edges(&engine), expected);
+    }
+
+    #[test]
+    fn test_imports_follow_edits_deletions_and_additions() {
+        let dir = TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("lib.rs"), "pub mod a;\n").unwrap();
+        std::fs::write(src.join("a.rs"), "use crate::b::B;\n").unwrap();
+        std::fs::write(src.join("b.rs"), "pub struct B;\n").unwrap();
+        std::fs::write(src.join("c.rs"), "pub struct C;\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-imports-stale").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        let name = |p: &str| src.join(p).to_string_lossy().to_string();
+        assert_eq!(import_edges(&engine), [(name("a.rs"), name("b.rs"))]);
+
+        std::fs::write(src.join("a.rs"), "use crate::c::C;\n").unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(import_edges(&engine), [(name("a.rs"), name("c.rs"))]);
+
+        // Without c.rs the import falls back to the crate root.
+        std::fs::remove_file(src.join("c.rs")).unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(import_edges(&engine), [(name("a.rs"), name("lib.rs"))]);
+
+        std::fs::write(src.join("c.rs"), "pub struct C(u8);\n").unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(import_edges(&engine), [(name("a.rs"), name("c.rs"))]);
+    }
+
+    #[test]
+    fn test_calls_edges_link_functions_across_files() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("search.rs"),
+            "pub fn search_all() {\n    rank_hits();\n    rank_hits();\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("rank.rs"), "pub fn rank_hits() {}\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-calls").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+
+        let callers = graph.callers_of("rank_hits").unwrap();
+        assert_eq!(callers.len(), 1);
+        let (caller, callee, weight) = &callers[0];
+        assert_eq!(
+            (caller.name.as_str(), callee.name.as_str(), *weight),
+            ("search_all", "rank_hits", 2.0)
+        );
+
+        // Rebuilding after a change neither duplicates nor keeps stale edges.
+        std::fs::write(dir.path().join("rank.rs"), "pub fn rank_hits() { }\n").unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert!(graph.callers_of("search_all").unwrap().is_empty());
+        assert!(!graph.callers_of("rank_hits").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_calls_relink_untouched_callers_of_added_functions() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("search.rs"), "pub fn search_all() {\n    rank_hits();\n}\n")
+            .unwrap();
+        std::fs::write(dir.path().join("other.rs"), "pub fn other_caller() {\n    helper_fn();\n}\n")
+            .unwrap();
+        std::fs::write(dir.path().join("helper.rs"), "pub fn helper_fn() {}\n").unwrap();
+
+        let engine = HermesEngine::in_memory("test-calls-incremental").unwrap();
+        let graph = make_graph_for(&engine);
+        let pipeline = IngestionPipeline::new(&graph);
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert!(graph.callers_of("rank_hits").unwrap().is_empty());
+        assert_eq!(graph.callers_of("helper_fn").unwrap().len(), 1);
+
+        // search.rs is unchanged, but now mentions a defined function.
+        std::fs::write(dir.path().join("rank.rs"), "pub fn rank_hits() {}\n").unwrap();
+        let report = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!(report.touched_paths().len(), 1);
+        let callers = graph.callers_of("rank_hits").unwrap();
+        assert_eq!(callers.len(), 1);
+        assert_eq!(callers[0].0.name, "search_all");
+        assert_eq!(graph.callers_of("helper_fn").unwrap().len(), 1);
+
+        std::fs::write(dir.path().join("rank.rs"), "pub fn rank_other() {}\n").unwrap();
+        pipeline.ingest_directory(dir.path()).unwrap();
+        assert!(graph.callers_of("rank_hits").unwrap().is_empty());
+        assert_eq!(graph.callers_of("helper_fn").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_oversized_files_are_skipped_and_unindexed() {
+        let dir = TempDir::new().unwrap();
+        let big = dir.path().join("generated.json");
+        std::fs::write(&big, "{}").unwrap();
+        std::fs::write(dir.path().join("schema.sql"), "CREATE TABLE t (id INT);").unwrap();
+
+        let engine = HermesEngine::in_memory("test-too-large").unwrap();
+        let graph = make_graph_for(&engine);
+        let options = CrawlOptions {
+            extra_extensions: vec!["sql".to_string()],
+            max_file_bytes: 64,
+            ..Default::default()
+        };
+        let pipeline = IngestionPipeline::new(&graph).with_crawl_options(options);
+        let first = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!((first.total_files, first.indexed, first.skipped_too_large), (2, 2, 0));
+        let sql = graph.get_all_nodes().unwrap();
+        assert!(sql.iter().any(|n| n.name == "schema.sql" && n.node_type == NodeType::File));
+
+        std::fs::write(&big, format!("{{\"data\": \"{}\"}}", "x".repeat(100))).unwrap();
+        let second = pipeline.ingest_directory(dir.path()).unwrap();
+        assert_eq!((second.total_files, second.skipped_too_large), (2, 1));
+        assert_eq!(second.deleted_paths, vec![big.to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn test_binary_files_are_skipped_and_invalid_utf8_is_indexed() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("icon.json"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let legacy = b"// caf\xe9\nfn legacy_entry() {}\n";
+        std::fs::write(dir.path().join("legacy.rs"), legacy).unwrap();
+
+        let engine = HermesEngine::in_memory("test-binary").unwrap();
+        let graph = make_graph_for(&engine);
+        let report = IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+        assert_eq!((report.total_files, report.skipped_binary), (2, 1));
+        assert_eq!((report.indexed, report.errors), (1, 0));
+        let nodes = graph.get_all_nodes().unwrap();
+        assert!(nodes.iter().any(|n| n.name == "legacy_entry"));
+        assert!(!nodes.iter().any(|n| n.name.ends_with("icon.json")));
+
+        let again = IngestionPipeline::new(&graph).ingest_directory(dir.path()).unwrap();
+        assert_eq!((again.skipped, again.errors), (1, 0));
     }
 
     #[test]
@@ -0,0 +1,235 @@
+use super::crawler;
+use crate::config::CrawlOptions;
+use crate::error::{HermesError, Result};
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{
+    new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// How often a blocked [`ProjectWatcher::next_batch`] checks its stop flag.
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+/// Watches a project for changed files through filesystem events. Events
+/// are debounced, and only paths the crawler would index (extensions,
+/// ignored directories, ignore files) are reported. Deleted files and the
+/// old name of a renamed one are reported too, so re-indexing a batch
+/// removes their nodes through `delete_nodes_for_file`.
+pub struct ProjectWatcher {
+    root: PathBuf,
+    options: CrawlOptions,
+    debounce: Duration,
+    events: Receiver<DebounceEventResult>,
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    pending: BTreeSet<PathBuf>,
+    retry_at: Option<Instant>,
+}
+
+impl ProjectWatcher {
+    /// Subscribes to events under `root`; only changes after this call are
+    /// reported, once no new event has arrived for `debounce`.
+    pub fn new(root: &Path, options: CrawlOptions, debounce: Duration) -> Result<Self> {
+        let root = root.canonicalize()?;
+        let (tx, events) = mpsc::channel();
+        let mut debouncer = new_debouncer(debounce, None, tx).map_err(watch_error)?;
+        debouncer.watch(&root, RecursiveMode::Recursive).map_err(watch_error)?;
+        Ok(Self {
+            root,
+            options,
+            debounce,
+            events,
+            _debouncer: debouncer,
+            pending: BTreeSet::new(),
+            retry_at: None,
+        })
+    }
+
+    /// Blocks until a burst of events touches watched paths, or requeued
+    /// paths are due, and returns them sorted; returns `None` once `stop`
+    /// is set.
+    pub fn next_batch(&mut self, stop: &AtomicBool) -> Option<Vec<PathBuf>> {
+        while !stop.load(Ordering::Relaxed) {
+            match self.events.recv_timeout(STOP_POLL) {
+                Ok(Ok(events)) => {
+                    self.collect(&events);
+                    if !self.pending.is_empty() {
+                        return Some(self.take_pending());
+                    }
+                }
+                Ok(Err(errors)) => {
+                    for error in errors {
+                        warn!(error = %error, "File watcher error");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.retry_at.is_some_and(|at| Instant::now() >= at) {
+                        return Some(self.take_pending());
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+        None
+    }
+
+    /// Hands `paths` out again with the next batch, or on their own once
+    /// the debounce period has passed.
+    pub fn requeue(&mut self, paths: Vec<PathBuf>) {
+        self.pending.extend(paths);
+        self.retry_at = Some(Instant::now() + self.debounce);
+    }
+
+    /// Adds the watched paths of `events` to the pending batch. Only
+    /// creations and renames bring in a directory that still exists; other
+    /// events on directories, and accesses, change no indexed content. When
+    /// the platform dropped events, the whole root is re-indexed.
+    fn collect(&mut self, events: &[DebouncedEvent]) {
+        for event in events {
+            if event.need_rescan() {
+                self.pending.insert(self.root.clone());
+                continue;
+            }
+            if matches!(event.kind, EventKind::Access(_) | EventKind::Other) {
+                continue;
+            }
+            let adds_dirs =
+                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)));
+            for path in &event.paths {
+                if path.is_dir() && !adds_dirs {
+                    continue;
+                }
+                if self.is_watched(path) {
+                    self.pending.insert(path.clone());
+                }
+            }
+        }
+    }
+
+    /// Whether the crawler would index `path`, or would have before it was
+    /// deleted. A deleted path without an extension may have been a
+    /// directory.
+    fn is_watched(&self, path: &Path) -> bool {
+        let is_dir = path.is_dir() || (!path.exists() && path.extension().is_none());
+        crawler::would_crawl(&self.root, path, is_dir, &self.options).unwrap_or_else(|error| {
+            warn!(path = %path.display(), error = %error, "Could not check a watched path");
+            false
+        })
+    }
+
+    fn take_pending(&mut self) -> Vec<PathBuf> {
+        self.retry_at = None;
+        let pending = std::mem::take(&mut self.pending);
+        let batch: BTreeSet<PathBuf> = pending.iter().map(|path| self.deleted_root(path)).collect();
+        batch.into_iter().collect()
+    }
+
+    /// The topmost deleted directory holding `path`, or `path` itself when
+    /// its parent still exists, so a batch never names a path whose parent
+    /// is gone and which thus cannot be resolved.
+    fn deleted_root(&self, path: &Path) -> PathBuf {
+        let mut path = path;
+        while let Some(parent) = path
+            .parent()
+            .filter(|parent| *parent != self.root && parent.starts_with(&self.root))
+            .filter(|parent| !parent.exists())
+        {
+            path = parent;
+        }
+        path.to_path_buf()
+    }
+}
+
+fn watch_error(error: notify::Error) -> HermesError {
+    HermesError::Io(std::io::Error::other(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+
+    const TEST_DEBOUNCE: Duration = Duration::from_millis(50);
+
+    /// Gathers batches until they cover `expected`, giving up after a few
+    /// seconds.
+    fn batches_covering(watcher: &mut ProjectWatcher, expected: &[PathBuf]) -> BTreeSet<PathBuf> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let timer = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(5));
+                stop.store(true, Ordering::Relaxed);
+            })
+        };
+        let mut seen = BTreeSet::new();
+        while !expected.iter().all(|path| seen.contains(path)) {
+            let Some(batch) = watcher.next_batch(&stop) else { break };
+            seen.extend(batch);
+        }
+        drop(timer);
+        seen
+    }
+
+    #[test]
+    fn events_cover_added_modified_and_deleted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (edited, removed) = (root.join("edited.rs"), root.join("gone.rs"));
+        for file in [&edited, &removed] {
+            fs::write(file, "fn f() {}").unwrap();
+        }
+        let mut watcher =
+            ProjectWatcher::new(&root, CrawlOptions::default(), TEST_DEBOUNCE).unwrap();
+
+        fs::create_dir(root.join("target")).unwrap();
+        fs::write(root.join("target/built.rs"), "").unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        fs::write(&edited, "fn f() { g() }").unwrap();
+        fs::remove_file(&removed).unwrap();
+        let added = root.join("added.rs");
+        fs::write(&added, "fn g() {}").unwrap();
+
+        let expected = [added, edited, removed];
+        let seen = batches_covering(&mut watcher, &expected);
+        assert_eq!(seen, expected.into_iter().collect());
+    }
+
+    #[test]
+    fn renames_and_deleted_directories_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("old/nested")).unwrap();
+        fs::write(root.join("old/nested/a.rs"), "").unwrap();
+        fs::write(root.join("before.rs"), "").unwrap();
+        let mut watcher =
+            ProjectWatcher::new(&root, CrawlOptions::default(), TEST_DEBOUNCE).unwrap();
+
+        fs::rename(root.join("before.rs"), root.join("after.rs")).unwrap();
+        fs::remove_dir_all(root.join("old")).unwrap();
+
+        let expected = [root.join("after.rs"), root.join("before.rs"), root.join("old")];
+        let seen = batches_covering(&mut watcher, &expected);
+        assert_eq!(seen, expected.into_iter().collect());
+    }
+
+    #[test]
+    fn requeued_paths_come_back_and_stop_ends_the_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().canonicalize().unwrap().join("lib.rs");
+        let mut watcher =
+            ProjectWatcher::new(dir.path(), CrawlOptions::default(), TEST_DEBOUNCE).unwrap();
+
+        watcher.requeue(vec![file.clone()]);
+        assert_eq!(watcher.next_batch(&AtomicBool::new(false)), Some(vec![file]));
+        assert_eq!(watcher.next_batch(&AtomicBool::new(true)), None);
+    }
+}
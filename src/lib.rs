@@ -1,64 +1,223 @@
 pub mod accounting;
-/// Optional Gemini embedding client — not used by the default search pipeline.
+#[cfg(feature = "async")]
+pub mod async_engine;
+mod busy;
+pub mod config;
+/// Embedding providers for the vector tier: local hashed vectors or the optional Gemini client.
 pub mod embedding;
+pub mod error;
+pub mod facade;
+pub mod mcp_prompts;
 pub mod mcp_server;
 pub mod mcp_tools_validation;
 pub mod graph;
 pub mod graph_builders;
+pub mod graph_export;
 pub mod graph_queries;
+pub mod graph_writes;
 pub mod ingestion;
+pub mod maintenance;
 pub mod pointer;
 pub mod schema;
 pub mod search;
 pub mod temporal;
 
-use anyhow::Result;
 use chrono::Local;
-use crate::pointer::PointerResponse;
-use rusqlite::Connection;
-use std::collections::HashMap;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+pub use crate::error::{HermesError, Result};
+#[cfg(feature = "async")]
+pub use crate::async_engine::AsyncHermesEngine;
+pub use crate::config::{
+    CrawlOptions, EmbeddingBackend, FtsWeights, HermesConfig, TokenizerOptions,
+};
+use crate::embedding::EmbeddingProvider;
+use crate::search::ann::AnnCache;
+use crate::search::{CacheStats, SearchCache};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-pub type SearchCacheMap = HashMap<String, (PointerResponse, Instant)>;
+/// Above this many changed files a targeted invalidation costs more than it
+/// saves, so the whole search cache is cleared instead.
+const TARGETED_INVALIDATION_MAX_FILES: usize = 64;
+
+/// Every table with a `project_id` column, in an order that deletes rows
+/// before the nodes they reference.
+const PROJECT_TABLES: [&str; 11] = [
+    "fts_content",
+    "embeddings",
+    "edges",
+    "temporal_facts",
+    "pointer_cache",
+    "nodes",
+    "file_hashes",
+    "chunk_hashes",
+    "config_registry",
+    "accounting",
+    "accounting_rollup",
+];
+
+/// A project found in the database by [`HermesEngine::list_projects`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectInfo {
+    pub project_id: String,
+    pub node_count: u64,
+    /// When a file of the project was last indexed (SQLite `datetime`, UTC).
+    pub last_indexed: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct HermesEngine {
     db: Arc<Mutex<Connection>>,
+    db_path: Option<PathBuf>,
     project_id: String,
     session_id: String,
-    search_cache: Arc<Mutex<SearchCacheMap>>,
+    search_cache: Arc<Mutex<SearchCache>>,
+    config: HermesConfig,
+    /// Built on first use, so engines that never embed (read-only ones,
+    /// tests) don't set up a network client.
+    embedder: Arc<OnceLock<Arc<dyn EmbeddingProvider>>>,
+    ann_cache: Arc<Mutex<AnnCache>>,
+    read_only: bool,
+    /// Set while [`Self::index`] runs on any clone.
+    indexing: Arc<AtomicBool>,
+    last_indexed: Arc<Mutex<Option<Instant>>>,
 }
 
 impl HermesEngine {
     pub fn new(db_path: &Path, project_id: &str) -> Result<Self> {
         let conn = Connection::open(db_path)?;
+        conn.busy_timeout(Duration::from_millis(HermesConfig::default().busy_timeout_ms))?;
         conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
         schema::run_migrations(&conn)?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
         Ok(Self {
             db: Arc::new(Mutex::new(conn)),
+            db_path: Some(db_path.to_path_buf()),
             project_id: project_id.to_string(),
             session_id: today_session_id(),
-            search_cache: Arc::new(Mutex::new(HashMap::new())),
+            search_cache: Arc::new(Mutex::new(SearchCache::new(
+                HermesConfig::default().cache_max_entries,
+            ))),
+            config: HermesConfig::default(),
+            embedder: Arc::default(),
+            ann_cache: Arc::new(Mutex::new(AnnCache::default())),
+            read_only: false,
+            indexing: Arc::default(),
+            last_indexed: Arc::default(),
+        })
+    }
+
+    /// Opens an existing database for queries only. The connection is
+    /// read-only, so it takes no write locks and runs no migrations; the
+    /// schema must already be at this build's version. Writes fail with
+    /// [`HermesError::ReadOnly`], except that searches and fetches skip
+    /// recording themselves in the accounting.
+    pub fn open_read_only(db_path: &Path, project_id: &str) -> Result<Self> {
+        if !db_path.is_file() {
+            return Err(HermesError::NotFound(format!("no database at {}", db_path.display())));
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let conn = Connection::open_with_flags(db_path, flags)?;
+        conn.busy_timeout(Duration::from_millis(HermesConfig::default().busy_timeout_ms))?;
+        schema::check_version(&conn)?;
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+            db_path: Some(db_path.to_path_buf()),
+            project_id: project_id.to_string(),
+            session_id: today_session_id(),
+            search_cache: Arc::new(Mutex::new(SearchCache::new(
+                HermesConfig::default().cache_max_entries,
+            ))),
+            config: HermesConfig::default(),
+            embedder: Arc::default(),
+            ann_cache: Arc::new(Mutex::new(AnnCache::default())),
+            read_only: true,
+            indexing: Arc::default(),
+            last_indexed: Arc::default(),
         })
     }
 
     pub fn in_memory(project_id: &str) -> Result<Self> {
         let conn = Connection::open_in_memory()?;
         schema::run_migrations(&conn)?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
         Ok(Self {
             db: Arc::new(Mutex::new(conn)),
+            db_path: None,
             project_id: project_id.to_string(),
             session_id: today_session_id(),
-            search_cache: Arc::new(Mutex::new(HashMap::new())),
+            search_cache: Arc::new(Mutex::new(SearchCache::new(
+                HermesConfig::default().cache_max_entries,
+            ))),
+            config: HermesConfig::default(),
+            embedder: Arc::default(),
+            ann_cache: Arc::new(Mutex::new(AnnCache::default())),
+            read_only: false,
+            indexing: Arc::default(),
+            last_indexed: Arc::default(),
         })
     }
 
+    /// Replaces the default tunables (cache TTL/sizes, short-circuit thresholds,
+    /// embedding backend, busy timeout).
+    pub fn with_config(mut self, config: HermesConfig) -> Self {
+        if let Ok(mut cache) = self.search_cache.lock() {
+            cache.set_capacity(config.cache_max_entries);
+        }
+        if config.busy_timeout_ms != self.config.busy_timeout_ms {
+            if let Ok(conn) = self.db.lock() {
+                let timeout = Duration::from_millis(config.busy_timeout_ms);
+                if let Err(e) = conn.busy_timeout(timeout) {
+                    tracing::warn!(error = %e, "Failed to set the database busy timeout");
+                }
+            }
+        }
+        if config.embedding_provider != self.config.embedding_provider
+            || config.vector_tokenizer != self.config.vector_tokenizer
+        {
+            self.embedder = Arc::default();
+        }
+        self.config = config;
+        self
+    }
+
+    /// Records this handle's operations under `session_id` instead of
+    /// today's date; clones made before the call keep their session.
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = session_id.into();
+        self
+    }
+
+    pub fn config(&self) -> &HermesConfig {
+        &self.config
+    }
+
+    /// The provider used for L2 vectors at ingestion and search time.
+    pub fn embedder(&self) -> Arc<dyn EmbeddingProvider> {
+        self.embedder.get_or_init(|| embedding::provider_for(&self.config)).clone()
+    }
+
     pub fn db(&self) -> &Arc<Mutex<Connection>> {
         &self.db
     }
 
+    /// The database file, which extra read-only connections can open;
+    /// `None` for in-memory engines.
+    pub fn db_path(&self) -> Option<&Path> {
+        self.db_path.as_deref()
+    }
+
+    /// Whether the engine was opened with [`Self::open_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     pub fn project_id(&self) -> &str {
         &self.project_id
     }
@@ -67,15 +226,122 @@ impl HermesEngine {
         &self.session_id
     }
 
-    pub fn search_cache(&self) -> Arc<Mutex<SearchCacheMap>> {
+    pub fn search_cache(&self) -> Arc<Mutex<SearchCache>> {
         self.search_cache.clone()
     }
 
+    /// ANN indexes built by the vector tier; dropped with the search cache.
+    pub fn ann_cache(&self) -> Arc<Mutex<AnnCache>> {
+        self.ann_cache.clone()
+    }
+
+    /// Hit/miss/eviction counters of the search cache since the engine opened.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.search_cache
+            .lock()
+            .map(|cache| cache.stats())
+            .unwrap_or_default()
+    }
+
+    /// The projects with indexed nodes or files in this database, by id.
+    pub fn list_projects(&self) -> Result<Vec<ProjectInfo>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT p.project_id,
+                    (SELECT COUNT(*) FROM nodes n WHERE n.project_id = p.project_id),
+                    (SELECT MAX(indexed_at) FROM file_hashes f WHERE f.project_id = p.project_id)
+             FROM (SELECT project_id FROM nodes UNION SELECT project_id FROM file_hashes) p
+             ORDER BY p.project_id",
+        )?;
+        let projects = stmt
+            .query_map([], |row| {
+                Ok(ProjectInfo {
+                    project_id: row.get(0)?,
+                    node_count: row.get::<_, i64>(1)?.max(0) as u64,
+                    last_indexed: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(projects)
+    }
+
+    /// Deletes every row of `project_id`, from the graph to its facts and
+    /// accounting, in one transaction. Returns the number of rows deleted.
+    pub fn delete_project(&self, project_id: &str) -> Result<u64> {
+        let deleted = {
+            let conn = self.db.lock()?;
+            let tx = busy::write_transaction(&conn)?;
+            let mut deleted = 0;
+            for table in PROJECT_TABLES {
+                let sql = format!("DELETE FROM {table} WHERE project_id = ?1");
+                deleted += tx.execute(&sql, params![project_id])? as u64;
+            }
+            tx.commit()?;
+            deleted
+        };
+        if project_id == self.project_id {
+            self.invalidate_search_cache();
+        }
+        Ok(deleted)
+    }
+
+    /// Makes the engine usable again after a panic unwound through a holder
+    /// of one of its locks, which would otherwise stay poisoned and fail
+    /// every later call. The connection is reused as is, since a transaction
+    /// open during the panic rolled back as it unwound; the caches, which
+    /// may have been left half-updated, are emptied.
+    pub fn recover_from_panic(&self) {
+        self.db.clear_poison();
+        self.search_cache.clear_poison();
+        self.ann_cache.clear_poison();
+        self.last_indexed.clear_poison();
+        self.invalidate_search_cache();
+    }
+
     pub fn invalidate_search_cache(&self) {
+        self.invalidate_search_responses();
+        self.invalidate_ann_cache();
+    }
+
+    /// Drops the cached search responses but keeps the ANN indexes, for
+    /// changes such as fact writes that alter responses but not vectors.
+    pub(crate) fn invalidate_search_responses(&self) {
         if let Ok(mut cache) = self.search_cache.lock() {
             cache.clear();
         }
     }
+
+    fn invalidate_ann_cache(&self) {
+        if let Ok(mut ann) = self.ann_cache.lock() {
+            ann.clear();
+        }
+    }
+
+    /// Drops only the cached responses that point into one of `file_paths`.
+    ///
+    /// Responses that never referenced a changed file are kept; they can at
+    /// worst miss a newly added match until the cache TTL expires. Falls back
+    /// to a full clear when many files changed at once.
+    pub fn invalidate_search_cache_for_files(&self, file_paths: &[String]) {
+        if file_paths.is_empty() {
+            return;
+        }
+        if file_paths.len() > TARGETED_INVALIDATION_MAX_FILES {
+            self.invalidate_search_cache();
+            return;
+        }
+        // Any changed vector makes the ANN index stale.
+        self.invalidate_ann_cache();
+        let changed: HashSet<&str> = file_paths.iter().map(String::as_str).collect();
+        if let Ok(mut cache) = self.search_cache.lock() {
+            cache.retain(|_, response| {
+                !response
+                    .pointers
+                    .iter()
+                    .any(|p| changed.contains(p.source.as_str()))
+            });
+        }
+    }
 }
 
 /// Returns today's local date as a session identifier (e.g. "2026-02-20").
@@ -89,6 +355,8 @@ fn today_session_id() -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pointer::PointerResponse;
+    use crate::search::SearchCacheKey;
 
     #[test]
     fn create_in_memory_engine() {
@@ -110,12 +378,234 @@ mod tests {
         {
             let cache_arc = engine.search_cache();
             let mut cache = cache_arc.lock().unwrap();
-            let dummy = PointerResponse::build(vec![], 0);
-            cache.insert("key".to_string(), (dummy, Instant::now()));
+            let dummy = PointerResponse::build(vec![], 0, 0);
+            let key = SearchCacheKey::new("test-inv", "key", &Default::default());
+            cache.insert(key, dummy);
         }
         engine.invalidate_search_cache();
         let cache_arc = engine.search_cache();
         let cache = cache_arc.lock().unwrap();
         assert!(cache.is_empty());
     }
+
+    fn cached_response_for(source: &str) -> PointerResponse {
+        let ptr = crate::pointer::Pointer {
+            id: "id".to_string(),
+            source: source.to_string(),
+            chunk: "chunk".to_string(),
+            lines: "1-2".to_string(),
+            relevance: 1.0,
+            summary: String::new(),
+            node_type: "function".to_string(),
+            last_modified: None,
+            content: None,
+            snippet: None,
+        };
+        PointerResponse::build(vec![ptr], 0, 0)
+    }
+
+    #[test]
+    fn targeted_invalidation_keeps_unrelated_entries() {
+        let engine = HermesEngine::in_memory("test-targeted").unwrap();
+        let key_a = SearchCacheKey::new("test-targeted", "a", &Default::default());
+        let key_b = SearchCacheKey::new("test-targeted", "b", &Default::default());
+        {
+            let cache_arc = engine.search_cache();
+            let mut cache = cache_arc.lock().unwrap();
+            cache.insert(key_a.clone(), cached_response_for("src/a.rs"));
+            cache.insert(key_b.clone(), cached_response_for("src/b.rs"));
+        }
+        engine.invalidate_search_cache_for_files(&["src/a.rs".to_string()]);
+        let cache_arc = engine.search_cache();
+        let cache = cache_arc.lock().unwrap();
+        assert!(!cache.contains_key(&key_a));
+        assert!(cache.contains_key(&key_b));
+    }
+
+    #[test]
+    fn with_config_resizes_cache_and_stats_report_it() {
+        let config = HermesConfig { cache_max_entries: 2, ..Default::default() };
+        let engine = HermesEngine::in_memory("test-stats").unwrap().with_config(config);
+        {
+            let cache_arc = engine.search_cache();
+            let mut cache = cache_arc.lock().unwrap();
+            for q in ["a", "b", "c"] {
+                let key = SearchCacheKey::new("test-stats", q, &Default::default());
+                cache.insert(key, PointerResponse::build(vec![], 0, 0));
+            }
+        }
+        let stats = engine.cache_stats();
+        assert_eq!(stats.capacity, 2);
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[test]
+    fn embedder_is_built_on_first_use_and_shared_by_clones() {
+        let engine = HermesEngine::in_memory("test-embedder").unwrap();
+        assert!(engine.embedder.get().is_none());
+        let hashed = HermesConfig {
+            embedding_provider: EmbeddingBackend::Hashed,
+            ..HermesConfig::default()
+        };
+        let engine = engine.with_config(hashed.clone());
+        let clone = engine.clone();
+        let model = engine.embedder().model().to_string();
+        assert!(clone.embedder.get().is_some());
+
+        let stopwords = !hashed.vector_tokenizer.stopwords;
+        let tokenizer = TokenizerOptions { stopwords, ..hashed.vector_tokenizer };
+        let reconfigured = clone.with_config(HermesConfig { vector_tokenizer: tokenizer, ..hashed });
+        assert!(reconfigured.embedder.get().is_none());
+        assert_ne!(reconfigured.embedder().model(), model);
+        assert_eq!(engine.embedder().model(), model);
+    }
+
+    #[test]
+    fn list_and_delete_projects_sharing_a_database() {
+        use crate::graph::{KnowledgeGraph, NodeType};
+        use crate::temporal::{FactType, TemporalStore};
+
+        let engine = HermesEngine::in_memory("alpha").unwrap();
+        for project in ["alpha", "beta"] {
+            let graph = KnowledgeGraph::new(engine.db().clone(), project);
+            for name in ["main", "run"] {
+                let node = graph
+                    .create_node_builder()
+                    .name(name)
+                    .node_type(NodeType::Function)
+                    .file_path("src/main.rs")
+                    .build();
+                graph.add_node(&node).unwrap();
+                graph.index_fts(&node, name).unwrap();
+            }
+            ingestion::hash_tracker::write_file_hash(
+                &engine.db().lock().unwrap(),
+                project,
+                "src/main.rs",
+                "h",
+                (0, 0),
+            )
+            .unwrap();
+        }
+        TemporalStore::new(engine.db().clone(), "beta")
+            .add_fact(None, FactType::Decision, "use sqlite", None)
+            .unwrap();
+
+        let projects = engine.list_projects().unwrap();
+        let ids: Vec<&str> = projects.iter().map(|p| p.project_id.as_str()).collect();
+        assert_eq!(ids, ["alpha", "beta"]);
+        assert_eq!(projects[1].node_count, 2);
+        assert!(projects[1].last_indexed.is_some());
+
+        // 2 nodes, 2 FTS rows, 1 file hash and 1 fact.
+        assert_eq!(engine.delete_project("beta").unwrap(), 6);
+        let projects = engine.list_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!((projects[0].project_id.as_str(), projects[0].node_count), ("alpha", 2));
+        assert_eq!(engine.delete_project("beta").unwrap(), 0);
+    }
+
+    #[test]
+    fn two_engines_write_to_one_database_file_concurrently() {
+        use crate::accounting::Accountant;
+        use crate::graph::KnowledgeGraph;
+        use crate::ingestion::IngestionPipeline;
+        use crate::temporal::{FactType, TemporalStore};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        let db_path = dir.path().join(".hermes.db");
+        let indexer = HermesEngine::new(&db_path, "shared").unwrap();
+        let recorder = HermesEngine::new(&db_path, "shared").unwrap();
+
+        let ingest = std::thread::spawn(move || {
+            let graph = KnowledgeGraph::new(indexer.db().clone(), indexer.project_id());
+            for round in 0..40 {
+                let body = format!("pub fn step_{round}() {{}}\npub fn shared() {{}}\n");
+                std::fs::write(src.join(format!("f{}.rs", round % 3)), body).unwrap();
+                IngestionPipeline::new(&graph).ingest_directory(&src).unwrap();
+            }
+        });
+        let accountant = Accountant::new(recorder.db().clone(), "shared", "s1");
+        let facts = TemporalStore::new(recorder.db().clone(), "shared");
+        for i in 0..200 {
+            accountant.record_query(&format!("query {i}"), 10, 20, 100).unwrap();
+            facts.add_fact(None, FactType::Learning, &format!("fact {i}"), None).unwrap();
+        }
+        ingest.join().unwrap();
+
+        assert_eq!(accountant.get_cumulative_stats().unwrap().total_queries, 200);
+        assert_eq!(facts.get_active_facts(None).unwrap().len(), 200);
+        let graph = KnowledgeGraph::new(recorder.db().clone(), "shared");
+        assert_eq!(graph.get_all_file_paths().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn read_only_engine_queries_but_refuses_writes() {
+        use crate::accounting::Operation;
+        use crate::graph::NodeType;
+        use crate::search::{SearchMode, SearchOptions};
+        use crate::temporal::FactType;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join(".hermes.db");
+        {
+            let writer = HermesEngine::new(&db_path, "ro").unwrap();
+            let graph = writer.graph();
+            let node = graph
+                .create_node_builder()
+                .name("parse_config")
+                .node_type(NodeType::Function)
+                .build();
+            graph.add_node(&node).unwrap();
+            graph.index_fts(&node, "fn parse_config() {}").unwrap();
+        }
+
+        let engine = HermesEngine::open_read_only(&db_path, "ro").unwrap();
+        assert!(engine.is_read_only());
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..SearchOptions::default() };
+        let response = engine.search("parse_config", &opts).unwrap();
+        let pointer = &response.pointers[0];
+        assert!(engine.fetch(&pointer.id, None).unwrap().is_some());
+        assert_eq!(engine.accountant().get_cumulative_stats().unwrap().total_queries, 0);
+
+        let node = engine.graph().create_node_builder().name("new").build();
+        assert!(matches!(engine.graph().add_node(&node), Err(HermesError::ReadOnly)));
+        let fact = engine.add_fact(None, FactType::Decision, "no", None);
+        assert!(matches!(fact, Err(HermesError::ReadOnly)));
+        let recorded = engine.accountant().record_operation(Operation::Search, "q", 1, 0, 1, None);
+        assert!(matches!(recorded, Err(HermesError::ReadOnly)));
+        let indexed = engine.index(dir.path(), &Default::default());
+        assert!(matches!(indexed, Err(HermesError::ReadOnly)));
+        let graph = engine.graph();
+        let pipeline = engine.ingestion_pipeline(&graph);
+        assert!(matches!(pipeline.ingest_directory(dir.path()), Err(HermesError::ReadOnly)));
+
+        Connection::open(&db_path)
+            .unwrap()
+            .execute("DELETE FROM schema_version WHERE version = ?1", [schema::LATEST_VERSION])
+            .unwrap();
+        assert!(matches!(
+            HermesEngine::open_read_only(&db_path, "ro"),
+            Err(HermesError::SchemaTooOld { .. })
+        ));
+    }
+
+    #[test]
+    fn targeted_invalidation_falls_back_to_full_clear() {
+        let engine = HermesEngine::in_memory("test-fallback").unwrap();
+        {
+            let cache_arc = engine.search_cache();
+            let mut cache = cache_arc.lock().unwrap();
+            let key = SearchCacheKey::new("test-fallback", "q", &Default::default());
+            cache.insert(key, cached_response_for("src/keep.rs"));
+        }
+        let many: Vec<String> = (0..=TARGETED_INVALIDATION_MAX_FILES)
+            .map(|i| format!("src/f{i}.rs"))
+            .collect();
+        engine.invalidate_search_cache_for_files(&many);
+        assert!(engine.search_cache().lock().unwrap().is_empty());
+    }
 }
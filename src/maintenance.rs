@@ -0,0 +1,407 @@
+use crate::busy;
+use crate::error::{HermesError, Result};
+use crate::schema;
+use crate::HermesEngine;
+use rusqlite::backup::{Backup, StepResult};
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A database housekeeping step run by [`HermesEngine::maintenance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceOp {
+    /// Rebuilds the file without the free pages left by deleted rows.
+    /// Skipped for in-memory engines.
+    Vacuum,
+    /// Gathers statistics on every index for the query planner, then runs
+    /// `PRAGMA optimize`.
+    Analyze,
+    /// `PRAGMA optimize` alone: re-analyzes only the tables whose statistics
+    /// look stale, so it is cheap enough to run after every reindex.
+    Optimize,
+    /// Copies the write-ahead log into the database and truncates it.
+    /// Skipped for in-memory engines, which have no log.
+    Checkpoint,
+    /// `PRAGMA integrity_check`. On a read-only engine the FTS5 index
+    /// cannot be validated, since that check writes, and is left out.
+    IntegrityCheck,
+}
+
+impl MaintenanceOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MaintenanceOp::Vacuum => "vacuum",
+            MaintenanceOp::Analyze => "analyze",
+            MaintenanceOp::Optimize => "optimize",
+            MaintenanceOp::Checkpoint => "checkpoint",
+            MaintenanceOp::IntegrityCheck => "integrity_check",
+        }
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "vacuum" => Some(MaintenanceOp::Vacuum),
+            "analyze" => Some(MaintenanceOp::Analyze),
+            "optimize" => Some(MaintenanceOp::Optimize),
+            "checkpoint" => Some(MaintenanceOp::Checkpoint),
+            "integrity_check" => Some(MaintenanceOp::IntegrityCheck),
+            _ => None,
+        }
+    }
+}
+
+/// What [`HermesEngine::maintenance`] did; a field is `None` when its
+/// operation was not requested or was skipped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MaintenanceReport {
+    /// Database size (page count times page size) before and after VACUUM.
+    pub vacuum: Option<VacuumReport>,
+    /// Whether ANALYZE or `PRAGMA optimize` ran.
+    pub analyzed: bool,
+    pub checkpoint: Option<CheckpointReport>,
+    /// Problems reported by `PRAGMA integrity_check`; empty when it found none.
+    pub integrity_errors: Option<Vec<String>>,
+    /// Requested operations that do not apply to this engine.
+    pub skipped: Vec<MaintenanceOp>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct VacuumReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CheckpointReport {
+    /// Another connection was reading or writing, so the log could not be
+    /// fully copied and truncated.
+    pub busy: bool,
+    pub wal_frames: u64,
+    pub checkpointed_frames: u64,
+}
+
+impl HermesEngine {
+    /// Runs `ops` in order on the shared connection. The connection's mutex
+    /// is held throughout, so no transaction of this engine is open while
+    /// VACUUM runs; other processes sharing the file are waited out.
+    pub fn maintenance(&self, ops: &[MaintenanceOp]) -> Result<MaintenanceReport> {
+        let conn = self.db().lock()?;
+        let mut report = MaintenanceReport::default();
+        let in_memory = self.db_path().is_none();
+        for &op in ops {
+            match op {
+                MaintenanceOp::Vacuum | MaintenanceOp::Checkpoint if in_memory => {
+                    report.skipped.push(op);
+                }
+                MaintenanceOp::Vacuum => {
+                    if !conn.is_autocommit() {
+                        return Err(HermesError::InvalidInput(
+                            "VACUUM cannot run inside an open transaction".to_string(),
+                        ));
+                    }
+                    let bytes_before = database_bytes(&conn)?;
+                    busy::retry_busy(|| conn.execute_batch("VACUUM;"))?;
+                    let bytes_after = database_bytes(&conn)?;
+                    report.vacuum = Some(VacuumReport { bytes_before, bytes_after });
+                }
+                MaintenanceOp::Analyze => {
+                    busy::retry_busy(|| conn.execute_batch("ANALYZE; PRAGMA optimize;"))?;
+                    report.analyzed = true;
+                }
+                MaintenanceOp::Optimize => {
+                    busy::retry_busy(|| conn.execute_batch("PRAGMA optimize;"))?;
+                    report.analyzed = true;
+                }
+                MaintenanceOp::Checkpoint => {
+                    let (busy, wal_frames, checkpointed_frames): (i64, i64, i64) =
+                        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+                            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                        })?;
+                    report.checkpoint = Some(CheckpointReport {
+                        busy: busy != 0,
+                        wal_frames: wal_frames.max(0) as u64,
+                        checkpointed_frames: checkpointed_frames.max(0) as u64,
+                    });
+                }
+                MaintenanceOp::IntegrityCheck => {
+                    let mut errors = integrity_errors(&conn)?;
+                    if self.is_read_only() {
+                        errors.retain(|e| !e.ends_with("attempt to write a readonly database"));
+                    }
+                    report.integrity_errors = Some(errors);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Copies the database to `path` while the engine keeps serving, and
+    /// returns the size of the copy in bytes.
+    ///
+    /// A file-backed database is read through its own connection in a single
+    /// backup step, which sees one snapshot and does not block writers in WAL
+    /// mode. The copy is written next to `path` and renamed over it when
+    /// complete, so an interrupted backup never leaves a truncated file there.
+    /// `path` must not be the database itself.
+    pub fn backup_to(&self, path: &Path) -> Result<u64> {
+        let same_file = |db_path: &Path| {
+            path.canonicalize().ok().is_some_and(|p| db_path.canonicalize().ok() == Some(p))
+        };
+        if self.db_path().is_some_and(same_file) {
+            return Err(HermesError::InvalidInput(format!(
+                "{} is the live database; back it up to another path",
+                path.display()
+            )));
+        }
+        let timeout = Duration::from_millis(self.config().busy_timeout_ms);
+        let partial = partial_path(path);
+        let copied = (|| -> Result<()> {
+            let mut dst = Connection::open(&partial)?;
+            match self.db_path() {
+                Some(db_path) => {
+                    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY;
+                    let src = Connection::open_with_flags(db_path, flags)?;
+                    src.busy_timeout(timeout)?;
+                    copy_database(&src, &mut dst, timeout)
+                }
+                None => copy_database(&*self.db().lock()?, &mut dst, timeout),
+            }
+        })();
+        if let Err(e) = copied {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+        std::fs::rename(&partial, path)?;
+        Ok(std::fs::metadata(path)?.len())
+    }
+
+    /// Replaces the whole database, every project in it, with the backup at
+    /// `path` and returns the number of bytes restored.
+    ///
+    /// The backup must pass `PRAGMA integrity_check` and must not be newer
+    /// than this build; an older one is migrated after the copy. The copy
+    /// goes through the engine's own connection under its mutex, so every
+    /// clone of the engine sees the restored data, and the search caches are
+    /// cleared afterwards.
+    pub fn restore_from(&self, path: &Path) -> Result<u64> {
+        if !path.is_file() {
+            return Err(HermesError::NotFound(format!("no backup at {}", path.display())));
+        }
+        // Not read-only: the FTS5 part of the integrity check writes to the
+        // file. Not created either, if it vanished since the check above.
+        let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+        let errors = integrity_errors(&src).map_err(|e| {
+            HermesError::InvalidInput(format!("{} cannot be checked: {e}", path.display()))
+        })?;
+        if !errors.is_empty() {
+            return Err(HermesError::InvalidInput(format!(
+                "{} fails the integrity check: {}",
+                path.display(),
+                errors.join("; ")
+            )));
+        }
+        let found = schema::schema_version(&src)?;
+        if found > schema::LATEST_VERSION {
+            let supported = schema::LATEST_VERSION;
+            return Err(HermesError::SchemaTooNew { found, supported });
+        }
+        {
+            let mut conn = self.db().lock()?;
+            let timeout = Duration::from_millis(self.config().busy_timeout_ms);
+            copy_database(&src, &mut conn, timeout)?;
+            schema::run_migrations(&conn)?;
+            conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+        }
+        self.invalidate_search_cache();
+        Ok(std::fs::metadata(path)?.len())
+    }
+}
+
+/// Copies every page of `src` into `dst` in one step, waiting while either
+/// database is locked by another connection. Fails with a busy database
+/// error once that has gone on for `timeout`.
+fn copy_database(src: &Connection, dst: &mut Connection, timeout: Duration) -> Result<()> {
+    let backup = Backup::new(src, dst)?;
+    let deadline = Instant::now() + timeout;
+    // `run_to_completion` only takes a positive page count, and copying in
+    // several steps restarts whenever another connection writes in between.
+    loop {
+        match backup.step(-1)? {
+            StepResult::Done => return Ok(()),
+            // More, Busy or Locked.
+            _ if Instant::now() >= deadline => {
+                let message = format!("database still locked after {}ms", timeout.as_millis());
+                let busy = rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY);
+                return Err(rusqlite::Error::SqliteFailure(busy, Some(message)).into());
+            }
+            _ => {
+                std::thread::sleep(Duration::from_millis(25));
+            }
+        }
+    }
+}
+
+/// `<path>.partial`, where [`HermesEngine::backup_to`] writes before renaming.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+/// The problems reported by `PRAGMA integrity_check`; empty when it found none.
+fn integrity_errors(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let errors = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter(|line| !matches!(line.as_deref(), Ok("ok")))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(errors)
+}
+
+fn database_bytes(conn: &Connection) -> Result<u64> {
+    let bytes: i64 = conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(bytes.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{KnowledgeGraph, NodeType};
+
+    #[test]
+    fn vacuum_shrinks_the_file_after_deletes_and_checks_pass() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = HermesEngine::new(&dir.path().join(".hermes.db"), "maint").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "maint");
+        let body = "x".repeat(4_000);
+        for i in 0..200 {
+            let node = graph
+                .create_node_builder()
+                .name(&format!("f{i}"))
+                .node_type(NodeType::Function)
+                .build();
+            graph.add_node(&node).unwrap();
+            graph.index_fts(&node, &body).unwrap();
+        }
+        engine.delete_project("maint").unwrap();
+
+        let report = engine
+            .maintenance(&[
+                MaintenanceOp::Checkpoint,
+                MaintenanceOp::Vacuum,
+                MaintenanceOp::Analyze,
+                MaintenanceOp::IntegrityCheck,
+            ])
+            .unwrap();
+        let vacuum = report.vacuum.unwrap();
+        assert!(vacuum.bytes_after < vacuum.bytes_before, "{vacuum:?}");
+        assert!(report.analyzed);
+        assert!(!report.checkpoint.unwrap().busy);
+        assert_eq!(report.integrity_errors, Some(vec![]));
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn backup_during_writes_restores_into_a_running_engine() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = HermesEngine::new(&dir.path().join(".hermes.db"), "maint").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "maint");
+        let add = |name: &str| {
+            let node =
+                graph.create_node_builder().name(name).node_type(NodeType::Function).build();
+            graph.add_node(&node).unwrap();
+        };
+        add("kept");
+
+        let writer = {
+            let graph = KnowledgeGraph::new(engine.db().clone(), "other");
+            std::thread::spawn(move || {
+                for i in 0..200 {
+                    let node = graph
+                        .create_node_builder()
+                        .name(&format!("w{i}"))
+                        .node_type(NodeType::Function)
+                        .build();
+                    graph.add_node(&node).unwrap();
+                }
+            })
+        };
+        let backup = dir.path().join("snapshot.db");
+        let bytes = engine.backup_to(&backup).unwrap();
+        writer.join().unwrap();
+        assert!(bytes > 0);
+        assert!(!partial_path(&backup).exists());
+
+        add("lost");
+        assert_eq!(engine.restore_from(&backup).unwrap(), bytes);
+        assert_eq!(graph.literal_search_by_name("kept", None).unwrap().len(), 1);
+        assert!(graph.literal_search_by_name("lost", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn backup_refuses_the_live_database_and_gives_up_on_a_held_lock() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join(".hermes.db");
+        let config = crate::HermesConfig { busy_timeout_ms: 100, ..Default::default() };
+        let engine = HermesEngine::new(&db_path, "maint").unwrap().with_config(config);
+        let graph = KnowledgeGraph::new(engine.db().clone(), "maint");
+        let node = graph.create_node_builder().name("kept").node_type(NodeType::Function).build();
+        graph.add_node(&node).unwrap();
+
+        let live = dir.path().join("./.hermes.db");
+        assert!(matches!(engine.backup_to(&live), Err(HermesError::InvalidInput(_))));
+        assert_eq!(graph.literal_search_by_name("kept", None).unwrap().len(), 1);
+
+        let backup = dir.path().join("snapshot.db");
+        engine.backup_to(&backup).unwrap();
+        let writer = Connection::open(&db_path).unwrap();
+        writer.execute_batch("BEGIN IMMEDIATE").unwrap();
+        let started = Instant::now();
+        let Err(HermesError::Database(error)) = engine.restore_from(&backup) else {
+            panic!("restore should fail while another connection writes");
+        };
+        assert!(busy::is_busy(&error), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        writer.execute_batch("ROLLBACK").unwrap();
+        assert_eq!(engine.restore_from(&backup).unwrap(), std::fs::metadata(&backup).unwrap().len());
+    }
+
+    #[test]
+    fn restore_refuses_a_file_that_is_not_a_sound_database() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = HermesEngine::in_memory("maint").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "maint");
+        let node = graph.create_node_builder().name("kept").node_type(NodeType::Function).build();
+        graph.add_node(&node).unwrap();
+
+        let garbage = dir.path().join("garbage.db");
+        std::fs::write(&garbage, vec![0x5a; 8192]).unwrap();
+        assert!(engine.restore_from(&garbage).is_err());
+        assert!(matches!(
+            engine.restore_from(&dir.path().join("missing.db")),
+            Err(HermesError::NotFound(_))
+        ));
+        assert_eq!(graph.literal_search_by_name("kept", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn in_memory_engines_skip_vacuum_and_checkpoint() {
+        let engine = HermesEngine::in_memory("maint").unwrap();
+        let report = engine
+            .maintenance(&[
+                MaintenanceOp::Vacuum,
+                MaintenanceOp::Checkpoint,
+                MaintenanceOp::Optimize,
+            ])
+            .unwrap();
+        assert_eq!(report.skipped, [MaintenanceOp::Vacuum, MaintenanceOp::Checkpoint]);
+        assert_eq!(report.vacuum, None);
+        assert!(report.analyzed);
+    }
+}
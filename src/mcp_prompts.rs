@@ -0,0 +1,142 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::search::{estimate_tokens, SearchMode, SearchOptions};
+use crate::{HermesEngine, HermesError};
+
+/// Tokens the `hermes_context` block is kept within when no budget is given.
+const DEFAULT_CONTEXT_BUDGET: u64 = 1500;
+
+/// The prompts offered by `prompts/list`.
+pub fn prompts_list() -> Value {
+    json!({
+        "prompts": [{
+            "name": "hermes_context",
+            "description": "Current project context: pointers to code about a topic, the active facts and an index summary, kept within a token budget.",
+            "arguments": [
+                { "name": "topic", "description": "What to search the code for; without it only facts and the summary are included", "required": false },
+                { "name": "token_budget", "description": "Approximate tokens for the whole block (default: 1500)", "required": false }
+            ]
+        }]
+    })
+}
+
+/// Renders the prompt named in `params`. MCP passes prompt arguments as
+/// strings; numbers are accepted too.
+pub fn prompt_get(engine: &HermesEngine, params: &Value) -> Result<Value> {
+    let name = params["name"].as_str().unwrap_or("");
+    if name != "hermes_context" {
+        return Err(HermesError::InvalidInput(format!("unknown prompt: {name}")).into());
+    }
+    let args = &params["arguments"];
+    let topic = args["topic"].as_str().map(str::trim).filter(|topic| !topic.is_empty());
+    let budget = match &args["token_budget"] {
+        Value::Null => Some(DEFAULT_CONTEXT_BUDGET),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        other => other.as_u64(),
+    }
+    .filter(|budget| *budget > 0)
+    .ok_or_else(|| {
+        HermesError::InvalidInput("hermes_context 'token_budget' must be a positive integer".into())
+    })?;
+
+    let text = render_context(engine, topic, budget)?;
+    let description = match topic {
+        Some(topic) => format!("Hermes context for \"{topic}\""),
+        None => "Hermes project context".to_string(),
+    };
+    Ok(json!({
+        "description": description,
+        "messages": [{ "role": "user", "content": { "type": "text", "text": text } }]
+    }))
+}
+
+/// The summary line is always included; the newest facts may take up to
+/// half of what is left of `budget`, and pointers for `topic` get the rest.
+/// The search is recorded in the accounting like any other.
+fn render_context(engine: &HermesEngine, topic: Option<&str>, budget: u64) -> Result<String> {
+    let graph = engine.graph().stats()?;
+    let totals = engine.stats(None)?.cumulative;
+    let summary = format!(
+        "{} nodes in {} files indexed; {} queries answered with {:.0}% fewer tokens than \
+         plain RAG.",
+        graph.nodes, graph.files, totals.total_queries, totals.cumulative_savings_pct
+    );
+    let mut remaining = budget.saturating_sub(estimate_tokens(&summary));
+
+    let mut facts = String::new();
+    let facts_budget = remaining / 2;
+    for fact in engine.temporal_store().get_active_facts(None)? {
+        let line = format!("- [{}] {}\n", fact.fact_type.as_str(), fact.content);
+        if estimate_tokens(&facts) + estimate_tokens(&line) > facts_budget {
+            break;
+        }
+        facts.push_str(&line);
+    }
+    remaining = remaining.saturating_sub(estimate_tokens(&facts));
+
+    let mut block = format!("# Project context: {}\n", engine.project_id());
+    if let Some(topic) = topic.filter(|_| remaining > 0) {
+        let opts = SearchOptions {
+            mode: SearchMode::Pointer,
+            token_budget: Some(remaining),
+            facts: false,
+            ..SearchOptions::default()
+        };
+        let pointers = engine.search_compact(topic, &opts)?;
+        block.push_str(&format!("\n## Code for \"{topic}\" (fetch a pointer for its content)\n"));
+        block.push_str(&pointers);
+        if !pointers.ends_with('\n') {
+            block.push('\n');
+        }
+    }
+    if !facts.is_empty() {
+        block.push_str("\n## Active facts\n");
+        block.push_str(&facts);
+    }
+    block.push_str("\n## Index\n");
+    block.push_str(&summary);
+    block.push('\n');
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::FactType;
+
+    #[test]
+    fn facts_stay_within_half_the_budget() {
+        let engine = HermesEngine::in_memory("prompts").unwrap();
+        for i in 0..50 {
+            let content = format!("constraint number {i} about how modules are laid out");
+            engine.add_fact(None, FactType::Constraint, &content, None).unwrap();
+        }
+
+        let small = render_context(&engine, None, 200).unwrap();
+        let listed = small.lines().filter(|line| line.starts_with("- [constraint]")).count();
+        assert!(listed > 0 && listed < 50, "{listed} facts in a 200-token block");
+        assert!(estimate_tokens(&small) <= 200 + 20);
+        assert!(small.contains("constraint number 49"), "newest facts come first");
+
+        let large = render_context(&engine, None, 10_000).unwrap();
+        assert_eq!(large.lines().filter(|line| line.starts_with("- [constraint]")).count(), 50);
+    }
+
+    #[test]
+    fn prompt_arguments_are_validated() {
+        let engine = HermesEngine::in_memory("prompts").unwrap();
+        let get = |params: Value| prompt_get(&engine, &params);
+        assert!(get(json!({ "name": "hermes_context" })).is_ok());
+        assert!(get(json!({ "name": "hermes_context", "arguments": { "token_budget": "300" } }))
+            .is_ok());
+        for params in [
+            json!({ "name": "other" }),
+            json!({ "name": "hermes_context", "arguments": { "token_budget": "lots" } }),
+            json!({ "name": "hermes_context", "arguments": { "token_budget": 0 } }),
+        ] {
+            let error = get(params.clone()).unwrap_err();
+            assert!(error.downcast_ref::<HermesError>().is_some(), "{params}");
+        }
+    }
+}
@@ -1,56 +1,500 @@
 
 use anyhow::Result;
 use serde_json::{json, Value};
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::panic::AssertUnwindSafe;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::{
-    accounting::Accountant,
-    graph::KnowledgeGraph,
-    ingestion::IngestionPipeline,
+    accounting::{CumulativeStats, Operation, TopQueryOrder, MAX_STATS_PERIODS},
+    facade::IndexOptions,
+    graph::{EdgeDirection, EdgeType, MetadataFilter},
+    ingestion::{
+        watcher::{ProjectWatcher, DEFAULT_DEBOUNCE},
+        IngestionReport,
+    },
+    maintenance::MaintenanceOp,
+    mcp_prompts::{prompt_get, prompts_list},
     mcp_tools_validation::{tool_check_consistency, tool_validate_env},
-    search::{SearchEngine, SearchMode},
-    temporal::{FactType, TemporalStore},
-    HermesEngine,
+    search::{
+        clamp_top_k, SearchMode, SearchOptions, DEFAULT_NEIGHBOR_LIMIT, DEFAULT_TRAVERSE_DEPTH,
+        DEFAULT_TRAVERSE_NODES, MAX_TOP_K, MAX_TRAVERSE_DEPTH, MAX_TRAVERSE_NODES,
+    },
+    temporal::FactType,
+    HermesEngine, HermesError,
 };
 
+/// JSON-RPC error codes; the -32000 to -32099 range is server-defined.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+const INTERNAL_ERROR: i32 = -32603;
+const NOT_FOUND: i32 = -32001;
+const EMBEDDING_UNAVAILABLE: i32 = -32002;
+const READ_ONLY: i32 = -32003;
+const ALREADY_INDEXING: i32 = -32004;
 
-fn spawn_auto_reindex(engine: HermesEngine, project_root: PathBuf) {
-    let interval_secs = std::env::var("HERMES_AUTO_INDEX_INTERVAL_SECS")
-        .ok()
-        .and_then(|v| v.parse::<u64>().ok())
-        .unwrap_or(300);
+/// Requests of one client answered at the same time.
+const REQUEST_WORKERS: usize = 4;
 
-    if interval_secs == 0 {
-        eprintln!("[hermes] auto-reindex disabled (HERMES_AUTO_INDEX_INTERVAL_SECS=0)");
-        return;
+/// A missing or malformed tool argument, answered with `INVALID_PARAMS`.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct InvalidParams(String);
+
+macro_rules! invalid_params {
+    ($($arg:tt)*) => {
+        anyhow::Error::new(InvalidParams(format!($($arg)*)))
+    };
+}
+
+
+/// A server thread that runs until asked to stop.
+struct BackgroundTask {
+    name: &'static str,
+    stop: Arc<AtomicBool>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+impl BackgroundTask {
+    fn spawn(name: &'static str, run: impl FnOnce(&AtomicBool) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        let handle = std::thread::spawn(move || run(&flag));
+        Self { name, stop, handle }
+    }
+
+    /// Sets the stop flag, wakes the thread if it is sleeping and waits up
+    /// to `timeout` for it to finish. A thread still busy then, such as one
+    /// mid-reindex, is left to finish on its own; returns whether it ended.
+    fn stop(self, timeout: Duration) -> bool {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.thread().unpark();
+        let deadline = Instant::now() + timeout;
+        while !self.handle.is_finished() {
+            if Instant::now() >= deadline {
+                eprintln!("[hermes] {} still running; not waiting for it", self.name);
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.handle.join();
+        true
+    }
+}
+
+/// How long [`run_with_io`] waits for background threads once its input ends.
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(5);
+
+/// The longest the auto-reindex thread sleeps before checking for a stop.
+const STOP_POLL: Duration = Duration::from_secs(1);
+
+/// Tells connected clients when a background re-index finishes, with a
+/// `notifications/hermes/index_complete` message, if `HERMES_INDEX_NOTIFICATIONS=1`.
+/// Off by default: some clients fail on notifications they do not know.
+#[derive(Default)]
+struct IndexNotifier {
+    enabled: bool,
+    /// The reply channel of each connected client, by subscription id.
+    clients: Mutex<Vec<(u64, mpsc::Sender<Value>)>>,
+    next_id: AtomicU64,
+}
+
+impl IndexNotifier {
+    fn from_env() -> Self {
+        let enabled =
+            matches!(std::env::var("HERMES_INDEX_NOTIFICATIONS").as_deref(), Ok("1" | "true"));
+        Self { enabled, ..Self::default() }
+    }
+
+    /// Adds a client's reply channel; the client must unsubscribe before
+    /// waiting for its writer, which runs until every sender is gone.
+    fn subscribe(&self, replies: &mpsc::Sender<Value>) -> Option<u64> {
+        if !self.enabled {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.lock().ok()?.push((id, replies.clone()));
+        Some(id)
+    }
+
+    fn unsubscribe(&self, subscription: Option<u64>) {
+        if let (Some(id), Ok(mut clients)) = (subscription, self.clients.lock()) {
+            clients.retain(|(client, _)| *client != id);
+        }
+    }
+
+    /// `trigger` names what re-indexed: `auto_reindex` or `watch`.
+    fn index_complete(&self, trigger: &str, report: &IngestionReport) {
+        if !self.enabled {
+            return;
+        }
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/hermes/index_complete",
+            "params": {
+                "trigger": trigger,
+                "total_files": report.total_files, "indexed": report.indexed,
+                "skipped": report.skipped, "errors": report.errors,
+                "deleted": report.deleted_paths.len(), "renamed": report.renamed_paths.len(),
+                "nodes_created": report.nodes_created,
+            }
+        });
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|(_, client)| client.send(notification.clone()).is_ok());
+        }
+    }
+}
+
+struct AutoReindex {
+    interval: Duration,
+    retention_days: Option<u64>,
+    optimize: bool,
+}
+
+impl AutoReindex {
+    fn from_env() -> Option<Self> {
+        let interval_secs = std::env::var("HERMES_AUTO_INDEX_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300);
+
+        if interval_secs == 0 {
+            eprintln!("[hermes] auto-reindex disabled (HERMES_AUTO_INDEX_INTERVAL_SECS=0)");
+            return None;
+        }
+
+        let retention_days = std::env::var("HERMES_ACCOUNTING_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|days| *days > 0);
+        let optimize =
+            matches!(std::env::var("HERMES_AUTO_OPTIMIZE").as_deref(), Ok("1" | "true"));
+        Some(Self { interval: Duration::from_secs(interval_secs), retention_days, optimize })
+    }
+
+    /// Re-indexes every `interval` until stopped. A cycle is skipped when an
+    /// index, such as a `hermes_index` call, finished within the interval.
+    fn spawn(
+        self,
+        engine: HermesEngine,
+        project_root: PathBuf,
+        notifier: Arc<IndexNotifier>,
+    ) -> BackgroundTask {
+        BackgroundTask::spawn("auto-reindex", move |stop| {
+            let secs = self.interval.as_secs();
+            eprintln!("[hermes] auto-reindex thread started (interval={}s)", secs);
+            while sleep_unless_stopped(self.interval, stop) {
+                if !reindex_due(engine.last_indexed(), self.interval) {
+                    continue;
+                }
+                self.cycle(&engine, &project_root, &notifier);
+            }
+        })
     }
 
-    std::thread::spawn(move || {
-        eprintln!("[hermes] auto-reindex thread started (interval={}s)", interval_secs);
-        loop {
-            std::thread::sleep(std::time::Duration::from_secs(interval_secs));
-            let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-            let pipeline = IngestionPipeline::new(&graph);
-            match pipeline.ingest_directory(&project_root) {
-                Ok(report) => eprintln!(
+    fn cycle(&self, engine: &HermesEngine, project_root: &Path, notifier: &IndexNotifier) {
+        match engine.index(project_root, &IndexOptions::default()) {
+            Ok(report) => {
+                eprintln!(
                     "[hermes] auto-reindex complete: {} indexed, {} skipped, {} errors",
                     report.indexed, report.skipped, report.errors
-                ),
-                Err(e) => eprintln!("[hermes] auto-reindex failed: {}", e),
+                );
+                notifier.index_complete("auto_reindex", &report);
             }
+            Err(HermesError::AlreadyIndexing) => return,
+            Err(e) => eprintln!("[hermes] auto-reindex failed: {}", e),
         }
-    });
+        if let Some(days) = self.retention_days {
+            let age = std::time::Duration::from_secs(days * 86_400);
+            match engine.accountant().prune_older_than(age) {
+                Ok(0) => {}
+                Ok(n) => eprintln!("[hermes] pruned {} accounting rows older than {}d", n, days),
+                Err(e) => eprintln!("[hermes] accounting prune failed: {}", e),
+            }
+        }
+        if self.optimize {
+            if let Err(e) = engine.maintenance(&[MaintenanceOp::Optimize]) {
+                eprintln!("[hermes] database optimize failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Sleeps `duration` in slices of at most [`STOP_POLL`]; returns false,
+/// early, once `stop` is set.
+fn sleep_unless_stopped(duration: Duration, stop: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::park_timeout((deadline - now).min(STOP_POLL));
+    }
+}
+
+/// Whether an index last finished at `last_indexed` is an interval old.
+fn reindex_due(last_indexed: Option<Instant>, interval: Duration) -> bool {
+    last_indexed.is_none_or(|at| at.elapsed() >= interval)
+}
+
+/// Re-indexes files as they change until `stop` is set, passing each
+/// batch's report to `on_report`. Batches go through [`HermesEngine::index`],
+/// so they never overlap another index; one that arrives meanwhile is
+/// retried. A batch that fails to index is logged and dropped; only failing
+/// to set up the watcher ends the watch.
+pub fn watch(
+    engine: &HermesEngine,
+    project_root: &Path,
+    stop: &AtomicBool,
+    mut on_report: impl FnMut(&IngestionReport),
+) -> Result<()> {
+    let mut watcher =
+        ProjectWatcher::new(project_root, engine.config().crawl.clone(), DEFAULT_DEBOUNCE)?;
+    while let Some(changed) = watcher.next_batch(stop) {
+        let opts = IndexOptions { paths: changed, ..Default::default() };
+        match engine.index(project_root, &opts) {
+            Ok(report) => on_report(&report),
+            Err(HermesError::AlreadyIndexing) => watcher.requeue(opts.paths),
+            Err(e) => {
+                eprintln!("[hermes] watch failed to index {} paths: {}", opts.paths.len(), e)
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs [`watch`] on a thread when `HERMES_WATCH=1`; stopping the task
+/// ends it after its current batch.
+fn spawn_watch(
+    engine: HermesEngine,
+    project_root: PathBuf,
+    notifier: Arc<IndexNotifier>,
+) -> Option<BackgroundTask> {
+    if !matches!(std::env::var("HERMES_WATCH").as_deref(), Ok("1" | "true")) {
+        return None;
+    }
+    Some(BackgroundTask::spawn("watch", move |stop| {
+        eprintln!("[hermes] watching {} for changes", project_root.display());
+        let result = watch(&engine, &project_root, stop, |report| {
+            eprintln!(
+                "[hermes] watch re-indexed {} files ({} deleted, {} errors)",
+                report.indexed,
+                report.deleted_paths.len(),
+                report.errors
+            );
+            notifier.index_complete("watch", report);
+        });
+        if let Err(e) = result {
+            eprintln!("[hermes] watch stopped: {}", e);
+        }
+    }))
 }
 
+/// Serves one client over stdin/stdout. A read-only engine serves queries
+/// without re-indexing in the background.
 pub fn run(engine: &HermesEngine, project_root: &Path) -> Result<()> {
-    spawn_auto_reindex(engine.clone(), project_root.to_path_buf());
+    run_with_io(engine, project_root, io::stdin().lock(), io::stdout())
+}
+
+/// [`run`] over any pair of streams: answers newline-delimited JSON-RPC
+/// requests from `input` on `output` until `input` ends, then stops the
+/// auto-reindex and watch threads, waiting up to [`SHUTDOWN_WAIT`] for them.
+pub fn run_with_io(
+    engine: &HermesEngine,
+    project_root: &Path,
+    input: impl BufRead,
+    output: impl Write + Send,
+) -> Result<()> {
+    let notifier = Arc::new(IndexNotifier::from_env());
+    let background = spawn_background(engine, project_root, &notifier);
+    let served = serve(engine, project_root, &notifier, input, output);
+    for task in background {
+        task.stop(SHUTDOWN_WAIT);
+    }
+    served
+}
+
+/// Serves every client that connects to `addr` until the process exits:
+/// `unix:<path>` is a Unix socket, anything else a TCP address such as
+/// `127.0.0.1:7420`. Clients share the engine and its caches; each
+/// connection gets its own thread and accounting session.
+pub fn run_listener(engine: &HermesEngine, project_root: &Path, addr: &str) -> Result<()> {
+    let listener = Listener::bind(addr)?;
+    eprintln!("[hermes] listening on {addr}");
+    let notifier = Arc::new(IndexNotifier::from_env());
+    let _background = spawn_background(engine, project_root, &notifier);
+    serve_connections(engine, project_root, &notifier, &listener)
+}
+
+fn spawn_background(
+    engine: &HermesEngine,
+    project_root: &Path,
+    notifier: &Arc<IndexNotifier>,
+) -> Vec<BackgroundTask> {
+    if engine.is_read_only() {
+        eprintln!("[hermes] index opened read-only; auto-reindex and watch are off");
+        return Vec::new();
+    }
+    let auto_reindex = AutoReindex::from_env()
+        .map(|auto| auto.spawn(engine.clone(), project_root.to_path_buf(), notifier.clone()));
+    let watch = spawn_watch(engine.clone(), project_root.to_path_buf(), notifier.clone());
+    auto_reindex.into_iter().chain(watch).collect()
+}
+
+type ClientStream = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn bind(addr: &str) -> Result<Self> {
+        if let Some(path) = addr.strip_prefix("unix:") {
+            return Self::bind_unix(Path::new(path));
+        }
+        Ok(Listener::Tcp(TcpListener::bind(addr)?))
+    }
+
+    /// A socket file left by a server that did not shut down cleanly is
+    /// replaced; one a server still answers on, or any other file at `path`,
+    /// is an error.
+    #[cfg(unix)]
+    fn bind_unix(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                anyhow::bail!("a server is already listening on {}", path.display());
+            }
+            std::fs::remove_file(path)?;
+        }
+        Ok(Listener::Unix(UnixListener::bind(path)?))
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: &Path) -> Result<Self> {
+        anyhow::bail!("Unix sockets are not supported on this platform")
+    }
+
+    fn accept(&self) -> io::Result<ClientStream> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+                Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+            }
+        }
+    }
+}
 
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut out = stdout.lock();
+/// Accepts connections forever; a failed accept or a connection that drops
+/// mid-response is logged and does not stop the server.
+fn serve_connections(
+    engine: &HermesEngine,
+    project_root: &Path,
+    notifier: &Arc<IndexNotifier>,
+    listener: &Listener,
+) -> Result<()> {
+    let connections = AtomicU64::new(0);
+    loop {
+        let (input, output) = match listener.accept() {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("[hermes] accept failed: {}", e);
+                continue;
+            }
+        };
+        let n = connections.fetch_add(1, Ordering::Relaxed) + 1;
+        let session_id = format!("{}#{}", engine.session_id(), n);
+        let engine = engine.clone().with_session_id(session_id);
+        let project_root = project_root.to_path_buf();
+        let notifier = notifier.clone();
+        std::thread::spawn(move || {
+            let input = BufReader::new(input);
+            if let Err(e) = serve(&engine, &project_root, &notifier, input, output) {
+                eprintln!("[hermes] connection {} closed: {}", n, e);
+            }
+        });
+    }
+}
 
-    for line in stdin.lock().lines() {
+/// Reads requests on the calling thread and answers them on
+/// `REQUEST_WORKERS` threads, so a long `hermes_index` does not hold up a
+/// `tools/list` behind it. Replies, and the notifications `notifier` sends,
+/// go out through one writer thread a whole line at a time.
+fn serve(
+    engine: &HermesEngine,
+    project_root: &Path,
+    notifier: &IndexNotifier,
+    input: impl BufRead,
+    mut out: impl Write + Send,
+) -> Result<()> {
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let (reply_tx, reply_rx) = mpsc::channel::<Value>();
+    let job_rx = Mutex::new(job_rx);
+    let subscription = notifier.subscribe(&reply_tx);
+    std::thread::scope(|scope| {
+        let writer = scope.spawn(move || -> Result<()> {
+            for reply in reply_rx {
+                writeln!(out, "{}", serde_json::to_string(&reply)?)?;
+                out.flush()?;
+            }
+            Ok(())
+        });
+        for _ in 0..REQUEST_WORKERS {
+            let (job_rx, reply_tx) = (&job_rx, reply_tx.clone());
+            scope.spawn(move || loop {
+                // The receiver is locked only while waiting, not while the job runs.
+                let job = job_rx.lock().ok().and_then(|jobs| jobs.recv().ok());
+                let Some(Job { id, method, params }) = job else { break };
+                let reply = answer(engine, &id, || {
+                    dispatch(engine, project_root, notifier, &method, &params)
+                });
+                if reply_tx.send(reply).is_err() {
+                    break;
+                }
+            });
+        }
+        let read = read_requests(input, &job_tx, &reply_tx);
+        drop((job_tx, reply_tx));
+        notifier.unsubscribe(subscription);
+        let written = writer.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        read.and(written)
+    })
+}
+
+struct Job {
+    id: Value,
+    method: String,
+    params: Value,
+}
+
+/// Queues each request in `input` as a job and answers lines that are not
+/// requests at once; stops at the end of `input` or once nobody is left to
+/// take jobs or write replies.
+fn read_requests(
+    input: impl BufRead,
+    jobs: &mpsc::Sender<Job>,
+    replies: &mpsc::Sender<Value>,
+) -> Result<()> {
+    for line in input.lines() {
         let line = line?;
         if line.trim().is_empty() {
             continue;
@@ -59,52 +503,201 @@ pub fn run(engine: &HermesEngine, project_root: &Path) -> Result<()> {
         let msg: Value = match serde_json::from_str(&line) {
             Ok(v) => v,
             Err(e) => {
-                write_error(&mut out, &Value::Null, -32700, &format!("parse error: {e}"))?;
+                let message = format!("parse error: {e}");
+                if replies.send(reply_error(&Value::Null, PARSE_ERROR, &message)).is_err() {
+                    break;
+                }
                 continue;
             }
         };
 
-        let id = msg.get("id").cloned().unwrap_or(Value::Null);
-        let method = msg["method"].as_str().unwrap_or("");
-        let params = msg.get("params").cloned().unwrap_or(Value::Null);
-
-        if method.starts_with("notifications/") {
-            continue;
+        let sent = match classify(&msg) {
+            Incoming::Notification => true,
+            Incoming::Invalid { id, message } => {
+                replies.send(reply_error(&id, INVALID_REQUEST, message)).is_ok()
+            }
+            Incoming::Request { id, method, params } => {
+                let job = Job { id, method: method.to_string(), params: params.clone() };
+                jobs.send(job).is_ok()
+            }
+        };
+        if !sent {
+            break;
         }
+    }
+    Ok(())
+}
+
+/// One parsed line of input, sorted by whether it gets a reply.
+enum Incoming<'a> {
+    Request { id: Value, method: &'a str, params: &'a Value },
+    /// `notifications/*` messages are never answered.
+    Notification,
+    /// Answered with `INVALID_REQUEST` under `id`, null when the request's
+    /// own id is missing or unusable.
+    Invalid { id: Value, message: &'static str },
+}
 
-        let result = dispatch(engine, project_root, method, &params);
-        match result {
-            Ok(payload) => write_ok(&mut out, &id, payload)?,
-            Err(e) => write_error(&mut out, &id, -32603, &e.to_string())?,
+fn classify(msg: &Value) -> Incoming<'_> {
+    let Some(fields) = msg.as_object() else {
+        return Incoming::Invalid { id: Value::Null, message: "request must be a JSON object" };
+    };
+    let method = fields.get("method").and_then(Value::as_str);
+    if method.is_some_and(|m| m.starts_with("notifications/")) {
+        return Incoming::Notification;
+    }
+    let id = match fields.get("id") {
+        Some(id @ (Value::Number(_) | Value::String(_))) => id.clone(),
+        None | Some(Value::Null) => {
+            return Incoming::Invalid { id: Value::Null, message: "request is missing an id" };
+        }
+        Some(_) => {
+            let message = "request id must be a string or a number";
+            return Incoming::Invalid { id: Value::Null, message };
+        }
+    };
+    match method {
+        Some(method) => {
+            Incoming::Request { id, method, params: fields.get("params").unwrap_or(&Value::Null) }
         }
+        None => Incoming::Invalid { id, message: "request is missing a method" },
     }
-    Ok(())
 }
 
 
 fn dispatch(
     engine: &HermesEngine,
     project_root: &Path,
+    notifier: &IndexNotifier,
     method: &str,
     params: &Value,
 ) -> Result<Value> {
     match method {
-        "initialize" => Ok(handle_initialize()),
+        "initialize" => Ok(handle_initialize(notifier.enabled)),
         "tools/list" => Ok(handle_tools_list()),
         "tools/call" => handle_tool_call(engine, project_root, params),
-        other => anyhow::bail!("unknown method: {other}"),
+        "resources/list" => handle_resources_list(engine, project_root, params),
+        "resources/read" => handle_resources_read(engine, project_root, params),
+        "prompts/list" => Ok(prompts_list()),
+        "prompts/get" => prompt_get(engine, params),
+        other => Err(UnknownMethod(other.to_string()).into()),
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("unknown method: {0}")]
+struct UnknownMethod(String);
+
 
-fn handle_initialize() -> Value {
+/// The tool list never changes. Index notifications are not part of MCP, so
+/// they are announced as an experimental capability, only when enabled.
+fn handle_initialize(index_notifications: bool) -> Value {
+    let mut capabilities = json!({
+        "tools": { "listChanged": false },
+        "resources": { "subscribe": false, "listChanged": false },
+        "prompts": { "listChanged": false },
+    });
+    if index_notifications {
+        capabilities["experimental"] = json!({ "hermes": { "indexNotifications": true } });
+    }
     json!({
         "protocolVersion": "2024-11-05",
-        "capabilities": { "tools": { "listChanged": false } },
+        "capabilities": capabilities,
         "serverInfo": { "name": "Hermes", "version": env!("CARGO_PKG_VERSION") }
     })
 }
 
+/// Resources listed per `resources/list` page.
+const RESOURCES_PAGE_SIZE: usize = 200;
+const FILE_URI_PREFIX: &str = "hermes://file/";
+const FACTS_URI: &str = "hermes://facts";
+
+/// Lists the active facts, then every indexed file, by path. `nextCursor` is
+/// the last path of a full page; passing it back continues after it.
+fn handle_resources_list(
+    engine: &HermesEngine,
+    project_root: &Path,
+    params: &Value,
+) -> Result<Value> {
+    let cursor = match params.get("cursor") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(cursor)) => Some(cursor.as_str()),
+        Some(_) => anyhow::bail!(invalid_params!("resources/list 'cursor' must be a string")),
+    };
+    let paths = engine.graph().file_paths_after(cursor, RESOURCES_PAGE_SIZE)?;
+    let mut resources = Vec::with_capacity(paths.len() + 1);
+    if cursor.is_none() {
+        resources.push(json!({
+            "uri": FACTS_URI,
+            "name": "Active facts",
+            "description": "Decisions, conventions and other facts recorded with hermes_fact",
+            "mimeType": "application/json",
+        }));
+    }
+    for path in &paths {
+        let relative = Path::new(path).strip_prefix(project_root).unwrap_or(Path::new(path));
+        let name = relative.to_string_lossy();
+        resources.push(json!({
+            "uri": format!("{FILE_URI_PREFIX}{name}"),
+            "name": name,
+            "mimeType": mime_type(Path::new(path)),
+        }));
+    }
+    let mut result = json!({ "resources": resources });
+    if paths.len() == RESOURCES_PAGE_SIZE {
+        result["nextCursor"] = json!(paths.last());
+    }
+    Ok(result)
+}
+
+/// Reads a file resource through [`HermesEngine::fetch`], so it is recorded
+/// as a fetch, or the active facts as JSON.
+fn handle_resources_read(
+    engine: &HermesEngine,
+    project_root: &Path,
+    params: &Value,
+) -> Result<Value> {
+    let uri = params["uri"].as_str().unwrap_or("");
+    anyhow::ensure!(!uri.is_empty(), invalid_params!("resources/read requires 'uri'"));
+    if uri == FACTS_URI {
+        let facts = engine.temporal_store().get_active_facts(None)?;
+        let text = serde_json::to_string_pretty(&facts)?;
+        return Ok(json!({
+            "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }]
+        }));
+    }
+    let not_found = || HermesError::NotFound(format!("no such resource: {uri}"));
+    let relative = uri.strip_prefix(FILE_URI_PREFIX).ok_or_else(not_found)?;
+    let path = project_root.join(relative);
+    let file_node = engine.graph().file_node(&path.to_string_lossy())?.ok_or_else(not_found)?;
+    let fetched = engine.fetch(&file_node.id, None)?.ok_or_else(not_found)?;
+    Ok(json!({
+        "contents": [{ "uri": uri, "mimeType": mime_type(&path), "text": fetched.content }]
+    }))
+}
+
+/// The MIME type of an indexed file, by extension.
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
+        "rs" => "text/x-rust",
+        "ts" | "tsx" => "text/x-typescript",
+        "js" | "jsx" => "text/javascript",
+        "py" => "text/x-python",
+        "go" => "text/x-go",
+        "java" => "text/x-java",
+        "kt" => "text/x-kotlin",
+        "c" | "h" => "text/x-c",
+        "cpp" | "hpp" => "text/x-c++",
+        "md" => "text/markdown",
+        "css" => "text/css",
+        "json" => "application/json",
+        "toml" => "application/toml",
+        "yml" | "yaml" => "application/yaml",
+        "sh" => "application/x-sh",
+        _ => "text/plain",
+    }
+}
+
 fn handle_tools_list() -> Value {
     json!({
         "tools": [
@@ -113,28 +706,108 @@ fn handle_tools_list() -> Value {
                 "description": "Search the codebase knowledge graph. Returns pointers (not full content). Records token savings in accounting.",
                 "inputSchema": {
                     "type": "object",
-                    "properties": { "query": { "type": "string", "description": "Natural-language or keyword search query" } },
+                    "properties": {
+                        "query": { "type": "string", "description": "Natural-language or keyword search query" },
+                        "top_k": { "type": "integer", "minimum": 1, "maximum": MAX_TOP_K, "description": "Maximum number of pointers to return (default 10, clamped to 1-100)" },
+                        "mode":  { "type": "string", "enum": ["pointer", "smart", "full"], "description": "pointer = locations only, smart = plus a short preview (default), full = plus chunk content up to a token budget" },
+                        "path_filter": { "type": "string", "description": "Restrict results to files matching a path prefix (src/search/) or glob (**/*.md)" },
+                        "metadata": { "type": "array", "items": { "type": "string" }, "description": "Only return nodes whose metadata has all of these values, e.g. [\"language=rust\", \"visibility=pub\"]" },
+                        "snippets": { "type": "boolean", "description": "Include a short excerpt explaining each match (default true; false saves tokens)" },
+                        "expand": { "type": "boolean", "description": "Append up to 5 contained/called neighbors of each of the top 3 hits (default false)" },
+                        "token_budget": { "type": "integer", "minimum": 1, "description": "Return as many pointers as fit in this many tokens instead of top_k (mutually exclusive with top_k)" },
+                        "min_score": { "type": "number", "minimum": 0, "description": "Drop results whose fused relevance is below this; may return fewer than top_k pointers, or none" },
+                        "explain": { "type": "boolean", "description": "Attach a 'debug' breakdown of per-tier ranks and scores, the short-circuit decision and cache state (default false; never cached)" },
+                        "facts": { "type": "boolean", "description": "Attach up to 3 active facts about the returned nodes or the query's keywords (default true; false saves tokens)" },
+                        "format": { "type": "string", "enum": ["json", "compact"], "description": "json (default) or compact: one plain-text line per pointer plus an accounting summary, far fewer tokens" }
+                    },
                     "required": ["query"]
                 }
             },
             {
                 "name": "hermes_fetch",
-                "description": "Fetch full content for a specific knowledge-graph node by ID returned by hermes_search.",
+                "description": "Fetch full content for a specific knowledge-graph node by the pointer ID (p1, p2, …) or node ID returned by hermes_search.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "node_id": { "type": "string", "description": "Pointer ID (p1, p2, …) or node ID from a previous search result" },
+                        "max_tokens": { "type": "integer", "minimum": 1, "description": "Truncate the content to about this many tokens (whole lines) and mark what was left out" }
+                    },
+                    "required": ["node_id"]
+                }
+            },
+            {
+                "name": "hermes_fetch_range",
+                "description": "Fetch lines of a project file directly by path and line range, e.g. from a compiler error, without a node ID.",
                 "inputSchema": {
                     "type": "object",
-                    "properties": { "node_id": { "type": "string", "description": "Node ID from a previous search result" } },
+                    "properties": {
+                        "file_path":  { "type": "string", "description": "File path, absolute or relative to the project root" },
+                        "start_line": { "type": "integer", "minimum": 1, "description": "First line to return (1-based)" },
+                        "end_line":   { "type": "integer", "minimum": 1, "description": "Last line to return (inclusive)" },
+                        "max_tokens": { "type": "integer", "minimum": 1, "description": "Truncate the content to about this many tokens (whole lines) and mark what was left out" }
+                    },
+                    "required": ["file_path", "start_line", "end_line"]
+                }
+            },
+            {
+                "name": "hermes_neighbors",
+                "description": "List the nodes connected to a pointer or node ID as pointers, e.g. what a file contains or who calls a function. Filter by direction and edge type to avoid flooding.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "node_id":    { "type": "string", "description": "Pointer ID (p1, p2, …) or node ID" },
+                        "direction":  { "type": "string", "enum": ["out", "in", "both"], "description": "out = edges from the node (what it contains or calls), in = edges to it (what contains or calls it), both (default)" },
+                        "edge_types": { "type": "array", "items": { "type": "string", "enum": ["calls", "imports", "implements", "depends_on", "contains", "documents"] }, "description": "Only follow these edge types (default: all)" },
+                        "limit":      { "type": "integer", "minimum": 1, "description": "Maximum neighbors to return (default 20)" }
+                    },
+                    "required": ["node_id"]
+                }
+            },
+            {
+                "name": "hermes_traverse",
+                "description": "Collect everything reachable from a pointer or node ID within a few hops, e.g. a module's contents and what they call, as a pointer list. Records token savings like a search.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "node_id":    { "type": "string", "description": "Pointer ID (p1, p2, …) or node ID to start from" },
+                        "max_depth":  { "type": "integer", "minimum": 1, "maximum": MAX_TRAVERSE_DEPTH, "description": "Maximum hops from the start node (default 2)" },
+                        "edge_types": { "type": "array", "items": { "type": "string", "enum": ["calls", "imports", "implements", "depends_on", "contains", "documents"] }, "description": "Only follow these edge types (default: all)" },
+                        "direction":  { "type": "string", "enum": ["out", "in", "both"], "description": "out = follow edges away from each node (default), in = towards it, both" },
+                        "max_nodes":  { "type": "integer", "minimum": 1, "maximum": MAX_TRAVERSE_NODES, "description": "Stop after reaching this many nodes (default 50)" },
+                        "mode":       { "type": "string", "enum": ["pointer", "smart", "full"], "description": "pointer = locations only (default), smart = plus a short preview, full = plus content up to a token budget" }
+                    },
                     "required": ["node_id"]
                 }
             },
+            {
+                "name": "hermes_graph_stats",
+                "description": "Report the size and health of the index: node and edge counts per type, files, FTS rows, embeddings, orphaned edges, nodes without a file path and the database size.",
+                "inputSchema": { "type": "object", "properties": {} }
+            },
             {
                 "name": "hermes_index",
                 "description": "Re-index the project files into the knowledge graph. Run after adding or changing files.",
-                "inputSchema": { "type": "object", "properties": {} }
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Only re-index this file or directory, absolute or relative to the project root (default: the whole project)" }
+                    }
+                }
             },
             {
                 "name": "hermes_stats",
                 "description": "Return cumulative token savings statistics across all Hermes sessions.",
-                "inputSchema": { "type": "object", "properties": {} }
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "granularity": { "type": "string", "enum": ["total", "daily", "weekly"], "description": "Add a 'series' of per-day or per-week totals (default: total, no series)" },
+                        "periods": { "type": "integer", "minimum": 1, "maximum": MAX_STATS_PERIODS, "description": "Days or weeks in the series, ending today (default: 14 days or 8 weeks, clamped to 730)" },
+                        "top_queries": { "type": "integer", "description": "How many entries to list under 'top_queries' (default: 10)" },
+                        "order_by": { "type": "string", "enum": ["frequency", "fetched_tokens", "savings"], "description": "Ranking of 'top_queries' (default: frequency)" },
+                        "scope": { "type": "string", "enum": ["cumulative", "session", "sessions"], "description": "cumulative = today and all-time totals (default), session = the current session only, sessions = per-session totals, newest first" },
+                        "limit": { "type": "integer", "description": "Sessions listed with scope 'sessions' (default: 20)" }
+                    }
+                }
             },
             {
                 "name": "hermes_fact",
@@ -142,18 +815,54 @@ fn handle_tools_list() -> Value {
                 "inputSchema": {
                     "type": "object",
                     "properties": {
-                        "fact_type": { "type": "string", "description": "One of: architecture, decision, learning, constraint, error_pattern, api_contract" },
-                        "content":   { "type": "string", "description": "The fact to record" }
+                        "fact_type": { "type": "string", "description": "One of: architecture, decision, learning, constraint, error_pattern, api_contract, or any other name for a custom type (e.g. todo)" },
+                        "content":   { "type": "string", "description": "The fact to record" },
+                        "file":      { "type": "string", "description": "Indexed file the fact is about, absolute or relative to the project root; attaches the fact to the file's node" }
                     },
                     "required": ["fact_type", "content"]
                 }
             },
             {
                 "name": "hermes_facts",
-                "description": "List active facts from the temporal store, optionally filtered by type.",
+                "description": "List active facts from the temporal store, optionally filtered by type, or the facts that were valid at a past time.",
                 "inputSchema": {
                     "type": "object",
-                    "properties": { "fact_type": { "type": "string", "description": "Optional filter type (omit for all)" } }
+                    "properties": {
+                        "fact_type": { "type": "string", "description": "Optional filter type, built-in or custom (omit for all)" },
+                        "as_of": { "type": "string", "description": "RFC 3339 time or YYYY-MM-DD date (midnight UTC): list what was believed then, including facts invalidated since" }
+                    }
+                }
+            },
+            {
+                "name": "hermes_fact_update",
+                "description": "Replace an active fact: records the new content with the same type and node, and invalidates the old fact with superseded_by pointing at the new one, in one step.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "fact_id": { "type": "string", "description": "Id of the active fact to replace" },
+                        "content": { "type": "string", "description": "The fact that replaces it" }
+                    },
+                    "required": ["fact_id", "content"]
+                }
+            },
+            {
+                "name": "hermes_fact_invalidate",
+                "description": "Retire an active fact that no longer holds, without a replacement.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": { "fact_id": { "type": "string", "description": "Id of the active fact to retire" } },
+                    "required": ["fact_id"]
+                }
+            },
+            {
+                "name": "hermes_fact_history",
+                "description": "Full history including invalidated facts, newest first: every fact recorded about a node, or the supersession chain through a fact. Pass exactly one of node_id and fact_id.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "node_id": { "type": "string", "description": "Node whose facts to list" },
+                        "fact_id": { "type": "string", "description": "Fact whose supersession chain to list" }
+                    }
                 }
             },
             {
@@ -174,128 +883,1479 @@ fn handle_tools_list() -> Value {
     })
 }
 
+/// Argument errors are protocol errors; an unknown tool is reported in the
+/// result with `isError`, as MCP asks, so the model can see and correct it.
 fn handle_tool_call(engine: &HermesEngine, project_root: &Path, params: &Value) -> Result<Value> {
     let name = params["name"].as_str().unwrap_or("");
+    anyhow::ensure!(!name.is_empty(), invalid_params!("tools/call requires 'name'"));
     let args = &params["arguments"];
+    anyhow::ensure!(
+        args.is_object() || args.is_null(),
+        invalid_params!("{name} 'arguments' must be an object")
+    );
 
     let text = match name {
         "hermes_search" => {
             let query = args["query"].as_str().unwrap_or("");
-            anyhow::ensure!(!query.is_empty(), "hermes_search requires 'query'");
-            tool_search(engine, query)?
+            anyhow::ensure!(!query.is_empty(), invalid_params!("hermes_search requires 'query'"));
+            let top_k = match args.get("top_k") {
+                None | Some(Value::Null) => engine.config().effective_top_k(),
+                Some(v) => {
+                    let k = v.as_u64().ok_or_else(|| {
+                        invalid_params!("hermes_search 'top_k' must be a positive integer")
+                    })?;
+                    clamp_top_k(k as usize)
+                }
+            };
+            let mode = match args["mode"].as_str() {
+                None => SearchMode::Smart,
+                Some(m) => SearchMode::parse_str(m).ok_or_else(|| {
+                    invalid_params!("hermes_search 'mode' must be one of: pointer, smart, full")
+                })?,
+            };
+            let path_filter = args["path_filter"].as_str().map(str::to_string);
+            let metadata_filters = match args.get("metadata") {
+                None | Some(Value::Null) => Vec::new(),
+                Some(Value::Array(filters)) => filters
+                    .iter()
+                    .map(|f| f.as_str().and_then(MetadataFilter::parse_str))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        invalid_params!("hermes_search 'metadata' entries must be KEY=VALUE")
+                    })?,
+                Some(_) => {
+                    let message = "hermes_search 'metadata' must be an array of strings";
+                    anyhow::bail!(invalid_params!("{message}"))
+                }
+            };
+            let snippets = args["snippets"].as_bool().unwrap_or(true);
+            let expand_graph = args["expand"].as_bool().unwrap_or(false);
+            let token_budget = optional_u64(args, "token_budget", "hermes_search")?;
+            anyhow::ensure!(
+                token_budget.is_none() || matches!(args.get("top_k"), None | Some(Value::Null)),
+                invalid_params!("hermes_search accepts either 'top_k' or 'token_budget', not both")
+            );
+            let min_score = match args.get("min_score") {
+                None | Some(Value::Null) => None,
+                Some(v) => Some(v.as_f64().filter(|s| s.is_finite() && *s >= 0.0).ok_or_else(|| {
+                    invalid_params!("hermes_search 'min_score' must be a non-negative number")
+                })?),
+            };
+            let explain = args["explain"].as_bool().unwrap_or(false);
+            let facts = args["facts"].as_bool().unwrap_or(true);
+            let compact = match args["format"].as_str() {
+                None | Some("json") => false,
+                Some("compact") => true,
+                Some(_) => {
+                    let message = "hermes_search 'format' must be one of: json, compact";
+                    anyhow::bail!(invalid_params!("{message}"))
+                }
+            };
+            let opts = SearchOptions {
+                top_k,
+                mode,
+                path_filter,
+                metadata_filters,
+                snippets,
+                expand_graph,
+                token_budget,
+                min_score,
+                explain,
+                facts,
+            };
+            tool_search(engine, query, &opts, compact)?
         }
         "hermes_fetch" => {
             let node_id = args["node_id"].as_str().unwrap_or("");
-            anyhow::ensure!(!node_id.is_empty(), "hermes_fetch requires 'node_id'");
-            tool_fetch(engine, node_id)?
+            anyhow::ensure!(
+                !node_id.is_empty(),
+                invalid_params!("hermes_fetch requires 'node_id'")
+            );
+            let max_tokens = optional_u64(args, "max_tokens", "hermes_fetch")?;
+            tool_fetch(engine, node_id, max_tokens)?
         }
-        "hermes_index"  => tool_index(engine, project_root)?,
-        "hermes_stats"  => tool_stats(engine)?,
+        "hermes_fetch_range" => {
+            let file_path = args["file_path"].as_str().unwrap_or("");
+            anyhow::ensure!(
+                !file_path.is_empty(),
+                invalid_params!("hermes_fetch_range requires 'file_path'")
+            );
+            let start = optional_u64(args, "start_line", "hermes_fetch_range")?;
+            let end = optional_u64(args, "end_line", "hermes_fetch_range")?;
+            let (Some(start), Some(end)) = (start, end) else {
+                let message = "hermes_fetch_range requires 'start_line' and 'end_line'";
+                anyhow::bail!(invalid_params!("{message}"));
+            };
+            let max_tokens = optional_u64(args, "max_tokens", "hermes_fetch_range")?;
+            tool_fetch_range(engine, project_root, file_path, start as i64, end as i64, max_tokens)?
+        }
+        "hermes_neighbors" => {
+            let node_id = args["node_id"].as_str().unwrap_or("");
+            anyhow::ensure!(
+                !node_id.is_empty(),
+                invalid_params!("hermes_neighbors requires 'node_id'")
+            );
+            let direction = edge_direction_arg(args, "hermes_neighbors", EdgeDirection::Both)?;
+            let edge_types = edge_types_arg(args, "hermes_neighbors")?;
+            let limit = optional_u64(args, "limit", "hermes_neighbors")?
+                .map_or(DEFAULT_NEIGHBOR_LIMIT, |n| n.max(1) as usize);
+            tool_neighbors(engine, node_id, direction, edge_types.as_deref(), limit)?
+        }
+        "hermes_traverse" => {
+            let node_id = args["node_id"].as_str().unwrap_or("");
+            anyhow::ensure!(
+                !node_id.is_empty(),
+                invalid_params!("hermes_traverse requires 'node_id'")
+            );
+            let direction = edge_direction_arg(args, "hermes_traverse", EdgeDirection::Outgoing)?;
+            let edge_types = edge_types_arg(args, "hermes_traverse")?;
+            let max_depth = optional_u64(args, "max_depth", "hermes_traverse")?
+                .map_or(DEFAULT_TRAVERSE_DEPTH, |n| (n as usize).min(MAX_TRAVERSE_DEPTH));
+            let max_nodes = optional_u64(args, "max_nodes", "hermes_traverse")?
+                .map_or(DEFAULT_TRAVERSE_NODES, |n| (n as usize).min(MAX_TRAVERSE_NODES));
+            let mode = match args["mode"].as_str() {
+                None => SearchMode::Pointer,
+                Some(s) => SearchMode::parse_str(s).ok_or_else(|| {
+                    invalid_params!("hermes_traverse 'mode' must be one of: pointer, smart, full")
+                })?,
+            };
+            let opts = SearchOptions { mode, ..SearchOptions::default() };
+            let edge_types = edge_types.as_deref();
+            let walk = Traversal { max_depth, edge_types, direction, max_nodes };
+            tool_traverse(engine, node_id, &walk, &opts)?
+        }
+        "hermes_graph_stats" => {
+            serde_json::to_string_pretty(&engine.graph().stats()?)?
+        }
+        "hermes_index"  => tool_index(engine, project_root, args["path"].as_str())?,
+        "hermes_stats"  => tool_stats(engine, &StatsRequest::from_args(args)?)?,
         "hermes_fact"   => {
             let ft = args["fact_type"].as_str().unwrap_or("");
             let c  = args["content"].as_str().unwrap_or("");
-            anyhow::ensure!(!ft.is_empty() && !c.is_empty(), "hermes_fact requires 'fact_type' and 'content'");
-            tool_add_fact(engine, ft, c)?
+            anyhow::ensure!(
+                !ft.is_empty() && !c.is_empty(),
+                invalid_params!("hermes_fact requires 'fact_type' and 'content'")
+            );
+            tool_add_fact(engine, project_root, ft, c, args["file"].as_str())?
         }
         "hermes_facts" => {
             let filter = args["fact_type"].as_str();
-            tool_list_facts(engine, filter)?
+            tool_list_facts(engine, filter, args["as_of"].as_str())?
+        }
+        "hermes_fact_update" => {
+            let id = args["fact_id"].as_str().unwrap_or("");
+            let c  = args["content"].as_str().unwrap_or("");
+            anyhow::ensure!(
+                !id.is_empty() && !c.is_empty(),
+                invalid_params!("hermes_fact_update requires 'fact_id' and 'content'")
+            );
+            tool_update_fact(engine, id, c)?
+        }
+        "hermes_fact_invalidate" => {
+            let id = args["fact_id"].as_str().unwrap_or("");
+            anyhow::ensure!(
+                !id.is_empty(),
+                invalid_params!("hermes_fact_invalidate requires 'fact_id'")
+            );
+            tool_invalidate_fact(engine, id)?
+        }
+        "hermes_fact_history" => {
+            let node_id = args["node_id"].as_str().filter(|id| !id.is_empty());
+            let fact_id = args["fact_id"].as_str().filter(|id| !id.is_empty());
+            tool_fact_history(engine, node_id, fact_id)?
         }
         "hermes_validate_env" => {
             let var = args["env_var"].as_str().unwrap_or("");
-            anyhow::ensure!(!var.is_empty(), "hermes_validate_env requires 'env_var'");
+            anyhow::ensure!(
+                !var.is_empty(),
+                invalid_params!("hermes_validate_env requires 'env_var'")
+            );
             tool_validate_env(engine, var)?
         }
         "hermes_check_consistency" => tool_check_consistency(engine)?,
-        other => anyhow::bail!("unknown tool: {other}"),
+        other => return Ok(tool_error(&format!("unknown tool: {other}"))),
     };
 
     Ok(json!({ "content": [{ "type": "text", "text": text }] }))
 }
 
+fn tool_error(message: &str) -> Value {
+    json!({ "content": [{ "type": "text", "text": message }], "isError": true })
+}
+
+
+/// Reads an optional `direction` argument (`out`, `in` or `both`).
+fn edge_direction_arg(args: &Value, tool: &str, default: EdgeDirection) -> Result<EdgeDirection> {
+    match args["direction"].as_str() {
+        None => Ok(default),
+        Some(s) => EdgeDirection::parse_str(s)
+            .ok_or_else(|| invalid_params!("{tool} 'direction' must be one of: out, in, both")),
+    }
+}
+
+/// Reads an optional `edge_types` array; absent or null is `None` (all types).
+fn edge_types_arg(args: &Value, tool: &str) -> Result<Option<Vec<EdgeType>>> {
+    let items = match args.get("edge_types") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Array(items)) => items,
+        Some(_) => return Err(invalid_params!("{tool} 'edge_types' must be an array of strings")),
+    };
+    let types = items
+        .iter()
+        .map(|item| {
+            let name = item.as_str().unwrap_or("");
+            EdgeType::ALL
+                .into_iter()
+                .find(|t| t.as_str() == name)
+                .ok_or_else(|| invalid_params!("{tool} unknown edge type: {item}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(types))
+}
 
-fn tool_search(engine: &HermesEngine, query: &str) -> Result<String> {
-    let graph  = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let search = SearchEngine::new(&graph, engine.search_cache());
-    let resp   = search.search(query, 10, &SearchMode::Smart)?;
-    let acct   = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    acct.record_query(query, resp.accounting.pointer_tokens, 0, resp.accounting.traditional_rag_estimate)?;
+/// Reads an optional non-negative integer argument; absent or null is `None`.
+fn optional_u64(args: &Value, key: &str, tool: &str) -> Result<Option<u64>> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(Some)
+            .ok_or_else(|| invalid_params!("{tool} '{key}' must be a positive integer")),
+    }
+}
+
+fn tool_search(engine: &HermesEngine, query: &str, opts: &SearchOptions, compact: bool) -> Result<String> {
+    if compact {
+        return Ok(engine.search_compact(query, opts)?);
+    }
+    Ok(serde_json::to_string_pretty(&engine.search(query, opts)?)?)
+}
+
+fn tool_fetch(engine: &HermesEngine, node_id: &str, max_tokens: Option<u64>) -> Result<String> {
+    let Some(resp) = engine.fetch(node_id, max_tokens)? else {
+        return Err(HermesError::NotFound(format!("node not found: {node_id}")).into());
+    };
     Ok(serde_json::to_string_pretty(&resp)?)
 }
 
-fn tool_fetch(engine: &HermesEngine, node_id: &str) -> Result<String> {
-    let graph  = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let search = SearchEngine::new(&graph, engine.search_cache());
-    let Some(resp) = search.fetch(node_id)? else {
-        anyhow::bail!("node not found: {node_id}");
+fn tool_fetch_range(
+    engine: &HermesEngine,
+    project_root: &Path,
+    file_path: &str,
+    start: i64,
+    end: i64,
+    max_tokens: Option<u64>,
+) -> Result<String> {
+    let resp = engine.fetch_range(project_root, file_path, start, end, max_tokens)?;
+    Ok(serde_json::to_string_pretty(&resp)?)
+}
+
+fn tool_neighbors(
+    engine: &HermesEngine,
+    node_id: &str,
+    direction: EdgeDirection,
+    edge_types: Option<&[EdgeType]>,
+    limit: usize,
+) -> Result<String> {
+    let graph = engine.graph();
+    let search = engine.search_engine(&graph);
+    let Some(resp) = search.neighbors(node_id, direction, edge_types, limit)? else {
+        return Err(HermesError::NotFound(format!("node not found: {node_id}")).into());
+    };
+    Ok(serde_json::to_string_pretty(&resp)?)
+}
+
+/// Parsed `hermes_traverse` walk limits.
+#[derive(Clone, Copy)]
+struct Traversal<'a> {
+    max_depth: usize,
+    edge_types: Option<&'a [EdgeType]>,
+    direction: EdgeDirection,
+    max_nodes: usize,
+}
+
+/// Recorded like a search: the pointers are what Hermes put in context and
+/// the full content of every reached node is the naive-RAG baseline.
+fn tool_traverse(
+    engine: &HermesEngine,
+    node_id: &str,
+    walk: &Traversal,
+    opts: &SearchOptions,
+) -> Result<String> {
+    let graph = engine.graph();
+    let search = engine.search_engine(&graph);
+    let started = Instant::now();
+    let Traversal { max_depth, edge_types, direction, max_nodes } = *walk;
+    let found = search.traverse(node_id, max_depth, edge_types, direction, max_nodes, opts)?;
+    let Some(resp) = found else {
+        return Err(HermesError::NotFound(format!("node not found: {node_id}")).into());
     };
-    let acct = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    acct.record_query(node_id, 0, resp.token_count, resp.token_count * 15)?;
+    let latency = Some(started.elapsed());
+    let a = &resp.accounting;
+    let query = format!("traverse {node_id}");
+    let baseline = a.traditional_rag_estimate;
+    let (pointer, fetched) = (a.pointer_tokens, a.fetched_tokens);
+    engine.record_operation(Operation::Search, &query, pointer, fetched, baseline, latency)?;
     Ok(serde_json::to_string_pretty(&resp)?)
 }
 
-fn tool_index(engine: &HermesEngine, project_root: &Path) -> Result<String> {
-    let graph    = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
-    let pipeline = IngestionPipeline::new(&graph);
-    let report   = pipeline.ingest_directory(project_root)?;
-    engine.invalidate_search_cache();
+fn tool_index(engine: &HermesEngine, project_root: &Path, path: Option<&str>) -> Result<String> {
+    let paths = path.map(PathBuf::from).into_iter().collect();
+    let report = engine.index(project_root, &IndexOptions { paths, ..Default::default() })?;
     Ok(serde_json::to_string_pretty(&json!({
         "total_files": report.total_files, "indexed": report.indexed,
         "skipped": report.skipped, "errors": report.errors,
-        "nodes_created": report.nodes_created,
+        "error_details": report.error_details,
+        "error_details_truncated": report.error_details_truncated,
+        "skipped_too_large": report.skipped_too_large, "skipped_binary": report.skipped_binary,
+        "deleted": report.deleted_paths.len(), "renamed": report.renamed_paths.len(),
+        "nodes_created": report.nodes_created, "chunks_removed": report.chunks_removed,
+        "timings": report.timings,
     }))?)
 }
 
-fn tool_stats(engine: &HermesEngine) -> Result<String> {
-    let acct = Accountant::new(engine.db().clone(), engine.project_id(), engine.session_id());
-    let today      = acct.get_today_stats()?;
-    let cumulative = acct.get_cumulative_stats()?;
-    Ok(serde_json::to_string_pretty(&json!({
-        "today": {
-            "total_queries":            today.total_queries,
-            "pointer_tokens_used":      today.total_pointer_tokens,
-            "fetched_tokens_used":      today.total_fetched_tokens,
-            "traditional_rag_estimate": today.total_traditional_estimate,
-            "tokens_saved":             today.cumulative_savings_tokens,
-            "savings_pct":              format!("{:.1}%", today.cumulative_savings_pct),
-        },
-        "cumulative": {
-            "total_queries":            cumulative.total_queries,
-            "pointer_tokens_used":      cumulative.total_pointer_tokens,
-            "fetched_tokens_used":      cumulative.total_fetched_tokens,
-            "traditional_rag_estimate": cumulative.total_traditional_estimate,
-            "tokens_saved":             cumulative.cumulative_savings_tokens,
-            "savings_pct":              format!("{:.1}%", cumulative.cumulative_savings_pct),
-        },
-    }))?)
+/// Parsed `hermes_stats` arguments.
+struct StatsRequest {
+    scope: StatsScope,
+    granularity: StatsGranularity,
+    periods: Option<u32>,
+    top_queries: usize,
+    order_by: TopQueryOrder,
+    limit: usize,
+}
+
+enum StatsScope {
+    Cumulative,
+    Session,
+    Sessions,
+}
+
+enum StatsGranularity {
+    Total,
+    Daily,
+    Weekly,
+}
+
+impl StatsRequest {
+    fn from_args(args: &Value) -> Result<Self> {
+        let scope = match args["scope"].as_str() {
+            None | Some("cumulative") => StatsScope::Cumulative,
+            Some("session") => StatsScope::Session,
+            Some("sessions") => StatsScope::Sessions,
+            Some(_) => anyhow::bail!(invalid_params!(
+                "hermes_stats 'scope' must be one of: session, cumulative, sessions"
+            )),
+        };
+        let granularity = match args["granularity"].as_str() {
+            None | Some("total") => StatsGranularity::Total,
+            Some("daily") => StatsGranularity::Daily,
+            Some("weekly") => StatsGranularity::Weekly,
+            Some(_) => anyhow::bail!(invalid_params!(
+                "hermes_stats 'granularity' must be one of: total, daily, weekly"
+            )),
+        };
+        let periods = optional_u64(args, "periods", "hermes_stats")?
+            .map(|n| n.min(u64::from(MAX_STATS_PERIODS)) as u32);
+        let top_queries = optional_u64(args, "top_queries", "hermes_stats")?.unwrap_or(10) as usize;
+        let order_by = match args["order_by"].as_str() {
+            None => TopQueryOrder::Frequency,
+            Some(o) => TopQueryOrder::parse_str(o).ok_or_else(|| {
+                invalid_params!(
+                    "hermes_stats 'order_by' must be one of: frequency, fetched_tokens, savings"
+                )
+            })?,
+        };
+        let limit = optional_u64(args, "limit", "hermes_stats")?.unwrap_or(20) as usize;
+        Ok(Self {
+            scope,
+            granularity,
+            periods,
+            top_queries,
+            order_by,
+            limit,
+        })
+    }
+}
+
+fn tool_stats(engine: &HermesEngine, req: &StatsRequest) -> Result<String> {
+    let acct = engine.accountant();
+    let stats = engine.stats(None)?;
+    let cache = stats.search_cache;
+    let search_cache = json!({
+        "hits":        cache.hits,
+        "misses":      cache.misses,
+        "hit_rate":    format!("{:.1}%", cache.hit_rate() * 100.0),
+        "evictions":   cache.evictions,
+        "expirations": cache.expirations,
+        "entries":     cache.entries,
+        "capacity":    cache.capacity,
+    });
+    let mut output = match req.scope {
+        StatsScope::Sessions => json!({ "sessions": acct.list_sessions(req.limit)? }),
+        StatsScope::Session => json!({
+            "session_id":   engine.session_id(),
+            "session":      stats_block(&stats.session),
+            "search_cache": search_cache,
+        }),
+        StatsScope::Cumulative => json!({
+            "today":        stats_block(&stats.today),
+            "cumulative":   stats_block(&stats.cumulative),
+            "search_cache": search_cache,
+            "top_queries":  acct.top_queries(req.top_queries, req.order_by)?,
+        }),
+    };
+    let series = match req.granularity {
+        StatsGranularity::Total => None,
+        StatsGranularity::Daily => Some(acct.get_daily_stats(req.periods.unwrap_or(14))?),
+        StatsGranularity::Weekly => Some(acct.get_weekly_stats(req.periods.unwrap_or(8))?),
+    };
+    if let Some(series) = series {
+        output["series"] = serde_json::to_value(series)?;
+    }
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+fn stats_block(stats: &CumulativeStats) -> Value {
+    json!({
+        "total_queries":            stats.total_queries,
+        "pointer_tokens_used":      stats.total_pointer_tokens,
+        "fetched_tokens_used":      stats.total_fetched_tokens,
+        "traditional_rag_estimate": stats.total_traditional_estimate,
+        "tokens_saved":             stats.cumulative_savings_tokens,
+        "savings_pct":              format!("{:.1}%", stats.cumulative_savings_pct),
+        "searches":                 stats.searches,
+        "fetches":                  stats.fetches,
+    })
 }
 
-fn tool_add_fact(engine: &HermesEngine, fact_type_str: &str, content: &str) -> Result<String> {
-    let store = TemporalStore::new(engine.db().clone(), engine.project_id());
-    let id = store.add_fact(None, FactType::parse_str(fact_type_str), content, None)?;
+fn tool_add_fact(
+    engine: &HermesEngine,
+    project_root: &Path,
+    fact_type_str: &str,
+    content: &str,
+    file: Option<&str>,
+) -> Result<String> {
+    let fact_type = FactType::parse_str(fact_type_str);
+    let id = match file {
+        Some(file) => engine.add_file_fact(project_root, file, fact_type, content)?,
+        None => engine.add_fact(None, fact_type, content, None)?,
+    };
     Ok(serde_json::to_string_pretty(&json!({ "id": id, "status": "recorded" }))?)
 }
 
-fn tool_list_facts(engine: &HermesEngine, filter: Option<&str>) -> Result<String> {
-    let store = TemporalStore::new(engine.db().clone(), engine.project_id());
-    let facts = store.get_active_facts(filter.map(FactType::parse_str).as_ref())?;
+fn tool_list_facts(
+    engine: &HermesEngine,
+    filter: Option<&str>,
+    as_of: Option<&str>,
+) -> Result<String> {
+    let fact_type = filter.map(FactType::parse_str);
+    let store = engine.temporal_store();
+    let facts = match as_of {
+        Some(as_of) => store.get_facts_as_of(as_of, fact_type.as_ref())?,
+        None => store.get_active_facts(fact_type.as_ref())?,
+    };
     Ok(serde_json::to_string_pretty(&facts)?)
 }
 
+fn tool_update_fact(engine: &HermesEngine, fact_id: &str, content: &str) -> Result<String> {
+    let id = engine.supersede_fact(fact_id, content, None)?;
+    let result = json!({ "id": id, "supersedes": fact_id, "status": "superseded" });
+    Ok(serde_json::to_string_pretty(&result)?)
+}
 
-fn write_ok(out: &mut impl Write, id: &Value, result: Value) -> Result<()> {
-    let envelope = json!({ "jsonrpc": "2.0", "id": id, "result": result });
-    writeln!(out, "{}", serde_json::to_string(&envelope)?)?;
-    out.flush()?;
-    Ok(())
+fn tool_invalidate_fact(engine: &HermesEngine, fact_id: &str) -> Result<String> {
+    engine.invalidate_fact(fact_id, None)?;
+    Ok(serde_json::to_string_pretty(&json!({ "id": fact_id, "status": "invalidated" }))?)
 }
 
-fn write_error(out: &mut impl Write, id: &Value, code: i32, message: &str) -> Result<()> {
-    let envelope = json!({
+/// A node's facts, or the chain through a fact; an unknown fact id is an
+/// error, a node without facts is an empty list.
+fn tool_fact_history(
+    engine: &HermesEngine,
+    node_id: Option<&str>,
+    fact_id: Option<&str>,
+) -> Result<String> {
+    let store = engine.temporal_store();
+    let facts = match (node_id, fact_id) {
+        (Some(node_id), None) => store.get_fact_history(node_id)?,
+        (None, Some(fact_id)) => {
+            let chain = store.get_fact_chain(fact_id)?;
+            if chain.is_empty() {
+                return Err(HermesError::NotFound(format!("no such fact: {fact_id}")).into());
+            }
+            chain
+        }
+        _ => {
+            return Err(invalid_params!(
+                "hermes_fact_history requires exactly one of 'node_id' and 'fact_id'"
+            ))
+        }
+    };
+    Ok(serde_json::to_string_pretty(&facts)?)
+}
+
+
+fn reply_ok(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+/// Maps an error to the JSON-RPC code a client can act on; library errors
+/// without a more specific code, and failures raised here such as a broken
+/// output stream, are internal errors.
+fn error_code(error: &anyhow::Error) -> i32 {
+    if error.is::<UnknownMethod>() {
+        return METHOD_NOT_FOUND;
+    }
+    if error.is::<InvalidParams>() {
+        return INVALID_PARAMS;
+    }
+    match error.downcast_ref::<HermesError>() {
+        Some(HermesError::NotFound(_)) => NOT_FOUND,
+        Some(HermesError::InvalidQuery(_) | HermesError::InvalidInput(_)) => INVALID_PARAMS,
+        Some(HermesError::EmbeddingApi(_)) => EMBEDDING_UNAVAILABLE,
+        Some(HermesError::ReadOnly) => READ_ONLY,
+        Some(HermesError::AlreadyIndexing) => ALREADY_INDEXING,
+        _ => INTERNAL_ERROR,
+    }
+}
+
+/// Replies to request `id` with what `handle` returns. A panic in the
+/// handler becomes an internal error reply, so the worker running it
+/// survives and the client is not left waiting, and `engine` recovers the
+/// locks the handler may have poisoned so later requests still work.
+fn answer(engine: &HermesEngine, id: &Value, handle: impl FnOnce() -> Result<Value>) -> Value {
+    match std::panic::catch_unwind(AssertUnwindSafe(handle)) {
+        Ok(Ok(payload)) => reply_ok(id, payload),
+        Ok(Err(e)) => reply_error(id, error_code(&e), &e.to_string()),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("handler panicked");
+            eprintln!("[hermes] request {id} panicked: {message}");
+            engine.recover_from_panic();
+            reply_error(id, INTERNAL_ERROR, &format!("internal error: {message}"))
+        }
+    }
+}
+
+fn reply_error(id: &Value, code: i32, message: &str) -> Value {
+    json!({
         "jsonrpc": "2.0", "id": id,
         "error": { "code": code, "message": message }
-    });
-    writeln!(out, "{}", serde_json::to_string(&envelope)?)?;
-    out.flush()?;
-    Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::NodeType;
+    use crate::search::estimate_tokens;
+
+    fn engine_with_nodes(names: &[&str]) -> HermesEngine {
+        let engine = HermesEngine::in_memory("mcp-test").unwrap();
+        let graph = engine.graph();
+        for name in names {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(NodeType::Function)
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        engine
+    }
+
+    fn call_tool(engine: &HermesEngine, name: &str, arguments: Value) -> Result<Value> {
+        call_tool_in(engine, Path::new("."), name, arguments)
+    }
+
+    fn call_tool_in(engine: &HermesEngine, root: &Path, name: &str, arguments: Value) -> Result<Value> {
+        let params = json!({ "name": name, "arguments": arguments });
+        let result = handle_tool_call(engine, root, &params)?;
+        let text = result["content"][0]["text"].as_str().unwrap().to_string();
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    #[test]
+    fn search_honors_top_k() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b", "alert_c", "alert_d"]);
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "top_k": 2 })).unwrap();
+        assert_eq!(resp["pointers"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn search_clamps_zero_top_k_to_one() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b"]);
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "top_k": 0 })).unwrap();
+        assert_eq!(resp["pointers"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn neighbors_lists_filtered_pointers_with_fetchable_aliases() {
+        let engine = engine_with_nodes(&[]);
+        let graph = engine.graph();
+        let file = graph.create_node_builder().name("lib.rs").node_type(NodeType::File).build();
+        graph.add_node(&file).unwrap();
+        for name in ["parse", "render"] {
+            let child = graph.create_node_builder().name(name).node_type(NodeType::Function).build();
+            graph.add_node(&child).unwrap();
+            let edge = graph
+                .create_edge_builder()
+                .source(&file.id)
+                .target(&child.id)
+                .edge_type(EdgeType::Contains)
+                .build();
+            graph.add_edge(&edge).unwrap();
+        }
+
+        let args = json!({ "node_id": file.id, "direction": "out", "edge_types": ["contains"], "limit": 1 });
+        let resp = call_tool(&engine, "hermes_neighbors", args).unwrap();
+        assert_eq!(resp["truncated"], true);
+        let neighbor = &resp["neighbors"][0];
+        assert_eq!(neighbor["chunk"], "parse");
+        assert_eq!(neighbor["direction"], "out");
+        assert_eq!(neighbor["id"], "p1");
+        let fetched = call_tool(&engine, "hermes_fetch", json!({ "node_id": "p1" }));
+        assert!(fetched.is_ok());
+
+        let incoming = json!({ "node_id": file.id, "direction": "in" });
+        let resp = call_tool(&engine, "hermes_neighbors", incoming).unwrap();
+        assert!(resp["neighbors"].as_array().unwrap().is_empty());
+        let bad_type = json!({ "node_id": file.id, "edge_types": ["owns"] });
+        assert!(call_tool(&engine, "hermes_neighbors", bad_type).is_err());
+        assert!(call_tool(&engine, "hermes_neighbors", json!({ "node_id": "missing" })).is_err());
+    }
+
+    #[test]
+    fn traverse_returns_a_recorded_pointer_response() {
+        let engine = engine_with_nodes(&[]);
+        let graph = engine.graph();
+        let node = |name: &str| {
+            let node = graph.create_node_builder().name(name).node_type(NodeType::Function).build();
+            graph.add_node(&node).unwrap();
+            node
+        };
+        let (module, parse, lex) = (node("module"), node("parse"), node("lex"));
+        let edges = [
+            (&module, &parse, EdgeType::Contains),
+            (&parse, &lex, EdgeType::Calls),
+            (&lex, &module, EdgeType::Imports),
+        ];
+        for (source, target, edge_type) in edges {
+            let edge = graph
+                .create_edge_builder()
+                .source(&source.id)
+                .target(&target.id)
+                .edge_type(edge_type)
+                .build();
+            graph.add_edge(&edge).unwrap();
+        }
+
+        let args = json!({ "node_id": module.id, "edge_types": ["contains", "calls"] });
+        let resp = call_tool(&engine, "hermes_traverse", args).unwrap();
+        let pointers = resp["pointers"].as_array().unwrap();
+        let chunks: Vec<&str> = pointers.iter().map(|p| p["chunk"].as_str().unwrap()).collect();
+        assert_eq!(chunks, ["module", "parse", "lex"]);
+        assert_eq!(pointers[2]["snippet"], "depth 2 via calls from parse");
+        let relevance = |i: usize| pointers[i]["relevance"].as_f64().unwrap();
+        assert!(relevance(2) < relevance(1));
+
+        let args = json!({ "node_id": module.id, "max_depth": 1 });
+        let shallow = call_tool(&engine, "hermes_traverse", args).unwrap();
+        assert_eq!(shallow["pointers"].as_array().unwrap().len(), 2);
+        let acct = engine.accountant();
+        assert_eq!(acct.get_cumulative_stats().unwrap().total_queries, 2);
+        assert!(call_tool(&engine, "hermes_traverse", json!({ "node_id": "missing" })).is_err());
+    }
+
+    #[test]
+    fn search_rejects_unknown_mode() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let err = call_tool(&engine, "hermes_search", json!({ "query": "alert", "mode": "verbose" }));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn search_pointer_mode_has_no_content() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "mode": "pointer" })).unwrap();
+        assert!(resp["pointers"][0].get("content").is_none());
+    }
+
+    #[test]
+    fn search_snippets_can_be_turned_off() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let on = call_tool(&engine, "hermes_search", json!({ "query": "alert_a" })).unwrap();
+        assert_eq!(on["pointers"][0]["snippet"], "alert_a");
+        let off = call_tool(&engine, "hermes_search", json!({ "query": "alert_a", "snippets": false })).unwrap();
+        assert!(off["pointers"][0].get("snippet").is_none());
+    }
+
+    #[test]
+    fn fetch_max_tokens_truncates_and_records_shown_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("big.md");
+        let body: Vec<String> = (0..200).map(|i| format!("line {i} of a long document")).collect();
+        std::fs::write(&file, body.join("\n")).unwrap();
+
+        let engine = HermesEngine::in_memory("mcp-fetch-cap").unwrap();
+        let graph = engine.graph();
+        let node = graph
+            .create_node_builder()
+            .name("big.md")
+            .node_type(NodeType::Document)
+            .file_path(&file.to_string_lossy())
+            .lines(1, 200)
+            .build();
+        graph.add_node(&node).unwrap();
+
+        let resp = call_tool(&engine, "hermes_fetch", json!({ "node_id": node.id, "max_tokens": 50 })).unwrap();
+        assert_eq!(resp["truncated"], true);
+        let shown = resp["token_count"].as_u64().unwrap();
+        assert!(shown < 100);
+
+        let acct = engine.accountant();
+        assert_eq!(acct.get_cumulative_stats().unwrap().total_fetched_tokens, shown);
+
+        let found = call_tool(&engine, "hermes_search", json!({ "query": "big.md" })).unwrap();
+        let alias = found["pointers"][0]["id"].as_str().unwrap();
+        assert_eq!(alias, "p1");
+        let by_alias = call_tool(&engine, "hermes_fetch", json!({ "node_id": alias })).unwrap();
+        assert_eq!(by_alias["pointer_id"], node.id.as_str());
+        assert!(call_tool(&engine, "hermes_fetch", json!({ "node_id": "p99" })).is_err());
+    }
+
+    #[test]
+    fn fetch_range_tool_returns_synthetic_pointer() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn a() {}\nfn b() {}\nfn c() {}").unwrap();
+        let engine = HermesEngine::in_memory("mcp-fetch-range").unwrap();
+
+        let args = json!({ "file_path": "main.rs", "start_line": 2, "end_line": 3 });
+        let resp = call_tool_in(&engine, dir.path(), "hermes_fetch_range", args).unwrap();
+        assert_eq!(resp["pointer_id"], "file:main.rs:2-3");
+        assert_eq!(resp["content"], "fn b() {}\nfn c() {}");
+
+        let missing = json!({ "file_path": "main.rs", "start_line": 2 });
+        assert!(call_tool_in(&engine, dir.path(), "hermes_fetch_range", missing).is_err());
+    }
+
+    #[test]
+    fn fact_tool_attaches_facts_to_indexed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        let engine = HermesEngine::in_memory("mcp-file-fact").unwrap();
+        engine.index(dir.path(), &IndexOptions::default()).unwrap();
+
+        let args = json!({ "fact_type": "constraint", "content": "no I/O", "file": "main.rs" });
+        call_tool_in(&engine, dir.path(), "hermes_fact", args).unwrap();
+        let range = json!({ "file_path": "main.rs", "start_line": 1, "end_line": 1 });
+        let resp = call_tool_in(&engine, dir.path(), "hermes_fetch_range", range).unwrap();
+        assert_eq!(resp["related_facts"][0]["content"], "no I/O");
+        assert_eq!(resp["related_facts"][0]["fact_type"], "constraint");
+
+        let args = json!({ "fact_type": "constraint", "content": "x", "file": "lib.rs" });
+        let error = call_tool_in(&engine, dir.path(), "hermes_fact", args).unwrap_err();
+        assert_eq!(error_code(&error), NOT_FOUND);
+    }
+
+    #[test]
+    fn search_token_budget_excludes_top_k() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b", "alert_c"]);
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "token_budget": 500 })).unwrap();
+        assert_eq!(resp["accounting"]["token_budget"], 500);
+        let both = call_tool(&engine, "hermes_search", json!({ "query": "alert", "top_k": 2, "token_budget": 500 }));
+        assert!(both.is_err());
+    }
+
+    #[test]
+    fn search_min_score_can_empty_the_response() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b"]);
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "min_score": 1e9 })).unwrap();
+        assert_eq!(resp["pointers"].as_array().unwrap().len(), 0);
+        assert_eq!(resp["accounting"]["pointer_tokens"], 0);
+        let negative = call_tool(&engine, "hermes_search", json!({ "query": "alert", "min_score": -1 }));
+        assert!(negative.is_err());
+    }
+
+    #[test]
+    fn search_explain_attaches_debug() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b"]);
+        let plain = call_tool(&engine, "hermes_search", json!({ "query": "alert" })).unwrap();
+        assert!(plain.get("debug").is_none());
+
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert", "explain": true })).unwrap();
+        assert_eq!(resp["debug"]["cache_hit"], true);
+        let explained = resp["debug"]["pointers"].as_array().unwrap();
+        assert_eq!(explained.len(), resp["pointers"].as_array().unwrap().len());
+        assert_eq!(explained[0]["tiers"][0]["tier"], "L0Literal");
+    }
+
+    #[test]
+    fn search_compact_format_returns_plain_text() {
+        let engine = engine_with_nodes(&["alert_a", "alert_b"]);
+        let params = json!({ "name": "hermes_search", "arguments": { "query": "alert", "format": "compact" } });
+        let result = handle_tool_call(&engine, Path::new("."), &params).unwrap();
+        let text = result["content"][0]["text"].as_str().unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.starts_with("p1 - fn alert_"));
+        assert!(text.lines().last().unwrap().starts_with("-- 2 pointers"));
+
+        let acct = engine.accountant();
+        assert_eq!(acct.get_cumulative_stats().unwrap().total_pointer_tokens, estimate_tokens(text));
+
+        let bad = call_tool(&engine, "hermes_search", json!({ "query": "alert", "format": "xml" }));
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn stats_break_down_searches_and_fetches() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        call_tool(&engine, "hermes_search", json!({ "query": "alert" })).unwrap();
+        call_tool(&engine, "hermes_search", json!({ "query": "alert_a" })).unwrap();
+        assert!(call_tool(&engine, "hermes_fetch", json!({ "node_id": "p1" })).is_ok());
+
+        let stats = call_tool(&engine, "hermes_stats", json!({})).unwrap();
+        let cumulative = &stats["cumulative"];
+        assert_eq!(cumulative["searches"]["count"], 2);
+        assert_eq!(cumulative["fetches"]["count"], 1);
+        assert!(cumulative["searches"]["p95_latency_ms"].is_u64());
+    }
+
+    #[test]
+    fn stats_granularity_adds_a_series() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        call_tool(&engine, "hermes_search", json!({ "query": "alert" })).unwrap();
+
+        let total = call_tool(&engine, "hermes_stats", json!({})).unwrap();
+        assert!(total.get("series").is_none());
+
+        let daily = call_tool(&engine, "hermes_stats", json!({ "granularity": "daily", "periods": 3 })).unwrap();
+        let series = daily["series"].as_array().unwrap();
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[2]["queries"], 1);
+
+        let weekly = call_tool(&engine, "hermes_stats", json!({ "granularity": "weekly" })).unwrap();
+        assert_eq!(weekly["series"].as_array().unwrap().len(), 8);
+
+        let huge = json!({ "granularity": "weekly", "periods": 200_000_000 });
+        let capped = call_tool(&engine, "hermes_stats", huge).unwrap();
+        assert_eq!(capped["series"].as_array().unwrap().len(), MAX_STATS_PERIODS as usize);
+
+        assert!(call_tool(&engine, "hermes_stats", json!({ "granularity": "hourly" })).is_err());
+    }
+
+    #[test]
+    fn stats_scope_selects_session_or_sessions() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        call_tool(&engine, "hermes_search", json!({ "query": "alert" })).unwrap();
+
+        let session = call_tool(&engine, "hermes_stats", json!({ "scope": "session" })).unwrap();
+        assert_eq!(session["session_id"], engine.session_id());
+        assert_eq!(session["session"]["total_queries"], 1);
+        assert!(session.get("cumulative").is_none());
+
+        let sessions = call_tool(&engine, "hermes_stats", json!({ "scope": "sessions" })).unwrap();
+        let list = sessions["sessions"].as_array().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0]["session_id"], engine.session_id());
+
+        let cumulative = call_tool(&engine, "hermes_stats", json!({ "scope": "cumulative" })).unwrap();
+        assert_eq!(cumulative["cumulative"]["total_queries"], 1);
+        assert!(call_tool(&engine, "hermes_stats", json!({ "scope": "forever" })).is_err());
+    }
+
+    #[test]
+    fn stats_lists_top_queries() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        call_tool(&engine, "hermes_search", json!({ "query": "alert" })).unwrap();
+        call_tool(&engine, "hermes_search", json!({ "query": "Alert " })).unwrap();
+        call_tool(&engine, "hermes_search", json!({ "query": "missing" })).unwrap();
+
+        let stats = call_tool(&engine, "hermes_stats", json!({ "top_queries": 1 })).unwrap();
+        let top = stats["top_queries"].as_array().unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0]["query"], "alert");
+        assert_eq!(top[0]["count"], 2);
+
+        assert!(call_tool(&engine, "hermes_stats", json!({ "order_by": "latency" })).is_err());
+    }
+
+    #[test]
+    fn search_accounting_row_matches_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("alerts.rs");
+        let source = "fn alert_dispatch() {\n    notify_all(queue.drain());\n}\n";
+        std::fs::write(&file, source).unwrap();
+        let engine = HermesEngine::in_memory("mcp-test").unwrap();
+        let graph = engine.graph();
+        let node = graph
+            .create_node_builder()
+            .name("alert_dispatch")
+            .node_type(NodeType::Function)
+            .file_path(file.to_str().unwrap())
+            .lines(1, 3)
+            .build();
+        graph.add_node(&node).unwrap();
+        graph.index_fts(&node, source).unwrap();
+
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert_dispatch", "mode": "full" })).unwrap();
+        let accounting = &resp["accounting"];
+        assert!(accounting["fetched_tokens"].as_u64().unwrap() > 0);
+
+        let row: (u64, u64, u64) = engine
+            .db()
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT pointer_tokens, fetched_tokens, traditional_est FROM accounting
+                 WHERE operation = 'search' ORDER BY id DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(row.0, accounting["pointer_tokens"].as_u64().unwrap());
+        assert_eq!(row.1, accounting["fetched_tokens"].as_u64().unwrap());
+        assert_eq!(row.2, accounting["traditional_rag_estimate"].as_u64().unwrap());
+    }
+
+    #[test]
+    fn search_rejects_non_integer_top_k() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let err = call_tool(&engine, "hermes_search", json!({ "query": "alert", "top_k": "many" }));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn library_errors_map_to_json_rpc_codes() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let missing = call_tool(&engine, "hermes_fetch", json!({ "node_id": "nope" })).unwrap_err();
+        assert_eq!(error_code(&missing), NOT_FOUND);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn a() {}").unwrap();
+        let args = json!({ "file_path": "main.rs", "start_line": 3, "end_line": 1 });
+        let inverted = call_tool_in(&engine, dir.path(), "hermes_fetch_range", args).unwrap_err();
+        assert_eq!(error_code(&inverted), INVALID_PARAMS);
+
+        let no_query = call_tool(&engine, "hermes_search", json!({})).unwrap_err();
+        assert_eq!(error_code(&no_query), INVALID_PARAMS);
+        let embedding = anyhow::Error::from(HermesError::EmbeddingApi("down".to_string()));
+        assert_eq!(error_code(&embedding), EMBEDDING_UNAVAILABLE);
+        let poisoned = anyhow::Error::from(HermesError::LockPoisoned("panic".to_string()));
+        assert_eq!(error_code(&poisoned), INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn a_panicking_handler_gets_an_internal_error_reply() {
+        let engine = engine_with_nodes(&[]);
+        let reply = answer(&engine, &json!(7), || panic!("index out of range"));
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["error"]["code"], INTERNAL_ERROR);
+        assert_eq!(reply["error"]["message"], "internal error: index out of range");
+        let formatted = answer(&engine, &json!(8), || panic!("line {}", 3));
+        assert_eq!(formatted["error"]["message"], "internal error: line 3");
+        assert_eq!(answer(&engine, &json!(9), || Ok(json!("ok")))["result"], "ok");
+    }
+
+    #[test]
+    fn a_panic_holding_the_db_lock_does_not_break_later_requests() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let search_cache = engine.search_cache();
+        let reply = answer(&engine, &json!(1), || {
+            let _conn = engine.db().lock().unwrap();
+            let _cache = search_cache.lock().unwrap();
+            panic!("handler bug");
+        });
+        assert_eq!(reply["error"]["code"], INTERNAL_ERROR);
+
+        let resp = call_tool(&engine, "hermes_search", json!({ "query": "alert_a" })).unwrap();
+        assert_eq!(resp["pointers"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn argument_errors_are_invalid_params() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let cases = [
+            ("hermes_search", json!({ "query": "alert", "mode": "fast" })),
+            ("hermes_search", json!({ "query": "alert", "top_k": -1 })),
+            ("hermes_search", json!({ "query": "alert", "metadata": "lang=rust" })),
+            ("hermes_fetch", json!({})),
+            ("hermes_fetch_range", json!({ "file_path": "main.rs", "start_line": 2 })),
+            ("hermes_neighbors", json!({ "node_id": "p1", "direction": "sideways" })),
+            ("hermes_traverse", json!({ "node_id": "p1", "edge_types": ["knows"] })),
+            ("hermes_stats", json!({ "scope": "galaxy" })),
+            ("hermes_fact", json!({ "content": "no type" })),
+            ("hermes_fact_update", json!({ "content": "no id" })),
+            ("hermes_fact_invalidate", json!({ "fact_id": "" })),
+            ("hermes_fact_history", json!({})),
+            ("hermes_facts", json!({ "as_of": "last tuesday" })),
+            ("hermes_fact_history", json!({ "node_id": "p1", "fact_id": "f1" })),
+            ("hermes_search", json!("alert")),
+        ];
+        for (tool, arguments) in cases {
+            let error = call_tool(&engine, tool, arguments.clone()).unwrap_err();
+            assert_eq!(error_code(&error), INVALID_PARAMS, "{tool} {arguments}: {error}");
+        }
+        let unnamed = handle_tool_call(&engine, Path::new("."), &json!({})).unwrap_err();
+        assert_eq!(error_code(&unnamed), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn fact_update_supersedes_and_history_returns_the_chain() {
+        let engine = engine_with_nodes(&[]);
+        let added = json!({ "fact_type": "decision", "content": "use ChromaDB" });
+        let first = call_tool(&engine, "hermes_fact", added).unwrap()["id"].clone();
+        let update = json!({ "fact_id": first, "content": "use Qdrant" });
+        let updated = call_tool(&engine, "hermes_fact_update", update.clone()).unwrap();
+        assert_eq!(updated["supersedes"], first);
+        let second = updated["id"].clone();
+
+        let active = call_tool(&engine, "hermes_facts", json!({})).unwrap();
+        assert_eq!(active.as_array().unwrap().len(), 1);
+        assert_eq!(active[0]["content"], "use Qdrant");
+        assert_eq!(active[0]["fact_type"], "Decision");
+
+        let history = call_tool(&engine, "hermes_fact_history", json!({ "fact_id": first }));
+        let history = history.unwrap();
+        assert_eq!(history.as_array().unwrap().len(), 2);
+        assert_eq!((&history[0]["id"], &history[1]["id"]), (&second, &first));
+        assert_eq!(history[1]["superseded_by"], second);
+        assert!(!history[1]["valid_to"].is_null());
+
+        let invalidate = json!({ "fact_id": second });
+        let retired = call_tool(&engine, "hermes_fact_invalidate", invalidate.clone()).unwrap();
+        assert_eq!(retired["status"], "invalidated");
+        assert_eq!(call_tool(&engine, "hermes_facts", json!({})).unwrap(), json!([]));
+
+        for (tool, arguments) in [
+            ("hermes_fact_update", update),
+            ("hermes_fact_invalidate", invalidate),
+            ("hermes_fact_history", json!({ "fact_id": "no-such-fact" })),
+        ] {
+            let error = call_tool(&engine, tool, arguments).unwrap_err();
+            assert_eq!(error_code(&error), NOT_FOUND, "{tool}: {error}");
+        }
+        let none = call_tool(&engine, "hermes_fact_history", json!({ "node_id": "no-such-node" }));
+        assert_eq!(none.unwrap(), json!([]));
+
+        let as_of = |at: &Value| {
+            let facts = call_tool(&engine, "hermes_facts", json!({ "as_of": at })).unwrap();
+            facts.as_array().unwrap().iter().map(|f| f["content"].clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(as_of(&history[1]["valid_from"]), ["use ChromaDB"]);
+        assert_eq!(as_of(&history[0]["valid_from"]), ["use Qdrant"]);
+        assert!(as_of(&json!("2000-01-01")).is_empty());
+    }
+
+    #[test]
+    fn unknown_tool_is_a_tool_level_error() {
+        let engine = engine_with_nodes(&[]);
+        let params = json!({ "name": "hermes_teleport", "arguments": {} });
+        let result = handle_tool_call(&engine, Path::new("."), &params).unwrap();
+        assert_eq!(result["isError"], true);
+        assert_eq!(result["content"][0]["text"], "unknown tool: hermes_teleport");
+
+        let found = call_tool(&engine, "hermes_search", json!({ "query": "x" })).unwrap();
+        assert!(found.get("isError").is_none());
+    }
+
+    #[test]
+    fn requests_need_a_usable_id_and_method() {
+        let engine = engine_with_nodes(&[]);
+        let script = [
+            json!({ "jsonrpc": "2.0", "method": "tools/list" }),
+            json!({ "jsonrpc": "2.0", "id": null, "method": "tools/list" }),
+            json!({ "jsonrpc": "2.0", "id": true, "method": "tools/list" }),
+            json!({ "jsonrpc": "2.0", "id": "req-7" }),
+            json!([{ "jsonrpc": "2.0", "id": 8, "method": "tools/list" }]),
+            json!({ "jsonrpc": "2.0", "method": "notifications/cancelled", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": "req-9", "method": "tools/list" }),
+        ]
+        .map(|message| message.to_string());
+        let replies = run_script(&engine, Path::new("."), &script);
+        assert_eq!(replies.len(), 6);
+        let rejected: Vec<_> = replies.iter().filter(|reply| reply["id"].is_null()).collect();
+        assert_eq!(rejected.len(), 4);
+        assert!(rejected.iter().all(|reply| reply["error"]["code"] == INVALID_REQUEST));
+        let no_method = &reply_to(&replies, "req-7")["error"];
+        assert_eq!(no_method["message"], "request is missing a method");
+        assert!(reply_to(&replies, "req-9")["result"]["tools"].is_array());
+    }
+
+    /// Feeds `lines` through [`run_with_io`] and returns one parsed reply per
+    /// output line, in the order the requests completed.
+    fn run_script(engine: &HermesEngine, root: &Path, lines: &[String]) -> Vec<Value> {
+        let input = lines.join("\n");
+        let mut out = Vec::new();
+        run_with_io(engine, root, input.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn reply_to(replies: &[Value], id: impl Into<Value>) -> &Value {
+        let id = id.into();
+        replies.iter().find(|reply| reply["id"] == id).expect("a reply with this id")
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string()
+    }
+
+    fn tool_text(reply: &Value) -> Value {
+        serde_json::from_str(reply["result"]["content"][0]["text"].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn run_with_io_serves_a_scripted_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() -> u32 {\n    7\n}\n")
+            .unwrap();
+        let engine = HermesEngine::in_memory("mcp-script").unwrap();
+        let index = [request(3, "tools/call", json!({ "name": "hermes_index", "arguments": {} }))];
+        let indexed = run_script(&engine, dir.path(), &index);
+        assert_eq!(tool_text(&indexed[0])["indexed"], 1);
+
+        let script = [
+            request(1, "initialize", json!({})),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string(),
+            String::new(),
+            request(2, "tools/list", Value::Null),
+            request(4, "tools/call", json!({
+                "name": "hermes_search",
+                "arguments": { "query": "parse_config", "mode": "pointer" },
+            })),
+            "{ not json".to_string(),
+            request(5, "completion/complete", Value::Null),
+            request(6, "tools/call", json!({ "name": "hermes_fetch", "arguments": {
+                "node_id": "no-such-node",
+            } })),
+        ];
+        let replies = run_script(&engine, dir.path(), &script);
+        assert_eq!(replies.len(), 6, "notifications and blank lines get no reply");
+        assert!(replies.iter().all(|r| r["jsonrpc"] == "2.0"));
+
+        let initialized = &reply_to(&replies, 1)["result"];
+        assert_eq!(initialized["serverInfo"]["name"], "Hermes");
+        assert!(initialized["protocolVersion"].is_string());
+
+        let tools = reply_to(&replies, 2)["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().any(|tool| tool["name"] == "hermes_search"));
+
+        let search = tool_text(reply_to(&replies, 4));
+        assert_eq!(search["pointers"][0]["chunk"], "parse_config");
+
+        assert_eq!(reply_to(&replies, Value::Null)["error"]["code"], PARSE_ERROR);
+        let unknown = &reply_to(&replies, 5)["error"];
+        assert_eq!(unknown["code"], METHOD_NOT_FOUND);
+        assert_eq!(unknown["message"], "unknown method: completion/complete");
+        let missing = reply_to(&replies, 6);
+        assert_eq!(missing["error"]["code"], NOT_FOUND);
+        assert!(missing.get("result").is_none());
+    }
+
+    #[test]
+    fn tcp_connections_get_their_own_sessions() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let listener = Listener::bind("127.0.0.1:0").unwrap();
+        let Listener::Tcp(tcp) = &listener else { unreachable!() };
+        let addr = tcp.local_addr().unwrap();
+        let server = engine.clone();
+        std::thread::spawn(move || {
+            serve_connections(&server, Path::new("."), &Arc::default(), &listener)
+        });
+
+        let call = |id: u64, name: &str, arguments: Value| {
+            let params = json!({ "name": name, "arguments": arguments });
+            json!({ "jsonrpc": "2.0", "id": id, "method": "tools/call", "params": params })
+        };
+        let search = call(1, "hermes_search", json!({ "query": "alert_a" }));
+        let stats = call(2, "hermes_stats", json!({ "scope": "session" }));
+        let mut sessions = Vec::new();
+        for _ in 0..2 {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            writeln!(stream, "{search}").unwrap();
+            let _search_reply = lines.next().unwrap().unwrap();
+            writeln!(stream, "{stats}").unwrap();
+            let reply: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+            let text = reply["result"]["content"][0]["text"].as_str().unwrap();
+            let session: Value = serde_json::from_str(text).unwrap();
+            assert_eq!(session["session"]["total_queries"], 1);
+            sessions.push(session["session_id"].as_str().unwrap().to_string());
+        }
+        assert_ne!(sessions[0], sessions[1]);
+        assert!(sessions[0].starts_with(engine.session_id()));
+    }
+
+    /// Sends each flushed line to a channel, so a test sees replies as the
+    /// server writes them.
+    struct LineSender(mpsc::Sender<Value>, Vec<u8>);
+
+    impl Write for LineSender {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let line = std::mem::take(&mut self.1);
+            let _ = self.0.send(serde_json::from_slice(&line).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn a_blocked_request_does_not_hold_up_later_ones() {
+        let engine = engine_with_nodes(&["alert_a"]);
+        let script = [
+            request(1, "tools/call", json!({
+                "name": "hermes_search",
+                "arguments": { "query": "alert_a" },
+            })),
+            request(2, "tools/list", Value::Null),
+        ]
+        .join("\n");
+        let (tx, replies) = mpsc::channel();
+        let db = engine.db().lock().unwrap();
+        let server = engine.clone();
+        let handle = std::thread::spawn(move || {
+            let out = LineSender(tx, Vec::new());
+            serve(&server, Path::new("."), &IndexNotifier::default(), script.as_bytes(), out)
+        });
+
+        let first = replies.recv().unwrap();
+        assert_eq!(first["id"], 2, "tools/list is answered while the search waits");
+        drop(db);
+        let second = replies.recv().unwrap();
+        assert_eq!(second["id"], 1);
+        assert!(second["result"]["content"].is_array());
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn index_while_indexing_is_refused() {
+        let engine = engine_with_nodes(&[]);
+        engine.indexing.store(true, Ordering::Release);
+        let error = call_tool(&engine, "hermes_index", json!({})).unwrap_err();
+        assert_eq!(error_code(&error), ALREADY_INDEXING);
+        assert!(error.to_string().starts_with("already indexing"));
+    }
+
+    #[test]
+    fn watch_retries_a_batch_that_arrives_while_indexing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let engine = HermesEngine::in_memory("watch-retry").unwrap();
+        engine.indexing.store(true, Ordering::Release);
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, reports) = mpsc::channel();
+        let watcher = {
+            let (engine, root, stop) = (engine.clone(), dir.path().to_path_buf(), stop.clone());
+            std::thread::spawn(move || {
+                watch(&engine, &root, &stop, |report| tx.send(report.indexed).unwrap())
+            })
+        };
+        std::thread::sleep(Duration::from_millis(200));
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() {}\n").unwrap();
+
+        assert!(reports.recv_timeout(DEFAULT_DEBOUNCE * 3).is_err(), "the batch waits");
+        engine.indexing.store(false, Ordering::Release);
+        assert_eq!(reports.recv_timeout(Duration::from_secs(10)).unwrap(), 1);
+        assert!(engine.last_indexed().is_some(), "watch batches count as indexes");
+        stop.store(true, Ordering::Relaxed);
+        watcher.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn stopping_wakes_a_sleeping_auto_reindex() {
+        let engine = engine_with_nodes(&[]);
+        let interval = Duration::from_secs(3600);
+        let auto = AutoReindex { interval, retention_days: None, optimize: false };
+        let task = auto.spawn(engine.clone(), PathBuf::from("."), Arc::default());
+        let started = Instant::now();
+        assert!(task.stop(Duration::from_secs(5)));
+        assert!(started.elapsed() < STOP_POLL, "the stop does not wait out a sleep slice");
+        assert!(engine.last_indexed().is_none());
+    }
+
+    #[test]
+    fn auto_reindex_indexes_each_interval_until_stopped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn a() {}\n").unwrap();
+        let engine = HermesEngine::in_memory("auto-reindex").unwrap();
+        let interval = Duration::from_millis(20);
+        let auto = AutoReindex { interval, retention_days: None, optimize: false };
+        let task = auto.spawn(engine.clone(), dir.path().to_path_buf(), Arc::default());
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while engine.last_indexed().is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(task.stop(Duration::from_secs(10)));
+        assert!(engine.last_indexed().is_some());
+        assert_eq!(engine.graph().stats().unwrap().files, 1);
+    }
+
+    #[test]
+    fn reindex_is_skipped_within_an_interval_of_the_last_index() {
+        let interval = Duration::from_secs(60);
+        assert!(reindex_due(None, interval));
+        assert!(!reindex_due(Some(Instant::now()), interval));
+        let long_ago = Instant::now().checked_sub(Duration::from_secs(61));
+        assert!(long_ago.is_none_or(|at| reindex_due(Some(at), interval)));
+    }
+
+    #[test]
+    fn index_notifications_reach_connected_clients_when_enabled() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn a() {}\n").unwrap();
+        let engine = HermesEngine::in_memory("notify").unwrap();
+        let notifier = Arc::new(IndexNotifier { enabled: true, ..IndexNotifier::default() });
+        let (input, mut client) = io::pipe().unwrap();
+        let (tx, replies) = mpsc::channel();
+        let server = {
+            let (engine, notifier) = (engine.clone(), notifier.clone());
+            let root = dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                let (input, out) = (BufReader::new(input), LineSender(tx, Vec::new()));
+                serve(&engine, &root, &notifier, input, out)
+            })
+        };
+
+        writeln!(client, "{}", request(1, "initialize", json!({}))).unwrap();
+        let initialized = replies.recv().unwrap();
+        let capabilities = &initialized["result"]["capabilities"];
+        assert_eq!(capabilities["experimental"]["hermes"]["indexNotifications"], true);
+
+        let interval = Duration::from_millis(20);
+        let auto = AutoReindex { interval, retention_days: None, optimize: false };
+        let task = auto.spawn(engine.clone(), dir.path().to_path_buf(), notifier.clone());
+        let notification = replies.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(task.stop(Duration::from_secs(10)));
+        assert_eq!(notification["method"], "notifications/hermes/index_complete");
+        assert!(notification.get("id").is_none());
+        assert_eq!(notification["params"]["trigger"], "auto_reindex");
+        assert_eq!(notification["params"]["indexed"], 1);
+
+        drop(client);
+        server.join().unwrap().unwrap();
+        assert!(notifier.clients.lock().unwrap().is_empty(), "the client unsubscribed");
+    }
+
+    #[test]
+    fn index_notifications_are_off_by_default() {
+        let notifier = IndexNotifier::default();
+        let (tx, replies) = mpsc::channel();
+        assert!(notifier.subscribe(&tx).is_none());
+        notifier.index_complete("watch", &IngestionReport::default());
+        assert!(replies.try_recv().is_err());
+        assert!(handle_initialize(false)["capabilities"].get("experimental").is_none());
+    }
+
+    #[test]
+    fn resources_list_and_read_files_and_facts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() {}\n").unwrap();
+        std::fs::write(dir.path().join("docs/guide.md"), "# Guide\n\nRun it.\n").unwrap();
+        let engine = HermesEngine::in_memory("resources").unwrap();
+        engine.index(dir.path(), &IndexOptions::default()).unwrap();
+        engine.add_fact(None, FactType::Decision, "keep config parsing pure", None).unwrap();
+        let root = dir.path();
+
+        let listed = handle_resources_list(&engine, root, &Value::Null).unwrap();
+        let resources = listed["resources"].as_array().unwrap();
+        let uris: Vec<_> = resources.iter().map(|r| r["uri"].as_str().unwrap()).collect();
+        assert_eq!(uris, [FACTS_URI, "hermes://file/docs/guide.md", "hermes://file/lib.rs"]);
+        assert_eq!(resources[1]["mimeType"], "text/markdown");
+        assert_eq!(resources[2]["name"], "lib.rs");
+        assert!(listed.get("nextCursor").is_none());
+
+        let read = handle_resources_read(&engine, root, &json!({ "uri": uris[2] })).unwrap();
+        assert_eq!(read["contents"][0]["text"], "pub fn parse_config() {}");
+        assert_eq!(read["contents"][0]["mimeType"], "text/x-rust");
+        assert_eq!(engine.stats(None).unwrap().session.fetches.count, 1);
+
+        let facts = handle_resources_read(&engine, root, &json!({ "uri": FACTS_URI })).unwrap();
+        assert!(facts["contents"][0]["text"].as_str().unwrap().contains("config parsing pure"));
+
+        for uri in ["hermes://file/missing.rs", "file:///etc/passwd"] {
+            let error = handle_resources_read(&engine, root, &json!({ "uri": uri })).unwrap_err();
+            assert_eq!(error_code(&error), NOT_FOUND, "{uri}");
+        }
+        let error = handle_resources_read(&engine, root, &json!({})).unwrap_err();
+        assert_eq!(error_code(&error), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn resources_list_pages_with_a_cursor() {
+        let engine = HermesEngine::in_memory("resource-pages").unwrap();
+        let graph = engine.graph();
+        for i in 0..=RESOURCES_PAGE_SIZE {
+            let path = format!("/repo/src/file_{i:04}.rs");
+            let node =
+                graph.create_node_builder().name(&path).node_type(NodeType::File).file_path(&path);
+            graph.add_node(&node.build()).unwrap();
+        }
+        let root = Path::new("/repo");
+
+        let first = handle_resources_list(&engine, root, &json!({})).unwrap();
+        assert_eq!(first["resources"].as_array().unwrap().len(), RESOURCES_PAGE_SIZE + 1);
+        let cursor = first["nextCursor"].clone();
+        assert!(cursor.is_string());
+
+        let second = handle_resources_list(&engine, root, &json!({ "cursor": cursor })).unwrap();
+        let resources = second["resources"].as_array().unwrap();
+        assert_eq!(resources.len(), 1);
+        let last = format!("hermes://file/src/file_{RESOURCES_PAGE_SIZE:04}.rs");
+        assert_eq!(resources[0]["uri"], last);
+        assert!(second.get("nextCursor").is_none());
+
+        let bad = handle_resources_list(&engine, root, &json!({ "cursor": 3 })).unwrap_err();
+        assert_eq!(error_code(&bad), INVALID_PARAMS);
+    }
+
+    #[test]
+    fn hermes_context_prompt_renders_pointers_facts_and_stats() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), "pub fn parse_config() -> u32 {\n    7\n}\n")
+            .unwrap();
+        let engine = HermesEngine::in_memory("mcp-prompts").unwrap();
+        engine.index(dir.path(), &IndexOptions::default()).unwrap();
+        engine.add_fact(None, FactType::Decision, "keep config parsing pure", None).unwrap();
+
+        let get = |id: u64, arguments: Value| {
+            request(id, "prompts/get", json!({ "name": "hermes_context", "arguments": arguments }))
+        };
+        let script = [
+            request(1, "initialize", json!({})),
+            request(2, "prompts/list", Value::Null),
+            get(3, json!({ "topic": "parse_config", "token_budget": "800" })),
+            get(4, json!({ "token_budget": "many" })),
+            request(5, "prompts/get", json!({ "name": "hermes_summary" })),
+        ];
+        let replies = run_script(&engine, dir.path(), &script);
+
+        let capabilities = &reply_to(&replies, 1)["result"]["capabilities"];
+        assert_eq!(capabilities["prompts"]["listChanged"], false);
+        let prompts = reply_to(&replies, 2)["result"]["prompts"].as_array().unwrap();
+        assert_eq!(prompts[0]["name"], "hermes_context");
+
+        let prompt = &reply_to(&replies, 3)["result"];
+        assert_eq!(prompt["messages"][0]["role"], "user");
+        let text = prompt["messages"][0]["content"]["text"].as_str().unwrap();
+        let code = text.find("parse_config").expect("pointer for the topic");
+        let facts = text.find("- [decision] keep config parsing pure").expect("active fact");
+        let index = text.find("## Index\n2 nodes in 1 files indexed").expect("stats line");
+        assert!(code < facts && facts < index, "{text}");
+        assert!(estimate_tokens(text) <= 800);
+
+        assert_eq!(reply_to(&replies, 4)["error"]["code"], INVALID_PARAMS);
+        assert_eq!(reply_to(&replies, 5)["error"]["code"], INVALID_PARAMS);
+    }
 }
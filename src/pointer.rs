@@ -1,3 +1,4 @@
+use crate::search::{estimate_tokens, SearchTier};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,13 +10,72 @@ pub struct Pointer {
     pub relevance: f64,
     pub summary: String,
     pub node_type: String,
+    /// RFC 3339 time the node was last indexed (or its file modified).
     pub last_modified: Option<String>,
+    /// Inlined chunk text: a short preview in Smart mode, the (budgeted) full
+    /// chunk in Full mode. Counted as fetched tokens, not pointer tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Why this pointer matched: the FTS excerpt, the matched name (literal)
+    /// or the first summary line (vector).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PointerResponse {
     pub pointers: Vec<Pointer>,
     pub accounting: AccountingReport,
+    /// Scoring breakdown, present only on explain searches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<SearchDebug>,
+    /// Active facts about the returned nodes or the query's keywords.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facts: Vec<RelatedFact>,
+}
+
+/// A recorded fact attached to a search response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedFact {
+    pub id: String,
+    pub fact_type: String,
+    pub content: String,
+    /// The pointer in the same response whose node the fact is about.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+}
+
+/// How an explain search arrived at its pointers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchDebug {
+    /// Whether a cached response existed for the same search. Explain
+    /// searches are recomputed either way.
+    pub cache_hit: bool,
+    /// `none`, `skip_vector` or `skip_all`: which tiers the literal hits
+    /// made the search skip.
+    pub short_circuit: String,
+    /// One entry per pointer, in response order.
+    pub pointers: Vec<PointerExplanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointerExplanation {
+    pub id: String,
+    /// The pointer's relevance after fusion and any graph-expansion damping.
+    pub fused_score: f64,
+    /// Tiers that found the node. Empty for pointers added by graph expansion.
+    pub tiers: Vec<TierContribution>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TierContribution {
+    pub tier: SearchTier,
+    /// 1-based position within the tier.
+    pub rank: usize,
+    /// The tier's own score, before rank fusion.
+    pub raw_score: f64,
+    /// Share of the fused score this tier contributed.
+    pub contribution: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +85,15 @@ pub struct AccountingReport {
     pub total_tokens: u64,
     pub traditional_rag_estimate: u64,
     pub savings_pct: f64,
+    /// Tokens of the attached facts; part of `total_tokens`.
+    #[serde(default)]
+    pub fact_tokens: u64,
+    /// The caller's pointer-token budget, when the search was budgeted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_budget: Option<u64>,
+    /// Pointer tokens spent out of `token_budget`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_used: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,30 +104,78 @@ pub struct FetchResponse {
     pub start_line: i64,
     pub end_line: i64,
     pub token_count: u64,
+    /// Set when `content` was cut to a `max_tokens` cap; the content then ends
+    /// with a marker naming the lines that were left out.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Active facts recorded about the file or any node in it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_facts: Vec<RelatedFact>,
+}
+
+/// A node reached over one edge, in pointer form: where it is and what it
+/// is, without content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeighborPointer {
+    pub id: String,
+    pub edge_type: String,
+    /// `out` when the edge starts at the queried node, `in` when it ends there.
+    pub direction: String,
+    pub source: String,
+    pub chunk: String,
+    pub lines: String,
+    pub node_type: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NeighborsResponse {
+    pub node_id: String,
+    pub neighbors: Vec<NeighborPointer>,
+    /// Set when the node has more matching neighbors than the limit.
+    #[serde(default)]
+    pub truncated: bool,
 }
 
 impl Pointer {
+    /// Ids are priced at about four characters per token: a UUID costs
+    /// several tokens, a `p12` alias one.
     pub fn estimate_token_count(&self) -> u64 {
         let text = format!(
-            "{} {} {} {}",
-            self.source, self.chunk, self.lines, self.summary
+            "{} {} {} {} {} {}",
+            self.source,
+            self.chunk,
+            self.lines,
+            self.summary,
+            self.last_modified.as_deref().unwrap_or_default(),
+            self.snippet.as_deref().unwrap_or_default()
         );
         let word_count = text.split_whitespace().count() as u64;
-        (word_count * 4).div_ceil(3) + 2
+        (word_count * 4).div_ceil(3) + (self.id.len() as u64).div_ceil(4) + 2
+    }
+}
+
+impl RelatedFact {
+    /// Priced like a pointer: words of the content plus the ids at about
+    /// four characters per token.
+    pub fn estimate_token_count(&self) -> u64 {
+        let text = format!("{} {}", self.fact_type, self.content);
+        let word_count = text.split_whitespace().count() as u64;
+        let pointer = self
+            .pointer
+            .as_deref()
+            .map_or(0, |p| (p.len() as u64).div_ceil(4));
+        (word_count * 4).div_ceil(3) + (self.id.len() as u64).div_ceil(4) + pointer + 2
     }
 }
 
 impl PointerResponse {
-    pub fn build(pointers: Vec<Pointer>, fetched_tokens: u64) -> Self {
+    /// `traditional_estimate` is what a naive RAG pipeline would have put in
+    /// context for the same search: the full content of every matched chunk.
+    pub fn build(pointers: Vec<Pointer>, fetched_tokens: u64, traditional_estimate: u64) -> Self {
         let pointer_tokens: u64 = pointers.iter().map(|p| p.estimate_token_count()).sum();
-        let traditional_estimate = pointer_tokens * 15;
         let total = pointer_tokens + fetched_tokens;
-        let savings_pct = if traditional_estimate > 0 {
-            (1.0 - (total as f64 / traditional_estimate as f64)) * 100.0
-        } else {
-            0.0
-        };
-
         Self {
             pointers,
             accounting: AccountingReport {
@@ -66,9 +183,101 @@ impl PointerResponse {
                 fetched_tokens,
                 total_tokens: total,
                 traditional_rag_estimate: traditional_estimate,
-                savings_pct: savings_pct.max(0.0),
+                savings_pct: savings_pct(total, traditional_estimate),
+                fact_tokens: 0,
+                token_budget: None,
+                budget_used: None,
             },
+            debug: None,
+            facts: Vec::new(),
+        }
+    }
+
+    /// Attaches `facts` and adds their tokens to the accounting.
+    pub fn with_facts(mut self, facts: Vec<RelatedFact>) -> Self {
+        let a = &mut self.accounting;
+        a.fact_tokens = facts.iter().map(RelatedFact::estimate_token_count).sum();
+        a.total_tokens = a.pointer_tokens + a.fetched_tokens + a.fact_tokens;
+        a.savings_pct = savings_pct(a.total_tokens, a.traditional_rag_estimate);
+        self.facts = facts;
+        self
+    }
+
+    /// Renders one line per pointer, e.g.
+    /// `p3 src/search/mod.rs:112-160 fn search (0.91) — hybrid search entry point`,
+    /// with inlined content indented below it, then a one-line accounting
+    /// summary measured on the rendered lines. Far cheaper in context than
+    /// the JSON form.
+    pub fn to_compact_string(&self) -> String {
+        let mut body = String::new();
+        for p in &self.pointers {
+            let location = if p.source.is_empty() {
+                "-".to_string()
+            } else {
+                format!("{}:{}", p.source, p.lines)
+            };
+            body.push_str(&format!(
+                "{} {location} {} {} ({:.2})",
+                p.id,
+                compact_kind(&p.node_type),
+                p.chunk,
+                p.relevance
+            ));
+            let note = p
+                .summary
+                .lines()
+                .next()
+                .filter(|line| !line.trim().is_empty())
+                .or(p.snippet.as_deref());
+            if let Some(note) = note {
+                body.push_str(&format!(" — {}", note.trim()));
+            }
+            body.push('\n');
+            for line in p.content.as_deref().unwrap_or_default().lines() {
+                body.push_str(&format!("    {line}\n"));
+            }
+        }
+        for fact in &self.facts {
+            body.push_str(&format!("fact [{}] {}", fact.fact_type, fact.content));
+            if let Some(pointer) = &fact.pointer {
+                body.push_str(&format!(" (about {pointer})"));
+            }
+            body.push('\n');
         }
+        let shown = estimate_tokens(&body);
+        let baseline = self.accounting.traditional_rag_estimate;
+        let savings_pct = savings_pct(shown, baseline);
+        body.push_str(&format!(
+            "-- {} pointers, {shown} tokens (naive RAG ≈{baseline}, saved {savings_pct:.0}%)",
+            self.pointers.len()
+        ));
+        body
+    }
+
+    /// Records the budget a search was run under alongside what it used.
+    pub fn with_token_budget(mut self, budget: u64) -> Self {
+        self.accounting.token_budget = Some(budget);
+        self.accounting.budget_used = Some(self.accounting.pointer_tokens);
+        self
+    }
+}
+
+/// Percentage of `baseline` not spent, never negative.
+fn savings_pct(spent: u64, baseline: u64) -> f64 {
+    if baseline > 0 {
+        ((1.0 - spent as f64 / baseline as f64) * 100.0).max(0.0)
+    } else {
+        0.0
+    }
+}
+
+/// Short keyword for a node type in compact output.
+fn compact_kind(node_type: &str) -> &str {
+    match node_type {
+        "function" => "fn",
+        "module" => "mod",
+        "document" => "doc",
+        other => other,
     }
 }
 
@@ -87,6 +296,8 @@ mod tests {
             summary: "Application entry point".to_string(),
             node_type: "function".to_string(),
             last_modified: None,
+            content: None,
+            snippet: None,
         };
         let tokens = ptr.estimate_token_count();
         assert!(tokens > 0 && tokens < 100);
@@ -103,15 +314,151 @@ mod tests {
             summary: "Main engine struct with configuration".to_string(),
             node_type: "struct".to_string(),
             last_modified: None,
+            content: None,
+            snippet: None,
         }];
-        let resp = PointerResponse::build(ptrs, 0);
+        let resp = PointerResponse::build(ptrs, 0, 450);
         assert!(resp.accounting.savings_pct > 0.0);
         assert!(resp.accounting.traditional_rag_estimate > resp.accounting.pointer_tokens);
     }
 
+    #[test]
+    fn snippet_counts_toward_pointer_tokens() {
+        let mut ptr = Pointer {
+            id: "abc".to_string(),
+            source: "src/main.rs".to_string(),
+            chunk: "fn main".to_string(),
+            lines: "1-20".to_string(),
+            relevance: 0.95,
+            summary: String::new(),
+            node_type: "function".to_string(),
+            last_modified: None,
+            content: None,
+            snippet: None,
+        };
+        let without = ptr.estimate_token_count();
+        ptr.snippet = Some("starts the server and waits for shutdown".to_string());
+        assert!(ptr.estimate_token_count() > without);
+    }
+
+    #[test]
+    fn last_modified_serializes_as_rfc3339_and_costs_tokens() {
+        let mut ptr = Pointer {
+            id: "abc".to_string(),
+            source: "src/main.rs".to_string(),
+            chunk: "fn main".to_string(),
+            lines: "1-20".to_string(),
+            relevance: 0.95,
+            summary: String::new(),
+            node_type: "function".to_string(),
+            last_modified: None,
+            content: None,
+            snippet: None,
+        };
+        let without = ptr.estimate_token_count();
+        ptr.last_modified = Some("2024-03-05T10:20:30Z".to_string());
+        assert!(ptr.estimate_token_count() > without);
+
+        let json = serde_json::to_value(&ptr).unwrap();
+        let stamp = json["last_modified"].as_str().unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(stamp).is_ok());
+        assert_eq!(stamp, "2024-03-05T10:20:30Z");
+    }
+
+    #[test]
+    fn short_ids_cost_fewer_tokens() {
+        let mut ptr = Pointer {
+            id: "3f2a9c1e-1b7d-4c1e-9a55-0c9d2e1f4b6a".to_string(),
+            source: "src/main.rs".to_string(),
+            chunk: "fn main".to_string(),
+            lines: "1-20".to_string(),
+            relevance: 0.95,
+            summary: String::new(),
+            node_type: "function".to_string(),
+            last_modified: None,
+            content: None,
+            snippet: None,
+        };
+        let uuid = ptr.estimate_token_count();
+        ptr.id = "p12".to_string();
+        assert_eq!(uuid - ptr.estimate_token_count(), 8);
+    }
+
+    #[test]
+    fn compact_string_is_one_line_per_pointer_and_cheaper_than_json() {
+        let ptrs = vec![
+            Pointer {
+                id: "p3".to_string(),
+                source: "src/search/mod.rs".to_string(),
+                chunk: "search".to_string(),
+                lines: "112-160".to_string(),
+                relevance: 0.912,
+                summary: "hybrid search entry point\nmore detail".to_string(),
+                node_type: "function".to_string(),
+                last_modified: None,
+                content: None,
+                snippet: None,
+            },
+            Pointer {
+                id: "p4".to_string(),
+                source: "src/search/cache.rs".to_string(),
+                chunk: "SearchCache".to_string(),
+                lines: "40-60".to_string(),
+                relevance: 0.5,
+                summary: String::new(),
+                node_type: "struct".to_string(),
+                last_modified: None,
+                content: Some("pub struct SearchCache {".to_string()),
+                snippet: Some("LRU cache".to_string()),
+            },
+        ];
+        let resp = PointerResponse::build(ptrs, 0, 400);
+        let text = resp.to_compact_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "p3 src/search/mod.rs:112-160 fn search (0.91) — hybrid search entry point"
+        );
+        assert_eq!(
+            lines[1],
+            "p4 src/search/cache.rs:40-60 struct SearchCache (0.50) — LRU cache"
+        );
+        assert_eq!(lines[2], "    pub struct SearchCache {");
+        assert!(lines[3].starts_with("-- 2 pointers, "));
+        assert_eq!(lines.len(), 4);
+
+        let json = serde_json::to_string_pretty(&resp).unwrap();
+        assert!(estimate_tokens(&text) < estimate_tokens(&json));
+    }
+
+    #[test]
+    fn attached_facts_count_toward_total_and_compact_text() {
+        let fact = |pointer: Option<&str>| RelatedFact {
+            id: "3f2b9c1e-0000-4000-8000-000000000000".to_string(),
+            fact_type: "decision".to_string(),
+            content: "use Qdrant for vectors".to_string(),
+            pointer: pointer.map(str::to_string),
+        };
+        let facts = vec![fact(Some("p1")), fact(None)];
+        let resp = PointerResponse::build(vec![], 0, 400).with_facts(facts);
+        let a = &resp.accounting;
+        assert!(a.fact_tokens > 0);
+        assert_eq!(a.total_tokens, a.fact_tokens);
+        assert!(a.savings_pct < 100.0);
+
+        let text = resp.to_compact_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "fact [decision] use Qdrant for vectors (about p1)"
+        );
+        assert_eq!(lines[1], "fact [decision] use Qdrant for vectors");
+        assert!(lines[2].starts_with("-- 0 pointers, "));
+    }
+
     #[test]
     fn pointer_response_empty_has_zero_savings() {
-        let resp = PointerResponse::build(vec![], 0);
+        let resp = PointerResponse::build(vec![], 0, 0);
         assert_eq!(resp.accounting.pointer_tokens, 0);
         assert_eq!(resp.accounting.savings_pct, 0.0);
         assert_eq!(resp.accounting.total_tokens, 0);
@@ -128,9 +475,11 @@ mod tests {
             summary: "Performs a hybrid search over the knowledge graph".to_string(),
             node_type: "function".to_string(),
             last_modified: None,
+            content: None,
+            snippet: None,
         };
-        let no_fetch = PointerResponse::build(vec![ptr.clone()], 0);
-        let with_fetch = PointerResponse::build(vec![ptr], 5000);
+        let no_fetch = PointerResponse::build(vec![ptr.clone()], 0, 6000);
+        let with_fetch = PointerResponse::build(vec![ptr], 5000, 6000);
         // Adding fetched tokens should reduce (or maintain) savings percentage
         assert!(with_fetch.accounting.savings_pct <= no_fetch.accounting.savings_pct);
         assert_eq!(with_fetch.accounting.fetched_tokens, 5000);
@@ -140,7 +489,7 @@ mod tests {
     fn savings_pct_floored_at_zero() {
         // Simulate a case where fetched tokens exceed the traditional estimate
         // by using an empty pointer list (traditional_estimate = 0)
-        let resp = PointerResponse::build(vec![], 9999);
+        let resp = PointerResponse::build(vec![], 9999, 0);
         assert!(resp.accounting.savings_pct >= 0.0);
     }
 
@@ -155,9 +504,11 @@ mod tests {
             summary: "short".to_string(),
             node_type: "function".to_string(),
             last_modified: None,
+            content: None,
+            snippet: None,
         };
         let fetched = 123;
-        let resp = PointerResponse::build(vec![ptr], fetched);
+        let resp = PointerResponse::build(vec![ptr], fetched, 0);
         assert_eq!(
             resp.accounting.total_tokens,
             resp.accounting.pointer_tokens + fetched
@@ -1,16 +1,232 @@
-use anyhow::Result;
-use rusqlite::Connection;
+use crate::busy;
+use crate::error::{HermesError, Result};
+use rusqlite::{params, Connection, OptionalExtension};
 
+/// One schema change; a database at version `n` has had the first `n` of
+/// [`MIGRATIONS`] applied.
+struct Migration {
+    name: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+}
+
+/// Every schema change, oldest first. Append new ones at the end and never
+/// edit or reorder one that has shipped. Those up to `node_metadata` predate
+/// `schema_version`: a database without that table may have any of them
+/// applied already, so they check before changing anything.
+const MIGRATIONS: &[Migration] = &[
+    Migration { name: "create_tables", apply: create_tables },
+    Migration { name: "accounting_session_id", apply: add_accounting_session_id },
+    Migration { name: "accounting_operation", apply: add_accounting_operation },
+    Migration { name: "nodes_name_lower_index", apply: add_name_lower_index },
+    Migration { name: "config_registry", apply: add_config_registry_table },
+    Migration { name: "embedding_dimension", apply: add_embedding_dimension },
+    Migration { name: "node_token_count", apply: add_node_token_count },
+    Migration { name: "pointer_cache", apply: create_pointer_cache_table },
+    Migration { name: "split_chunk_hashes", apply: split_chunk_hashes },
+    Migration { name: "file_hash_metadata", apply: add_file_hash_metadata },
+    Migration { name: "node_metadata", apply: add_node_metadata },
+    Migration { name: "foreign_key_actions", apply: add_foreign_key_actions },
+];
+
+/// The schema version this build creates and understands.
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Brings the database up to [`LATEST_VERSION`], applying each pending
+/// migration in its own transaction and recording it in `schema_version`.
+/// Fails without touching anything if the database is newer than this build.
+///
+/// Turns foreign key enforcement off, which the bundled SQLite enables by
+/// default: table rebuilds drop tables, and with enforcement on that would
+/// fire the cascades of the rows they hold. Callers turn it back on after.
 pub fn run_migrations(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "PRAGMA foreign_keys=OFF;
+         CREATE TABLE IF NOT EXISTS schema_version (
+            version     INTEGER PRIMARY KEY,
+            name        TEXT NOT NULL,
+            applied_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );",
+    )?;
+    let current = schema_version(conn)?;
+    if current > LATEST_VERSION {
+        return Err(HermesError::SchemaTooNew { found: current, supported: LATEST_VERSION });
+    }
+    for (version, migration) in (1..).zip(MIGRATIONS).skip(current as usize) {
+        let tx = busy::write_transaction(conn)?;
+        // Another process sharing the file may have migrated it meanwhile.
+        if schema_version(&tx)? >= version {
+            continue;
+        }
+        (migration.apply)(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+            params![version, migration.name],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// The highest migration recorded in `schema_version`; 0 for a new
+/// database or one created before versioning.
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    let versioned: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master
+                        WHERE type = 'table' AND name = 'schema_version')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !versioned {
+        return Ok(0);
+    }
+    let version: Option<u32> =
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0))?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Fails unless the database is at exactly [`LATEST_VERSION`], for
+/// connections that cannot run [`run_migrations`] because they are read-only.
+pub fn check_version(conn: &Connection) -> Result<()> {
+    let found = schema_version(conn)?;
+    let supported = LATEST_VERSION;
+    match found.cmp(&supported) {
+        std::cmp::Ordering::Greater => Err(HermesError::SchemaTooNew { found, supported }),
+        std::cmp::Ordering::Less => Err(HermesError::SchemaTooOld { found, supported }),
+        std::cmp::Ordering::Equal => Ok(()),
+    }
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let found = conn
+        .query_row(
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+            params![table, column],
+            |_| Ok(()),
+        )
+        .optional()?;
+    Ok(found.is_some())
+}
+
+/// `ALTER TABLE ... ADD COLUMN` unless an unversioned database already has it.
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition};"))?;
+    }
+    Ok(())
+}
+
+/// The tables as they were when versioning began. Tables created by later
+/// migrations are not part of it.
+fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute_batch(CREATE_TABLES_SQL)?;
-    create_fts_table(conn)?;
-    add_accounting_session_id(conn);
-    add_name_lower_index(conn);
-    add_config_registry_table(conn)?;
+    conn.execute_batch(CREATE_FILE_HASHES_SQL)?;
+    create_fts_table(conn)
+}
+
+/// Rebuilds `edges` to cascade deletes of either endpoint and
+/// `temporal_facts` to forget a deleted node, so `PRAGMA foreign_keys=ON`
+/// keeps them consistent. Rows that already dangle are removed (edges) or
+/// detached (facts) first, since enforcement would reject them.
+fn add_foreign_key_actions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM edges
+         WHERE source_id NOT IN (SELECT id FROM nodes) OR target_id NOT IN (SELECT id FROM nodes);
+         UPDATE temporal_facts SET node_id = NULL
+         WHERE node_id IS NOT NULL AND node_id NOT IN (SELECT id FROM nodes);
+
+         CREATE TABLE edges_new (
+             id          TEXT PRIMARY KEY,
+             project_id  TEXT NOT NULL,
+             source_id   TEXT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+             target_id   TEXT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+             edge_type   TEXT NOT NULL,
+             weight      REAL DEFAULT 1.0,
+             created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+             UNIQUE(source_id, target_id, edge_type)
+         );
+         INSERT INTO edges_new SELECT id, project_id, source_id, target_id, edge_type, weight, created_at
+             FROM edges;
+         DROP TABLE edges;
+         ALTER TABLE edges_new RENAME TO edges;
+         CREATE INDEX idx_edges_source ON edges(source_id);
+         CREATE INDEX idx_edges_target ON edges(target_id);
+         CREATE INDEX idx_edges_project ON edges(project_id);
+
+         CREATE TABLE temporal_facts_new (
+             id                TEXT PRIMARY KEY,
+             project_id        TEXT NOT NULL,
+             node_id           TEXT REFERENCES nodes(id) ON DELETE SET NULL,
+             fact_type         TEXT NOT NULL,
+             content           TEXT NOT NULL,
+             valid_from        TEXT NOT NULL,
+             valid_to          TEXT,
+             superseded_by     TEXT,
+             source_reference  TEXT,
+             created_at        TEXT NOT NULL DEFAULT (datetime('now'))
+         );
+         INSERT INTO temporal_facts_new
+             SELECT id, project_id, node_id, fact_type, content, valid_from, valid_to,
+                    superseded_by, source_reference, created_at
+             FROM temporal_facts;
+         DROP TABLE temporal_facts;
+         ALTER TABLE temporal_facts_new RENAME TO temporal_facts;
+         CREATE INDEX idx_facts_project ON temporal_facts(project_id);
+         CREATE INDEX idx_facts_node ON temporal_facts(node_id);
+         CREATE INDEX idx_facts_active
+             ON temporal_facts(project_id, fact_type) WHERE valid_to IS NULL;",
+    )?;
     Ok(())
 }
 
-/// Idempotent: creates the config_registry table for env var tracking.
+/// Free-form JSON attributes of a node; NULL on rows written before it.
+fn add_node_metadata(conn: &Connection) -> Result<()> {
+    add_column(conn, "nodes", "metadata", "TEXT")
+}
+
+/// Modification time (nanoseconds since the epoch) and size of each file
+/// when it was hashed, so unchanged files can be skipped without reading them.
+fn add_file_hash_metadata(conn: &Connection) -> Result<()> {
+    add_column(conn, "file_hashes", "mtime_ns", "INTEGER")?;
+    add_column(conn, "file_hashes", "size", "INTEGER")
+}
+
+/// Chunk hashes (keyed `path::chunk name`) get their own `chunk_hashes`
+/// table. Older databases stored them in `file_hashes`, keyed by path alone,
+/// so two projects sharing the DB overwrote each other's rows; that table is
+/// rebuilt keyed by project and path, with the chunk rows moved across.
+fn split_chunk_hashes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS chunk_hashes (
+            project_id   TEXT NOT NULL,
+            chunk_key    TEXT NOT NULL,
+            content_hash TEXT NOT NULL,
+            indexed_at   TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (project_id, chunk_key)
+        );",
+    )?;
+    let sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type='table' AND name='file_hashes'",
+        [],
+        |row| row.get(0),
+    )?;
+    if sql.contains("PRIMARY KEY (project_id, file_path)") {
+        return Ok(());
+    }
+    conn.execute_batch(&format!(
+        "INSERT OR REPLACE INTO chunk_hashes (project_id, chunk_key, content_hash, indexed_at)
+             SELECT project_id, file_path, content_hash, indexed_at FROM file_hashes
+             WHERE file_path LIKE '%::%';
+         ALTER TABLE file_hashes RENAME TO file_hashes_old;
+         {CREATE_FILE_HASHES_SQL}
+         INSERT INTO file_hashes (file_path, project_id, content_hash, indexed_at)
+             SELECT file_path, project_id, content_hash, indexed_at FROM file_hashes_old
+             WHERE file_path NOT LIKE '%::%';
+         DROP TABLE file_hashes_old;"
+    ))?;
+    Ok(())
+}
+
+/// Creates the config_registry table for env var tracking.
 ///
 /// `is_defined` → var was seen in a definition context (.env, YAML, Markdown table).
 /// `is_used`    → var was accessed in code (Rust/JS/Python/Shell pattern).
@@ -29,16 +245,64 @@ fn add_config_registry_table(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-fn add_name_lower_index(conn: &Connection) {
-    let _ = conn.execute_batch(
-        "CREATE INDEX IF NOT EXISTS idx_nodes_name_lower ON nodes (LOWER(name));",
-    );
+/// `operation` is `search` or `fetch` (empty on rows recorded before it
+/// existed); `latency_ms` is how long the operation took.
+fn add_accounting_operation(conn: &Connection) -> Result<()> {
+    add_column(conn, "accounting", "operation", "TEXT NOT NULL DEFAULT ''")?;
+    add_column(conn, "accounting", "latency_ms", "INTEGER")
+}
+
+fn add_name_lower_index(conn: &Connection) -> Result<()> {
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_nodes_name_lower ON nodes (LOWER(name));")?;
+    Ok(())
+}
+
+/// Providers differ in vector length, so each stored vector records its own.
+fn add_embedding_dimension(conn: &Connection) -> Result<()> {
+    add_column(conn, "embeddings", "dimension", "INTEGER NOT NULL DEFAULT 0")
 }
 
-fn add_accounting_session_id(conn: &Connection) {
-    let _ = conn.execute_batch(
-        "ALTER TABLE accounting ADD COLUMN session_id TEXT NOT NULL DEFAULT '';",
-    );
+/// `pointer_cache` maps short pointer aliases (`p1`, `p2`, …) to node ids per
+/// project. Older databases carry an earlier, never-written layout of the
+/// table, which is dropped and recreated.
+fn create_pointer_cache_table(conn: &Connection) -> Result<()> {
+    let sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type='table' AND name='pointer_cache'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if sql.is_some_and(|sql| !sql.contains("alias")) {
+        conn.execute_batch("DROP TABLE pointer_cache;")?;
+    }
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS pointer_cache (
+            project_id  TEXT NOT NULL,
+            alias       TEXT NOT NULL,
+            seq         INTEGER NOT NULL,
+            node_id     TEXT NOT NULL,
+            last_used   TEXT NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (project_id, alias)
+        );
+        CREATE INDEX IF NOT EXISTS idx_pointers_node ON pointer_cache(project_id, node_id);",
+    )?;
+    Ok(())
+}
+
+/// Token estimate of each node's full content, recorded at ingestion so
+/// searches can price a naive RAG baseline without re-reading files.
+fn add_node_token_count(conn: &Connection) -> Result<()> {
+    add_column(conn, "nodes", "token_count", "INTEGER")
+}
+
+fn add_accounting_session_id(conn: &Connection) -> Result<()> {
+    add_column(conn, "accounting", "session_id", "TEXT NOT NULL DEFAULT ''")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_accounting_session ON accounting(project_id, session_id);",
+    )?;
+    Ok(())
 }
 
 fn create_fts_table(conn: &Connection) -> Result<()> {
@@ -50,7 +314,32 @@ fn create_fts_table(conn: &Connection) -> Result<()> {
 
     if !fts_exists {
         conn.execute_batch(CREATE_FTS_SQL)?;
+    } else {
+        unindex_fts_id_columns(conn)?;
+    }
+    Ok(())
+}
+
+/// Older databases created `fts_content` with `node_id` and `project_id` as
+/// indexed columns, so UUID fragments and project names matched queries.
+/// Rebuilds the table with both columns `UNINDEXED`, copying the stored rows
+/// across (the indexed text is not kept in `nodes`).
+fn unindex_fts_id_columns(conn: &Connection) -> Result<()> {
+    let sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type='table' AND name='fts_content'",
+        [],
+        |row| row.get(0),
+    )?;
+    if sql.contains("UNINDEXED") {
+        return Ok(());
     }
+    conn.execute_batch(&format!(
+        "ALTER TABLE fts_content RENAME TO fts_content_old;
+         {CREATE_FTS_SQL}
+         INSERT INTO fts_content (node_id, project_id, name, content, file_path)
+             SELECT node_id, project_id, name, content, file_path FROM fts_content_old;
+         DROP TABLE fts_content_old;"
+    ))?;
     Ok(())
 }
 
@@ -65,6 +354,7 @@ CREATE TABLE IF NOT EXISTS nodes (
     end_line    INTEGER,
     summary     TEXT,
     content_hash TEXT,
+    token_count INTEGER,
     created_at  TEXT NOT NULL DEFAULT (datetime('now')),
     updated_at  TEXT NOT NULL DEFAULT (datetime('now'))
 );
@@ -107,29 +397,6 @@ CREATE INDEX IF NOT EXISTS idx_facts_node ON temporal_facts(node_id);
 CREATE INDEX IF NOT EXISTS idx_facts_active
     ON temporal_facts(project_id, fact_type) WHERE valid_to IS NULL;
 
-CREATE TABLE IF NOT EXISTS pointer_cache (
-    id           TEXT PRIMARY KEY,
-    project_id   TEXT NOT NULL,
-    node_id      TEXT NOT NULL REFERENCES nodes(id),
-    chunk_label  TEXT NOT NULL,
-    file_path    TEXT NOT NULL,
-    start_line   INTEGER NOT NULL,
-    end_line     INTEGER NOT NULL,
-    summary      TEXT NOT NULL,
-    token_estimate INTEGER NOT NULL DEFAULT 0,
-    created_at   TEXT NOT NULL DEFAULT (datetime('now'))
-);
-
-CREATE INDEX IF NOT EXISTS idx_pointers_project ON pointer_cache(project_id);
-CREATE INDEX IF NOT EXISTS idx_pointers_node ON pointer_cache(node_id);
-
-CREATE TABLE IF NOT EXISTS file_hashes (
-    file_path   TEXT PRIMARY KEY,
-    project_id  TEXT NOT NULL,
-    content_hash TEXT NOT NULL,
-    indexed_at  TEXT NOT NULL DEFAULT (datetime('now'))
-);
-
 CREATE TABLE IF NOT EXISTS accounting (
     id              INTEGER PRIMARY KEY AUTOINCREMENT,
     project_id      TEXT NOT NULL,
@@ -138,15 +405,48 @@ CREATE TABLE IF NOT EXISTS accounting (
     pointer_tokens  INTEGER NOT NULL DEFAULT 0,
     fetched_tokens  INTEGER NOT NULL DEFAULT 0,
     traditional_est INTEGER NOT NULL DEFAULT 0,
+    operation       TEXT NOT NULL DEFAULT '',
+    latency_ms      INTEGER,
     created_at      TEXT NOT NULL DEFAULT (datetime('now'))
 );
-CREATE INDEX IF NOT EXISTS idx_accounting_session ON accounting(project_id, session_id);
+
+CREATE TABLE IF NOT EXISTS accounting_rollup (
+    project_id      TEXT PRIMARY KEY,
+    queries         INTEGER NOT NULL DEFAULT 0,
+    pointer_tokens  INTEGER NOT NULL DEFAULT 0,
+    fetched_tokens  INTEGER NOT NULL DEFAULT 0,
+    traditional_est INTEGER NOT NULL DEFAULT 0,
+    searches        INTEGER NOT NULL DEFAULT 0,
+    fetches         INTEGER NOT NULL DEFAULT 0,
+    pruned_through  TEXT
+);
+
+CREATE TABLE IF NOT EXISTS embeddings (
+    node_id     TEXT NOT NULL,
+    project_id  TEXT NOT NULL,
+    vector      BLOB NOT NULL,
+    model       TEXT NOT NULL,
+    dimension   INTEGER NOT NULL DEFAULT 0,
+    updated_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (node_id, model)
+);
+CREATE INDEX IF NOT EXISTS idx_embeddings_project ON embeddings(project_id);
+";
+
+const CREATE_FILE_HASHES_SQL: &str = "
+CREATE TABLE IF NOT EXISTS file_hashes (
+    file_path   TEXT NOT NULL,
+    project_id  TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    indexed_at  TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (project_id, file_path)
+);
 ";
 
 const CREATE_FTS_SQL: &str = "
 CREATE VIRTUAL TABLE fts_content USING fts5(
-    node_id,
-    project_id,
+    node_id UNINDEXED,
+    project_id UNINDEXED,
     name,
     content,
     file_path,
@@ -171,6 +471,167 @@ mod tests {
         run_migrations(&conn).unwrap();
     }
 
+    fn recorded_migrations(conn: &Connection) -> Vec<(u32, String)> {
+        let sql = "SELECT version, name FROM schema_version ORDER BY version";
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn fresh_database_reaches_the_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        let recorded = recorded_migrations(&conn);
+        assert_eq!(recorded.len(), MIGRATIONS.len());
+        assert_eq!(recorded[0], (1, "create_tables".to_string()));
+        assert_eq!(recorded.last().unwrap().0, LATEST_VERSION);
+    }
+
+    #[test]
+    fn rerunning_migrations_changes_nothing() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        let schema = |conn: &Connection| -> Vec<String> {
+            let sql = "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name";
+            let mut stmt = conn.prepare(sql).unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        let total_changes = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT total_changes()", [], |row| row.get(0)).unwrap()
+        };
+        let (before, changes) = (schema(&conn), total_changes(&conn));
+        run_migrations(&conn).unwrap();
+        assert_eq!(schema(&conn), before);
+        assert_eq!(total_changes(&conn), changes);
+        assert_eq!(recorded_migrations(&conn).len(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn unversioned_old_layout_upgrades_and_keeps_its_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE nodes (
+                id TEXT PRIMARY KEY, project_id TEXT NOT NULL, name TEXT NOT NULL,
+                node_type TEXT NOT NULL, file_path TEXT, start_line INTEGER, end_line INTEGER,
+                summary TEXT, content_hash TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            CREATE TABLE accounting (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, project_id TEXT NOT NULL,
+                query_text TEXT NOT NULL, pointer_tokens INTEGER NOT NULL DEFAULT 0,
+                fetched_tokens INTEGER NOT NULL DEFAULT 0, traditional_est INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO nodes (id, project_id, name, node_type) VALUES ('n1', 'p', 'main', 'function');
+            INSERT INTO accounting (project_id, query_text) VALUES ('p', 'where is main');",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), LATEST_VERSION);
+        for (table, column) in [
+            ("nodes", "token_count"),
+            ("nodes", "metadata"),
+            ("accounting", "session_id"),
+            ("accounting", "operation"),
+            ("accounting", "latency_ms"),
+        ] {
+            assert!(has_column(&conn, table, column).unwrap(), "{table}.{column}");
+        }
+        let (name, session): (String, String) = conn
+            .query_row(
+                "SELECT n.name, a.session_id FROM nodes n, accounting a WHERE n.id = 'n1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((name.as_str(), session.as_str()), ("main", ""));
+    }
+
+    #[test]
+    fn foreign_key_migration_clears_dangling_rows_before_enforcement() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(CREATE_TABLES_SQL).unwrap();
+        // Older builds never turned enforcement on explicitly, and other
+        // clients may have it off, so dangling rows can already exist.
+        conn.execute_batch(
+            "PRAGMA foreign_keys=OFF;
+             INSERT INTO nodes (id, project_id, name, node_type) VALUES ('a', 'p', 'a', 'function'),
+                                                                        ('b', 'p', 'b', 'function');
+             INSERT INTO edges (id, project_id, source_id, target_id, edge_type)
+                 VALUES ('kept', 'p', 'a', 'b', 'calls'), ('dangling', 'p', 'a', 'gone', 'calls');
+             INSERT INTO temporal_facts (id, project_id, node_id, fact_type, content, valid_from)
+                 VALUES ('f1', 'p', 'b', 'decision', 'on b', 'now'),
+                        ('f2', 'p', 'gone', 'decision', 'on gone', 'now');",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+        conn.execute_batch("PRAGMA foreign_keys=ON;").unwrap();
+
+        let violations: i64 = conn
+            .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(violations, 0);
+        let edges: Vec<String> = conn
+            .prepare("SELECT id FROM edges")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(edges, ["kept"]);
+        let detached: Option<String> = conn
+            .query_row("SELECT node_id FROM temporal_facts WHERE id = 'f2'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(detached, None);
+
+        conn.execute("DELETE FROM nodes WHERE id = 'b'", []).unwrap();
+        let (edges, fact_node): (i64, Option<String>) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM edges), node_id FROM temporal_facts WHERE id = 'f1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((edges, fact_node), (0, None));
+    }
+
+    #[test]
+    fn newer_schema_version_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, name) VALUES (?1, 'from_the_future')",
+            params![LATEST_VERSION + 1],
+        )
+        .unwrap();
+        let error = run_migrations(&conn).unwrap_err();
+        let newer = LATEST_VERSION + 1;
+        assert!(matches!(error, HermesError::SchemaTooNew { found, .. } if found == newer));
+        assert!(error.to_string().contains("upgrade hermes"), "{error}");
+    }
+
+    #[test]
+    fn node_metadata_column_added_once_and_null_for_existing_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO nodes (id, project_id, name, node_type) VALUES ('n1', 'p', 'main', 'function')",
+            [],
+        )
+        .unwrap();
+        run_migrations(&conn).unwrap();
+        let metadata: Option<String> = conn
+            .query_row("SELECT metadata FROM nodes WHERE id = 'n1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(metadata, None);
+    }
+
     #[test]
     fn fts_table_created() {
         let conn = Connection::open_in_memory().unwrap();
@@ -184,4 +645,103 @@ mod tests {
             .unwrap();
         assert_eq!(count, 1);
     }
+
+    #[test]
+    fn embeddings_table_created() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='embeddings'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn old_pointer_cache_is_replaced() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE pointer_cache (id TEXT PRIMARY KEY, project_id TEXT NOT NULL, node_id TEXT NOT NULL);
+             CREATE INDEX idx_pointers_node ON pointer_cache(node_id);",
+        )
+        .unwrap();
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO pointer_cache (project_id, alias, seq, node_id, last_used) VALUES ('p', 'p1', 1, 'n', 'now')",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn chunk_hashes_move_out_of_old_file_hashes() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE file_hashes (
+                file_path TEXT PRIMARY KEY, project_id TEXT NOT NULL,
+                content_hash TEXT NOT NULL, indexed_at TEXT NOT NULL DEFAULT (datetime('now'))
+            );
+            INSERT INTO file_hashes (file_path, project_id, content_hash)
+                VALUES ('src/lib.rs', 'acme', 'f1'), ('src/lib.rs::main', 'acme', 'c1');",
+        )
+        .unwrap();
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+
+        let keys = |table: &str, column: &str| -> Vec<String> {
+            let sql = format!("SELECT {column} FROM {table}");
+            let mut stmt = conn.prepare(&sql).unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        assert_eq!(keys("file_hashes", "file_path"), ["src/lib.rs"]);
+        assert_eq!(keys("chunk_hashes", "chunk_key"), ["src/lib.rs::main"]);
+        conn.execute(
+            "INSERT INTO file_hashes (file_path, project_id, content_hash) VALUES ('src/lib.rs', 'other', 'f2')",
+            [],
+        )
+        .unwrap();
+    }
+
+    fn fts_match_count(conn: &Connection, query: &str) -> i64 {
+        conn.query_row(
+            "SELECT COUNT(*) FROM fts_content WHERE fts_content MATCH ?1",
+            [query],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn old_fts_schema_is_rebuilt_with_unindexed_ids() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE fts_content USING fts5(
+                node_id, project_id, name, content, file_path,
+                tokenize='unicode61 remove_diacritics 2'
+            );
+            INSERT INTO fts_content (node_id, project_id, name, content, file_path)
+                VALUES ('3f2a9c1e-node', 'acme', 'load_config', 'reads the config file', 'src/config.rs');",
+        )
+        .unwrap();
+        assert_eq!(fts_match_count(&conn, "acme"), 1);
+
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(fts_match_count(&conn, "config"), 1);
+        assert_eq!(fts_match_count(&conn, "acme"), 0);
+        assert_eq!(fts_match_count(&conn, "3f2a9c1e"), 0);
+        let node_id: String = conn
+            .query_row(
+                "SELECT node_id FROM fts_content WHERE fts_content MATCH 'load_config'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(node_id, "3f2a9c1e-node");
+    }
 }
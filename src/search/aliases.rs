@@ -0,0 +1,189 @@
+use crate::busy;
+use crate::error::{HermesError, Result};
+use crate::graph::KnowledgeGraph;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, DatabaseName, OptionalExtension};
+use std::collections::HashMap;
+
+/// Aliases kept per project; the least recently returned ones are dropped
+/// beyond this.
+pub const MAX_POINTER_ALIASES: usize = 500;
+
+/// Whether `id` has the shape of a pointer alias (`p` followed by digits)
+/// rather than a node id.
+pub fn is_alias(id: &str) -> bool {
+    id.strip_prefix('p')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The node id behind `alias` in the graph's project, if still remembered.
+pub fn resolve(graph: &KnowledgeGraph, alias: &str) -> Result<Option<String>> {
+    let conn = graph.db().lock()?;
+    let node_id = conn
+        .query_row(
+            "SELECT node_id FROM pointer_cache WHERE project_id = ?1 AND alias = ?2",
+            params![graph.project_id(), alias],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(node_id)
+}
+
+/// Compact pointer ids (`p1`, `p2`, …) for one search response, persisted
+/// per project in `pointer_cache` so a node keeps its alias across searches.
+///
+/// On a read-only connection new aliases could not be saved, so nodes
+/// without one are pointed at by their node id instead.
+pub struct PointerAliases {
+    by_node: HashMap<String, String>,
+    next_seq: i64,
+    read_only: bool,
+}
+
+impl PointerAliases {
+    /// Loads the aliases already given to `node_ids` and the next free number.
+    pub fn load<'n>(graph: &KnowledgeGraph, node_ids: impl IntoIterator<Item = &'n str>) -> Result<Self> {
+        let conn = graph.db().lock()?;
+        let next_seq: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(seq), 0) + 1 FROM pointer_cache WHERE project_id = ?1",
+            params![graph.project_id()],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT alias FROM pointer_cache WHERE project_id = ?1 AND node_id = ?2",
+        )?;
+        let mut by_node = HashMap::new();
+        for node_id in node_ids {
+            let alias: Option<String> = stmt
+                .query_row(params![graph.project_id(), node_id], |row| row.get(0))
+                .optional()?;
+            if let Some(alias) = alias {
+                by_node.insert(node_id.to_string(), alias);
+            }
+        }
+        let read_only = conn.is_readonly(DatabaseName::Main)?;
+        Ok(Self { by_node, next_seq, read_only })
+    }
+
+    /// The alias `node_id` has, or would get from the next [`Self::assign`].
+    pub fn peek(&self, node_id: &str) -> String {
+        match self.by_node.get(node_id) {
+            Some(alias) => alias.clone(),
+            None if self.read_only => node_id.to_string(),
+            None => format!("p{}", self.next_seq),
+        }
+    }
+
+    /// Returns the alias of `node_id`, numbering it if it has none yet.
+    pub fn assign(&mut self, node_id: &str) -> String {
+        if let Some(alias) = self.by_node.get(node_id) {
+            return alias.clone();
+        }
+        if self.read_only {
+            return node_id.to_string();
+        }
+        let alias = format!("p{}", self.next_seq);
+        self.next_seq += 1;
+        self.by_node.insert(node_id.to_string(), alias.clone());
+        alias
+    }
+
+    /// Persists the aliases of `node_ids`, marks them as just used and prunes
+    /// the project down to [`MAX_POINTER_ALIASES`]. The newest alias is never
+    /// pruned, so numbers are not handed out twice.
+    pub fn save<'n>(&self, graph: &KnowledgeGraph, node_ids: impl IntoIterator<Item = &'n str>) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let conn = graph.db().lock()?;
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let tx = busy::write_transaction(&conn)?;
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO pointer_cache (project_id, alias, seq, node_id, last_used)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (project_id, alias) DO UPDATE SET last_used = excluded.last_used",
+            )?;
+            for node_id in node_ids {
+                let Some(alias) = self.by_node.get(node_id) else {
+                    continue;
+                };
+                let seq: i64 = alias[1..].parse().map_err(|_| {
+                    HermesError::InvalidInput(format!("malformed pointer alias: {alias}"))
+                })?;
+                upsert.execute(params![graph.project_id(), alias, seq, node_id, now])?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM pointer_cache
+             WHERE project_id = ?1
+               AND seq < (SELECT MAX(seq) FROM pointer_cache WHERE project_id = ?1)
+               AND alias NOT IN (
+                   SELECT alias FROM pointer_cache WHERE project_id = ?1
+                   ORDER BY last_used DESC, seq DESC LIMIT ?2
+               )",
+            params![graph.project_id(), MAX_POINTER_ALIASES as i64],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HermesEngine;
+
+    #[test]
+    fn alias_shape() {
+        assert!(is_alias("p1"));
+        assert!(is_alias("p420"));
+        assert!(!is_alias("p"));
+        assert!(!is_alias("pa1"));
+        assert!(!is_alias("3f2a9c1e-1b7d-4c1e-9a55-0c9d2e1f4b6a"));
+    }
+
+    #[test]
+    fn aliases_are_stable_and_resolve() {
+        let engine = HermesEngine::in_memory("aliases").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "aliases");
+
+        let mut first = PointerAliases::load(&graph, ["node-a", "node-b"]).unwrap();
+        assert_eq!(first.peek("node-a"), "p1");
+        assert_eq!(first.assign("node-a"), "p1");
+        assert_eq!(first.assign("node-b"), "p2");
+        first.save(&graph, ["node-a", "node-b"]).unwrap();
+
+        let mut second = PointerAliases::load(&graph, ["node-b", "node-c"]).unwrap();
+        assert_eq!(second.assign("node-c"), "p3");
+        assert_eq!(second.assign("node-b"), "p2");
+        second.save(&graph, ["node-c", "node-b"]).unwrap();
+
+        assert_eq!(resolve(&graph, "p2").unwrap().as_deref(), Some("node-b"));
+        assert_eq!(resolve(&graph, "p9").unwrap(), None);
+        let other = KnowledgeGraph::new(engine.db().clone(), "other-project");
+        assert_eq!(resolve(&other, "p2").unwrap(), None);
+    }
+
+    #[test]
+    fn least_recently_used_aliases_rotate_out() {
+        let engine = HermesEngine::in_memory("rotate").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "rotate");
+        let ids: Vec<String> = (0..MAX_POINTER_ALIASES + 20).map(|i| format!("node-{i}")).collect();
+        for chunk in ids.chunks(10) {
+            let mut aliases = PointerAliases::load(&graph, chunk.iter().map(String::as_str)).unwrap();
+            for id in chunk {
+                aliases.assign(id);
+            }
+            aliases.save(&graph, chunk.iter().map(String::as_str)).unwrap();
+        }
+
+        assert_eq!(resolve(&graph, "p1").unwrap(), None);
+        assert_eq!(resolve(&graph, "p21").unwrap().as_deref(), Some("node-20"));
+        let newest = format!("p{}", ids.len());
+        assert_eq!(resolve(&graph, &newest).unwrap().as_deref(), Some(ids.last().unwrap().as_str()));
+
+        let next = PointerAliases::load(&graph, ["fresh"]).unwrap();
+        assert_eq!(next.peek("fresh"), format!("p{}", ids.len() + 1));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::graph::Node;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// k-means rounds used to place the centroids. Coarse centroids only need to
+/// be roughly right; exact scoring happens on the probed lists.
+const KMEANS_ITERATIONS: usize = 6;
+/// Training uses at most this many points per list to keep builds fast.
+const TRAIN_POINTS_PER_LIST: usize = 32;
+const MAX_LISTS: usize = 1024;
+
+/// IVF-style approximate nearest neighbour index: vectors are bucketed under
+/// their closest k-means centroid and a query only scans the buckets of its
+/// `nprobe` closest centroids.
+pub struct IvfIndex<T> {
+    entries: Vec<(T, Vec<f32>)>,
+    centroids: Vec<Vec<f32>>,
+    lists: Vec<Vec<usize>>,
+}
+
+impl<T: Send + Sync> IvfIndex<T> {
+    /// Builds an index with about `sqrt(n)` lists.
+    pub fn build(entries: Vec<(T, Vec<f32>)>) -> Self {
+        let n = entries.len();
+        if n == 0 {
+            return Self {
+                entries,
+                centroids: Vec::new(),
+                lists: Vec::new(),
+            };
+        }
+        let nlist = ((n as f64).sqrt() as usize).clamp(1, MAX_LISTS);
+        let normalized: Vec<Vec<f32>> = entries.par_iter().map(|(_, v)| unit(v)).collect();
+
+        let stride = (n / nlist).max(1);
+        let mut centroids: Vec<Vec<f32>> =
+            (0..nlist).filter_map(|i| normalized.get(i * stride).cloned()).collect();
+
+        let train_step = (n / (nlist * TRAIN_POINTS_PER_LIST)).max(1);
+        let training: Vec<&Vec<f32>> = normalized.iter().step_by(train_step).collect();
+        for _ in 0..KMEANS_ITERATIONS {
+            let assignment: Vec<usize> = training
+                .par_iter()
+                .map(|v| nearest(&centroids, v))
+                .collect();
+            let dim = centroids[0].len();
+            let mut sums = vec![vec![0.0f32; dim]; centroids.len()];
+            let mut counts = vec![0usize; centroids.len()];
+            for (v, c) in training.iter().zip(&assignment) {
+                counts[*c] += 1;
+                for (s, x) in sums[*c].iter_mut().zip(v.iter()) {
+                    *s += x;
+                }
+            }
+            for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+                // Empty clusters keep their previous centroid.
+                if count > 0 {
+                    *centroid = unit(&sum);
+                }
+            }
+        }
+
+        let assignment: Vec<usize> = normalized
+            .par_iter()
+            .map(|v| nearest(&centroids, v))
+            .collect();
+        let mut lists = vec![Vec::new(); centroids.len()];
+        for (i, c) in assignment.into_iter().enumerate() {
+            lists[c].push(i);
+        }
+
+        Self {
+            entries,
+            centroids,
+            lists,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// A probe count that keeps recall high: a tenth of the lists, at least 4.
+    pub fn default_nprobe(&self) -> usize {
+        (self.centroids.len() / 10).max(4)
+    }
+
+    /// Entries in the `nprobe` lists whose centroids are closest to `query`.
+    pub fn probe(&self, query: &[f32], nprobe: usize) -> impl Iterator<Item = &(T, Vec<f32>)> {
+        let query = unit(query);
+        let mut order: Vec<(usize, f32)> = self
+            .centroids
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i, dot(c, &query)))
+            .collect();
+        order.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        order
+            .into_iter()
+            .take(nprobe)
+            .flat_map(move |(list, _)| self.lists[list].iter().map(move |&i| &self.entries[i]))
+    }
+}
+
+/// Lazily built indexes, one per project and embedding model, shared by
+/// every search on an engine. Cleared whenever the search cache is
+/// invalidated; each index also records the database generation it was
+/// built at, so writes by another process, which do not invalidate it, make
+/// it stale too.
+#[derive(Default)]
+pub struct AnnCache {
+    by_key: HashMap<(String, String), CachedIndex>,
+}
+
+struct CachedIndex {
+    generation: i64,
+    index: Arc<IvfIndex<Node>>,
+}
+
+impl AnnCache {
+    /// The index built for `project_id` and `model` at `generation`.
+    pub fn get(
+        &self,
+        project_id: &str,
+        model: &str,
+        generation: i64,
+    ) -> Option<Arc<IvfIndex<Node>>> {
+        let key = (project_id.to_string(), model.to_string());
+        self.by_key
+            .get(&key)
+            .filter(|cached| cached.generation == generation)
+            .map(|cached| cached.index.clone())
+    }
+
+    pub fn insert(
+        &mut self,
+        project_id: &str,
+        model: &str,
+        generation: i64,
+        index: Arc<IvfIndex<Node>>,
+    ) {
+        let key = (project_id.to_string(), model.to_string());
+        self.by_key.insert(key, CachedIndex { generation, index });
+    }
+
+    pub fn clear(&mut self) {
+        self.by_key.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+}
+
+fn nearest(centroids: &[Vec<f32>], v: &[f32]) -> usize {
+    centroids
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, dot(c, v)))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map_or(0, |(i, _)| i)
+}
+
+fn dot(lhs: &[f32], rhs: &[f32]) -> f32 {
+    lhs.iter().zip(rhs).map(|(a, b)| a * b).sum()
+}
+
+fn unit(v: &[f32]) -> Vec<f32> {
+    let norm = dot(v, v).sqrt();
+    if norm <= f32::EPSILON {
+        return v.to_vec();
+    }
+    v.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Deterministic pseudo-random floats in [-1, 1).
+    fn lcg(seed: &mut u64) -> f32 {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((*seed >> 40) as f32 / (1u64 << 24) as f32) * 2.0 - 1.0
+    }
+
+    /// Points scattered around `clusters` random centres, like chunks of
+    /// code that group by topic.
+    fn synthetic(n: usize, dim: usize, clusters: usize, seed: &mut u64) -> Vec<Vec<f32>> {
+        let centres: Vec<Vec<f32>> = (0..clusters)
+            .map(|_| (0..dim).map(|_| lcg(seed)).collect())
+            .collect();
+        (0..n)
+            .map(|i| {
+                let centre = &centres[i % clusters];
+                centre.iter().map(|c| c + 0.3 * lcg(seed)).collect()
+            })
+            .collect()
+    }
+
+    fn top_k<'a>(
+        query: &[f32],
+        items: impl Iterator<Item = &'a (usize, Vec<f32>)>,
+        k: usize,
+    ) -> Vec<usize> {
+        let q = unit(query);
+        let mut scored: Vec<(usize, f32)> = items.map(|(id, v)| (*id, dot(&q, &unit(v)))).collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        scored.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn ann_recall_matches_brute_force_on_synthetic_data() {
+        let mut seed = 42;
+        let points = synthetic(6_000, 32, 60, &mut seed);
+        let entries: Vec<(usize, Vec<f32>)> = points.into_iter().enumerate().collect();
+        let index = IvfIndex::build(entries.clone());
+        assert_eq!(index.len(), 6_000);
+
+        let queries = synthetic(50, 32, 60, &mut seed);
+        let k = 10;
+        let mut hits = 0;
+        for query in &queries {
+            let exact: HashSet<usize> = top_k(query, entries.iter(), k).into_iter().collect();
+            let approx = top_k(query, index.probe(query, index.default_nprobe()), k);
+            hits += approx.iter().filter(|id| exact.contains(id)).count();
+        }
+        let recall = hits as f64 / (queries.len() * k) as f64;
+        assert!(recall >= 0.9, "recall {recall}");
+    }
+
+    #[test]
+    fn empty_index_probes_nothing() {
+        let index: IvfIndex<usize> = IvfIndex::build(Vec::new());
+        assert!(index.is_empty());
+        assert_eq!(index.probe(&[1.0, 0.0], 4).count(), 0);
+    }
+
+    #[test]
+    fn probing_every_list_is_exhaustive() {
+        let mut seed = 7;
+        let entries: Vec<(usize, Vec<f32>)> =
+            synthetic(400, 8, 5, &mut seed).into_iter().enumerate().collect();
+        let index = IvfIndex::build(entries);
+        let probed = index.probe(&[1.0; 8], usize::MAX).count();
+        assert_eq!(probed, 400);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::pointer::PointerResponse;
+use crate::search::SearchCacheKey;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Hit/miss/eviction counters for the search cache, as reported by
+/// [`crate::HermesEngine::cache_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room (least recently used first).
+    pub evictions: u64,
+    /// Entries dropped because their TTL had passed when looked up.
+    pub expirations: u64,
+    pub entries: usize,
+    pub capacity: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+struct Entry {
+    response: PointerResponse,
+    inserted_at: Instant,
+    /// Position in `recency`; larger is more recently used.
+    tick: u64,
+}
+
+/// Least-recently-used cache of search responses with a per-lookup TTL.
+///
+/// Recency is tracked with a monotonically increasing tick per access and a
+/// `BTreeMap` from tick to key, so lookups, inserts and evictions are all
+/// O(log n) instead of scanning every entry.
+pub struct SearchCache {
+    entries: HashMap<SearchCacheKey, Entry>,
+    recency: BTreeMap<u64, SearchCacheKey>,
+    capacity: usize,
+    next_tick: u64,
+    stats: CacheStats,
+}
+
+impl SearchCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            capacity: capacity.max(1),
+            next_tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns a fresh copy of the cached response and marks it most recently
+    /// used. Entries older than `ttl` are dropped and count as a miss.
+    pub fn get(&mut self, key: &SearchCacheKey, ttl: Duration) -> Option<PointerResponse> {
+        let Some(entry) = self.entries.get(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        if entry.inserted_at.elapsed() >= ttl {
+            self.remove(key);
+            self.stats.expirations += 1;
+            self.stats.misses += 1;
+            return None;
+        }
+        let old_tick = entry.tick;
+        let tick = self.bump_tick();
+        self.recency.remove(&old_tick);
+        self.recency.insert(tick, key.clone());
+        let entry = self.entries.get_mut(key)?;
+        entry.tick = tick;
+        self.stats.hits += 1;
+        Some(entry.response.clone())
+    }
+
+    /// Inserts or replaces an entry, evicting the least recently used entry
+    /// when the cache is full.
+    pub fn insert(&mut self, key: SearchCacheKey, response: PointerResponse) {
+        self.insert_at(key, response, Instant::now());
+    }
+
+    fn insert_at(&mut self, key: SearchCacheKey, response: PointerResponse, inserted_at: Instant) {
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+        let tick = self.bump_tick();
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                response,
+                inserted_at,
+                tick,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &SearchCacheKey) -> Option<PointerResponse> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        Some(entry.response)
+    }
+
+    /// Whether `key` is cached, without touching recency or counters.
+    pub fn contains_key(&self, key: &SearchCacheKey) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Keeps only the entries for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&SearchCacheKey, &PointerResponse) -> bool) {
+        let recency = &mut self.recency;
+        self.entries.retain(|key, entry| {
+            let kept = keep(key, &entry.response);
+            if !kept {
+                recency.remove(&entry.tick);
+            }
+            kept
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the capacity, evicting least recently used entries if needed.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            capacity: self.capacity,
+            ..self.stats
+        }
+    }
+
+    fn bump_tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+}
+
+/// Identifies a file on disk at the time its content was read. A changed
+/// modification time or length means the cached text is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    /// `None` when the file cannot be stat'ed (e.g. it was deleted).
+    pub(crate) fn of(path: &str) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+}
+
+type FetchKey = (String, i64, i64);
+
+/// Bounded cache of file line ranges read by `fetch`, evicting in insertion
+/// order. Each entry remembers the [`FileStamp`] it was read at so callers
+/// can detect edits made since.
+pub(crate) struct FetchCache {
+    entries: HashMap<FetchKey, (String, Option<FileStamp>, u64)>,
+    order: BTreeMap<u64, FetchKey>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl FetchCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            capacity: capacity.max(1),
+            next_seq: 0,
+        }
+    }
+
+    /// The cached content, provided the file still has the given stamp.
+    pub(crate) fn get(&self, key: &FetchKey, stamp: Option<FileStamp>) -> Option<&str> {
+        let (content, cached_stamp, _) = self.entries.get(key)?;
+        (*cached_stamp == stamp).then_some(content.as_str())
+    }
+
+    pub(crate) fn insert(&mut self, key: FetchKey, content: String, stamp: Option<FileStamp>) {
+        if let Some((_, _, seq)) = self.entries.remove(&key) {
+            self.order.remove(&seq);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.next_seq += 1;
+        self.order.insert(self.next_seq, key.clone());
+        self.entries.insert(key, (content, stamp, self.next_seq));
+    }
+
+    #[cfg(test)]
+    fn contains_key(&self, key: &FetchKey) -> bool {
+        self.entries.contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchOptions;
+    use std::sync::{Arc, Mutex};
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn key(q: &str) -> SearchCacheKey {
+        SearchCacheKey::new("cache-test", q, &SearchOptions::default())
+    }
+
+    fn empty() -> PointerResponse {
+        PointerResponse::build(vec![], 0, 0)
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let mut cache = SearchCache::new(4);
+        assert!(cache.get(&key("a"), TTL).is_none());
+        cache.insert(key("a"), empty());
+        assert!(cache.get(&key("a"), TTL).is_some());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = SearchCache::new(2);
+        cache.insert(key("a"), empty());
+        cache.insert(key("b"), empty());
+        // Touch "a" so "b" becomes the least recently used entry.
+        cache.get(&key("a"), TTL);
+        cache.insert(key("c"), empty());
+        assert!(cache.contains_key(&key("a")));
+        assert!(!cache.contains_key(&key("b")));
+        assert!(cache.contains_key(&key("c")));
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn replacing_a_key_is_not_an_eviction() {
+        let mut cache = SearchCache::new(2);
+        cache.insert(key("a"), empty());
+        cache.insert(key("a"), empty());
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn expired_entries_miss_and_are_removed() {
+        let mut cache = SearchCache::new(2);
+        let past = Instant::now() - Duration::from_secs(120);
+        cache.insert_at(key("old"), empty(), past);
+        assert!(cache.get(&key("old"), TTL).is_none());
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats().expirations, 1);
+    }
+
+    #[test]
+    fn shrinking_capacity_evicts_oldest() {
+        let mut cache = SearchCache::new(3);
+        for q in ["a", "b", "c"] {
+            cache.insert(key(q), empty());
+        }
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains_key(&key("c")));
+    }
+
+    #[test]
+    fn fetch_cache_evicts_oldest_insertion() {
+        let mut cache = FetchCache::new(2);
+        let k = |p: &str| (p.to_string(), 1, 2);
+        cache.insert(k("a"), "A".into(), None);
+        cache.insert(k("b"), "B".into(), None);
+        cache.insert(k("c"), "C".into(), None);
+        assert!(!cache.contains_key(&k("a")));
+        assert!(cache.contains_key(&k("b")));
+        assert!(cache.contains_key(&k("c")));
+    }
+
+    #[test]
+    fn fetch_cache_rejects_stale_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("f.txt");
+        std::fs::write(&path, "one").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let key = (path.clone(), 0, 0);
+
+        let mut cache = FetchCache::new(4);
+        cache.insert(key.clone(), "one".into(), FileStamp::of(&path));
+        assert_eq!(cache.get(&key, FileStamp::of(&path)), Some("one"));
+
+        std::fs::write(&path, "one two").unwrap();
+        assert_eq!(cache.get(&key, FileStamp::of(&path)), None);
+    }
+
+    #[test]
+    fn concurrent_access_keeps_counters_consistent() {
+        let cache = Arc::new(Mutex::new(SearchCache::new(16)));
+        let threads = 8;
+        let ops = 500;
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    for i in 0..ops {
+                        let k = key(&format!("q{}", (t * 7 + i) % 40));
+                        let mut guard = cache.lock().unwrap();
+                        if guard.get(&k, TTL).is_none() {
+                            guard.insert(k, empty());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let guard = cache.lock().unwrap();
+        let stats = guard.stats();
+        assert_eq!(stats.hits + stats.misses, (threads * ops) as u64);
+        assert!(stats.entries <= 16);
+        assert_eq!(guard.recency.len(), guard.entries.len());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::error::Result;
+use crate::graph::KnowledgeGraph;
+use crate::pointer::RelatedFact;
+use crate::search::literal::split_identifier;
+use crate::temporal::{TemporalFact, TemporalStore};
+use std::collections::HashSet;
+
+/// At most this many facts are attached to one search response.
+pub const MAX_SEARCH_FACTS: usize = 3;
+/// Words shorter than this ("a", "of", "fn") match too many facts to count
+/// as keyword overlap.
+const MIN_KEYWORD_CHARS: usize = 3;
+
+/// Active facts relevant to a search: those recorded about one of the
+/// returned nodes, best-ranked pointer first, then those sharing the most
+/// keywords with `query`. Ties go to the newest fact. `pointers` pairs each
+/// returned node id with its pointer id, in response order.
+pub fn relevant_facts(
+    graph: &KnowledgeGraph,
+    query: &str,
+    pointers: &[(&str, &str)],
+    limit: usize,
+) -> Result<Vec<RelatedFact>> {
+    let keywords = keywords(query);
+    let store = TemporalStore::new(graph.db().clone(), graph.project_id());
+    let mut scored: Vec<(usize, usize, RelatedFact)> = store
+        .get_active_facts(None)?
+        .into_iter()
+        .filter_map(|fact| {
+            let rank = fact.node_id.as_deref().and_then(|node_id| {
+                pointers.iter().position(|(id, _)| *id == node_id)
+            });
+            let overlap = keywords.intersection(&self::keywords(&fact.content)).count();
+            if rank.is_none() && overlap == 0 {
+                return None;
+            }
+            let related = related_fact(fact, rank.map(|i| pointers[i].1));
+            Some((rank.unwrap_or(usize::MAX), overlap, related))
+        })
+        .collect();
+    // Stable, so equally ranked facts keep the store's newest-first order.
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    Ok(scored.into_iter().take(limit).map(|(_, _, fact)| fact).collect())
+}
+
+/// Active facts about a file or any node in it, newest first, for fetches
+/// of the file's content.
+pub fn file_facts(graph: &KnowledgeGraph, file_path: &str) -> Result<Vec<RelatedFact>> {
+    if file_path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let store = TemporalStore::new(graph.db().clone(), graph.project_id());
+    let facts = store.get_facts_for_file(file_path)?;
+    Ok(facts.into_iter().map(|fact| related_fact(fact, None)).collect())
+}
+
+fn related_fact(fact: TemporalFact, pointer: Option<&str>) -> RelatedFact {
+    RelatedFact {
+        id: fact.id,
+        fact_type: fact.fact_type.as_str().to_string(),
+        content: fact.content,
+        pointer: pointer.map(str::to_string),
+    }
+}
+
+fn keywords(text: &str) -> HashSet<String> {
+    split_identifier(text)
+        .into_iter()
+        .filter(|word| word.chars().count() >= MIN_KEYWORD_CHARS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::FactType;
+    use crate::HermesEngine;
+
+    #[test]
+    fn facts_about_returned_nodes_rank_before_keyword_matches() {
+        let engine = HermesEngine::in_memory("facts").unwrap();
+        let graph = engine.graph();
+        let node = graph.create_node_builder().name("parse_config").build();
+        graph.add_node(&node).unwrap();
+        engine.add_fact(None, FactType::Decision, "config files are TOML", None).unwrap();
+        engine.add_fact(None, FactType::Learning, "parse errors name the config key", None).unwrap();
+        engine.add_fact(Some(&node.id), FactType::Constraint, "must not touch disk", None).unwrap();
+        engine.add_fact(None, FactType::Decision, "use Qdrant for vectors", None).unwrap();
+
+        let pointers = [(node.id.as_str(), "p1")];
+        let facts = relevant_facts(&graph, "parseConfig", &pointers, MAX_SEARCH_FACTS).unwrap();
+        let contents: Vec<_> = facts.iter().map(|f| f.content.as_str()).collect();
+        assert_eq!(
+            contents,
+            ["must not touch disk", "parse errors name the config key", "config files are TOML"]
+        );
+        assert_eq!(facts[0].pointer.as_deref(), Some("p1"));
+        assert_eq!(facts[0].fact_type, "constraint");
+        assert!(facts[1].pointer.is_none());
+
+        assert_eq!(relevant_facts(&graph, "parse", &[], 1).unwrap().len(), 1);
+        assert!(relevant_facts(&graph, "an fn of", &[], MAX_SEARCH_FACTS).unwrap().is_empty());
+    }
+}
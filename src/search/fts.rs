@@ -1,10 +1,15 @@
+use crate::config::FtsWeights;
+use crate::error::Result;
 use crate::graph::{KnowledgeGraph, Node};
-use crate::search::{SearchResult, SearchTier};
-use anyhow::Result;
+use crate::search::{PathFilter, SearchResult, SearchTier};
 
 const FTS_LIMIT: usize = 20;
 const STRATEGY_MIN_RESULTS: usize = 3;
 const MAX_QUERY_WORDS: usize = 10;
+/// Maximum number of tokens allowed between query words for the NEAR strategy.
+const NEAR_DISTANCE: usize = 10;
+/// Upper bound on the excerpt returned as `matched_content`.
+const SNIPPET_MAX_CHARS: usize = 120;
 
 // Returns true for characters that belong to scripts without whitespace word
 // boundaries (CJK ideographs, Hiragana, Katakana, Hangul). Each such character
@@ -22,8 +27,11 @@ fn is_cjk(ch: char) -> bool {
 }
 
 // Extracts alphanumeric/underscore tokens from the raw query and removes
-// FTS operators. This prevents FTS5 syntax errors when the user includes
-// punctuation (for example "/api/alerts").
+// FTS operators. Everything else — double quotes, column-filter colons,
+// `*`, `^`, `-`, parentheses, emoji — acts as a separator, so no fts5 syntax
+// can reach the MATCH expression (for example "/api/alerts" or
+// `fn new("foo")`). Underscore-only tokens are dropped because the
+// tokenizer indexes nothing for them.
 // CJK characters are emitted individually because those scripts use no spaces
 // as word boundaries — grouping them into one token would prevent matching.
 fn extract_words(query: &str) -> Vec<String> {
@@ -32,30 +40,32 @@ fn extract_words(query: &str) -> Vec<String> {
     for ch in query.chars() {
         if is_cjk(ch) {
             // Flush any Latin/ASCII token accumulated so far.
-            if !cur.is_empty() {
-                if !is_fts_operator(&cur) {
-                    words.push(cur.clone());
-                }
-                cur.clear();
-            }
+            flush_word(&mut cur, &mut words);
             // Each CJK character becomes its own search token.
             words.push(ch.to_string());
         } else if ch.is_alphanumeric() || ch == '_' {
             cur.push(ch);
-        } else if !cur.is_empty() {
-            if !is_fts_operator(&cur) {
-                words.push(cur.clone());
-            }
-            cur.clear();
+        } else {
+            flush_word(&mut cur, &mut words);
         }
     }
-    if !cur.is_empty() && !is_fts_operator(&cur) {
-        words.push(cur);
-    }
+    flush_word(&mut cur, &mut words);
     words.into_iter().take(MAX_QUERY_WORDS).collect()
 }
 
-pub fn fts_search(graph: &KnowledgeGraph, query: &str) -> Result<Vec<SearchResult>> {
+fn flush_word(cur: &mut String, words: &mut Vec<String>) {
+    let word = std::mem::take(cur);
+    if word.chars().any(char::is_alphanumeric) && !is_fts_operator(&word) {
+        words.push(word);
+    }
+}
+
+pub fn fts_search(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+    weights: &FtsWeights,
+) -> Result<Vec<SearchResult>> {
     // sanitize the query into plain word tokens before building FTS5 queries
     let words: Vec<String> = extract_words(query);
 
@@ -63,46 +73,85 @@ pub fn fts_search(graph: &KnowledgeGraph, query: &str) -> Result<Vec<SearchResul
         return Ok(Vec::new());
     }
 
+    let path_like = path_filter.and_then(PathFilter::like_pattern);
+    let run = |fts_query: &str| -> Result<Vec<(Node, f64, String)>> {
+        let rows = graph.fts_search(fts_query, FTS_LIMIT, path_like.as_deref(), weights)?;
+        Ok(match path_filter {
+            Some(filter) => rows
+                .into_iter()
+                .filter(|(n, _, _)| n.file_path.as_deref().is_some_and(|p| filter.matches(p)))
+                .collect(),
+            None => rows,
+        })
+    };
+
     if words.len() == 1 {
-        let single = format!("\"{}\"", words[0]);
-        return Ok(to_search_results(graph.fts_search(&single, FTS_LIMIT)?));
+        return Ok(to_search_results(run(&quote(&words[0]))?));
     }
 
-    let phrase_query = format!("\"{}\"", words.join(" "));
-    let s1 = graph.fts_search(&phrase_query, FTS_LIMIT)?;
-    if s1.len() >= STRATEGY_MIN_RESULTS {
-        return Ok(to_search_results(s1));
+    // Fallback ladder, most to least specific: exact phrase, all words within
+    // NEAR_DISTANCE tokens of each other, all words anywhere, any word.
+    for strategy in [phrase_query(&words), near_query(&words), and_query(&words)] {
+        let hits = run(&strategy)?;
+        if hits.len() >= STRATEGY_MIN_RESULTS {
+            return Ok(to_search_results(hits));
+        }
     }
+    Ok(to_search_results(run(&or_query(&words))?))
+}
 
-    let and_query = words
-        .iter()
-        .map(|w| format!("\"{}\"*", w))
-        .collect::<Vec<_>>()
-        .join(" AND ");
-    let s2 = graph.fts_search(&and_query, FTS_LIMIT)?;
-    if s2.len() >= STRATEGY_MIN_RESULTS {
-        return Ok(to_search_results(s2));
-    }
+/// Wraps a word in an fts5 string literal, doubling any embedded quotes.
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('"', "\"\""))
+}
+
+fn phrase_query(words: &[String]) -> String {
+    quote(&words.join(" "))
+}
 
-    let or_query = words
+fn near_query(words: &[String]) -> String {
+    let terms = words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ");
+    format!("NEAR({terms}, {NEAR_DISTANCE})")
+}
+
+fn and_query(words: &[String]) -> String {
+    words
         .iter()
-        .map(|w| format!("\"{w}\""))
+        .map(|w| format!("{}*", quote(w)))
         .collect::<Vec<_>>()
-        .join(" OR ");
-    Ok(to_search_results(graph.fts_search(&or_query, FTS_LIMIT)?))
+        .join(" AND ")
+}
+
+fn or_query(words: &[String]) -> String {
+    words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" OR ")
 }
 
-fn to_search_results(raw: Vec<(Node, f64)>) -> Vec<SearchResult> {
+fn to_search_results(raw: Vec<(Node, f64, String)>) -> Vec<SearchResult> {
     raw.into_iter()
-        .map(|(node, rank)| SearchResult {
+        .map(|(node, rank, snippet)| SearchResult {
             node,
             score: normalize_bm25_score(rank),
             tier: SearchTier::L1Fts,
-            matched_content: None,
+            matched_content: clean_snippet(&snippet),
         })
         .collect()
 }
 
+// Collapses whitespace (snippets often span several source lines) and caps
+// the excerpt at SNIPPET_MAX_CHARS on a char boundary.
+fn clean_snippet(raw: &str) -> Option<String> {
+    let collapsed = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    if collapsed.chars().count() <= SNIPPET_MAX_CHARS {
+        return Some(collapsed);
+    }
+    let mut cut: String = collapsed.chars().take(SNIPPET_MAX_CHARS - 1).collect();
+    cut.push('…');
+    Some(cut)
+}
+
 fn is_fts_operator(word: &str) -> bool {
     matches!(word.to_uppercase().as_str(), "AND" | "OR" | "NOT" | "NEAR")
 }
@@ -163,6 +212,112 @@ mod tests {
         assert_eq!(tokens, vec!["api".to_string(), "alerts".to_string(), "handler".to_string()]);
     }
 
+    #[test]
+    fn strategy_queries_quote_each_word() {
+        let words = vec!["cache".to_string(), "evic\"tion".to_string()];
+        assert_eq!(phrase_query(&words), "\"cache evic\"\"tion\"");
+        assert_eq!(near_query(&words), "NEAR(\"cache\" \"evic\"\"tion\", 10)");
+        assert_eq!(and_query(&words), "\"cache\"* AND \"evic\"\"tion\"*");
+        assert_eq!(or_query(&words), "\"cache\" OR \"evic\"\"tion\"");
+    }
+
+    fn index_docs(graph: &crate::graph::KnowledgeGraph, docs: &[(&str, &str)]) {
+        for (name, content) in docs {
+            let node = graph.create_node_builder().name(name).build();
+            graph.add_node(&node).unwrap();
+            graph.index_fts(&node, content).unwrap();
+        }
+    }
+
+    const SCATTERED: &str = "cache warmup happens at boot and afterwards we log many \
+        unrelated lines about metrics dashboards alerts tracing spans and eventually \
+        mention eviction plus a retry policy";
+
+    #[test]
+    fn near_strategy_beats_scattered_matches() {
+        let engine = HermesEngine::in_memory("test-fts-near").unwrap();
+        let graph = make_graph(&engine);
+        index_docs(
+            &graph,
+            &[
+                ("near_a", "the cache lru eviction policy"),
+                ("near_b", "cache and eviction policy notes"),
+                ("near_c", "cache eviction is governed by policy"),
+                ("scattered", SCATTERED),
+            ],
+        );
+
+        let results = fts_search(&graph, "cache eviction policy", None, &FtsWeights::default()).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.node.name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert!(!names.contains(&"scattered"));
+    }
+
+    #[test]
+    fn and_strategy_used_when_near_is_too_sparse() {
+        let engine = HermesEngine::in_memory("test-fts-and").unwrap();
+        let graph = make_graph(&engine);
+        index_docs(
+            &graph,
+            &[
+                ("near_a", "the cache lru eviction policy"),
+                ("scattered", SCATTERED),
+                ("scattered_2", SCATTERED),
+            ],
+        );
+
+        let results = fts_search(&graph, "cache eviction policy", None, &FtsWeights::default()).unwrap();
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn syntax_characters_are_stripped_from_words() {
+        assert_eq!(extract_words("\"quoted\""), vec!["quoted"]);
+        assert_eq!(extract_words("name:value"), vec!["name", "value"]);
+        assert_eq!(extract_words("foo*bar"), vec!["foo", "bar"]);
+        assert_eq!(extract_words("^start"), vec!["start"]);
+        assert_eq!(extract_words("module::path"), vec!["module", "path"]);
+        assert!(extract_words("-").is_empty());
+        assert!(extract_words("🦀").is_empty());
+        assert!(extract_words("_ __").is_empty());
+        assert_eq!(extract_words("snake_case"), vec!["snake_case"]);
+    }
+
+    #[test]
+    fn pathological_queries_never_error() {
+        let engine = HermesEngine::in_memory("test-fts-sanitize").unwrap();
+        let graph = make_graph(&engine);
+        index_docs(&graph, &[("target", "quoted name value foo bar module path new")]);
+
+        for query in ["-", "🦀", "\"", "_", "^*:"] {
+            assert!(fts_search(&graph, query, None, &FtsWeights::default()).unwrap().is_empty(), "{query:?}");
+        }
+        for query in ["\"quoted\"", "name:value", "foo*bar", "module::path", "fn new(\"foo\")"] {
+            let results = fts_search(&graph, query, None, &FtsWeights::default()).unwrap();
+            assert_eq!(results.len(), 1, "{query:?}");
+        }
+
+        for query in [
+            "\"quoted\"",
+            "name:value",
+            "foo*bar",
+            "-",
+            "🦀",
+            "fn new(\"foo\")",
+            "module::path",
+            "^start",
+            "_",
+            "__ _",
+            "\"",
+            "a\"b",
+            "NEAR(",
+            "x OR",
+        ] {
+            let result = fts_search(&graph, query, None, &FtsWeights::default());
+            assert!(result.is_ok(), "query {query:?} failed: {result:?}");
+        }
+    }
+
     #[test]
     fn bm25_normalization() {
         assert!(normalize_bm25_score(-5.0) > 0.5);
@@ -174,15 +329,34 @@ mod tests {
     fn empty_query_returns_empty() {
         let engine = HermesEngine::in_memory("test-fts").unwrap();
         let graph = make_graph(&engine);
-        let results = fts_search(&graph, "").unwrap();
+        let results = fts_search(&graph, "", None, &FtsWeights::default()).unwrap();
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn path_filter_excludes_other_directories() {
+        let engine = HermesEngine::in_memory("test-fts-path").unwrap();
+        let graph = make_graph(&engine);
+        for (id, path) in [("a", "/p/src/search/fts.rs"), ("b", "/p/src/graph.rs")] {
+            let node = graph
+                .create_node_builder()
+                .name(id)
+                .file_path(path)
+                .build();
+            graph.add_node(&node).unwrap();
+            graph.index_fts(&node, "bm25 ranking helper").unwrap();
+        }
+        let filter = PathFilter::parse("src/search/**").unwrap();
+        let results = fts_search(&graph, "ranking", Some(&filter), &FtsWeights::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.name, "a");
+    }
+
     #[test]
     fn operator_only_query_returns_empty() {
         let engine = HermesEngine::in_memory("test-fts").unwrap();
         let graph = make_graph(&engine);
-        let results = fts_search(&graph, "AND OR NOT").unwrap();
+        let results = fts_search(&graph, "AND OR NOT", None, &FtsWeights::default()).unwrap();
         assert!(results.is_empty());
     }
 }
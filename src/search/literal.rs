@@ -1,25 +1,85 @@
-use crate::graph::KnowledgeGraph;
-use crate::search::{SearchResult, SearchTier};
-use anyhow::Result;
+use crate::error::Result;
+use crate::graph::{KnowledgeGraph, MetadataFilter};
+use crate::search::{PathFilter, SearchResult, SearchTier};
 
-pub fn literal_search(graph: &KnowledgeGraph, query: &str) -> Result<Vec<SearchResult>> {
+/// Fuzzy matching only runs for queries at least this long; shorter ones
+/// match too many unrelated names within one or two edits.
+const FUZZY_MIN_QUERY_CHARS: usize = 4;
+/// Candidates below this normalized similarity are discarded.
+const FUZZY_MIN_SIMILARITY: f64 = 0.75;
+/// Fuzzy hits score at most this, below any exact/prefix/contains match.
+const FUZZY_SCORE_CAP: f64 = 0.7;
+const FUZZY_LIMIT: usize = 20;
+/// Length slack (in chars) when pre-filtering candidate names in SQL: two
+/// edits either way, plus room for a file extension on file nodes.
+const FUZZY_LENGTH_SLACK: usize = 2;
+const FUZZY_EXTENSION_SLACK: usize = 6;
+
+/// A name must cover at least this fraction of the query tokens to match.
+const TOKEN_MIN_COVERAGE: f64 = 0.5;
+/// Token matches score in `TOKEN_SCORE_BASE..=TOKEN_SCORE_BASE + TOKEN_COVERAGE_WEIGHT
+/// + TOKEN_ORDER_BONUS`, i.e. at most 0.85 — below exact and prefix matches.
+const TOKEN_SCORE_BASE: f64 = 0.35;
+const TOKEN_COVERAGE_WEIGHT: f64 = 0.45;
+const TOKEN_ORDER_BONUS: f64 = 0.05;
+
+pub fn literal_search(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+) -> Result<Vec<SearchResult>> {
+    literal_search_where(graph, query, path_filter, &[])
+}
+
+/// [`literal_search`] over the nodes whose metadata matches every one of
+/// `metadata`; name matches are restricted in SQL.
+pub fn literal_search_where(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+    metadata: &[MetadataFilter],
+) -> Result<Vec<SearchResult>> {
     let query_lower = query.to_lowercase();
-    let nodes = graph.literal_search_by_name(query)?;
+    let path_like = path_filter.and_then(PathFilter::like_pattern);
+    let nodes = graph.literal_search_by_name_where(query, path_like.as_deref(), metadata)?;
 
     let mut results: Vec<SearchResult> = nodes
         .into_iter()
+        .filter(|node| match path_filter {
+            Some(filter) => node.file_path.as_deref().is_some_and(|p| filter.matches(p)),
+            None => true,
+        })
         .map(|node| {
             let name_lower = node.name.to_lowercase();
             let score = compute_literal_score(&query_lower, &name_lower);
+            let matched = node.name.clone();
             SearchResult {
                 node,
                 score,
                 tier: SearchTier::L0Literal,
-                matched_content: None,
+                matched_content: Some(matched),
             }
         })
         .collect();
 
+    let query_tokens = split_identifier(query);
+    if query_tokens.len() >= 2 {
+        merge_token_matches(
+            &mut results,
+            graph,
+            &query_tokens,
+            path_filter,
+            path_like.as_deref(),
+        )?;
+    }
+
+    results = matching_metadata(results, metadata);
+
+    if results.is_empty() {
+        let fuzzy = fuzzy_search(graph, &query_lower, path_filter, path_like.as_deref())?;
+        return Ok(matching_metadata(fuzzy, metadata));
+    }
+
     results.sort_by(|a, b| {
         b.score
             .partial_cmp(&a.score)
@@ -29,6 +89,156 @@ pub fn literal_search(graph: &KnowledgeGraph, query: &str) -> Result<Vec<SearchR
     Ok(results)
 }
 
+/// The hits whose node matches every one of `metadata`.
+pub fn matching_metadata(
+    hits: Vec<SearchResult>,
+    metadata: &[MetadataFilter],
+) -> Vec<SearchResult> {
+    if metadata.is_empty() {
+        return hits;
+    }
+    hits.into_iter()
+        .filter(|hit| metadata.iter().all(|f| f.matches(&hit.node)))
+        .collect()
+}
+
+/// Adds nodes whose identifier tokens cover the query words (so "exchange
+/// rate" finds `fetchExchangeRate`), keeping the higher score for nodes that
+/// already matched as a substring.
+fn merge_token_matches(
+    results: &mut Vec<SearchResult>,
+    graph: &KnowledgeGraph,
+    query_tokens: &[String],
+    path_filter: Option<&PathFilter>,
+    path_like: Option<&str>,
+) -> Result<()> {
+    for node in graph.literal_search_by_tokens(query_tokens, path_like)? {
+        if let Some(filter) = path_filter {
+            if !node.file_path.as_deref().is_some_and(|p| filter.matches(p)) {
+                continue;
+            }
+        }
+        let Some(score) = token_match_score(query_tokens, &split_identifier(&node.name)) else {
+            continue;
+        };
+        match results.iter_mut().find(|r| r.node.id == node.id) {
+            Some(existing) => existing.score = existing.score.max(score),
+            None => {
+                let matched = node.name.clone();
+                results.push(SearchResult {
+                    node,
+                    score,
+                    tier: SearchTier::L0Literal,
+                    matched_content: Some(matched),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scores a name by the fraction of query tokens it covers, with a small
+/// bonus when the covered tokens appear in the same order as in the query.
+/// A query token covers a name token it equals or is a prefix of.
+fn token_match_score(query_tokens: &[String], name_tokens: &[String]) -> Option<f64> {
+    let positions: Vec<usize> = query_tokens
+        .iter()
+        .filter_map(|q| name_tokens.iter().position(|n| n.starts_with(q.as_str())))
+        .collect();
+    let coverage = positions.len() as f64 / query_tokens.len() as f64;
+    if positions.is_empty() || coverage < TOKEN_MIN_COVERAGE {
+        return None;
+    }
+    let in_order = positions.windows(2).all(|w| w[0] < w[1]);
+    let order_bonus = if in_order { TOKEN_ORDER_BONUS } else { 0.0 };
+    Some(TOKEN_SCORE_BASE + TOKEN_COVERAGE_WEIGHT * coverage + order_bonus)
+}
+
+/// Splits an identifier or query into lowercase words on non-alphanumeric
+/// separators and camelCase boundaries. Acronyms stay together (`FTSSearch`
+/// → `fts`, `search`) and digits stick to the preceding word (`sha256Hash`
+/// → `sha256`, `hash`).
+pub fn split_identifier(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for part in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut current = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            if i > 0 && c.is_uppercase() {
+                let prev = chars[i - 1];
+                let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+                let boundary = prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_uppercase() && next_is_lower);
+                if boundary && !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            current.extend(c.to_lowercase());
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+    }
+    tokens
+}
+
+/// Typo-tolerant fallback for when no name contains the query: ranks names of
+/// similar length by Damerau-Levenshtein similarity (transpositions count as a
+/// single edit). File names are also compared without their extension.
+fn fuzzy_search(
+    graph: &KnowledgeGraph,
+    query_lower: &str,
+    path_filter: Option<&PathFilter>,
+    path_like: Option<&str>,
+) -> Result<Vec<SearchResult>> {
+    let query_chars = query_lower.chars().count();
+    if query_chars < FUZZY_MIN_QUERY_CHARS {
+        return Ok(Vec::new());
+    }
+    let min_len = query_chars.saturating_sub(FUZZY_LENGTH_SLACK).max(1);
+    let max_len = query_chars + FUZZY_LENGTH_SLACK + FUZZY_EXTENSION_SLACK;
+    let candidates = graph.fuzzy_name_candidates(min_len, max_len, path_like)?;
+
+    let mut results: Vec<SearchResult> = candidates
+        .into_iter()
+        .filter(|node| match path_filter {
+            Some(filter) => node.file_path.as_deref().is_some_and(|p| filter.matches(p)),
+            None => true,
+        })
+        .filter_map(|node| {
+            let similarity = fuzzy_similarity(query_lower, &node.name.to_lowercase());
+            if similarity < FUZZY_MIN_SIMILARITY {
+                return None;
+            }
+            let matched = node.name.clone();
+            Some(SearchResult {
+                node,
+                score: similarity * FUZZY_SCORE_CAP,
+                tier: SearchTier::L0Literal,
+                matched_content: Some(matched),
+            })
+        })
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    results.truncate(FUZZY_LIMIT);
+    Ok(results)
+}
+
+fn fuzzy_similarity(query: &str, name: &str) -> f64 {
+    let whole = strsim::normalized_damerau_levenshtein(query, name);
+    let stem = match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => strsim::normalized_damerau_levenshtein(query, stem),
+        _ => 0.0,
+    };
+    whole.max(stem)
+}
+
 fn compute_literal_score(query: &str, name: &str) -> f64 {
     if name == query {
         return 1.0;
@@ -61,4 +271,138 @@ mod tests {
         let score = compute_literal_score("rate", "exchange_rate_service");
         assert!(score > 0.5 && score < 0.9);
     }
+
+    // ── identifier tokens ────────────────────────────────────────────────────
+
+    fn toks(text: &str) -> Vec<String> {
+        split_identifier(text)
+    }
+
+    #[test]
+    fn split_handles_camel_and_snake_case() {
+        assert_eq!(toks("fetchExchangeRate"), ["fetch", "exchange", "rate"]);
+        assert_eq!(toks("fetch_exchange_rate"), ["fetch", "exchange", "rate"]);
+        assert_eq!(toks("exchange rate"), ["exchange", "rate"]);
+    }
+
+    #[test]
+    fn split_keeps_acronyms_together() {
+        assert_eq!(toks("FTSSearch"), ["fts", "search"]);
+        assert_eq!(toks("parseHTTPResponse"), ["parse", "http", "response"]);
+        assert_eq!(toks("MAX_TOP_K"), ["max", "top", "k"]);
+    }
+
+    #[test]
+    fn split_keeps_digits_with_preceding_word() {
+        assert_eq!(toks("sha256Hash"), ["sha256", "hash"]);
+        assert_eq!(toks("utf8_decode"), ["utf8", "decode"]);
+        assert_eq!(toks("Http2Client"), ["http2", "client"]);
+    }
+
+    #[test]
+    fn token_score_rewards_coverage_and_order() {
+        let query = toks("exchange rate");
+        let ordered = token_match_score(&query, &toks("fetchExchangeRate")).unwrap();
+        let reversed = token_match_score(&query, &toks("rate_exchange")).unwrap();
+        assert!(ordered > reversed);
+        assert!(ordered < 0.9);
+        assert!(token_match_score(&toks("alpha beta gamma"), &toks("alpha_delta")).is_none());
+    }
+
+    #[test]
+    fn multi_word_query_matches_identifier_tokens() {
+        let engine = crate::HermesEngine::in_memory("lit-tokens").unwrap();
+        let graph = graph_with_names(
+            &engine,
+            &[
+                "fetchExchangeRate",
+                "fetch_exchange_rate",
+                "update_chunk_hash",
+                "render",
+            ],
+        );
+        let results = literal_search(&graph, "exchange rate", None).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.node.name.as_str()).collect();
+        assert!(names.contains(&"fetchExchangeRate"));
+        assert!(names.contains(&"fetch_exchange_rate"));
+
+        let results = literal_search(&graph, "hash tracker update", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.name, "update_chunk_hash");
+    }
+
+    #[test]
+    fn exact_name_still_scores_one_alongside_token_matches() {
+        let engine = crate::HermesEngine::in_memory("lit-tokens-exact").unwrap();
+        let graph = graph_with_names(&engine, &["fetch_rate", "rate_fetch"]);
+        let results = literal_search(&graph, "fetch_rate", None).unwrap();
+        assert_eq!(results[0].node.name, "fetch_rate");
+        assert_eq!(results[0].score, 1.0);
+        assert!(results[1].score < 1.0);
+    }
+
+    // ── fuzzy fallback ───────────────────────────────────────────────────────
+
+    fn graph_with_names(engine: &crate::HermesEngine, names: &[&str]) -> KnowledgeGraph {
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        for name in names {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(crate::graph::NodeType::File)
+                .file_path(&format!("src/{name}"))
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn fuzzy_finds_insertion_typo_in_file_name() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-ins").unwrap();
+        let graph = graph_with_names(&engine, &["chunker.rs", "crawler.rs"]);
+        let results = literal_search(&graph, "chuncker", None).unwrap();
+        assert_eq!(results[0].node.name, "chunker.rs");
+        assert!(results[0].score <= FUZZY_SCORE_CAP);
+    }
+
+    #[test]
+    fn fuzzy_finds_transposition() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-swap").unwrap();
+        let graph = graph_with_names(&engine, &["tokenize"]);
+        let results = literal_search(&graph, "tokneize", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.name, "tokenize");
+    }
+
+    #[test]
+    fn fuzzy_finds_deletion() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-del").unwrap();
+        let graph = graph_with_names(&engine, &["accountant"]);
+        let results = literal_search(&graph, "acountant", None).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn fuzzy_yields_nothing_for_garbage() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-garbage").unwrap();
+        let graph = graph_with_names(&engine, &["chunker.rs", "tokenize", "accountant"]);
+        assert!(literal_search(&graph, "qzxvwk", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fuzzy_skips_short_queries() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-short").unwrap();
+        let graph = graph_with_names(&engine, &["run"]);
+        assert!(literal_search(&graph, "rnu", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn exact_substring_match_suppresses_fuzzy() {
+        let engine = crate::HermesEngine::in_memory("lit-fuzzy-exact").unwrap();
+        let graph = graph_with_names(&engine, &["tokenize", "tokenizer"]);
+        let results = literal_search(&graph, "tokenizer", None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].score, 1.0);
+    }
 }
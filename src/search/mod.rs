@@ -1,30 +1,174 @@
+pub mod aliases;
+pub mod ann;
+pub mod cache;
+pub mod facts;
 pub mod fts;
 pub mod literal;
+pub mod path_filter;
 pub mod vector;
 
-use crate::graph::{KnowledgeGraph, Node};
-use crate::pointer::{FetchResponse, Pointer, PointerResponse};
-use crate::SearchCacheMap;
-use anyhow::Result;
+pub use cache::{CacheStats, SearchCache};
+use cache::{FetchCache, FileStamp};
+pub use path_filter::PathFilter;
+
+use crate::embedding::EmbeddingProvider;
+use crate::error::{HermesError, Result};
+use crate::graph::{Edge, EdgeDirection, EdgeType, KnowledgeGraph, MetadataFilter, Node, NodeType};
+use crate::pointer::{
+    FetchResponse, NeighborPointer, NeighborsResponse, Pointer, PointerExplanation, PointerResponse, SearchDebug,
+    TierContribution,
+};
+use crate::HermesConfig;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+/// Total tokens of chunk content that a Full-mode response may inline.
+const FULL_MODE_TOKEN_BUDGET: u64 = 4000;
+/// Number of leading chunk lines shown as a Smart-mode preview.
+const SMART_PREVIEW_LINES: usize = 3;
+
+/// Graph expansion walks the neighbors of this many top hits (depth 1).
+const EXPAND_TOP_HITS: usize = 3;
+/// At most this many neighbors are appended per expanded hit.
+const EXPAND_MAX_NEIGHBORS_PER_HIT: usize = 5;
+/// Expanded neighbors score this fraction of their parent's score.
+const EXPAND_SCORE_DAMPING: f64 = 0.5;
 
-const CACHE_TTL_SECS: u64 = 60;
-const CACHE_MAX_ENTRIES: usize = 256;
-const FETCH_CACHE_MAX_ENTRIES: usize = 50;
+pub const DEFAULT_TOP_K: usize = 10;
+/// Neighbors listed by [`SearchEngine::neighbors`] callers that pass no limit.
+pub const DEFAULT_NEIGHBOR_LIMIT: usize = 20;
+/// Hops and node cap of a traversal whose caller passes none.
+pub const DEFAULT_TRAVERSE_DEPTH: usize = 2;
+pub const DEFAULT_TRAVERSE_NODES: usize = 50;
+/// Hard caps on traversal requests, however large the caller asks.
+pub const MAX_TRAVERSE_DEPTH: usize = 5;
+pub const MAX_TRAVERSE_NODES: usize = 500;
+pub const MAX_TOP_K: usize = 100;
 
-const SHORT_CIRCUIT_SKIP_ALL: f64 = 0.9;
-const SHORT_CIRCUIT_SKIP_L2: f64 = 0.8;
+/// Clamps a caller-supplied result count into `1..=MAX_TOP_K` so a client
+/// can neither request an empty response nor dump the whole graph.
+pub fn clamp_top_k(top_k: usize) -> usize {
+    top_k.clamp(1, MAX_TOP_K)
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Controls how much content a search response carries.
+///
+/// - `Pointer` → location and summary only.
+/// - `Smart`   → pointer plus a short preview of the chunk's first lines.
+/// - `Full`    → pointer plus the chunk content, up to a total token budget.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum SearchMode {
     Pointer,
     Smart,
     Full,
 }
 
+impl SearchMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pointer => "pointer",
+            Self::Smart => "smart",
+            Self::Full => "full",
+        }
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "pointer" => Some(Self::Pointer),
+            "smart" => Some(Self::Smart),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Per-call knobs for [`SearchEngine::search`].
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub top_k: usize,
+    pub mode: SearchMode,
+    /// Prefix or glob restricting results by `file_path` (see [`PathFilter`]).
+    pub path_filter: Option<String>,
+    /// Only return nodes whose metadata matches all of these.
+    pub metadata_filters: Vec<MetadataFilter>,
+    /// Attach a short "why it matched" excerpt to each pointer. Costs a few
+    /// pointer tokens per result; token-conscious callers can turn it off.
+    pub snippets: bool,
+    /// Append `Contains` children and `Calls` targets of the top hits.
+    pub expand_graph: bool,
+    /// Return as many ranked pointers as fit in this many pointer tokens
+    /// (always at least one) instead of a fixed `top_k`.
+    pub token_budget: Option<u64>,
+    /// Drop results whose fused score is below this, even if that leaves
+    /// fewer than `top_k` (or none).
+    pub min_score: Option<f64>,
+    /// Attach a [`SearchDebug`] breakdown of how each pointer was scored.
+    /// Explain searches always run the tiers and are never cached.
+    pub explain: bool,
+    /// Attach up to [`facts::MAX_SEARCH_FACTS`] active facts about the
+    /// returned nodes or the query's keywords, counted as fact tokens.
+    pub facts: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            top_k: DEFAULT_TOP_K,
+            mode: SearchMode::Smart,
+            path_filter: None,
+            metadata_filters: Vec::new(),
+            snippets: true,
+            expand_graph: false,
+            token_budget: None,
+            min_score: None,
+            explain: false,
+            facts: true,
+        }
+    }
+}
+
+/// Identifies a cached search response. Every input that can change the
+/// response must be part of the key, including the project: the cache lives
+/// on `HermesEngine` and may be shared by graphs for different projects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchCacheKey {
+    pub project_id: String,
+    pub query: String,
+    pub top_k: usize,
+    pub mode: SearchMode,
+    pub path_filter: Option<String>,
+    /// The metadata filters in their `Display` form.
+    pub metadata_filters: Vec<String>,
+    pub snippets: bool,
+    pub expand_graph: bool,
+    pub token_budget: Option<u64>,
+    /// `min_score` as raw bits, since `f64` is neither `Eq` nor `Hash`.
+    pub min_score: Option<u64>,
+    pub facts: bool,
+}
+
+impl SearchCacheKey {
+    pub fn new(project_id: &str, query: &str, opts: &SearchOptions) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            query: query.trim().to_lowercase(),
+            top_k: opts.top_k,
+            mode: opts.mode.clone(),
+            path_filter: opts.path_filter.clone(),
+            metadata_filters: opts.metadata_filters.iter().map(ToString::to_string).collect(),
+            snippets: opts.snippets,
+            expand_graph: opts.expand_graph,
+            token_budget: opts.token_budget,
+            min_score: opts.min_score.map(f64::to_bits),
+            facts: opts.facts,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     pub node: Node,
@@ -40,246 +184,872 @@ pub enum SearchTier {
     L2Vector,
 }
 
+impl SearchTier {
+    /// Position in the L0 → L1 → L2 cascade; also the tie-break order.
+    fn index(&self) -> usize {
+        match self {
+            Self::L0Literal => 0,
+            Self::L1Fts => 1,
+            Self::L2Vector => 2,
+        }
+    }
+}
+
+/// Which tiers still run after L0, decided from the literal hit scores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShortCircuit {
+    SkipAll,
+    SkipVector,
+    None,
+}
+
+impl ShortCircuit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::SkipAll => "skip_all",
+            Self::SkipVector => "skip_vector",
+            Self::None => "none",
+        }
+    }
+}
+
 pub struct SearchEngine<'a> {
     graph: &'a KnowledgeGraph,
-    search_cache: Arc<Mutex<SearchCacheMap>>,
-    fetch_cache: Mutex<HashMap<(String, i64, i64), String>>,
+    search_cache: Arc<Mutex<SearchCache>>,
+    fetch_cache: Mutex<FetchCache>,
+    config: HermesConfig,
+    embedder: Arc<dyn EmbeddingProvider>,
+    ann_cache: Arc<Mutex<ann::AnnCache>>,
 }
 
 impl<'a> SearchEngine<'a> {
-    pub fn new(graph: &'a KnowledgeGraph, search_cache: Arc<Mutex<SearchCacheMap>>) -> Self {
+    pub fn new(
+        graph: &'a KnowledgeGraph,
+        search_cache: Arc<Mutex<SearchCache>>,
+        config: HermesConfig,
+    ) -> Self {
         Self {
             graph,
             search_cache,
-            fetch_cache: Mutex::new(HashMap::new()),
+            fetch_cache: Mutex::new(FetchCache::new(config.fetch_cache_max_entries)),
+            embedder: Arc::new(vector::HashedEmbedder::new(config.vector_tokenizer)),
+            config,
+            ann_cache: Arc::new(Mutex::new(ann::AnnCache::default())),
         }
     }
 
-    pub fn search(&self, query: &str, top_k: usize, mode: &SearchMode) -> Result<PointerResponse> {
-        let cache_key = format!("{}:{}", query.trim().to_lowercase(), top_k);
-        if let Some(cached) = self.get_from_cache(&cache_key) {
-            return Ok(cached);
+    /// Uses `embedder` for the L2 vector tier instead of hashed vectors.
+    pub fn with_embedder(mut self, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        self.embedder = embedder;
+        self
+    }
+
+    /// Shares ANN indexes with other searches, normally the engine's
+    /// [`crate::HermesEngine::ann_cache`].
+    pub fn with_ann_cache(mut self, ann_cache: Arc<Mutex<ann::AnnCache>>) -> Self {
+        self.ann_cache = ann_cache;
+        self
+    }
+
+    pub fn search(&self, query: &str, opts: &SearchOptions) -> Result<PointerResponse> {
+        let cache_key = SearchCacheKey::new(self.graph.project_id(), query, opts);
+        if !opts.explain {
+            if let Some(cached) = self.get_from_cache(&cache_key) {
+                return Ok(cached);
+            }
+        }
+
+        let path_filter = opts
+            .path_filter
+            .as_deref()
+            .map(PathFilter::parse)
+            .transpose()?;
+
+        let top_k = if opts.token_budget.is_some() { MAX_TOP_K } else { opts.top_k };
+        let metadata = &opts.metadata_filters;
+        let (hits, short_circuit) = self.run_tiers(query, top_k, path_filter.as_ref(), metadata)?;
+        let breakdown = opts.explain.then(|| Self::tier_breakdown(&hits, self.config.rrf_k));
+        let mut merged = self.rank(hits, top_k);
+        if opts.expand_graph {
+            self.expand_with_neighbors(&mut merged, path_filter.as_ref(), metadata)?;
         }
+        if let Some(min_score) = opts.min_score {
+            merged.retain(|r| r.score >= min_score);
+        }
+        let mut aliases = aliases::PointerAliases::load(self.graph, merged.iter().map(|r| r.node.id.as_str()))?;
+        if let Some(budget) = opts.token_budget {
+            merged = Self::select_within_budget(merged, budget, opts, &aliases);
+        }
+        for r in &merged {
+            aliases.assign(&r.node.id);
+        }
+        aliases.save(self.graph, merged.iter().map(|r| r.node.id.as_str()))?;
+        let (pointers, inlined_tokens) = self.results_to_pointers(&merged, opts, &aliases);
+        let baseline = self.rag_baseline_tokens(&merged)?;
+        let mut response = PointerResponse::build(pointers, inlined_tokens, baseline);
+        if let Some(budget) = opts.token_budget {
+            response = response.with_token_budget(budget);
+        }
+        if opts.facts {
+            let pointers: Vec<(&str, &str)> = merged
+                .iter()
+                .zip(&response.pointers)
+                .map(|(r, p)| (r.node.id.as_str(), p.id.as_str()))
+                .collect();
+            let limit = facts::MAX_SEARCH_FACTS;
+            let related = facts::relevant_facts(self.graph, query, &pointers, limit)?;
+            response = response.with_facts(related);
+        }
+        if let Some(mut breakdown) = breakdown {
+            let pointers = merged
+                .iter()
+                .zip(&response.pointers)
+                .map(|(r, p)| PointerExplanation {
+                    id: p.id.clone(),
+                    fused_score: p.relevance,
+                    tiers: breakdown.remove(&r.node.id).unwrap_or_default(),
+                })
+                .collect();
+            response.debug = Some(SearchDebug {
+                cache_hit: self.is_cached(&cache_key),
+                short_circuit: short_circuit.as_str().to_string(),
+                pointers,
+            });
+            return Ok(response);
+        }
+        self.insert_into_cache(cache_key, response.clone());
+        Ok(response)
+    }
 
+    /// Runs the L0 → L1 → L2 tiers, short-circuiting when literal matches are
+    /// confident enough. Returns the unfused hits of every tier that ran and
+    /// the short-circuit decision. Every tier drops hits failing a
+    /// `metadata` filter, so they cannot trigger a short circuit either.
+    fn run_tiers(
+        &self,
+        query: &str,
+        top_k: usize,
+        path_filter: Option<&PathFilter>,
+        metadata: &[MetadataFilter],
+    ) -> Result<(Vec<SearchResult>, ShortCircuit)> {
         let mut all_results: Vec<SearchResult> = Vec::new();
+        let fts = || -> Result<Vec<SearchResult>> {
+            let hits = fts::fts_search(self.graph, query, path_filter, &self.config.fts_weights)?;
+            Ok(literal::matching_metadata(hits, metadata))
+        };
 
-        let l0_results = literal::literal_search(self.graph, query)?;
+        let l0_results = literal::literal_search_where(self.graph, query, path_filter, metadata)?;
 
-        if l0_results.len() >= top_k {
-            let min_score = l0_results
-                .iter()
-                .take(top_k)
-                .map(|r| r.score)
-                .fold(f64::INFINITY, f64::min);
-
-            if min_score >= SHORT_CIRCUIT_SKIP_ALL {
-                let merged = Self::deduplicate_and_rank(l0_results, top_k);
-                let pointers = Self::results_to_pointers(&merged, mode);
-                let response = PointerResponse::build(pointers, 0);
-                self.insert_into_cache(cache_key, response.clone());
-                return Ok(response);
+        let short_circuit = self.short_circuit(&l0_results, top_k);
+        match short_circuit {
+            ShortCircuit::SkipAll => {
+                return Ok((l0_results, short_circuit));
             }
-
-            if min_score >= SHORT_CIRCUIT_SKIP_L2 {
+            ShortCircuit::SkipVector => {
                 all_results.extend(l0_results);
-                let l1_results = fts::fts_search(self.graph, query)?;
-                all_results.extend(l1_results);
-                let merged = Self::deduplicate_and_rank(all_results, top_k);
-                let pointers = Self::results_to_pointers(&merged, mode);
-                let response = PointerResponse::build(pointers, 0);
-                self.insert_into_cache(cache_key, response.clone());
-                return Ok(response);
+                all_results.extend(fts()?);
+                return Ok((all_results, short_circuit));
             }
+            ShortCircuit::None => {}
         }
 
         all_results.extend(l0_results);
+        all_results.extend(fts()?);
+        let vector_hits = vector::vector_search(
+            self.graph,
+            query,
+            path_filter,
+            self.embedder.as_ref(),
+            &vector::HashedEmbedder::new(self.config.vector_tokenizer),
+            &vector::AnnSettings {
+                cache: &self.ann_cache,
+                threshold: self.config.ann_threshold,
+            },
+        )?;
+        all_results.extend(literal::matching_metadata(vector_hits, metadata));
 
-        let l1_results = fts::fts_search(self.graph, query)?;
-        all_results.extend(l1_results);
+        Ok((all_results, short_circuit))
+    }
 
-        let l2_results = vector::vector_search(self.graph, query)?;
-        all_results.extend(l2_results);
+    /// Appends depth-1 neighbors of the top hits that they contain or call,
+    /// scored at a fraction of the parent's score. Neighbors already present
+    /// are skipped, and each hit contributes at most a handful of neighbors so
+    /// a file with hundreds of children cannot flood the response.
+    fn expand_with_neighbors(
+        &self,
+        results: &mut Vec<SearchResult>,
+        path_filter: Option<&PathFilter>,
+        metadata: &[MetadataFilter],
+    ) -> Result<()> {
+        let mut seen: HashSet<String> = results.iter().map(|r| r.node.id.clone()).collect();
+        let parents: Vec<(Node, f64, SearchTier)> = results
+            .iter()
+            .take(EXPAND_TOP_HITS)
+            .map(|r| (r.node.clone(), r.score, r.tier.clone()))
+            .collect();
 
-        let merged = Self::deduplicate_and_rank(all_results, top_k);
-        let pointers = Self::results_to_pointers(&merged, mode);
-        let response = PointerResponse::build(pointers, 0);
-        self.insert_into_cache(cache_key, response.clone());
-        Ok(response)
+        for (parent, score, tier) in parents {
+            let mut neighbors: Vec<(Edge, Node)> = self
+                .graph
+                .get_neighbors_filtered(
+                    &parent.id,
+                    EdgeDirection::Outgoing,
+                    Some(&[EdgeType::Contains, EdgeType::Calls]),
+                    usize::MAX,
+                )?
+                .into_iter()
+                .filter(|(_, node)| match path_filter {
+                    Some(filter) => node.file_path.as_deref().is_some_and(|p| filter.matches(p)),
+                    None => true,
+                })
+                .filter(|(_, node)| metadata.iter().all(|f| f.matches(node)))
+                .collect();
+            neighbors.sort_by(|(a, an), (b, bn)| {
+                b.weight
+                    .partial_cmp(&a.weight)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| an.start_line.cmp(&bn.start_line))
+            });
+
+            let mut added = 0;
+            for (edge, node) in neighbors {
+                if added == EXPAND_MAX_NEIGHBORS_PER_HIT {
+                    break;
+                }
+                if !seen.insert(node.id.clone()) {
+                    continue;
+                }
+                let relation = match edge.edge_type {
+                    EdgeType::Calls => "called by",
+                    _ => "in",
+                };
+                results.push(SearchResult {
+                    node,
+                    score: score * EXPAND_SCORE_DAMPING,
+                    tier: tier.clone(),
+                    matched_content: Some(format!("{relation} {}", parent.name)),
+                });
+                added += 1;
+            }
+        }
+        Ok(())
     }
 
-    pub fn fetch(&self, pointer_id: &str) -> Result<Option<FetchResponse>> {
-        let node = self.graph.get_node(pointer_id)?;
-        let Some(node) = node else {
+    fn short_circuit(&self, l0_results: &[SearchResult], top_k: usize) -> ShortCircuit {
+        if l0_results.len() < top_k {
+            return ShortCircuit::None;
+        }
+        let min_score = l0_results
+            .iter()
+            .take(top_k)
+            .map(|r| r.score)
+            .fold(f64::INFINITY, f64::min);
+
+        if min_score >= self.config.short_circuit_skip_all {
+            ShortCircuit::SkipAll
+        } else if min_score >= self.config.short_circuit_skip_l2 {
+            ShortCircuit::SkipVector
+        } else {
+            ShortCircuit::None
+        }
+    }
+
+    /// Returns the content behind a pointer, given its alias (`p3`) or node
+    /// id. With `max_tokens`, content is cut at line granularity and ends
+    /// with a marker naming the lines left out.
+    pub fn fetch(&self, pointer_id: &str, max_tokens: Option<u64>) -> Result<Option<FetchResponse>> {
+        let Some(node) = self.resolve_pointer(pointer_id)? else {
             return Ok(None);
         };
 
         let content = self.read_node_content_cached(&node)?;
+        let first_line = node.start_line.unwrap_or(1).max(1);
+        let (content, truncated) = match max_tokens {
+            Some(limit) => cap_content(&content, limit, first_line),
+            None => (content, false),
+        };
 
         let token_count = estimate_tokens(&content);
+        let file_path = node.file_path.unwrap_or_default();
 
         Ok(Some(FetchResponse {
             pointer_id: node.id.clone(),
             content,
-            file_path: node.file_path.unwrap_or_default(),
+            related_facts: facts::file_facts(self.graph, &file_path)?,
+            file_path,
             start_line: node.start_line.unwrap_or(0),
             end_line: node.end_line.unwrap_or(0),
             token_count,
+            truncated,
         }))
     }
 
-
-    fn get_from_cache(&self, key: &str) -> Option<PointerResponse> {
-        let ttl = Duration::from_secs(CACHE_TTL_SECS);
-        let mut cache = self.search_cache.lock().ok()?;
-        if let Some((response, inserted_at)) = cache.get(key) {
-            if inserted_at.elapsed() < ttl {
-                return Some(response.clone());
+    /// The node behind an alias (`p3`) or node id.
+    fn resolve_pointer(&self, pointer_id: &str) -> Result<Option<Node>> {
+        let node_id = if aliases::is_alias(pointer_id) {
+            match aliases::resolve(self.graph, pointer_id)? {
+                Some(node_id) => node_id,
+                None => return Ok(None),
             }
-            cache.remove(key);
+        } else {
+            pointer_id.to_string()
+        };
+        self.graph.get_node(&node_id)
+    }
+
+    /// Lists up to `limit` neighbors of a pointer as pointers, following
+    /// edges in `direction` and, if given, only of `edge_types`. Neighbors
+    /// get aliases like search results, so they can be fetched directly.
+    pub fn neighbors(
+        &self,
+        pointer_id: &str,
+        direction: EdgeDirection,
+        edge_types: Option<&[EdgeType]>,
+        limit: usize,
+    ) -> Result<Option<NeighborsResponse>> {
+        let Some(node) = self.resolve_pointer(pointer_id)? else {
+            return Ok(None);
+        };
+        let fetch = limit.saturating_add(1);
+        let mut rows = self.graph.get_neighbors_filtered(&node.id, direction, edge_types, fetch)?;
+        let truncated = rows.len() > limit;
+        rows.truncate(limit);
+
+        let node_ids = || rows.iter().map(|(_, n)| n.id.as_str());
+        let mut aliases = aliases::PointerAliases::load(self.graph, node_ids())?;
+        let neighbors = rows
+            .iter()
+            .map(|(edge, neighbor)| NeighborPointer {
+                id: aliases.assign(&neighbor.id),
+                edge_type: edge.edge_type.as_str().to_string(),
+                direction: if edge.source_id == node.id { "out" } else { "in" }.to_string(),
+                source: neighbor.file_path.clone().unwrap_or_default(),
+                chunk: neighbor.name.clone(),
+                lines: format!("{}-{}", neighbor.start_line.unwrap_or(0), neighbor.end_line.unwrap_or(0)),
+                node_type: neighbor.node_type.as_str().to_string(),
+                summary: neighbor.summary.clone().unwrap_or_default(),
+            })
+            .collect();
+        aliases.save(self.graph, node_ids())?;
+        Ok(Some(NeighborsResponse { node_id: node.id, neighbors, truncated }))
+    }
+
+    /// The working set reachable from a pointer within `max_depth` hops (see
+    /// [`KnowledgeGraph::traverse`]), as a pointer response led by the start
+    /// node. Relevance halves with each hop and each snippet names the hop
+    /// that reached the node. Only `mode` and `snippets` of `opts` apply.
+    pub fn traverse(
+        &self,
+        pointer_id: &str,
+        max_depth: usize,
+        edge_types: Option<&[EdgeType]>,
+        direction: EdgeDirection,
+        max_nodes: usize,
+        opts: &SearchOptions,
+    ) -> Result<Option<PointerResponse>> {
+        let Some(start) = self.resolve_pointer(pointer_id)? else {
+            return Ok(None);
+        };
+        let steps = self.graph.traverse(&start.id, max_depth, edge_types, direction, max_nodes)?;
+        let names: HashMap<&str, &str> = std::iter::once(&start)
+            .chain(steps.iter().map(|s| &s.node))
+            .map(|n| (n.id.as_str(), n.name.as_str()))
+            .collect();
+        let mut results = vec![SearchResult {
+            node: start.clone(),
+            score: 1.0,
+            tier: SearchTier::L0Literal,
+            matched_content: Some("start".to_string()),
+        }];
+        for step in &steps {
+            let edge = &step.edge;
+            let reached_backwards = edge.source_id == step.node.id;
+            let from = if reached_backwards { &edge.target_id } else { &edge.source_id };
+            let relation = format!(
+                "depth {} via {} from {}",
+                step.depth,
+                step.edge.edge_type.as_str(),
+                names.get(from.as_str()).copied().unwrap_or_default()
+            );
+            results.push(SearchResult {
+                node: step.node.clone(),
+                score: EXPAND_SCORE_DAMPING.powi(step.depth as i32),
+                tier: SearchTier::L0Literal,
+                matched_content: Some(relation),
+            });
         }
-        None
+
+        let node_ids = || results.iter().map(|r| r.node.id.as_str());
+        let mut aliases = aliases::PointerAliases::load(self.graph, node_ids())?;
+        for r in &results {
+            aliases.assign(&r.node.id);
+        }
+        aliases.save(self.graph, node_ids())?;
+        let (pointers, inlined_tokens) = self.results_to_pointers(&results, opts, &aliases);
+        let baseline = self.rag_baseline_tokens(&results)?;
+        Ok(Some(PointerResponse::build(pointers, inlined_tokens, baseline)))
     }
 
-    fn insert_into_cache(&self, key: String, response: PointerResponse) {
-        let Ok(mut cache) = self.search_cache.lock() else {
-            return;
+    /// Fetches lines `start..=end` of a file directly, without a node id.
+    ///
+    /// `file_path` may be absolute or relative to `project_root`, but must
+    /// resolve inside it. The response carries a synthetic pointer id of the
+    /// form `file:<relative path>:<start>-<end>`.
+    pub fn fetch_range(
+        &self,
+        project_root: &Path,
+        file_path: &str,
+        start: i64,
+        end: i64,
+        max_tokens: Option<u64>,
+    ) -> Result<FetchResponse> {
+        if start < 1 || end < start {
+            return Err(HermesError::InvalidQuery(format!(
+                "invalid line range {start}-{end} (expected 1 <= start <= end)"
+            )));
+        }
+        let root = project_root.canonicalize().map_err(|e| {
+            let root = project_root.display();
+            HermesError::NotFound(format!("project root not found: {root}: {e}"))
+        })?;
+        let resolved = root
+            .join(file_path)
+            .canonicalize()
+            .map_err(|e| HermesError::NotFound(format!("file not found: {file_path}: {e}")))?;
+        if !resolved.starts_with(&root) {
+            let message = format!("{file_path} is outside the project root");
+            return Err(HermesError::InvalidQuery(message));
+        }
+        if !resolved.is_file() {
+            return Err(HermesError::InvalidQuery(format!("{file_path} is not a file")));
+        }
+
+        let relative = resolved
+            .strip_prefix(&root)
+            .unwrap_or(&resolved)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let resolved_str = resolved.to_string_lossy().to_string();
+
+        let content = self.read_lines_cached(&resolved_str, start, end)?;
+        let (content, truncated) = match max_tokens {
+            Some(limit) => cap_content(&content, limit, start),
+            None => (content, false),
         };
-        if cache.len() >= CACHE_MAX_ENTRIES {
-            let ttl = Duration::from_secs(CACHE_TTL_SECS);
-            cache.retain(|_, (_, inserted)| inserted.elapsed() < ttl);
-            if cache.len() >= CACHE_MAX_ENTRIES {
-                if let Some(oldest_key) = cache
-                    .iter()
-                    .min_by_key(|(_, (_, t))| *t)
-                    .map(|(k, _)| k.clone())
-                {
-                    cache.remove(&oldest_key);
-                }
-            }
+        let token_count = estimate_tokens(&content);
+        // Facts hang off nodes, whose paths are the root joined as given.
+        let indexed_path = project_root.join(&relative).to_string_lossy().to_string();
+
+        Ok(FetchResponse {
+            pointer_id: format!("file:{relative}:{start}-{end}"),
+            content,
+            file_path: resolved_str,
+            start_line: start,
+            end_line: end,
+            token_count,
+            truncated,
+            related_facts: facts::file_facts(self.graph, &indexed_path)?,
+        })
+    }
+
+    fn get_from_cache(&self, key: &SearchCacheKey) -> Option<PointerResponse> {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        self.search_cache.lock().ok()?.get(key, ttl)
+    }
+
+    /// Whether a response is cached for `key`, without counting a lookup.
+    fn is_cached(&self, key: &SearchCacheKey) -> bool {
+        self.search_cache.lock().is_ok_and(|cache| cache.contains_key(key))
+    }
+
+    fn insert_into_cache(&self, key: SearchCacheKey, response: PointerResponse) {
+        if let Ok(mut cache) = self.search_cache.lock() {
+            cache.insert(key, response);
         }
-        cache.insert(key, (response, Instant::now()));
     }
 
 
     fn read_node_content_cached(&self, node: &Node) -> Result<String> {
-        let file_path = node.file_path.clone().unwrap_or_default();
-        let start = node.start_line.unwrap_or(0);
-        let end = node.end_line.unwrap_or(0);
-        let cache_key = (file_path.clone(), start, end);
-
-        if !file_path.is_empty() {
-            if let Ok(cache) = self.fetch_cache.lock() {
-                if let Some(content) = cache.get(&cache_key) {
-                    return Ok(content.clone());
-                }
+        let Some(ref file_path) = node.file_path else {
+            return Ok(String::new());
+        };
+        self.read_lines_cached(file_path, node.start_line.unwrap_or(0), node.end_line.unwrap_or(0))
+    }
+
+    /// Reads a line range through the fetch cache. Cached text is served only
+    /// while the file's modification time and length are unchanged; otherwise
+    /// the range is re-read and the entry replaced.
+    fn read_lines_cached(&self, file_path: &str, start: i64, end: i64) -> Result<String> {
+        if file_path.is_empty() {
+            return Self::read_lines(file_path, start, end);
+        }
+        let cache_key = (file_path.to_string(), start, end);
+        let stamp = FileStamp::of(file_path);
+
+        if let Ok(cache) = self.fetch_cache.lock() {
+            if let Some(content) = cache.get(&cache_key, stamp) {
+                return Ok(content.to_string());
             }
         }
 
-        let content = Self::read_node_content(node)?;
+        let content = Self::read_lines(file_path, start, end)?;
 
-        if !file_path.is_empty() {
-            if let Ok(mut cache) = self.fetch_cache.lock() {
-                if cache.len() >= FETCH_CACHE_MAX_ENTRIES {
-                    if let Some(oldest) = cache.keys().next().cloned() {
-                        cache.remove(&oldest);
-                    }
-                }
-                cache.insert(cache_key, content.clone());
-            }
+        if let Ok(mut cache) = self.fetch_cache.lock() {
+            cache.insert(cache_key, content.clone(), stamp);
         }
 
         Ok(content)
     }
 
+    /// Fuses tier results, collapses nested chunks of the same file and keeps
+    /// the best `top_k`. Suppression runs before truncation so dropped chunks
+    /// make room for other results.
+    fn rank(&self, results: Vec<SearchResult>, top_k: usize) -> Vec<SearchResult> {
+        let ranked = Self::deduplicate_and_rank(results, usize::MAX, self.config.rrf_k);
+        let mut kept = Self::suppress_overlaps(ranked, self.config.overlap_epsilon);
+        kept.truncate(top_k);
+        kept
+    }
 
-    fn deduplicate_and_rank(results: Vec<SearchResult>, top_k: usize) -> Vec<SearchResult> {
-        let mut best: HashMap<String, SearchResult> = HashMap::new();
+    /// Walks `ranked` best-first and drops a result when its line range
+    /// contains, or is contained in, an already kept result from the same
+    /// file that outscores it by at least `epsilon`. A `File` node always
+    /// loses to a chunk it contains, whatever the scores.
+    fn suppress_overlaps(ranked: Vec<SearchResult>, epsilon: f64) -> Vec<SearchResult> {
+        let mut kept: Vec<SearchResult> = Vec::with_capacity(ranked.len());
+        for candidate in ranked {
+            let mut superseded_files: Vec<usize> = Vec::new();
+            let mut keep = true;
+            for (i, existing) in kept.iter().enumerate() {
+                if !nested(&existing.node, &candidate.node) {
+                    continue;
+                }
+                let existing_is_file = existing.node.node_type == NodeType::File;
+                let candidate_is_file = candidate.node.node_type == NodeType::File;
+                if existing_is_file && !candidate_is_file {
+                    superseded_files.push(i);
+                } else if candidate_is_file || existing.score - candidate.score >= epsilon {
+                    keep = false;
+                    break;
+                }
+            }
+            if keep {
+                for i in superseded_files.into_iter().rev() {
+                    kept.remove(i);
+                }
+                kept.push(candidate);
+            }
+        }
+        kept
+    }
 
-        for result in results {
-            let tier_bonus = match result.tier {
-                SearchTier::L0Literal => 0.3,
-                SearchTier::L1Fts => 0.1,
-                SearchTier::L2Vector => 0.0,
-            };
-            let boosted_score = result.score + tier_bonus;
-
-            best.entry(result.node.id.clone())
-                .and_modify(|existing| {
-                    let existing_boosted = existing.score
-                        + match existing.tier {
-                            SearchTier::L0Literal => 0.3,
-                            SearchTier::L1Fts => 0.1,
-                            SearchTier::L2Vector => 0.0,
-                        };
-                    if boosted_score > existing_boosted {
-                        *existing = SearchResult {
-                            score: result.score,
-                            ..result.clone()
-                        };
-                    }
-                })
-                .or_insert(result);
+    /// Merges tier results with reciprocal rank fusion: each hit contributes
+    /// `1 / (rrf_k + rank)` where `rank` is its 1-based position within its own
+    /// tier, so raw scores on different scales (literal, BM25, cosine) are never
+    /// compared directly. Results are deduplicated by node id and keep the tier
+    /// and match of their best-contributing hit. The fused score is normalized
+    /// by the best score achievable across the tiers that returned anything,
+    /// so a node ranked first in every such tier scores 1.0.
+    fn deduplicate_and_rank(results: Vec<SearchResult>, top_k: usize, rrf_k: f64) -> Vec<SearchResult> {
+        let by_tier = Self::rank_within_tiers(results);
+        let max_score = Self::max_fused_score(&by_tier, rrf_k);
+
+        // node id → (fused score, best single contribution, best hit)
+        let mut fused: HashMap<String, (f64, f64, SearchResult)> = HashMap::new();
+        for tier in by_tier {
+            for (i, result) in tier.into_iter().enumerate() {
+                let contribution = 1.0 / (rrf_k + (i + 1) as f64);
+                fused
+                    .entry(result.node.id.clone())
+                    .and_modify(|(total, best, hit)| {
+                        *total += contribution;
+                        if contribution > *best {
+                            *best = contribution;
+                            *hit = result.clone();
+                        }
+                    })
+                    .or_insert((contribution, contribution, result));
+            }
         }
 
-        let mut ranked: Vec<SearchResult> = best.into_values().collect();
+        let mut ranked: Vec<SearchResult> = fused
+            .into_values()
+            .map(|(total, _, hit)| SearchResult {
+                score: total / max_score,
+                ..hit
+            })
+            .collect();
         ranked.sort_by(|a, b| {
             b.score
                 .partial_cmp(&a.score)
                 .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.tier.index().cmp(&b.tier.index()))
+                .then_with(|| a.node.id.cmp(&b.node.id))
         });
         ranked.truncate(top_k);
         ranked
     }
 
-    fn results_to_pointers(results: &[SearchResult], _mode: &SearchMode) -> Vec<Pointer> {
-        results
+    /// Tokens a naive RAG pipeline would spend on `results`: the full content
+    /// of each one, from the counts recorded at ingestion or, for nodes
+    /// without one, by reading the content.
+    fn rag_baseline_tokens(&self, results: &[SearchResult]) -> Result<u64> {
+        let ids: Vec<&str> = results.iter().map(|r| r.node.id.as_str()).collect();
+        let counts = self.graph.token_counts(&ids)?;
+        let mut total = 0;
+        for r in results {
+            total += match counts.get(&r.node.id) {
+                Some(tokens) => *tokens,
+                None => estimate_tokens(&self.read_node_content_cached(&r.node).unwrap_or_default()),
+            };
+        }
+        Ok(total)
+    }
+
+    /// Splits results by tier, each sorted best-first with only the first hit
+    /// per node kept, so a result's index is its rank within its tier.
+    fn rank_within_tiers(results: Vec<SearchResult>) -> [Vec<SearchResult>; 3] {
+        let mut by_tier: [Vec<SearchResult>; 3] = Default::default();
+        for result in results {
+            by_tier[result.tier.index()].push(result);
+        }
+        for tier in &mut by_tier {
+            tier.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let mut seen: HashSet<String> = HashSet::new();
+            tier.retain(|r| seen.insert(r.node.id.clone()));
+        }
+        by_tier
+    }
+
+    /// The fused score of a node ranked first in every tier that returned
+    /// anything; fused scores are divided by it.
+    fn max_fused_score(by_tier: &[Vec<SearchResult>; 3], rrf_k: f64) -> f64 {
+        let tiers_present = by_tier.iter().filter(|t| !t.is_empty()).count();
+        tiers_present as f64 / (rrf_k + 1.0)
+    }
+
+    /// Per-node rank, raw score and normalized fusion contribution in each
+    /// tier that found it, for explain responses.
+    fn tier_breakdown(results: &[SearchResult], rrf_k: f64) -> HashMap<String, Vec<TierContribution>> {
+        let by_tier = Self::rank_within_tiers(results.to_vec());
+        let max_score = Self::max_fused_score(&by_tier, rrf_k);
+        let mut breakdown: HashMap<String, Vec<TierContribution>> = HashMap::new();
+        for tier in by_tier {
+            for (i, result) in tier.into_iter().enumerate() {
+                breakdown.entry(result.node.id).or_default().push(TierContribution {
+                    tier: result.tier,
+                    rank: i + 1,
+                    raw_score: result.score,
+                    contribution: 1.0 / (rrf_k + (i + 1) as f64) / max_score,
+                });
+            }
+        }
+        breakdown
+    }
+
+    /// Keeps the longest ranked prefix of `results` whose pointer tokens fit
+    /// in `budget`. The first result is always kept, even if it alone exceeds
+    /// the budget, so a search never comes back empty for lack of budget.
+    /// Costs are priced with the alias each result will be given.
+    fn select_within_budget(
+        results: Vec<SearchResult>,
+        budget: u64,
+        opts: &SearchOptions,
+        aliases: &aliases::PointerAliases,
+    ) -> Vec<SearchResult> {
+        let mut used: u64 = 0;
+        let mut selected = Vec::new();
+        for r in results {
+            let mut pointer = Self::base_pointer(&r, opts);
+            pointer.id = aliases.peek(&r.node.id);
+            let cost = pointer.estimate_token_count();
+            if !selected.is_empty() && used + cost > budget {
+                break;
+            }
+            used += cost;
+            selected.push(r);
+        }
+        selected
+    }
+
+    /// Converts ranked results into pointers, inlining content according to
+    /// `mode`, with each pointer identified by its alias. Returns the pointers
+    /// and the number of inlined content tokens.
+    fn results_to_pointers(
+        &self,
+        results: &[SearchResult],
+        opts: &SearchOptions,
+        aliases: &aliases::PointerAliases,
+    ) -> (Vec<Pointer>, u64) {
+        let mut inlined_tokens: u64 = 0;
+        let pointers = results
             .iter()
-            .map(|r| Pointer {
-                id: r.node.id.clone(),
-                source: r.node.file_path.clone().unwrap_or_default(),
-                chunk: r.node.name.clone(),
-                lines: format!(
-                    "{}-{}",
-                    r.node.start_line.unwrap_or(0),
-                    r.node.end_line.unwrap_or(0)
-                ),
-                relevance: r.score,
-                summary: r.node.summary.clone().unwrap_or_default(),
-                node_type: r.node.node_type.as_str().to_string(),
-                last_modified: None,
+            .map(|r| {
+                let content = match opts.mode {
+                    SearchMode::Pointer => None,
+                    SearchMode::Smart => self.smart_preview(&r.node),
+                    SearchMode::Full => {
+                        let remaining = FULL_MODE_TOKEN_BUDGET.saturating_sub(inlined_tokens);
+                        self.full_content(&r.node, remaining)
+                    }
+                };
+                if let Some(ref text) = content {
+                    inlined_tokens += estimate_tokens(text);
+                }
+                Pointer {
+                    id: aliases.peek(&r.node.id),
+                    content,
+                    ..Self::base_pointer(r, opts)
+                }
             })
-            .collect()
+            .collect();
+        (pointers, inlined_tokens)
     }
 
-    fn read_node_content(node: &Node) -> Result<String> {
-        let Some(ref path) = node.file_path else {
-            return Ok(String::new());
-        };
+    /// The pointer for a result without any inlined content.
+    fn base_pointer(r: &SearchResult, opts: &SearchOptions) -> Pointer {
+        Pointer {
+            id: r.node.id.clone(),
+            source: r.node.file_path.clone().unwrap_or_default(),
+            chunk: r.node.name.clone(),
+            lines: format!(
+                "{}-{}",
+                r.node.start_line.unwrap_or(0),
+                r.node.end_line.unwrap_or(0)
+            ),
+            relevance: r.score,
+            summary: r.node.summary.clone().unwrap_or_default(),
+            node_type: r.node.node_type.as_str().to_string(),
+            last_modified: last_modified(&r.node),
+            content: None,
+            snippet: opts.snippets.then(|| r.matched_content.clone()).flatten(),
+        }
+    }
 
+    fn smart_preview(&self, node: &Node) -> Option<String> {
+        node.file_path.as_ref()?;
+        let content = self.read_node_content_cached(node).ok()?;
+        let preview = content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .take(SMART_PREVIEW_LINES)
+            .collect::<Vec<_>>()
+            .join("\n");
+        (!preview.is_empty()).then_some(preview)
+    }
+
+    fn full_content(&self, node: &Node, budget: u64) -> Option<String> {
+        if budget == 0 {
+            return None;
+        }
+        node.file_path.as_ref()?;
+        let content = self.read_node_content_cached(node).ok()?;
+        let truncated = truncate_to_tokens(&content, budget);
+        (!truncated.is_empty()).then_some(truncated)
+    }
+
+    /// Reads lines `start..=end` (1-based) of `path`; `end == 0` means the
+    /// whole file.
+    fn read_lines(path: &str, start: i64, end: i64) -> Result<String> {
         let file_content = match std::fs::read_to_string(path) {
             Ok(c) => c,
             Err(_) => return Ok(format!("[File not found: {path}]")),
         };
 
-        let start = node.start_line.unwrap_or(1).max(1) as usize;
-        let end = node.end_line.unwrap_or(0) as usize;
+        let start = start.max(1) as usize;
+        let end = end.max(0) as usize;
 
         if end == 0 {
             return Ok(file_content);
         }
 
         let lines: Vec<&str> = file_content.lines().collect();
-        let start_idx = (start - 1).min(lines.len());
         let end_idx = end.min(lines.len());
+        // Rows stored before ranges were validated may end before they start.
+        let start_idx = (start - 1).min(end_idx);
         Ok(lines[start_idx..end_idx].join("\n"))
     }
 }
 
+/// RFC 3339 time of the node's last write: `updated_at` when the node came
+/// from the database, otherwise the file's modification time. Rows written
+/// by SQLite's `datetime('now')` default are read as UTC.
+fn last_modified(node: &Node) -> Option<String> {
+    if let Some(stamp) = node.updated_at.as_deref() {
+        let parsed = DateTime::parse_from_rfc3339(stamp)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc()));
+        if let Ok(time) = parsed {
+            return Some(time.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+    }
+    let modified = std::fs::metadata(node.file_path.as_deref()?).ok()?.modified().ok()?;
+    Some(DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Whether one node's range lies within the other's in the same file. A
+/// `File` node spans its whole file; other nodes need both line bounds.
+fn nested(a: &Node, b: &Node) -> bool {
+    if a.id == b.id || a.file_path.is_none() || a.file_path != b.file_path {
+        return false;
+    }
+    match (a.node_type == NodeType::File, b.node_type == NodeType::File) {
+        (true, true) => false,
+        (true, false) | (false, true) => true,
+        (false, false) => match (a.start_line, a.end_line, b.start_line, b.end_line) {
+            (Some(a_start), Some(a_end), Some(b_start), Some(b_end)) => {
+                (a_start <= b_start && b_end <= a_end) || (b_start <= a_start && a_end <= b_end)
+            }
+            _ => false,
+        },
+    }
+}
+
 pub fn estimate_tokens(content: &str) -> u64 {
     let word_count = content.split_whitespace().count() as u64;
     (word_count * 4).div_ceil(3)
 }
 
+/// Keeps whole leading lines of `content` while their estimated token count
+/// stays within `max_tokens`.
+fn truncate_to_tokens(content: &str, max_tokens: u64) -> String {
+    let mut used = 0;
+    let mut kept: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        let cost = estimate_tokens(line);
+        if used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        kept.push(line);
+    }
+    kept.join("\n")
+}
+
+/// Truncates `content` (whose first line is line `first_line` of its file) to
+/// `max_tokens` and appends a marker telling the caller which lines to fetch
+/// next. Returns the content unchanged when it already fits.
+fn cap_content(content: &str, max_tokens: u64, first_line: i64) -> (String, bool) {
+    let total = estimate_tokens(content);
+    if total <= max_tokens {
+        return (content.to_string(), false);
+    }
+    let kept = truncate_to_tokens(content, max_tokens);
+    let shown = estimate_tokens(&kept);
+    let kept_lines = if kept.is_empty() { 0 } else { kept.lines().count() as i64 };
+    let next_line = first_line + kept_lines;
+    let last_line = first_line + content.lines().count() as i64 - 1;
+    let marker = format!(
+        "[truncated: {shown} of {total} tokens shown, fetch lines {next_line}-{last_line} for more]"
+    );
+    let capped = if kept.is_empty() { marker } else { format!("{kept}\n{marker}") };
+    (capped, true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,6 +1066,8 @@ mod tests {
             end_line: None,
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
 
         let results = vec![
@@ -313,29 +1085,693 @@ mod tests {
             },
         ];
 
-        let deduped = SearchEngine::deduplicate_and_rank(results, 10);
+        let deduped = SearchEngine::deduplicate_and_rank(results, 10, 60.0);
         assert_eq!(deduped.len(), 1);
         assert_eq!(deduped[0].tier, SearchTier::L0Literal);
+        assert!((deduped[0].score - 1.0).abs() < 1e-9);
+    }
+
+    fn hit(id: &str, score: f64, tier: SearchTier) -> SearchResult {
+        SearchResult {
+            node: Node {
+                id: id.to_string(),
+                project_id: "test".to_string(),
+                name: id.to_string(),
+                node_type: crate::graph::NodeType::Function,
+                file_path: None,
+                start_line: None,
+                end_line: None,
+                summary: None,
+                content_hash: None,
+                updated_at: None,
+                metadata: None,
+            },
+            score,
+            tier,
+            matched_content: None,
+        }
+    }
+
+    #[test]
+    fn rrf_prefers_agreement_across_tiers() {
+        // "solo" has the highest raw score but is ranked first in only one tier.
+        let results = vec![
+            hit("both", 0.6, SearchTier::L0Literal),
+            hit("both", 0.3, SearchTier::L1Fts),
+            hit("other", 0.2, SearchTier::L1Fts),
+            hit("solo", 0.99, SearchTier::L2Vector),
+        ];
+        let ranked = SearchEngine::deduplicate_and_rank(results, 10, 60.0);
+        let ids: Vec<&str> = ranked.iter().map(|r| r.node.id.as_str()).collect();
+        assert_eq!(ids, ["both", "solo", "other"]);
+        assert_eq!(ranked[0].tier, SearchTier::L0Literal);
+    }
+
+    #[test]
+    fn rrf_ignores_raw_score_scale_between_tiers() {
+        // A tiny BM25-normalized score ranked first must tie a large cosine
+        // score ranked first; only the tier order breaks the tie.
+        let results = vec![
+            hit("fts", 0.05, SearchTier::L1Fts),
+            hit("vec", 0.95, SearchTier::L2Vector),
+        ];
+        let ranked = SearchEngine::deduplicate_and_rank(results, 10, 60.0);
+        assert_eq!(ranked[0].node.id, "fts");
+        assert!((ranked[0].score - ranked[1].score).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rrf_truncates_to_top_k() {
+        let results: Vec<SearchResult> = (0..5)
+            .map(|i| hit(&format!("n{i}"), 1.0 - i as f64 * 0.1, SearchTier::L1Fts))
+            .collect();
+        let ranked = SearchEngine::deduplicate_and_rank(results, 2, 60.0);
+        let ids: Vec<&str> = ranked.iter().map(|r| r.node.id.as_str()).collect();
+        assert_eq!(ids, ["n0", "n1"]);
+    }
+
+    fn chunk(id: &str, score: f64, node_type: NodeType, file: &str, lines: (i64, i64)) -> SearchResult {
+        let mut result = hit(id, score, SearchTier::L1Fts);
+        result.node.node_type = node_type;
+        result.node.file_path = Some(file.to_string());
+        result.node.start_line = Some(lines.0);
+        result.node.end_line = Some(lines.1);
+        result
+    }
+
+    fn ids(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.node.id.as_str()).collect()
+    }
+
+    #[test]
+    fn overlap_keeps_the_better_of_nested_impl_and_function() {
+        let ranked = vec![
+            chunk("impl", 1.0, NodeType::Impl, "src/a.rs", (1, 50)),
+            chunk("close_fn", 0.995, NodeType::Function, "src/a.rs", (30, 40)),
+            chunk("weak_fn", 0.5, NodeType::Function, "src/a.rs", (10, 20)),
+        ];
+        let kept = SearchEngine::suppress_overlaps(ranked, 0.01);
+        assert_eq!(ids(&kept), ["impl", "close_fn"]);
+
+        let ranked = vec![
+            chunk("method", 0.9, NodeType::Function, "src/a.rs", (10, 20)),
+            chunk("impl", 0.6, NodeType::Impl, "src/a.rs", (1, 50)),
+        ];
+        let kept = SearchEngine::suppress_overlaps(ranked, 0.01);
+        assert_eq!(ids(&kept), ["method"]);
+    }
+
+    #[test]
+    fn overlap_ignores_disjoint_ranges_and_other_files() {
+        let ranked = vec![
+            chunk("first", 1.0, NodeType::Function, "src/a.rs", (1, 10)),
+            chunk("second", 0.5, NodeType::Function, "src/a.rs", (11, 20)),
+            chunk("elsewhere", 0.4, NodeType::Function, "src/b.rs", (2, 5)),
+        ];
+        let kept = SearchEngine::suppress_overlaps(ranked, 0.01);
+        assert_eq!(ids(&kept), ["first", "second", "elsewhere"]);
+    }
+
+    #[test]
+    fn file_nodes_lose_to_their_chunks() {
+        let ranked = vec![
+            chunk("a.rs", 1.0, NodeType::File, "src/a.rs", (1, 100)),
+            chunk("inner", 0.2, NodeType::Function, "src/a.rs", (5, 9)),
+            chunk("b.rs", 0.1, NodeType::File, "src/b.rs", (1, 10)),
+        ];
+        let kept = SearchEngine::suppress_overlaps(ranked, 0.01);
+        assert_eq!(ids(&kept), ["inner", "b.rs"]);
+    }
+
+    fn literal_hits(scores: &[f64]) -> Vec<SearchResult> {
+        scores
+            .iter()
+            .map(|&score| SearchResult {
+                node: Node {
+                    id: format!("n{score}"),
+                    project_id: "test".to_string(),
+                    name: "n".to_string(),
+                    node_type: crate::graph::NodeType::Function,
+                    file_path: None,
+                    start_line: None,
+                    end_line: None,
+                    summary: None,
+                    content_hash: None,
+                    updated_at: None,
+                    metadata: None,
+                },
+                score,
+                tier: SearchTier::L0Literal,
+                matched_content: None,
+            })
+            .collect()
     }
 
     #[test]
     fn short_circuit_skips_on_high_l0_confidence() {
-        assert!(SHORT_CIRCUIT_SKIP_ALL > SHORT_CIRCUIT_SKIP_L2);
-        assert!(SHORT_CIRCUIT_SKIP_ALL <= 1.0);
-        assert!(SHORT_CIRCUIT_SKIP_L2 > 0.0);
+        let engine = crate::HermesEngine::in_memory("test-sc-default").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-sc-default");
+        let search = SearchEngine::new(&graph, engine.search_cache(), HermesConfig::default());
+        assert_eq!(search.short_circuit(&literal_hits(&[1.0, 0.95]), 2), ShortCircuit::SkipAll);
+        assert_eq!(search.short_circuit(&literal_hits(&[1.0, 0.85]), 2), ShortCircuit::SkipVector);
+        assert_eq!(search.short_circuit(&literal_hits(&[1.0, 0.5]), 2), ShortCircuit::None);
+        assert_eq!(search.short_circuit(&literal_hits(&[1.0]), 2), ShortCircuit::None);
+    }
+
+    #[test]
+    fn thresholds_above_one_never_short_circuit() {
+        let engine = crate::HermesEngine::in_memory("test-sc-off").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-sc-off");
+        let config = HermesConfig {
+            short_circuit_skip_all: 1.1,
+            short_circuit_skip_l2: 1.1,
+            ..Default::default()
+        };
+        let search = SearchEngine::new(&graph, engine.search_cache(), config);
+        for top_k in 1..=3 {
+            let hits = literal_hits(&[1.0, 1.0, 1.0]);
+            assert_eq!(search.short_circuit(&hits, top_k), ShortCircuit::None);
+        }
+    }
+
+    #[test]
+    fn zero_ttl_config_disables_cache_hits() {
+        let engine = crate::HermesEngine::in_memory("test-ttl").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-ttl");
+        let config = HermesConfig { cache_ttl_secs: 0, ..Default::default() };
+        let search = SearchEngine::new(&graph, engine.search_cache(), config);
+        let opts = SearchOptions::default();
+        search.search("anything", &opts).unwrap();
+        let key = SearchCacheKey::new("test-ttl", "anything", &opts);
+        assert!(search.get_from_cache(&key).is_none());
     }
 
     #[test]
     fn cache_miss_then_hit() {
         let engine = crate::HermesEngine::in_memory("test-cache-mod").unwrap();
         let cache = engine.search_cache();
-        let dummy = PointerResponse::build(vec![], 0);
+        let dummy = PointerResponse::build(vec![], 0, 0);
+        let key = SearchCacheKey::new("test-cache-mod", "key", &SearchOptions::default());
         {
             let mut c = cache.lock().unwrap();
-            c.insert("key:10".to_string(), (dummy, Instant::now()));
+            c.insert(key.clone(), dummy);
         }
         let c = cache.lock().unwrap();
-        assert!(c.contains_key("key:10"));
+        assert!(c.contains_key(&key));
+    }
+
+    #[test]
+    fn cache_key_differs_by_project_mode_and_filter() {
+        let opts = SearchOptions::default();
+        let base = SearchCacheKey::new("a", "Query ", &opts);
+        assert_eq!(base, SearchCacheKey::new("a", "query", &opts));
+        assert_ne!(base, SearchCacheKey::new("b", "query", &opts));
+
+        let full = SearchOptions { mode: SearchMode::Full, ..Default::default() };
+        assert_ne!(base, SearchCacheKey::new("a", "query", &full));
+
+        let scoped = SearchOptions { path_filter: Some("src/".to_string()), ..Default::default() };
+        assert_ne!(base, SearchCacheKey::new("a", "query", &scoped));
+    }
+
+    #[test]
+    fn projects_sharing_an_engine_never_see_each_others_cache() {
+        let engine = crate::HermesEngine::in_memory("proj-a").unwrap();
+        let graph_a = KnowledgeGraph::new(engine.db().clone(), "proj-a");
+        let graph_b = KnowledgeGraph::new(engine.db().clone(), "proj-b");
+        let node = graph_a
+            .create_node_builder()
+            .name("shared_name")
+            .node_type(crate::graph::NodeType::Function)
+            .build();
+        graph_a.add_node(&node).unwrap();
+
+        let search_a = SearchEngine::new(&graph_a, engine.search_cache(), engine.config().clone());
+        let search_b = SearchEngine::new(&graph_b, engine.search_cache(), engine.config().clone());
+        let opts = SearchOptions::default();
+
+        let resp_a = search_a.search("shared_name", &opts).unwrap();
+        assert_eq!(resp_a.pointers.len(), 1);
+        let resp_b = search_b.search("shared_name", &opts).unwrap();
+        assert!(resp_b.pointers.is_empty());
+
+        // Both responses are cached independently.
+        let cache = engine.search_cache();
+        assert_eq!(cache.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn clamp_top_k_bounds() {
+        assert_eq!(clamp_top_k(0), 1);
+        assert_eq!(clamp_top_k(25), 25);
+        assert_eq!(clamp_top_k(10_000), MAX_TOP_K);
+    }
+
+    #[test]
+    fn cache_distinguishes_top_k() {
+        let engine = crate::HermesEngine::in_memory("test-cache-topk").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        for i in 0..5 {
+            let node = graph
+                .create_node_builder()
+                .name(&format!("handler_{i}"))
+                .node_type(crate::graph::NodeType::Function)
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+        let opts = |top_k| SearchOptions { top_k, ..Default::default() };
+        let three = search.search("handler", &opts(3)).unwrap();
+        let five = search.search("handler", &opts(5)).unwrap();
+        assert_eq!(three.pointers.len(), 3);
+        assert_eq!(five.pointers.len(), 5);
+    }
+
+    fn engine_with_file_chunk(dir: &std::path::Path) -> crate::HermesEngine {
+        let file = dir.join("lib.rs");
+        let body: Vec<String> = (0..20).map(|i| format!("    let value_{i} = {i};")).collect();
+        std::fs::write(&file, format!("fn compute() {{\n{}\n}}\n", body.join("\n"))).unwrap();
+
+        let engine = crate::HermesEngine::in_memory("test-modes").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let node = graph
+            .create_node_builder()
+            .name("compute")
+            .node_type(crate::graph::NodeType::Function)
+            .file_path(&file.to_string_lossy())
+            .lines(1, 22)
+            .build();
+        graph.add_node(&node).unwrap();
+        engine
+    }
+
+    #[test]
+    fn search_mode_parse_roundtrip() {
+        for mode in [SearchMode::Pointer, SearchMode::Smart, SearchMode::Full] {
+            assert_eq!(SearchMode::parse_str(mode.as_str()), Some(mode));
+        }
+        assert_eq!(SearchMode::parse_str("FULL"), Some(SearchMode::Full));
+        assert!(SearchMode::parse_str("verbose").is_none());
+    }
+
+    #[test]
+    fn modes_change_inlined_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_with_file_chunk(dir.path());
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let opts = |mode| SearchOptions { top_k: 5, mode, ..Default::default() };
+        let pointer = search.search("compute", &opts(SearchMode::Pointer)).unwrap();
+        assert!(pointer.pointers[0].content.is_none());
+        assert_eq!(pointer.accounting.fetched_tokens, 0);
+
+        let smart = search.search("compute", &opts(SearchMode::Smart)).unwrap();
+        let preview = smart.pointers[0].content.as_deref().unwrap();
+        assert_eq!(preview.lines().count(), SMART_PREVIEW_LINES);
+        assert!(smart.accounting.fetched_tokens > 0);
+
+        let full = search.search("compute", &opts(SearchMode::Full)).unwrap();
+        let content = full.pointers[0].content.as_deref().unwrap();
+        assert!(content.contains("value_19"));
+        assert!(full.accounting.fetched_tokens > smart.accounting.fetched_tokens);
+    }
+
+    #[test]
+    fn path_filter_scopes_results_and_cache() {
+        let engine = crate::HermesEngine::in_memory("test-path-filter").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        for (name, path) in [
+            ("search_docs", "/p/docs/search.md"),
+            ("search_impl", "/p/src/search/mod.rs"),
+        ] {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(crate::graph::NodeType::Function)
+                .file_path(path)
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let all = search.search("search", &SearchOptions::default()).unwrap();
+        assert_eq!(all.pointers.len(), 2);
+
+        let scoped = SearchOptions {
+            path_filter: Some("**/*.md".to_string()),
+            ..Default::default()
+        };
+        let docs = search.search("search", &scoped).unwrap();
+        assert_eq!(docs.pointers.len(), 1);
+        assert_eq!(docs.pointers[0].chunk, "search_docs");
+    }
+
+    #[test]
+    fn metadata_filters_scope_every_tier_and_the_cache() {
+        let engine = crate::HermesEngine::in_memory("test-metadata-filter").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        for (name, language) in [("search_docs", "markdown"), ("search_impl", "rust")] {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(crate::graph::NodeType::Function)
+                .file_path("/p/src/search.rs")
+                .metadata(serde_json::json!({ "language": language }))
+                .build();
+            graph.add_node(&node).unwrap();
+            graph.index_fts(&node, "ranks hits for the search tiers").unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+        let rust = SearchOptions {
+            metadata_filters: vec![MetadataFilter::parse_str("language=rust").unwrap()],
+            ..Default::default()
+        };
+
+        // A literal hit on both names, and an FTS-only query.
+        assert_eq!(search.search("search", &SearchOptions::default()).unwrap().pointers.len(), 2);
+        for query in ["search", "ranks hits"] {
+            let hits = search.search(query, &rust).unwrap();
+            let chunks: Vec<&str> = hits.pointers.iter().map(|p| p.chunk.as_str()).collect();
+            assert_eq!(chunks, ["search_impl"], "{query}");
+        }
+    }
+
+    #[test]
+    fn snippets_explain_matches_and_can_be_disabled() {
+        let engine = crate::HermesEngine::in_memory("test-snippets").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-snippets");
+        let node = graph
+            .create_node_builder()
+            .name("dispatch")
+            .node_type(crate::graph::NodeType::Function)
+            .file_path("src/router.rs")
+            .build();
+        graph.add_node(&node).unwrap();
+        graph
+            .index_fts(&node, "routes every webhook payload to its registered handler")
+            .unwrap();
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let literal = search.search("dispatch", &SearchOptions::default()).unwrap();
+        assert_eq!(literal.pointers[0].snippet.as_deref(), Some("dispatch"));
+
+        let fts = search.search("webhook", &SearchOptions::default()).unwrap();
+        let snippet = fts.pointers[0].snippet.as_deref().unwrap();
+        assert!(snippet.contains("webhook"));
+
+        let off = SearchOptions { snippets: false, ..Default::default() };
+        let bare = search.search("webhook", &off).unwrap();
+        assert!(bare.pointers[0].snippet.is_none());
+        assert!(bare.accounting.pointer_tokens < fts.accounting.pointer_tokens);
+    }
+
+    #[test]
+    fn expand_graph_appends_capped_children_of_top_hits() {
+        let engine = crate::HermesEngine::in_memory("test-expand").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-expand");
+        let file = graph
+            .create_node_builder()
+            .name("router.rs")
+            .node_type(crate::graph::NodeType::File)
+            .file_path("src/router.rs")
+            .build();
+        graph.add_node(&file).unwrap();
+        for i in 0..(EXPAND_MAX_NEIGHBORS_PER_HIT + 3) {
+            let child = graph
+                .create_node_builder()
+                .name(&format!("chunk_{i}"))
+                .node_type(crate::graph::NodeType::Function)
+                .file_path("src/router.rs")
+                .lines(i as i64 * 10 + 1, i as i64 * 10 + 9)
+                .build();
+            graph.add_node(&child).unwrap();
+            let edge = graph
+                .create_edge_builder()
+                .source(&file.id)
+                .target(&child.id)
+                .edge_type(EdgeType::Contains)
+                .build();
+            graph.add_edge(&edge).unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let plain = search.search("router.rs", &SearchOptions { top_k: 1, ..Default::default() }).unwrap();
+        assert_eq!(plain.pointers.len(), 1);
+
+        let opts = SearchOptions { top_k: 1, expand_graph: true, ..Default::default() };
+        let expanded = search.search("router.rs", &opts).unwrap();
+        assert_eq!(expanded.pointers.len(), 1 + EXPAND_MAX_NEIGHBORS_PER_HIT);
+        let parent = &expanded.pointers[0];
+        assert_eq!(parent.chunk, "router.rs");
+        assert_eq!(expanded.pointers[1].chunk, "chunk_0");
+        assert!((expanded.pointers[1].relevance - parent.relevance * EXPAND_SCORE_DAMPING).abs() < 1e-9);
+        assert_eq!(expanded.pointers[1].snippet.as_deref(), Some("in router.rs"));
+    }
+
+    #[test]
+    fn token_budget_limits_pointer_tokens() {
+        let engine = crate::HermesEngine::in_memory("test-budget").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-budget");
+        for i in 0..30 {
+            let node = graph
+                .create_node_builder()
+                .name(&format!("budget_item_{i:02}"))
+                .node_type(crate::graph::NodeType::Function)
+                .file_path("src/items.rs")
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let opts = SearchOptions { token_budget: Some(60), ..Default::default() };
+        let resp = search.search("budget_item", &opts).unwrap();
+        assert!(resp.pointers.len() > 1);
+        assert!(resp.pointers.len() < 30);
+        assert!(resp.accounting.pointer_tokens <= 60);
+        assert_eq!(resp.accounting.token_budget, Some(60));
+        assert_eq!(resp.accounting.budget_used, Some(resp.accounting.pointer_tokens));
+
+        let tiny = SearchOptions { token_budget: Some(1), ..Default::default() };
+        let resp = search.search("budget_item", &tiny).unwrap();
+        assert_eq!(resp.pointers.len(), 1);
+    }
+
+    #[test]
+    fn min_score_drops_weak_results() {
+        let engine = crate::HermesEngine::in_memory("test-min-score").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-min-score");
+        for name in ["parse_config", "config_loader"] {
+            let node = graph
+                .create_node_builder()
+                .name(name)
+                .node_type(crate::graph::NodeType::Function)
+                .file_path("src/config.rs")
+                .build();
+            graph.add_node(&node).unwrap();
+        }
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let all = search.search("config", &SearchOptions::default()).unwrap();
+        assert_eq!(all.pointers.len(), 2);
+        let floor = all.pointers[0].relevance;
+        assert!(all.pointers[1].relevance < floor);
+
+        let strict = SearchOptions { min_score: Some(floor), ..Default::default() };
+        let resp = search.search("config", &strict).unwrap();
+        assert_eq!(resp.pointers.len(), 1);
+        assert!(resp.accounting.pointer_tokens < all.accounting.pointer_tokens);
+
+        let none = SearchOptions { min_score: Some(f64::MAX), ..Default::default() };
+        let empty = search.search("config", &none).unwrap();
+        assert!(empty.pointers.is_empty());
+        assert_eq!(empty.accounting.pointer_tokens, 0);
+        assert_eq!(empty.accounting.total_tokens, 0);
+    }
+
+    #[test]
+    fn explain_reports_tiers_and_bypasses_the_cache() {
+        let engine = crate::HermesEngine::in_memory("test-explain").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), "test-explain");
+        let node = graph
+            .create_node_builder()
+            .name("dispatch")
+            .node_type(crate::graph::NodeType::Function)
+            .file_path("src/router.rs")
+            .build();
+        graph.add_node(&node).unwrap();
+        graph.index_fts(&node, "dispatch routes webhook payloads").unwrap();
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let opts = SearchOptions { top_k: 1, explain: true, ..Default::default() };
+        let first = search.search("dispatch", &opts).unwrap();
+        let debug = first.debug.as_ref().unwrap();
+        assert!(!debug.cache_hit);
+        assert_eq!(debug.short_circuit, "skip_all");
+        let tiers = &debug.pointers[0].tiers;
+        assert_eq!(tiers[0].tier, SearchTier::L0Literal);
+        assert_eq!(tiers[0].rank, 1);
+        assert!((debug.pointers[0].fused_score - first.pointers[0].relevance).abs() < 1e-9);
+        assert_eq!(engine.cache_stats().entries, 0);
+
+        let plain = search.search("dispatch", &SearchOptions { top_k: 1, ..Default::default() }).unwrap();
+        assert!(plain.debug.is_none());
+        let again = search.search("dispatch", &opts).unwrap();
+        assert!(again.debug.unwrap().cache_hit);
+        assert_eq!(engine.cache_stats().hits, 0);
+    }
+
+    #[test]
+    fn pointers_carry_last_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_with_file_chunk(dir.path());
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let resp = search.search("compute", &SearchOptions::default()).unwrap();
+        let stamp = resp.pointers[0].last_modified.as_deref().unwrap();
+        assert!(DateTime::parse_from_rfc3339(stamp).is_ok(), "{stamp}");
+    }
+
+    #[test]
+    fn last_modified_normalizes_sqlite_timestamps_and_falls_back_to_mtime() {
+        let mut node = hit("n", 1.0, SearchTier::L0Literal).node;
+        node.updated_at = Some("2024-03-05 10:20:30".to_string());
+        assert_eq!(last_modified(&node).as_deref(), Some("2024-03-05T10:20:30Z"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lib.rs");
+        std::fs::write(&file, "fn a() {}\n").unwrap();
+        node.updated_at = None;
+        node.file_path = Some(file.to_string_lossy().to_string());
+        assert!(last_modified(&node).is_some());
+
+        node.file_path = None;
+        assert!(last_modified(&node).is_none());
+    }
+
+    #[test]
+    fn rag_baseline_sums_full_content_of_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_with_file_chunk(dir.path());
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+        let opts = SearchOptions { mode: SearchMode::Pointer, ..Default::default() };
+
+        let read = search.search("compute", &opts).unwrap();
+        let content = std::fs::read_to_string(dir.path().join("lib.rs")).unwrap();
+        assert_eq!(read.accounting.traditional_rag_estimate, estimate_tokens(&content));
+
+        let node_id = graph.literal_search_by_name("compute", None).unwrap()[0].id.clone();
+        graph.set_token_count(&node_id, 1234).unwrap();
+        engine.invalidate_search_cache();
+        let recorded = search.search("compute", &opts).unwrap();
+        assert_eq!(recorded.accounting.traditional_rag_estimate, 1234);
+    }
+
+    #[test]
+    fn truncate_to_tokens_keeps_whole_lines() {
+        let text = "one two three\nfour five six\nseven eight nine";
+        assert_eq!(truncate_to_tokens(text, 4), "one two three");
+        assert_eq!(truncate_to_tokens(text, 0), "");
+        assert_eq!(truncate_to_tokens(text, 100), text);
+    }
+
+    #[test]
+    fn cap_content_marks_remaining_lines() {
+        let text = "one two three\nfour five six\nseven eight nine";
+        let (capped, truncated) = cap_content(text, 4, 10);
+        assert!(truncated);
+        assert_eq!(
+            capped,
+            "one two three\n[truncated: 4 of 12 tokens shown, fetch lines 11-12 for more]"
+        );
+        assert_eq!(cap_content(text, 100, 10), (text.to_string(), false));
+    }
+
+    #[test]
+    fn fetch_respects_max_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_with_file_chunk(dir.path());
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+        let id = search.search("compute", &SearchOptions::default()).unwrap().pointers[0].id.clone();
+
+        let full = search.fetch(&id, None).unwrap().unwrap();
+        assert!(!full.truncated);
+
+        let capped = search.fetch(&id, Some(20)).unwrap().unwrap();
+        assert!(capped.truncated);
+        assert!(capped.token_count < full.token_count);
+        assert!(capped.content.starts_with("fn compute() {"));
+        assert!(capped.content.ends_with("-22 for more]"));
+    }
+
+    #[test]
+    fn fetch_of_a_stored_inverted_range_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = engine_with_file_chunk(dir.path());
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+        let nodes = graph.get_all_nodes().unwrap();
+        let id = nodes.iter().find(|n| n.name == "compute").unwrap().id.clone();
+        engine
+            .db()
+            .lock()
+            .unwrap()
+            .execute("UPDATE nodes SET start_line = 6, end_line = 2 WHERE id = ?1", [&id])
+            .unwrap();
+
+        assert_eq!(search.fetch(&id, None).unwrap().unwrap().content, "");
+    }
+
+    #[test]
+    fn fetch_range_reads_lines_inside_project_root() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("src")).unwrap();
+        let body: Vec<String> = (1..=50).map(|i| format!("line {i}")).collect();
+        std::fs::write(root.path().join("src/lib.rs"), body.join("\n")).unwrap();
+
+        let engine = crate::HermesEngine::in_memory("test-range").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        let resp = search.fetch_range(root.path(), "src/lib.rs", 10, 12, None).unwrap();
+        assert_eq!(resp.pointer_id, "file:src/lib.rs:10-12");
+        assert_eq!(resp.content, "line 10\nline 11\nline 12");
+        assert_eq!(resp.token_count, estimate_tokens(&resp.content));
+
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "nope").unwrap();
+        let abs = outside.path().join("secret.txt");
+        assert!(search.fetch_range(root.path(), &abs.to_string_lossy(), 1, 1, None).is_err());
+        assert!(search.fetch_range(root.path(), "../secret.txt", 1, 1, None).is_err());
+        assert!(search.fetch_range(root.path(), "src/lib.rs", 5, 2, None).is_err());
+    }
+
+    #[test]
+    fn fetch_returns_new_content_after_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.md");
+        std::fs::write(&file, "first version").unwrap();
+
+        let engine = crate::HermesEngine::in_memory("test-fetch-stale").unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let node = graph
+            .create_node_builder()
+            .name("notes.md")
+            .node_type(crate::graph::NodeType::Document)
+            .file_path(&file.to_string_lossy())
+            .build();
+        graph.add_node(&node).unwrap();
+        let search = SearchEngine::new(&graph, engine.search_cache(), engine.config().clone());
+
+        assert_eq!(search.fetch(&node.id, None).unwrap().unwrap().content, "first version");
+        std::fs::write(&file, "second, longer version").unwrap();
+        assert_eq!(
+            search.fetch(&node.id, None).unwrap().unwrap().content,
+            "second, longer version"
+        );
     }
 
     #[test]
@@ -0,0 +1,155 @@
+use crate::error::{HermesError, Result};
+use regex::Regex;
+
+/// Restricts search results to nodes whose `file_path` matches a prefix or glob.
+///
+/// Indexed paths are stored as crawled (`<project_root>/src/lib.rs`), so a
+/// relative filter such as `src/search/` or `src/search/**` may start at any
+/// path-component boundary. Filters starting with `/` are anchored at the
+/// start of the stored path.
+///
+/// Glob syntax: `*` (any run within one component), `**` (any number of
+/// components), `?` (one character). Anything without a wildcard is a prefix.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    raw: String,
+    literal: String,
+    regex: Regex,
+}
+
+impl PathFilter {
+    pub fn parse(filter: &str) -> Result<Self> {
+        let raw = filter.trim().replace('\\', "/");
+        if raw.is_empty() {
+            return Err(HermesError::InvalidQuery("path filter must not be empty".to_string()));
+        }
+
+        let is_glob = raw.contains(['*', '?']);
+        let anchored = raw.starts_with('/');
+        let body = if is_glob {
+            glob_to_regex(&raw)
+        } else {
+            format!("{}.*", regex::escape(&raw))
+        };
+        let pattern = if anchored {
+            format!("^{body}$")
+        } else {
+            format!("^(?:.*/)?{}$", body.trim_start_matches('/'))
+        };
+        let regex = Regex::new(&pattern)
+            .map_err(|e| HermesError::InvalidQuery(format!("invalid path filter: {filter}: {e}")))?;
+
+        let literal = raw
+            .split(['*', '?'])
+            .next()
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(Self {
+            raw,
+            literal,
+            regex,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn matches(&self, file_path: &str) -> bool {
+        self.regex.is_match(&file_path.replace('\\', "/"))
+    }
+
+    /// A `LIKE` pattern (escaped with `\`) that every matching path satisfies.
+    /// Used to push a coarse filter into SQL; callers still post-filter with
+    /// [`PathFilter::matches`] for exact glob semantics.
+    pub fn like_pattern(&self) -> Option<String> {
+        if self.literal.is_empty() {
+            return None;
+        }
+        let escaped = self
+            .literal
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        if self.raw.starts_with('/') {
+            Some(format!("{escaped}%"))
+        } else {
+            Some(format!("%{escaped}%"))
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::new();
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    out.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    out.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => out.push_str("[^/]*"),
+            '?' => out.push_str("[^/]"),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_matches_at_component_boundary() {
+        let f = PathFilter::parse("src/search/").unwrap();
+        assert!(f.matches("/home/me/proj/src/search/fts.rs"));
+        assert!(f.matches("src/search/mod.rs"));
+        assert!(!f.matches("/home/me/proj/src/searcher.rs"));
+        assert!(!f.matches("/home/me/proj/xsrc/search/fts.rs"));
+    }
+
+    #[test]
+    fn double_star_glob_matches_nested_dirs() {
+        let f = PathFilter::parse("src/search/**").unwrap();
+        assert!(f.matches("/p/src/search/fts.rs"));
+        assert!(f.matches("/p/src/search/deep/nested.rs"));
+        assert!(!f.matches("/p/src/ingestion/crawler.rs"));
+    }
+
+    #[test]
+    fn extension_glob_matches_any_directory() {
+        let f = PathFilter::parse("**/*.md").unwrap();
+        assert!(f.matches("/p/README.md"));
+        assert!(f.matches("/p/docs/guide.md"));
+        assert!(!f.matches("/p/src/lib.rs"));
+        assert!(f.like_pattern().is_none());
+    }
+
+    #[test]
+    fn like_pattern_escapes_wildcards() {
+        let f = PathFilter::parse("src/hash_tracker").unwrap();
+        assert_eq!(f.like_pattern().as_deref(), Some("%src/hash\\_tracker%"));
+    }
+
+    #[test]
+    fn anchored_filter_requires_leading_match() {
+        let f = PathFilter::parse("/p/src").unwrap();
+        assert!(f.matches("/p/src/lib.rs"));
+        assert!(!f.matches("/other/p/src/lib.rs"));
+    }
+
+    #[test]
+    fn empty_filter_is_rejected() {
+        assert!(PathFilter::parse("  ").is_err());
+    }
+}
@@ -1,55 +1,280 @@
-use crate::graph::KnowledgeGraph;
-use crate::search::{SearchResult, SearchTier};
-use anyhow::Result;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use crate::config::TokenizerOptions;
+use crate::embedding::EmbeddingProvider;
+use crate::error::{HermesError, Result};
+use crate::graph::{KnowledgeGraph, Node};
+use crate::search::ann::{AnnCache, IvfIndex};
+use crate::search::{PathFilter, SearchResult, SearchTier};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Model tags stored alongside vectors built by [`HashedEmbedder`], one per
+/// tokenizer setting. Bump the version when tokenization or hashing changes
+/// so stale rows are recomputed.
+///
+/// Every tag starts with [`HASHED_MODEL_PREFIX`], so ingestion can tell rows
+/// of another setting or version apart from external providers' rows.
+pub(crate) const HASHED_MODEL_PREFIX: &str = "hashed-bow-";
+const HASHED_MODEL: &str = "hashed-bow-256-v1";
+const HASHED_MODEL_STOP: &str = "hashed-bow-256-v1+stop";
+const HASHED_MODEL_STEM: &str = "hashed-bow-256-v1+stem";
+const HASHED_MODEL_STOP_STEM: &str = "hashed-bow-256-v1+stop+stem";
+
+/// Common English words that carry no meaning for code search.
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "also", "an", "and", "any", "are", "as", "at", "be", "been", "but",
+    "by", "can", "could", "do", "does", "each", "for", "from", "had", "has", "have", "he", "her",
+    "his", "how", "if", "in", "into", "is", "it", "its", "may", "more", "must", "no", "not", "of",
+    "on", "one", "only", "or", "other", "our", "out", "over", "should", "so", "some", "such",
+    "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "those",
+    "to", "under", "up", "was", "we", "were", "what", "when", "where", "which", "while", "who",
+    "will", "with", "would", "you", "your",
+];
 
 const VECTOR_DIMENSION: usize = 256;
 const VECTOR_LIMIT: usize = 20;
 const MIN_SCORE: f64 = 0.20;
 
-pub fn vector_search(graph: &KnowledgeGraph, query: &str) -> Result<Vec<SearchResult>> {
-    let query_tokens = tokenize(query);
+/// Where the vector tier may find or store an ANN index, and the candidate
+/// count from which it is worth building one.
+pub struct AnnSettings<'a> {
+    pub cache: &'a Mutex<AnnCache>,
+    pub threshold: usize,
+}
+
+/// Scores nodes against `query` with `embedder`'s stored vectors. Nodes the
+/// provider has no vector for (say, after a failed embedding request) are
+/// left out; when none has one, or the query cannot be embedded, the tier
+/// falls back to hashed vectors so incompatible vector spaces are never
+/// compared.
+///
+/// Without a path filter and with at least `ann.threshold` candidates, an IVF
+/// index is built once and probed instead of scanning every vector.
+pub fn vector_search(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+    embedder: &dyn EmbeddingProvider,
+    hashed: &HashedEmbedder,
+    ann: &AnnSettings<'_>,
+) -> Result<Vec<SearchResult>> {
+    if embedder.model() != hashed.model() {
+        match provider_search(graph, query, path_filter, embedder, ann) {
+            Ok(Some(results)) => return Ok(results),
+            Ok(None) => warn!(
+                model = embedder.model(),
+                "Stored vectors do not match the active embedding model, using hashed vectors"
+            ),
+            Err(e) => warn!(error = %e, "Query embedding failed, using hashed vectors"),
+        }
+    }
+    hashed_search(graph, query, path_filter, hashed, ann)
+}
+
+fn hashed_search(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+    hashed: &HashedEmbedder,
+    ann: &AnnSettings<'_>,
+) -> Result<Vec<SearchResult>> {
+    let query_tokens = hashed.tokenize(query);
     if query_tokens.is_empty() {
         return Ok(Vec::new());
     }
 
     let query_vec = build_vector(&query_tokens);
-    let mut results = graph
-        .get_all_nodes()?
+    // Nodes indexed before vectors were persisted are embedded on the fly.
+    let results = search_model(
+        graph,
+        hashed.model(),
+        &query_vec,
+        path_filter,
+        ann,
+        |node| hashed.node_vector(node),
+    )?;
+    Ok(results.unwrap_or_default())
+}
+
+/// `None` when no candidate has a vector for the embedder's model, or one has
+/// a dimension that differs from the query vector.
+fn provider_search(
+    graph: &KnowledgeGraph,
+    query: &str,
+    path_filter: Option<&PathFilter>,
+    embedder: &dyn EmbeddingProvider,
+    ann: &AnnSettings<'_>,
+) -> Result<Option<Vec<SearchResult>>> {
+    let query_vec = embedder.embed(&[query])?.pop().ok_or_else(|| {
+        HermesError::EmbeddingApi("embedding provider returned no vector for the query".into())
+    })?;
+    search_model(
+        graph,
+        embedder.model(),
+        &query_vec,
+        path_filter,
+        ann,
+        |_| None,
+    )
+}
+
+/// Ranks the stored `model` vectors against `query_vec`. A node without a
+/// stored vector is scored with the one `on_missing` returns, or skipped when
+/// it returns `None`. `Ok(None)` when there are candidates but none of them
+/// has a vector, or a vector's dimension differs from the query's.
+fn search_model(
+    graph: &KnowledgeGraph,
+    model: &str,
+    query_vec: &[f32],
+    path_filter: Option<&PathFilter>,
+    ann: &AnnSettings<'_>,
+    on_missing: impl Fn(&Node) -> Option<Vec<f32>>,
+) -> Result<Option<Vec<SearchResult>>> {
+    let use_ann = path_filter.is_none();
+    let generation = if use_ann { graph.data_version()? } else { 0 };
+    if use_ann {
+        let project_id = graph.project_id();
+        let cached = ann
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(project_id, model, generation));
+        if let Some(index) = cached {
+            let probed = index.probe(query_vec, index.default_nprobe());
+            return Ok(Some(rank(
+                query_vec,
+                probed.map(|(n, v)| (n, v.as_slice())),
+            )));
+        }
+    }
+
+    let loaded = load_candidates(graph, model, path_filter)?;
+    let any_loaded = !loaded.is_empty();
+    let mut candidates = Vec::new();
+    for (node, stored) in loaded {
+        let Some(vector) = stored.or_else(|| on_missing(&node)) else {
+            continue;
+        };
+        if vector.len() != query_vec.len() {
+            return Ok(None);
+        }
+        candidates.push((node, vector));
+    }
+    if any_loaded && candidates.is_empty() {
+        return Ok(None);
+    }
+
+    if use_ann && candidates.len() >= ann.threshold {
+        let index = Arc::new(IvfIndex::build(candidates));
+        if let Ok(mut cache) = ann.cache.lock() {
+            cache.insert(graph.project_id(), model, generation, index.clone());
+        }
+        let probed = index.probe(query_vec, index.default_nprobe());
+        return Ok(Some(rank(
+            query_vec,
+            probed.map(|(n, v)| (n, v.as_slice())),
+        )));
+    }
+    Ok(Some(rank(
+        query_vec,
+        candidates.iter().map(|(n, v)| (n, v.as_slice())),
+    )))
+}
+
+fn load_candidates(
+    graph: &KnowledgeGraph,
+    model: &str,
+    path_filter: Option<&PathFilter>,
+) -> Result<Vec<(Node, Option<Vec<f32>>)>> {
+    let path_like = path_filter.and_then(PathFilter::like_pattern);
+    Ok(graph
+        .nodes_with_embeddings(model, path_like.as_deref())?
         .into_iter()
-        .filter_map(|node| {
-            let text = combined_node_text(&node);
-            let tokens = tokenize(&text);
-            if tokens.is_empty() {
-                return None;
-            }
+        .filter(|(node, _)| match path_filter {
+            Some(filter) => node.file_path.as_deref().is_some_and(|p| filter.matches(p)),
+            None => true,
+        })
+        .collect())
+}
 
-            let node_vec = build_vector(&tokens);
-            let score = cosine_similarity(&query_vec, &node_vec);
-            if score < MIN_SCORE {
-                return None;
-            }
+fn rank<'n>(
+    query_vec: &[f32],
+    nodes: impl Iterator<Item = (&'n Node, &'n [f32])>,
+) -> Vec<SearchResult> {
+    let mut scored: Vec<(&Node, f64)> = nodes
+        .map(|(node, node_vec)| (node, cosine_similarity(query_vec, node_vec)))
+        .filter(|(_, score)| *score >= MIN_SCORE)
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(VECTOR_LIMIT);
 
-            Some(SearchResult {
-                node,
+    scored
+        .into_iter()
+        .map(|(node, score)| {
+            let matched = node
+                .summary
+                .as_deref()
+                .and_then(|s| s.lines().map(str::trim).find(|l| !l.is_empty()))
+                .map(str::to_string);
+            SearchResult {
+                node: node.clone(),
                 score,
                 tier: SearchTier::L2Vector,
-                matched_content: None,
-            })
+                matched_content: matched,
+            }
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+/// The local token-hashing provider. Needs no external service.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashedEmbedder {
+    options: TokenizerOptions,
+}
+
+impl HashedEmbedder {
+    pub fn new(options: TokenizerOptions) -> Self {
+        Self { options }
+    }
+
+    /// The hashed bag-of-words vector for a node's name, summary and path, or
+    /// `None` when none of them contain a usable token.
+    pub fn node_vector(&self, node: &Node) -> Option<Vec<f32>> {
+        let tokens = self.tokenize(&embedding_text(node));
+        if tokens.is_empty() {
+            return None;
+        }
+        Some(build_vector(&tokens))
+    }
+
+    fn tokenize(&self, input: &str) -> Vec<String> {
+        tokenize(input)
+            .into_iter()
+            .filter(|t| !(self.options.stopwords && STOPWORDS.contains(&t.as_str())))
+            .map(|t| if self.options.stemming { stem(t) } else { t })
+            .collect()
+    }
+}
+
+impl EmbeddingProvider for HashedEmbedder {
+    fn model(&self) -> &str {
+        match (self.options.stopwords, self.options.stemming) {
+            (false, false) => HASHED_MODEL,
+            (true, false) => HASHED_MODEL_STOP,
+            (false, true) => HASHED_MODEL_STEM,
+            (true, true) => HASHED_MODEL_STOP_STEM,
+        }
+    }
 
-    results.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    results.truncate(VECTOR_LIMIT);
-    Ok(results)
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|t| build_vector(&self.tokenize(t)))
+            .collect())
+    }
 }
 
-fn combined_node_text(node: &crate::graph::Node) -> String {
+/// The text a node is embedded from: its name, summary and file path.
+pub fn embedding_text(node: &Node) -> String {
     let mut text = String::new();
     text.push_str(&node.name);
     if let Some(summary) = &node.summary {
@@ -71,6 +296,30 @@ fn tokenize(input: &str) -> Vec<String> {
         .collect()
 }
 
+/// Light suffix stripping: "caches", "caching", "cached" and "cache" all
+/// become "cach". Tokens with `_` or digits are identifiers and kept intact.
+fn stem(token: String) -> String {
+    if !token.chars().all(char::is_alphabetic) {
+        return token;
+    }
+    let mut stem = token.as_str();
+    if stem.len() > 5 && stem.ends_with("ing") {
+        stem = &stem[..stem.len() - 3];
+    } else if stem.len() > 4 && stem.ends_with("ed") {
+        stem = &stem[..stem.len() - 2];
+    } else if stem.len() > 4 && stem.ends_with("ies") {
+        return format!("{}y", &stem[..stem.len() - 3]);
+    } else if stem.len() > 4 && stem.ends_with("es") {
+        stem = &stem[..stem.len() - 2];
+    } else if stem.len() > 3 && stem.ends_with('s') && !stem.ends_with("ss") {
+        stem = &stem[..stem.len() - 1];
+    }
+    if stem.len() > 3 && stem.ends_with('e') {
+        stem = &stem[..stem.len() - 1];
+    }
+    stem.to_string()
+}
+
 fn build_vector(tokens: &[String]) -> Vec<f32> {
     let mut vec = vec![0.0f32; VECTOR_DIMENSION];
     for token in tokens {
@@ -81,10 +330,15 @@ fn build_vector(tokens: &[String]) -> Vec<f32> {
     vec
 }
 
+/// FNV-1a, so persisted vectors do not depend on the std hasher, whose
+/// output may change between Rust releases.
 fn stable_hash(value: &str) -> usize {
-    let mut hasher = DefaultHasher::new();
-    value.hash(&mut hasher);
-    hasher.finish() as usize
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in value.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash as usize
 }
 
 fn normalize(vec: &mut [f32]) {
@@ -101,11 +355,22 @@ fn normalize(vec: &mut [f32]) {
     }
 }
 
+/// Full cosine rather than a dot product: hashed vectors are unit length but
+/// external providers (e.g. Ollama models) do not always normalize theirs.
 fn cosine_similarity(lhs: &[f32], rhs: &[f32]) -> f64 {
-    lhs.iter()
-        .zip(rhs.iter())
-        .map(|(a, b)| (*a as f64) * (*b as f64))
-        .sum::<f64>()
+    let (mut dot, mut lhs_sq, mut rhs_sq) = (0.0f64, 0.0f64, 0.0f64);
+    for (a, b) in lhs.iter().zip(rhs.iter()) {
+        let (a, b) = (*a as f64, *b as f64);
+        dot += a * b;
+        lhs_sq += a * a;
+        rhs_sq += b * b;
+    }
+    let norm = (lhs_sq * rhs_sq).sqrt();
+    if norm < f64::EPSILON {
+        0.0
+    } else {
+        dot / norm
+    }
 }
 
 #[cfg(test)]
@@ -119,19 +384,245 @@ mod tests {
         assert!(!tokens.contains(&"a".to_string()));
     }
 
+    fn hashed_vector(text: &str) -> Vec<f32> {
+        let hashed = HashedEmbedder::default();
+        build_vector(&hashed.tokenize(text))
+    }
+
     #[test]
     fn cosine_similarity_is_high_for_similar_text() {
-        let lhs = build_vector(&tokenize("fetch exchange rate currency"));
-        let rhs = build_vector(&tokenize("exchange rate service currency"));
+        // Three of four tokens shared, no bucket collisions: 3 / (2 * 2).
+        let lhs = hashed_vector("fetch exchange rate currency");
+        let rhs = hashed_vector("exchange rate service currency");
         let score = cosine_similarity(&lhs, &rhs);
-        assert!(score > 0.4);
+        assert!((score - 0.75).abs() < 1e-6, "{score}");
+    }
+
+    #[test]
+    fn stopwords_are_dropped_and_suffixes_stripped() {
+        let hashed = HashedEmbedder::default();
+        assert_eq!(hashed.tokenize("the cache with a TTL"), vec!["cach", "ttl"]);
+        let stems: Vec<String> = ["caches", "caching", "cached", "cache"]
+            .iter()
+            .flat_map(|w| hashed.tokenize(w))
+            .collect();
+        assert!(stems.iter().all(|s| s == "cach"), "{stems:?}");
+        assert_eq!(hashed.tokenize("entries class"), vec!["entry", "class"]);
+        // Identifiers are never stemmed.
+        assert_eq!(
+            hashed.tokenize("fetch_rates v2_files"),
+            vec!["fetch_rates", "v2_files"]
+        );
+    }
+
+    #[test]
+    fn tokenizer_options_can_be_disabled() {
+        let plain = HashedEmbedder::new(TokenizerOptions {
+            stopwords: false,
+            stemming: false,
+        });
+        assert_eq!(plain.tokenize("the caches"), vec!["the", "caches"]);
+        assert_eq!(plain.model(), HASHED_MODEL);
+        assert_ne!(plain.model(), HashedEmbedder::default().model());
+    }
+
+    #[test]
+    fn stopwords_no_longer_inflate_prose_similarity() {
+        let lhs = hashed_vector("the worker reads from the queue with a timeout");
+        let rhs = hashed_vector("the parser builds from the tokens with a lookahead");
+        assert!(cosine_similarity(&lhs, &rhs) < 0.2);
+    }
+
+    #[test]
+    fn stable_hash_is_fixed_across_runs() {
+        assert_eq!(stable_hash(""), 0xcbf2_9ce4_8422_2325_u64 as usize);
+        assert_eq!(stable_hash("a"), 0xaf63_dc4c_8601_ec8c_u64 as usize);
+    }
+
+    /// Embeds every text to the same unit vector, so any stored vector equal
+    /// to it scores 1.0 and anything else scores 0.
+    struct FixedEmbedder;
+
+    impl EmbeddingProvider for FixedEmbedder {
+        fn model(&self) -> &str {
+            "fixed-test"
+        }
+
+        fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect())
+        }
+    }
+
+    fn no_ann() -> AnnSettings<'static> {
+        static CACHE: std::sync::OnceLock<Mutex<AnnCache>> = std::sync::OnceLock::new();
+        AnnSettings {
+            cache: CACHE.get_or_init(Mutex::default),
+            threshold: usize::MAX,
+        }
+    }
+
+    #[test]
+    fn ann_index_is_built_above_threshold_and_reused() {
+        let (_engine, graph, _nodes) = graph_with_nodes("vec-ann");
+        let cache = Mutex::new(AnnCache::default());
+        let ann = AnnSettings {
+            cache: &cache,
+            threshold: 2,
+        };
+        let hashed = HashedEmbedder::default();
+
+        let first =
+            vector_search(&graph, "fetch_exchange_rate", None, &hashed, &hashed, &ann).unwrap();
+        let generation = graph.data_version().unwrap();
+        let cached = cache
+            .lock()
+            .unwrap()
+            .get(graph.project_id(), hashed.model(), generation);
+        assert!(cached.is_some());
+        let second =
+            vector_search(&graph, "fetch_exchange_rate", None, &hashed, &hashed, &ann).unwrap();
+        assert_eq!(first[0].node.id, second[0].node.id);
+        assert_eq!(first[0].node.name, "fetch_exchange_rate");
+
+        // A path filter always scans, and never builds an index.
+        cache.lock().unwrap().clear();
+        let filter = PathFilter::parse("src/").unwrap();
+        vector_search(
+            &graph,
+            "fetch_exchange_rate",
+            Some(&filter),
+            &hashed,
+            &hashed,
+            &ann,
+        )
+        .unwrap();
+        assert!(cache.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ann_index_is_rebuilt_after_another_process_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("hermes.db");
+        let server = crate::HermesEngine::new(&db_path, "vec-ann-shared").unwrap();
+        let indexer = crate::HermesEngine::new(&db_path, "vec-ann-shared").unwrap();
+        let (graph, other) = (server.graph(), indexer.graph());
+        let add = |name: &str| {
+            let node = other
+                .create_node_builder()
+                .name(name)
+                .node_type(crate::graph::NodeType::Function)
+                .file_path(&format!("src/{name}.rs"))
+                .build();
+            other.add_node(&node).unwrap();
+        };
+        add("fetch_exchange_rate");
+        add("redis_worker");
+        let cache = Mutex::new(AnnCache::default());
+        let ann = AnnSettings {
+            cache: &cache,
+            threshold: 2,
+        };
+        let hashed = HashedEmbedder::default();
+        vector_search(&graph, "parse_invoice_total", None, &hashed, &hashed, &ann).unwrap();
+        assert!(!cache.lock().unwrap().is_empty());
+
+        add("parse_invoice_total");
+        let hits =
+            vector_search(&graph, "parse_invoice_total", None, &hashed, &hashed, &ann).unwrap();
+        assert_eq!(hits[0].node.name, "parse_invoice_total");
+    }
+
+    fn graph_with_nodes(project: &str) -> (crate::HermesEngine, KnowledgeGraph, Vec<Node>) {
+        let engine = crate::HermesEngine::in_memory(project).unwrap();
+        let graph = KnowledgeGraph::new(engine.db().clone(), engine.project_id());
+        let nodes: Vec<Node> = ["fetch_exchange_rate", "redis_worker"]
+            .iter()
+            .map(|name| {
+                let node = graph
+                    .create_node_builder()
+                    .name(name)
+                    .node_type(crate::graph::NodeType::Function)
+                    .file_path(&format!("src/{name}.rs"))
+                    .build();
+                graph.add_node(&node).unwrap();
+                node
+            })
+            .collect();
+        (engine, graph, nodes)
+    }
+
+    #[test]
+    fn provider_vectors_are_used_when_every_node_has_one() {
+        let (_engine, graph, nodes) = graph_with_nodes("vec-provider");
+        graph
+            .upsert_embedding(&nodes[0].id, &[0.0, 1.0], "fixed-test")
+            .unwrap();
+        graph
+            .upsert_embedding(&nodes[1].id, &[1.0, 0.0], "fixed-test")
+            .unwrap();
+
+        let hashed = HashedEmbedder::default();
+        let results = vector_search(
+            &graph,
+            "exchange rate",
+            None,
+            &FixedEmbedder,
+            &hashed,
+            &no_ann(),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.name, "redis_worker");
+    }
+
+    #[test]
+    fn nodes_without_provider_vectors_are_skipped() {
+        let (_engine, graph, nodes) = graph_with_nodes("vec-partial");
+        graph
+            .upsert_embedding(&nodes[1].id, &[1.0, 0.0], "fixed-test")
+            .unwrap();
+
+        let hashed = HashedEmbedder::default();
+        let results = vector_search(
+            &graph,
+            "fetch_exchange_rate",
+            None,
+            &FixedEmbedder,
+            &hashed,
+            &no_ann(),
+        )
+        .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].node.name, "redis_worker");
+    }
+
+    #[test]
+    fn no_provider_vectors_fall_back_to_hashed() {
+        let (_engine, graph, _nodes) = graph_with_nodes("vec-fallback");
+        let hashed = HashedEmbedder::default();
+        let results = vector_search(
+            &graph,
+            "fetch_exchange_rate",
+            None,
+            &FixedEmbedder,
+            &hashed,
+            &no_ann(),
+        )
+        .unwrap();
+        assert_eq!(results[0].node.name, "fetch_exchange_rate");
+    }
+
+    #[test]
+    fn cosine_similarity_ignores_vector_length() {
+        assert!((cosine_similarity(&[3.0, 4.0], &[0.6, 0.8]) - 1.0).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
     }
 
     #[test]
     fn cosine_similarity_is_low_for_unrelated_text() {
-        let lhs = build_vector(&tokenize("redis pubsub worker"));
-        let rhs = build_vector(&tokenize("currency exchange rate"));
+        let lhs = hashed_vector("redis pubsub worker");
+        let rhs = hashed_vector("currency exchange rate");
         let score = cosine_similarity(&lhs, &rhs);
-        assert!(score < 0.4);
+        assert_eq!(score, 0.0);
     }
 }
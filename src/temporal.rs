@@ -1,6 +1,7 @@
-use anyhow::Result;
-use chrono::Utc;
-use rusqlite::{params, Connection};
+use crate::busy;
+use crate::error::{HermesError, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
@@ -18,7 +19,13 @@ pub struct TemporalFact {
     pub source_reference: Option<String>,
 }
 
+/// The kind of a fact: one of the built-in types, or any other lowercase
+/// name (`todo`, `security`) kept as [`FactType::Custom`].
+///
+/// In JSON the built-ins keep their variant names (`"Decision"`), as before
+/// custom types existed; a custom type is its name.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(from = "String", into = "String")]
 pub enum FactType {
     Architecture,
     ApiContract,
@@ -26,10 +33,20 @@ pub enum FactType {
     ErrorPattern,
     Constraint,
     Learning,
+    Custom(String),
 }
 
 impl FactType {
-    pub fn as_str(&self) -> &'static str {
+    pub const BUILT_IN: [FactType; 6] = [
+        Self::Architecture,
+        Self::ApiContract,
+        Self::Decision,
+        Self::ErrorPattern,
+        Self::Constraint,
+        Self::Learning,
+    ];
+
+    pub fn as_str(&self) -> &str {
         match self {
             Self::Architecture => "architecture",
             Self::ApiContract => "api_contract",
@@ -37,22 +54,64 @@ impl FactType {
             Self::ErrorPattern => "error_pattern",
             Self::Constraint => "constraint",
             Self::Learning => "learning",
+            Self::Custom(name) => name,
         }
     }
 
+    /// Case-insensitive; a name that is not a built-in becomes a custom type.
     pub fn parse_str(s: &str) -> Self {
-        match s {
-            "architecture" => Self::Architecture,
-            "api_contract" => Self::ApiContract,
-            "decision" => Self::Decision,
-            "error_pattern" => Self::ErrorPattern,
-            "constraint" => Self::Constraint,
-            "learning" => Self::Learning,
-            _ => Self::Decision,
+        let name = s.trim().to_lowercase();
+        Self::BUILT_IN
+            .into_iter()
+            .find(|builtin| builtin.as_str() == name)
+            .unwrap_or(Self::Custom(name))
+    }
+
+    fn variant_name(&self) -> &str {
+        match self {
+            Self::Architecture => "Architecture",
+            Self::ApiContract => "ApiContract",
+            Self::Decision => "Decision",
+            Self::ErrorPattern => "ErrorPattern",
+            Self::Constraint => "Constraint",
+            Self::Learning => "Learning",
+            Self::Custom(name) => name,
         }
     }
 }
 
+impl From<String> for FactType {
+    fn from(name: String) -> Self {
+        Self::BUILT_IN
+            .into_iter()
+            .find(|builtin| builtin.variant_name() == name)
+            .unwrap_or_else(|| Self::parse_str(&name))
+    }
+}
+
+impl From<FactType> for String {
+    fn from(fact_type: FactType) -> Self {
+        fact_type.variant_name().to_string()
+    }
+}
+
+/// Ids of every fact in the supersession chain through `?2`, in project
+/// `?1`, as the CTEs `older` and `newer`. UNION rather than UNION ALL, so a
+/// corrupt cyclic chain still ends.
+const CHAIN_CTE: &str = "WITH RECURSIVE
+   older(id) AS (
+     SELECT ?2
+     UNION
+     SELECT f.id FROM temporal_facts f JOIN older o ON f.superseded_by = o.id
+     WHERE f.project_id = ?1
+   ),
+   newer(id) AS (
+     SELECT ?2
+     UNION
+     SELECT f.superseded_by FROM temporal_facts f JOIN newer n ON f.id = n.id
+     WHERE f.project_id = ?1 AND f.superseded_by IS NOT NULL
+   )";
+
 pub struct TemporalStore {
     db: Arc<Mutex<Connection>>,
     project_id: String,
@@ -73,11 +132,74 @@ impl TemporalStore {
         content: &str,
         source_reference: Option<&str>,
     ) -> Result<String> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let id = Uuid::new_v4().to_string();
         let now = Utc::now().to_rfc3339();
 
-        conn.execute(
+        busy::retry_busy(|| {
+            conn.execute(
+                "INSERT INTO temporal_facts
+                 (id, project_id, node_id, fact_type, content, valid_from, source_reference)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    id,
+                    self.project_id,
+                    node_id,
+                    fact_type.as_str(),
+                    content,
+                    now,
+                    source_reference,
+                ],
+            )
+        })?;
+        Ok(id)
+    }
+
+    /// Ends an active fact's validity, optionally pointing at the fact that
+    /// replaces it. Fails with [`HermesError::NotFound`] when there is no
+    /// active fact `fact_id`, so an already invalidated fact keeps its chain.
+    pub fn invalidate_fact(&self, fact_id: &str, superseded_by: Option<&str>) -> Result<()> {
+        let conn = self.db.lock()?;
+        let now = Utc::now().to_rfc3339();
+        let changed = busy::retry_busy(|| {
+            conn.execute(
+                "UPDATE temporal_facts SET valid_to = ?1, superseded_by = ?2
+                 WHERE id = ?3 AND project_id = ?4 AND valid_to IS NULL",
+                params![now, superseded_by, fact_id, self.project_id],
+            )
+        })?;
+        if changed == 0 {
+            return Err(HermesError::NotFound(format!("no active fact: {fact_id}")));
+        }
+        Ok(())
+    }
+
+    /// Records `content` as the successor of the active fact `fact_id`, with
+    /// the same node and type, and invalidates the old fact in the same
+    /// transaction. Returns the new fact's id.
+    pub fn supersede_fact(
+        &self,
+        fact_id: &str,
+        content: &str,
+        source_reference: Option<&str>,
+    ) -> Result<String> {
+        let conn = self.db.lock()?;
+        let tx = busy::write_transaction(&conn)?;
+        let old: Option<(Option<String>, String)> = tx
+            .query_row(
+                "SELECT node_id, fact_type FROM temporal_facts
+                 WHERE id = ?1 AND project_id = ?2 AND valid_to IS NULL",
+                params![fact_id, self.project_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((node_id, fact_type)) = old else {
+            return Err(HermesError::NotFound(format!("no active fact: {fact_id}")));
+        };
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        tx.execute(
             "INSERT INTO temporal_facts
              (id, project_id, node_id, fact_type, content, valid_from, source_reference)
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
@@ -85,28 +207,23 @@ impl TemporalStore {
                 id,
                 self.project_id,
                 node_id,
-                fact_type.as_str(),
+                fact_type,
                 content,
                 now,
-                source_reference,
+                source_reference
             ],
         )?;
-        Ok(id)
-    }
-
-    pub fn invalidate_fact(&self, fact_id: &str, superseded_by: Option<&str>) -> Result<()> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
-        let now = Utc::now().to_rfc3339();
-        conn.execute(
+        tx.execute(
             "UPDATE temporal_facts SET valid_to = ?1, superseded_by = ?2
              WHERE id = ?3 AND project_id = ?4",
-            params![now, superseded_by, fact_id, self.project_id],
+            params![now, id, fact_id, self.project_id],
         )?;
-        Ok(())
+        tx.commit()?;
+        Ok(id)
     }
 
     pub fn get_active_facts(&self, fact_type: Option<&FactType>) -> Result<Vec<TemporalFact>> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
 
         let (sql, fact_type_str);
         let base_params: Vec<&dyn rusqlite::types::ToSql>;
@@ -136,8 +253,51 @@ impl TemporalStore {
         Ok(rows)
     }
 
+    /// Active facts about `file_path` or any node in it, newest first.
+    /// `file_path` is in the indexed form, as in [`crate::graph::Node::file_path`].
+    pub fn get_facts_for_file(&self, file_path: &str) -> Result<Vec<TemporalFact>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.project_id, f.node_id, f.fact_type, f.content, f.valid_from, f.valid_to,
+                    f.superseded_by, f.source_reference
+             FROM temporal_facts f JOIN nodes n ON n.id = f.node_id
+             WHERE f.project_id = ?1 AND n.file_path = ?2 AND f.valid_to IS NULL
+             ORDER BY f.valid_from DESC",
+        )?;
+        let rows = stmt
+            .query_map(params![self.project_id, file_path], Self::map_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// The facts that were valid at `timestamp`: recorded at or before it
+    /// and not invalidated until after it, newest first. `timestamp` is RFC
+    /// 3339 (`2024-03-01T12:00:00Z`) or a plain date, read as midnight UTC.
+    pub fn get_facts_as_of(
+        &self,
+        timestamp: &str,
+        fact_type: Option<&FactType>,
+    ) -> Result<Vec<TemporalFact>> {
+        // Stored times are UTC `to_rfc3339` strings, which order correctly
+        // as text once the input is written the same way.
+        let as_of = parse_as_of(timestamp)?.to_rfc3339();
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, node_id, fact_type, content, valid_from, valid_to, superseded_by, source_reference
+             FROM temporal_facts
+             WHERE project_id = ?1 AND valid_from <= ?2 AND (valid_to IS NULL OR valid_to > ?2)
+               AND (?3 IS NULL OR fact_type = ?3)
+             ORDER BY valid_from DESC",
+        )?;
+        let fact_type = fact_type.map(FactType::as_str);
+        let rows = stmt
+            .query_map(params![self.project_id, as_of, fact_type], Self::map_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
     pub fn get_fact_history(&self, node_id: &str) -> Result<Vec<TemporalFact>> {
-        let conn = self.db.lock().map_err(|e| anyhow::anyhow!("{e}"))?;
+        let conn = self.db.lock()?;
         let mut stmt = conn.prepare(
             "SELECT id, project_id, node_id, fact_type, content, valid_from, valid_to, superseded_by, source_reference
              FROM temporal_facts
@@ -150,6 +310,90 @@ impl TemporalStore {
         Ok(rows)
     }
 
+    /// Every fact in the supersession chain through `fact_id`, invalidated
+    /// ones included, newest first; empty when there is no such fact.
+    pub fn get_fact_chain(&self, fact_id: &str) -> Result<Vec<TemporalFact>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(&format!(
+            "{CHAIN_CTE}
+             SELECT id, project_id, node_id, fact_type, content, valid_from, valid_to, superseded_by, source_reference
+             FROM temporal_facts
+             WHERE project_id = ?1 AND id IN (SELECT id FROM older UNION SELECT id FROM newer)
+             ORDER BY valid_from DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params![self.project_id, fact_id], Self::map_row)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// The types of the active facts with how many facts each has, most
+    /// used first.
+    pub fn count_active_by_type(&self) -> Result<Vec<(FactType, u64)>> {
+        let conn = self.db.lock()?;
+        let mut stmt = conn.prepare(
+            "SELECT fact_type, COUNT(*) FROM temporal_facts
+             WHERE project_id = ?1 AND valid_to IS NULL
+             GROUP BY fact_type
+             ORDER BY COUNT(*) DESC, fact_type",
+        )?;
+        let rows = stmt
+            .query_map(params![self.project_id], |row| {
+                let fact_type: String = row.get(0)?;
+                Ok((
+                    FactType::parse_str(&fact_type),
+                    row.get::<_, i64>(1)? as u64,
+                ))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// A fact by id, valid or not.
+    pub fn get_fact(&self, fact_id: &str) -> Result<Option<TemporalFact>> {
+        let conn = self.db.lock()?;
+        let fact = conn
+            .query_row(
+                "SELECT id, project_id, node_id, fact_type, content, valid_from, valid_to, superseded_by, source_reference
+                 FROM temporal_facts
+                 WHERE id = ?1 AND project_id = ?2",
+                params![fact_id, self.project_id],
+                Self::map_row,
+            )
+            .optional()?;
+        Ok(fact)
+    }
+
+    /// Corrects an active fact's content by superseding it with a copy that
+    /// keeps its source reference, so the old text stays in the history.
+    /// Returns the new fact's id.
+    pub fn update_fact_content(&self, fact_id: &str, new_content: &str) -> Result<String> {
+        let Some(fact) = self.get_fact(fact_id)? else {
+            return Err(HermesError::NotFound(format!("no such fact: {fact_id}")));
+        };
+        self.supersede_fact(fact_id, new_content, fact.source_reference.as_deref())
+    }
+
+    /// Permanently deletes `fact_id` and every fact in its supersession
+    /// chain, older and newer. Returns how many facts were deleted.
+    pub fn purge_fact(&self, fact_id: &str) -> Result<usize> {
+        let conn = self.db.lock()?;
+        let tx = busy::write_transaction(&conn)?;
+        let deleted = tx.execute(
+            &format!(
+                "{CHAIN_CTE}
+                 DELETE FROM temporal_facts
+                 WHERE project_id = ?1 AND id IN (SELECT id FROM older UNION SELECT id FROM newer)"
+            ),
+            params![self.project_id, fact_id],
+        )?;
+        if deleted == 0 {
+            return Err(HermesError::NotFound(format!("no such fact: {fact_id}")));
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
     fn map_row(row: &rusqlite::Row) -> rusqlite::Result<TemporalFact> {
         Ok(TemporalFact {
             id: row.get(0)?,
@@ -165,6 +409,23 @@ impl TemporalStore {
     }
 }
 
+/// Parses an RFC 3339 timestamp, or a `YYYY-MM-DD` date as midnight UTC.
+fn parse_as_of(timestamp: &str) -> Result<DateTime<Utc>> {
+    let timestamp = timestamp.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(timestamp, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc())
+        .ok_or_else(|| {
+            HermesError::InvalidInput(format!(
+                "invalid as-of time '{timestamp}' (expected RFC 3339 or YYYY-MM-DD)"
+            ))
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,6 +522,8 @@ mod tests {
             end_line: None,
             summary: None,
             content_hash: None,
+            updated_at: None,
+            metadata: None,
         };
         graph.add_node(&real_node).unwrap();
 
@@ -290,21 +553,56 @@ mod tests {
     }
 
     #[test]
-    fn fact_type_parse_str_unknown_falls_back_to_decision() {
-        assert_eq!(FactType::parse_str("unknown_type"), FactType::Decision);
+    fn fact_type_parse_str_keeps_unknown_types_as_custom() {
+        assert_eq!(
+            FactType::parse_str(" TODO "),
+            FactType::Custom("todo".to_string())
+        );
+        assert_eq!(FactType::parse_str("Decision"), FactType::Decision);
+
+        let todo = FactType::parse_str("todo");
+        assert_eq!(serde_json::to_value(&todo).unwrap(), "todo");
+        assert_eq!(
+            serde_json::to_value(FactType::ApiContract).unwrap(),
+            "ApiContract"
+        );
+        for json in ["\"ApiContract\"", "\"api_contract\""] {
+            let parsed: FactType = serde_json::from_str(json).unwrap();
+            assert_eq!(parsed, FactType::ApiContract, "{json}");
+        }
+        assert_eq!(serde_json::from_str::<FactType>("\"todo\"").unwrap(), todo);
+    }
+
+    #[test]
+    fn custom_fact_types_are_stored_and_filtered() {
+        let engine = HermesEngine::in_memory("test-custom").unwrap();
+        let store = TemporalStore::new(engine.db().clone(), "test-custom");
+        store
+            .add_fact(None, FactType::parse_str("todo"), "fix the cache", None)
+            .unwrap();
+        store
+            .add_fact(None, FactType::parse_str("Todo"), "drop the v1 API", None)
+            .unwrap();
+        store
+            .add_fact(None, FactType::Decision, "use Rust", None)
+            .unwrap();
+
+        let todos = store
+            .get_active_facts(Some(&FactType::parse_str("todo")))
+            .unwrap();
+        assert_eq!(todos.len(), 2);
+        assert!(todos.iter().all(|fact| fact.fact_type.as_str() == "todo"));
+        let counts = store.count_active_by_type().unwrap();
+        assert_eq!(
+            counts,
+            [(FactType::parse_str("todo"), 2), (FactType::Decision, 1)]
+        );
     }
 
     #[test]
     fn fact_type_roundtrip_all_variants() {
-        let variants = [
-            FactType::Architecture,
-            FactType::ApiContract,
-            FactType::Decision,
-            FactType::ErrorPattern,
-            FactType::Constraint,
-            FactType::Learning,
-        ];
-        for v in &variants {
+        let custom = FactType::Custom("security".to_string());
+        for v in FactType::BUILT_IN.iter().chain([&custom]) {
             assert_eq!(&FactType::parse_str(v.as_str()), v);
         }
     }
@@ -336,6 +634,119 @@ mod tests {
         assert_eq!(all_facts[0].content, "Max 500 connections");
     }
 
+    #[test]
+    fn supersede_fact_keeps_type_and_chains_both_ways() {
+        let engine = HermesEngine::in_memory("test-supersede").unwrap();
+        let store = TemporalStore::new(engine.db().clone(), "test-supersede");
+        let first = store
+            .add_fact(None, FactType::Decision, "Use ChromaDB", None)
+            .unwrap();
+        let second = store.supersede_fact(&first, "Use Qdrant", None).unwrap();
+        let third = store
+            .supersede_fact(&second, "Use Qdrant with HNSW", Some("ADR-7"))
+            .unwrap();
+        store
+            .add_fact(None, FactType::Decision, "Unrelated", None)
+            .unwrap();
+
+        let active = store.get_active_facts(Some(&FactType::Decision)).unwrap();
+        assert_eq!(active.len(), 2);
+        assert!(matches!(
+            store.supersede_fact(&first, "Use pgvector", None),
+            Err(HermesError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.invalidate_fact(&first, None),
+            Err(HermesError::NotFound(_))
+        ));
+
+        let chain = store.get_fact_chain(&second).unwrap();
+        let ids: Vec<_> = chain.iter().map(|fact| fact.id.as_str()).collect();
+        assert_eq!(ids, [third.as_str(), second.as_str(), first.as_str()]);
+        assert_eq!(chain[1].superseded_by.as_deref(), Some(third.as_str()));
+        assert_eq!(
+            chain[1].valid_to.as_deref(),
+            Some(chain[0].valid_from.as_str())
+        );
+        assert!(chain[0].valid_to.is_none());
+        assert!(store.get_fact_chain("missing-fact").unwrap().is_empty());
+    }
+
+    #[test]
+    fn as_of_returns_the_version_valid_at_that_time() {
+        let engine = HermesEngine::in_memory("test-as-of").unwrap();
+        let store = TemporalStore::new(engine.db().clone(), "test-as-of");
+        let old = store
+            .add_fact(None, FactType::Decision, "Use ChromaDB", None)
+            .unwrap();
+        store
+            .add_fact(None, FactType::Learning, "Embeddings drift", None)
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let new = store.supersede_fact(&old, "Use Qdrant", None).unwrap();
+        let chain = store.get_fact_chain(&new).unwrap();
+        let (recorded, superseded) = (&chain[1].valid_from, chain[0].valid_from.clone());
+
+        let decisions = |at: &str| -> Vec<String> {
+            let facts = store
+                .get_facts_as_of(at, Some(&FactType::Decision))
+                .unwrap();
+            facts.into_iter().map(|fact| fact.content).collect()
+        };
+        assert!(decisions("2000-01-01").is_empty());
+        assert_eq!(decisions(recorded), ["Use ChromaDB"]);
+        let before =
+            DateTime::parse_from_rfc3339(&superseded).unwrap() - chrono::Duration::milliseconds(1);
+        assert_eq!(decisions(&before.to_rfc3339()), ["Use ChromaDB"]);
+        assert_eq!(decisions(&superseded), ["Use Qdrant"]);
+        assert_eq!(decisions("2999-12-31T23:00:00+02:00"), ["Use Qdrant"]);
+        assert_eq!(store.get_facts_as_of(&superseded, None).unwrap().len(), 2);
+
+        for bad in ["yesterday", "2024-13-01", "2024-03-01 12:00"] {
+            let error = store.get_facts_as_of(bad, None).unwrap_err();
+            assert!(matches!(error, HermesError::InvalidInput(_)), "{bad}");
+        }
+    }
+
+    #[test]
+    fn edits_supersede_and_purges_take_the_whole_chain() {
+        let engine = HermesEngine::in_memory("test-purge").unwrap();
+        let store = TemporalStore::new(engine.db().clone(), "test-purge");
+        let first = store
+            .add_fact(None, FactType::Decision, "Use Qdrnat", Some("ADR-7"))
+            .unwrap();
+        let second = store.update_fact_content(&first, "Use Qdrant").unwrap();
+        let third = store
+            .update_fact_content(&second, "Use Qdrant with HNSW")
+            .unwrap();
+        let other = store
+            .add_fact(None, FactType::Decision, "Use Rust", None)
+            .unwrap();
+
+        let edited = store.get_fact(&second).unwrap().unwrap();
+        assert_eq!(edited.content, "Use Qdrant");
+        assert_eq!(edited.source_reference.as_deref(), Some("ADR-7"));
+        assert_eq!(edited.superseded_by.as_deref(), Some(third.as_str()));
+        assert!(matches!(
+            store.update_fact_content(&first, "Use Milvus"),
+            Err(HermesError::NotFound(_))
+        ));
+
+        assert_eq!(store.purge_fact(&second).unwrap(), 3);
+        for id in [&first, &second, &third] {
+            assert!(
+                store.get_fact(id).unwrap().is_none(),
+                "{id} survived the purge"
+            );
+        }
+        assert_eq!(store.get_active_facts(None).unwrap()[0].id, other);
+        assert!(matches!(
+            store.purge_fact(&second),
+            Err(HermesError::NotFound(_))
+        ));
+        assert!(store.get_fact("missing-fact").unwrap().is_none());
+    }
+
     #[test]
     fn source_reference_is_stored_and_retrieved() {
         let engine = HermesEngine::in_memory("test-ref").unwrap();